@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS config (
+  is_initialized BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+INSERT INTO config DEFAULT VALUES;
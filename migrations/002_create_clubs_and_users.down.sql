@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS sessions;
+DROP TABLE IF EXISTS users CASCADE;
+DROP TABLE IF EXISTS clubs;
+DROP TABLE IF EXISTS global_users;
@@ -0,0 +1,47 @@
+-- CREATE TYPE global_roles AS ENUM ('super_admin'); -- for now all global users are always global admins
+-- TODO: add lower roles for other global users
+
+CREATE TABLE IF NOT EXISTS global_users (
+  id VARCHAR(36) PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+  username text     NOT NULL UNIQUE,
+  password  text    NOT NULL,
+
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS clubs (
+  id TEXT PRIMARY KEY NOT NULL,
+  title TEXT     NOT NULL UNIQUE,
+  
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS users (
+  id VARCHAR(36) PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+  username text     NOT NULL UNIQUE,
+  password  text    NOT NULL,
+  club_id VARCHAR(36)  NOT NULL,
+
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+  CONSTRAINT fk_club
+    FOREIGN KEY(club_id)
+	  REFERENCES clubs(id)
+	  ON DELETE RESTRICT
+);
+
+CREATE TABLE IF NOT EXISTS sessions (
+    id VARCHAR(36) PRIMARY KEY NOT NULL,
+    user_id VARCHAR(36)  NOT NULL,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    CONSTRAINT fk_user
+        FOREIGN KEY(user_id)
+	    REFERENCES users(id)
+	    ON DELETE CASCADE
+);
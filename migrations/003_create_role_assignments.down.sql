@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS global_role_assignments;
+DROP TYPE IF EXISTS global_roles;
+DROP TABLE IF EXISTS role_assignments;
+DROP TYPE IF EXISTS user_roles;
@@ -0,0 +1,6 @@
+DROP TABLE IF EXISTS game_invites;
+DROP TYPE IF EXISTS game_invite_response;
+DROP TABLE IF EXISTS games;
+DROP TYPE IF EXISTS location_kind;
+DROP TABLE IF EXISTS events;
+DROP TABLE IF EXISTS teams;
@@ -1,17 +0,0 @@
-DROP TABLE IF EXISTS game_invites;
-DROP TABLE IF EXISTS events CASCADE;
-DROP TABLE IF EXISTS games CASCADE;
-DROP TYPE IF EXISTS location_kind;
-DROP TYPE IF EXISTS game_invite_response CASCADE;
-DROP TABLE IF EXISTS teams;
-DROP TABLE IF EXISTS service_invites;
-DROP TABLE IF EXISTS role_assignments;
-DROP TYPE IF EXISTS user_roles;
-DROP TABLE IF EXISTS global_role_assignments;
-DROP TYPE IF EXISTS global_roles;
-DROP TABLE IF EXISTS sessions;
-DROP TABLE IF EXISTS users CASCADE;
-DROP TABLE IF EXISTS clubs;
-DROP TABLE IF EXISTS global_users;
--- DROP TYPE IF EXISTS user_roles;
-DROP TABLE IF EXISTS config;
\ No newline at end of file
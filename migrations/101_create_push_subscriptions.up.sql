@@ -0,0 +1,20 @@
+-- one row per browser/device that opted into Web Push notifications
+CREATE TABLE IF NOT EXISTS push_subscriptions (
+    id VARCHAR(36) PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    user_id VARCHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- the session that registered the device, logging it out ends the notifications
+    session_id VARCHAR(36) REFERENCES sessions(id) ON DELETE CASCADE,
+
+    -- push service URL, unique per browser installation
+    endpoint TEXT NOT NULL UNIQUE,
+    -- client keys from PushSubscription.toJSON(), base64url-encoded
+    p256dh TEXT NOT NULL,
+    auth TEXT NOT NULL,
+    user_agent TEXT,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS push_subscriptions_user_id_idx ON push_subscriptions(user_id);
+CREATE INDEX IF NOT EXISTS push_subscriptions_session_id_idx ON push_subscriptions(session_id);
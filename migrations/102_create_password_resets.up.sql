@@ -0,0 +1,21 @@
+-- where password reset links get sent; managing addresses is not exposed yet
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email TEXT;
+
+-- set by an admin: the user has to pick a new password before doing anything else
+ALTER TABLE users ADD COLUMN IF NOT EXISTS must_reset_password BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS password_reset_tokens (
+    id VARCHAR(36) PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    user_id VARCHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- SHA-256 of the token, the token itself is only ever shown to its recipient
+    token_hash TEXT NOT NULL UNIQUE,
+    -- admin who issued the token by hand, NULL for self-service requests
+    issued_by VARCHAR(36) REFERENCES users(id) ON DELETE SET NULL,
+
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS password_reset_tokens_user_id_idx ON password_reset_tokens(user_id);
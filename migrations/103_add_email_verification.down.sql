@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS users_email_lower_key;
+-- unverified addresses go back to where they came from
+UPDATE users SET email = pending_email WHERE email IS NULL AND pending_email IS NOT NULL;
+ALTER TABLE users DROP COLUMN IF EXISTS pending_email;
+ALTER TABLE users DROP COLUMN IF EXISTS email_verified_at;
@@ -0,0 +1,8 @@
+-- `email` only ever holds a verified address (or NULL), a requested change waits in `pending_email`
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified_at TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS pending_email TEXT;
+
+-- addresses from before verification existed are kept, unverified, until their owner confirms them
+UPDATE users SET pending_email = email, email = NULL WHERE email IS NOT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_lower_key ON users (lower(email));
@@ -0,0 +1,3 @@
+ALTER TABLE two_factor_challenges DROP COLUMN IF EXISTS login_identifier;
+DROP TABLE IF EXISTS audit_log;
+DROP TABLE IF EXISTS failed_logins;
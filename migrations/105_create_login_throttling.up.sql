@@ -0,0 +1,32 @@
+-- failed log-ins per submitted username/email and per client IP, whether or not the account exists
+CREATE TABLE IF NOT EXISTS failed_logins (
+    -- 'identifier' (lowercased username or email) or 'ip'
+    key_kind TEXT NOT NULL,
+    key TEXT NOT NULL,
+    failure_count INTEGER NOT NULL DEFAULT 0,
+    last_failed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    locked_until TIMESTAMPTZ,
+
+    PRIMARY KEY (key_kind, key)
+);
+
+-- append-only record of security-relevant events
+CREATE TABLE IF NOT EXISTS audit_log (
+    id VARCHAR(36) PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    -- NULL for events caused by anonymous requests, e.g. a lockout after failed log-ins
+    actor_user_id VARCHAR(36) REFERENCES users(id) ON DELETE SET NULL,
+    -- no foreign key, the log outlives the club: entries of a deleted club keep its id
+    club_id VARCHAR(36),
+    action TEXT NOT NULL,
+    target_type TEXT,
+    target_id TEXT,
+    details JSONB NOT NULL DEFAULT '{}',
+    ip TEXT,
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_club_id_created_at_idx ON audit_log(club_id, created_at);
+
+-- the username/email the password was entered for, wrong codes count as failed log-ins for it
+ALTER TABLE two_factor_challenges ADD COLUMN IF NOT EXISTS login_identifier TEXT;
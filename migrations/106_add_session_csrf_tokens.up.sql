@@ -0,0 +1,3 @@
+-- synchronizer token, has to be sent as X-CSRF-Token with every state-changing request of the session
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS csrf_token TEXT NOT NULL
+    DEFAULT replace(gen_random_uuid()::text || gen_random_uuid()::text, '-', '');
@@ -0,0 +1,18 @@
+-- personal tokens for scripts, sent as `Authorization: Bearer <token>`
+CREATE TABLE IF NOT EXISTS api_tokens (
+    id VARCHAR(36) PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    user_id VARCHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    -- SHA-256 of the token, the token itself is only shown once on creation
+    token_hash TEXT NOT NULL UNIQUE,
+    -- start of the token, so users can tell their tokens apart
+    token_prefix TEXT NOT NULL,
+    -- e.g. 'read', 'teams:write'
+    scopes TEXT[] NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    last_used_at TIMESTAMPTZ,
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS api_tokens_user_id_idx ON api_tokens(user_id);
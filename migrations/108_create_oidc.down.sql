@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS user_oidc_identities;
+DROP TABLE IF EXISTS club_oidc_providers;
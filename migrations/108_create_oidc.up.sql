@@ -0,0 +1,28 @@
+-- optional single sign-on via the club's own OpenID Connect provider (Keycloak, Authentik, ...)
+CREATE TABLE IF NOT EXISTS club_oidc_providers (
+    club_id VARCHAR(36) PRIMARY KEY NOT NULL REFERENCES clubs(id) ON DELETE CASCADE,
+    -- discovery happens via <issuer_url>/.well-known/openid-configuration
+    issuer_url TEXT NOT NULL,
+    client_id TEXT NOT NULL,
+    client_secret TEXT NOT NULL,
+    -- create unknown users on their first log-in, with this role
+    jit_provisioning BOOLEAN NOT NULL DEFAULT FALSE,
+    default_role user_roles NOT NULL DEFAULT 'player',
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- which IdP account belongs to which local user
+CREATE TABLE IF NOT EXISTS user_oidc_identities (
+    id VARCHAR(36) PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    user_id VARCHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    UNIQUE (issuer, subject)
+);
+
+CREATE INDEX IF NOT EXISTS user_oidc_identities_user_id_idx ON user_oidc_identities(user_id);
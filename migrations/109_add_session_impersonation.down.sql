@@ -0,0 +1,2 @@
+ALTER TABLE sessions DROP COLUMN IF EXISTS impersonation_expires_at;
+ALTER TABLE sessions DROP COLUMN IF EXISTS impersonated_user_id;
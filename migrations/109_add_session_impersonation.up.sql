@@ -0,0 +1,4 @@
+-- an admin's session can act as another user for a limited time, the real user stays `user_id`
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS impersonated_user_id VARCHAR(36)
+    REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS impersonation_expires_at TIMESTAMPTZ;
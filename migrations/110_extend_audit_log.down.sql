@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS audit_log_target_idx;
+
+ALTER TABLE audit_log DROP COLUMN IF EXISTS request_id;
+ALTER TABLE audit_log DROP COLUMN IF EXISTS after;
+ALTER TABLE audit_log DROP COLUMN IF EXISTS before;
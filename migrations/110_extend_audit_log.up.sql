@@ -0,0 +1,6 @@
+-- the audit log covers data changes too: snapshots of the target and the request that caused them
+ALTER TABLE audit_log ADD COLUMN IF NOT EXISTS before JSONB;
+ALTER TABLE audit_log ADD COLUMN IF NOT EXISTS after JSONB;
+ALTER TABLE audit_log ADD COLUMN IF NOT EXISTS request_id TEXT;
+
+CREATE INDEX IF NOT EXISTS audit_log_target_idx ON audit_log(target_type, target_id);
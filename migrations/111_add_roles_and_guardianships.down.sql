@@ -0,0 +1,25 @@
+DROP TABLE IF EXISTS guardianships;
+
+-- Postgres can't drop enum values, so the type is recreated without them
+DELETE FROM role_assignments
+    WHERE role IN ('team_manager', 'referee_manager', 'referee', 'parent');
+UPDATE games SET invited_roles = array_remove(array_remove(array_remove(array_remove(
+    invited_roles, 'team_manager'), 'referee_manager'), 'referee'), 'parent');
+UPDATE club_oidc_providers SET default_role = 'player'
+    WHERE default_role IN ('team_manager', 'referee_manager', 'referee', 'parent');
+
+ALTER TYPE user_roles RENAME TO user_roles_old;
+CREATE TYPE user_roles AS ENUM ('super_admin', 'club_admin', 'coach', 'player');
+
+ALTER TABLE role_assignments
+    ALTER COLUMN role TYPE user_roles USING role::text::user_roles;
+ALTER TABLE games ALTER COLUMN invited_roles DROP DEFAULT;
+ALTER TABLE games
+    ALTER COLUMN invited_roles TYPE user_roles[] USING invited_roles::text[]::user_roles[];
+ALTER TABLE games ALTER COLUMN invited_roles SET DEFAULT '{}'::user_roles[];
+ALTER TABLE club_oidc_providers ALTER COLUMN default_role DROP DEFAULT;
+ALTER TABLE club_oidc_providers
+    ALTER COLUMN default_role TYPE user_roles USING default_role::text::user_roles;
+ALTER TABLE club_oidc_providers ALTER COLUMN default_role SET DEFAULT 'player';
+
+DROP TYPE user_roles_old;
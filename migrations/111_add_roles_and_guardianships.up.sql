@@ -0,0 +1,18 @@
+-- enum values can't be used in the transaction that adds them, nothing below does
+ALTER TYPE user_roles ADD VALUE IF NOT EXISTS 'team_manager';
+ALTER TYPE user_roles ADD VALUE IF NOT EXISTS 'referee_manager';
+ALTER TYPE user_roles ADD VALUE IF NOT EXISTS 'referee';
+ALTER TYPE user_roles ADD VALUE IF NOT EXISTS 'parent';
+
+-- parents act on behalf of their minors, e.g. answering game invites
+CREATE TABLE IF NOT EXISTS guardianships (
+    parent_user_id VARCHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    minor_user_id VARCHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    PRIMARY KEY (parent_user_id, minor_user_id),
+    CHECK (parent_user_id <> minor_user_id)
+);
+
+CREATE INDEX IF NOT EXISTS guardianships_minor_user_id_idx ON guardianships(minor_user_id);
@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS referee_requests;
+DROP TYPE IF EXISTS referee_request_status;
+DROP TABLE IF EXISTS referee_slots;
+DROP TABLE IF EXISTS team_referee_defaults;
+DROP TYPE IF EXISTS referee_position;
@@ -0,0 +1,41 @@
+CREATE TYPE referee_position AS ENUM ('main', 'assistant', 'table_official');
+
+-- slots every new game of a team gets
+CREATE TABLE IF NOT EXISTS team_referee_defaults (
+    team_id TEXT NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    position referee_position NOT NULL,
+    count INTEGER NOT NULL CHECK (count > 0),
+
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    PRIMARY KEY (team_id, position)
+);
+
+CREATE TABLE IF NOT EXISTS referee_slots (
+    id VARCHAR(36) PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    game_id VARCHAR(36) NOT NULL REFERENCES games(id) ON DELETE CASCADE,
+    position referee_position NOT NULL,
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS referee_slots_game_id_idx ON referee_slots(game_id);
+
+CREATE TYPE referee_request_status AS ENUM ('pending', 'accepted', 'declined');
+
+-- a slot is filled once its request is accepted, declined requests are kept for the record
+CREATE TABLE IF NOT EXISTS referee_requests (
+    id VARCHAR(36) PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    slot_id VARCHAR(36) NOT NULL REFERENCES referee_slots(id) ON DELETE CASCADE,
+    referee_user_id VARCHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    requested_by_user_id VARCHAR(36) REFERENCES users(id) ON DELETE SET NULL,
+    status referee_request_status NOT NULL DEFAULT 'pending',
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    responded_at TIMESTAMPTZ
+);
+
+-- one referee per slot at a time
+CREATE UNIQUE INDEX IF NOT EXISTS referee_requests_open_slot_idx ON referee_requests(slot_id)
+    WHERE status <> 'declined';
+CREATE INDEX IF NOT EXISTS referee_requests_referee_user_id_idx ON referee_requests(referee_user_id);
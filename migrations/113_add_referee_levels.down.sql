@@ -0,0 +1,3 @@
+ALTER TABLE referee_slots DROP COLUMN IF EXISTS min_level;
+ALTER TABLE team_referee_defaults DROP COLUMN IF EXISTS min_level;
+DROP TABLE IF EXISTS referee_profiles;
@@ -0,0 +1,12 @@
+-- qualification of referees, slots may require a minimum
+CREATE TABLE IF NOT EXISTS referee_profiles (
+    user_id VARCHAR(36) PRIMARY KEY NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    level INTEGER NOT NULL DEFAULT 0 CHECK (level >= 0),
+
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+ALTER TABLE team_referee_defaults ADD COLUMN IF NOT EXISTS min_level INTEGER NOT NULL DEFAULT 0
+    CHECK (min_level >= 0);
+ALTER TABLE referee_slots ADD COLUMN IF NOT EXISTS min_level INTEGER NOT NULL DEFAULT 0
+    CHECK (min_level >= 0);
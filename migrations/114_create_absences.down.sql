@@ -0,0 +1,3 @@
+ALTER TABLE game_invites DROP COLUMN IF EXISTS decline_reason;
+DROP TABLE IF EXISTS absences;
+DROP TYPE IF EXISTS absence_reason;
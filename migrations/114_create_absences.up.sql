@@ -0,0 +1,20 @@
+CREATE TYPE absence_reason AS ENUM ('holiday', 'illness', 'injury', 'work', 'other');
+
+-- whole days, both ends included
+CREATE TABLE IF NOT EXISTS absences (
+    id VARCHAR(36) PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    user_id VARCHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    starts_on DATE NOT NULL,
+    ends_on DATE NOT NULL,
+    reason absence_reason NOT NULL,
+    note TEXT,
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    CHECK (ends_on >= starts_on)
+);
+
+CREATE INDEX IF NOT EXISTS absences_user_id_idx ON absences(user_id, starts_on);
+
+-- set when the app declined the invite, e.g. 'absent'
+ALTER TABLE game_invites ADD COLUMN IF NOT EXISTS decline_reason TEXT;
@@ -0,0 +1,4 @@
+-- sessions end server-side too, not just when the browser drops the cookie
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ NOT NULL
+    DEFAULT now() + INTERVAL '7 days';
+ALTER TABLE sessions ALTER COLUMN expires_at DROP DEFAULT;
@@ -0,0 +1,254 @@
+//! Operator tasks without an API route, used by the admin CLI: moving a club between databases
+//! and purging expired data.
+//!
+//! An export holds a club's rows table by table as JSON, secrets left out. Importing it needs the
+//! same schema version; users get random passwords they must change on their next log-in.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+
+use crate::auth::{login_throttle::purge_stale_failed_logins, utils::generate_token};
+
+const IMPORTED_PASSWORD_LEN: usize = 16;
+
+const CLUB_USERS: &str = "SELECT id FROM users WHERE club_id = $1";
+const CLUB_TEAMS: &str = "SELECT id FROM teams WHERE club_id = $1";
+const CLUB_GAMES: &str =
+    "SELECT g.id FROM games g JOIN teams t ON t.id = g.team_id WHERE t.club_id = $1";
+
+/// the tables holding a club's data and how to find its rows (`$1` is the club id), in an order
+/// that satisfies foreign keys on import
+fn club_tables() -> Vec<(&'static str, String)> {
+    vec![
+        ("clubs", "id = $1".to_string()),
+        ("users", "club_id = $1".to_string()),
+        ("role_assignments", format!("user_id IN ({})", CLUB_USERS)),
+        ("guardianships", format!("parent_user_id IN ({})", CLUB_USERS)),
+        ("referee_profiles", format!("user_id IN ({})", CLUB_USERS)),
+        ("absences", format!("user_id IN ({})", CLUB_USERS)),
+        ("service_invites", "club_id = $1".to_string()),
+        ("teams", "club_id = $1".to_string()),
+        (
+            "team_referee_defaults",
+            format!("team_id IN ({})", CLUB_TEAMS),
+        ),
+        (
+            "events",
+            "id IN (SELECT g.event_id FROM games g JOIN teams t ON t.id = g.team_id WHERE t.club_id = $1)"
+                .to_string(),
+        ),
+        ("games", format!("team_id IN ({})", CLUB_TEAMS)),
+        ("game_invites", format!("game_id IN ({})", CLUB_GAMES)),
+        ("referee_slots", format!("game_id IN ({})", CLUB_GAMES)),
+        (
+            "referee_requests",
+            format!(
+                "slot_id IN (SELECT id FROM referee_slots WHERE game_id IN ({}))",
+                CLUB_GAMES
+            ),
+        ),
+    ]
+}
+
+/// columns of `users` that don't leave the database
+const USER_SECRETS: &[&str] = &[
+    "password",
+    "totp_secret",
+    "totp_pending_secret",
+    "totp_enabled_at",
+    "totp_last_step",
+];
+
+#[derive(Serialize, Deserialize)]
+pub struct ClubExport {
+    /// the latest migration of the exporting database
+    pub schema_version: i64,
+    pub exported_at: DateTime<Utc>,
+    pub club_id: String,
+    pub tables: Vec<ExportedTable>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportedTable {
+    pub table: String,
+    pub rows: Vec<Value>,
+}
+
+#[derive(Serialize)]
+pub struct ImportedUser {
+    pub username: String,
+    /// random, to be changed on the first log-in
+    pub password: String,
+}
+
+#[derive(Serialize)]
+pub struct ImportedClub {
+    pub club_id: String,
+    pub users: Vec<ImportedUser>,
+}
+
+#[derive(Serialize)]
+pub struct PurgeReport {
+    pub sessions: u64,
+    pub password_reset_tokens: u64,
+    pub two_factor_challenges: u64,
+    pub api_tokens: u64,
+    pub failed_logins: u64,
+}
+
+pub async fn export_club(
+    pool: &PgPool,
+    club_id: &str,
+    schema_version: i64,
+) -> Result<ClubExport, String> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM clubs WHERE id = $1) AS "exists!""#,
+        club_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|err| err.to_string())?;
+    if !exists {
+        return Err(format!("no club with id {}", club_id));
+    }
+
+    let mut tables = vec![];
+    for (table, filter) in club_tables() {
+        let without_secrets = if table == "users" {
+            USER_SECRETS
+                .iter()
+                .map(|column| format!(" - '{}'", column))
+                .collect::<String>()
+        } else {
+            String::new()
+        };
+        let rows = sqlx::query_scalar::<_, Value>(&format!(
+            "SELECT coalesce(jsonb_agg(to_jsonb(t){}), '[]') FROM {} t WHERE {}",
+            without_secrets, table, filter
+        ))
+        .bind(club_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|err| format!("exporting {} failed: {}", table, err))?;
+        let Value::Array(rows) = rows else {
+            return Err(format!("exporting {} returned no list", table));
+        };
+        tables.push(ExportedTable {
+            table: table.to_string(),
+            rows,
+        });
+    }
+
+    Ok(ClubExport {
+        schema_version,
+        exported_at: Utc::now(),
+        club_id: club_id.to_string(),
+        tables,
+    })
+}
+
+/// All or nothing, ids are kept - a club that still exists is refused.
+pub async fn import_club(
+    pool: &PgPool,
+    export: ClubExport,
+    schema_version: i64,
+) -> Result<ImportedClub, String> {
+    if export.schema_version != schema_version {
+        return Err(format!(
+            "the export is from schema version {}, this database is at {}",
+            export.schema_version, schema_version
+        ));
+    }
+    let known_tables = club_tables();
+    if let Some(unknown) = export.tables.iter().find(|exported| {
+        !known_tables
+            .iter()
+            .any(|(table, _)| *table == exported.table)
+    }) {
+        return Err(format!("the export has an unknown table {}", unknown.table));
+    }
+
+    let mut users = vec![];
+    let mut tx = pool.begin().await.map_err(|err| err.to_string())?;
+    // in foreign key order, whatever the order in the file
+    for (table, _) in known_tables {
+        let Some(exported) = export
+            .tables
+            .iter()
+            .find(|exported| exported.table == table)
+        else {
+            continue;
+        };
+        let mut rows = exported.rows.clone();
+        if table == "users" {
+            for row in rows.iter_mut() {
+                let Value::Object(user) = row else {
+                    return Err("the export has a user that is no object".to_string());
+                };
+                let password = generate_token(IMPORTED_PASSWORD_LEN);
+                user.insert("password".to_string(), Value::String(password.clone()));
+                user.insert("must_reset_password".to_string(), Value::Bool(true));
+                users.push(ImportedUser {
+                    username: user
+                        .get("username")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_string(),
+                    password,
+                });
+            }
+        }
+        sqlx::query(&format!(
+            "INSERT INTO {0} SELECT * FROM jsonb_populate_recordset(NULL::{0}, $1)",
+            table
+        ))
+        .bind(Value::Array(rows))
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| format!("importing {} failed: {}", table, err))?;
+    }
+    tx.commit().await.map_err(|err| err.to_string())?;
+
+    Ok(ImportedClub {
+        club_id: export.club_id,
+        users,
+    })
+}
+
+/// Removes sessions, tokens and log-in failures that no longer have any effect.
+pub async fn purge_expired(pool: &PgPool) -> Result<PurgeReport, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let sessions = sqlx::query!(r#"DELETE FROM sessions WHERE expires_at < now()"#)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    let password_reset_tokens = sqlx::query!(
+        r#"DELETE FROM password_reset_tokens WHERE expires_at < now() OR used_at IS NOT NULL"#
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    let two_factor_challenges =
+        sqlx::query!(r#"DELETE FROM two_factor_challenges WHERE expires_at < now()"#)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+    let api_tokens = sqlx::query!(r#"DELETE FROM api_tokens WHERE expires_at < now()"#)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    let failed_logins = purge_stale_failed_logins(&mut tx).await?;
+
+    tx.commit().await?;
+
+    Ok(PurgeReport {
+        sessions,
+        password_reset_tokens,
+        two_factor_challenges,
+        api_tokens,
+        failed_logins,
+    })
+}
@@ -0,0 +1,359 @@
+//! Personal API tokens for scripts and integrations, sent as `Authorization: Bearer <token>`.
+//!
+//! A token acts as its owner, limited by its scopes: `read` allows every GET request a token
+//! may make at all, `<group>:read` / `<group>:write` allow one route group (the first path
+//! segment under `/api/user`, e.g. `teams`). Managing the account itself (password, email,
+//! 2FA, tokens, the club, deleting it) and resetting other members' credentials always needs a
+//! real log-in.
+
+use std::{fmt, str::FromStr};
+
+use axum::{
+    extract::{Path, State},
+    http::{Method, StatusCode},
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+use utoipa::{OpenApi, ToSchema};
+
+use crate::{
+    auth::utils::{generate_token, hash_token, AuthContext},
+    utils::{
+        api::AppState,
+        error::ApiError,
+        pagination::{ListParams, ListQuery, Paginated, SortKey, TIME_KEY_FORMAT},
+        validation::{Validate, ValidatedJson, Validator, MAX_NAME_LEN},
+    },
+};
+
+/// makes leaked tokens easy to find with secret scanners
+pub const TOKEN_PREFIX: &str = "spt_";
+const TOKEN_LEN: usize = 40;
+/// characters kept to tell tokens apart in listings
+const DISPLAYED_PREFIX_LEN: usize = 8;
+const DEFAULT_TTL_DAYS: i64 = 90;
+const MAX_TTL_DAYS: i64 = 365;
+
+/// route groups under `/api/user` tokens can be scoped to
+pub const TOKEN_ROUTE_GROUPS: &[&str] = &[
+    "users",
+    "teams",
+    "games",
+    "game-invites",
+    "roles",
+    "invites-to-club",
+    "guardianships",
+    "referee-assignments",
+    "absences",
+];
+
+/// routes inside the token route groups that manage accounts, and so need a real log-in whatever
+/// the scopes - `(group, first segment after it)`
+const TOKEN_DENIED_ROUTES: &[(&str, &str)] = &[
+    ("users", "delete-own"),
+    ("users", "reset-2fa"),
+    ("users", "force-password-reset"),
+    ("users", "create-password-reset"),
+    ("users", "unlock-login"),
+];
+
+pub fn api_token_router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/create", post(create_api_token))
+        .route("/list-own", get(list_own_api_tokens))
+        .route("/revoke/{id}", delete(revoke_api_token))
+        .with_state(state.clone())
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(create_api_token, list_own_api_tokens, revoke_api_token))]
+pub struct ApiTokenApi;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiTokenScope {
+    /// GET requests to every token route group
+    Read,
+    Group {
+        group: String,
+        write: bool,
+    },
+}
+
+impl FromStr for ApiTokenScope {
+    type Err = String;
+
+    fn from_str(scope: &str) -> Result<Self, Self::Err> {
+        if scope == "read" {
+            return Ok(ApiTokenScope::Read);
+        }
+        let invalid = || format!("Invalid scope: {}", scope);
+        let (group, access) = scope.split_once(':').ok_or_else(invalid)?;
+        if !TOKEN_ROUTE_GROUPS.contains(&group) {
+            return Err(invalid());
+        }
+        let write = match access {
+            "read" => false,
+            "write" => true,
+            _ => return Err(invalid()),
+        };
+        Ok(ApiTokenScope::Group {
+            group: group.to_string(),
+            write,
+        })
+    }
+}
+
+impl fmt::Display for ApiTokenScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiTokenScope::Read => write!(f, "read"),
+            ApiTokenScope::Group { group, write } => {
+                write!(f, "{}:{}", group, if *write { "write" } else { "read" })
+            }
+        }
+    }
+}
+
+/// `path` is relative to `/api/user`
+pub fn check_token_scopes(scopes: &[String], method: &Method, path: &str) -> Result<(), ApiError> {
+    let mut segments = path.trim_start_matches('/').split('/');
+    let group = segments.next().unwrap_or("");
+    let action = segments.next().unwrap_or("");
+    if !TOKEN_ROUTE_GROUPS.contains(&group) || TOKEN_DENIED_ROUTES.contains(&(group, action)) {
+        return Err(ApiError::Forbidden("Not available with API tokens".into()));
+    }
+    let read_only = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+
+    // scopes were validated on creation, anything unparseable grants nothing
+    let allowed = scopes
+        .iter()
+        .filter_map(|scope| scope.parse::<ApiTokenScope>().ok())
+        .any(|scope| match scope {
+            ApiTokenScope::Read => read_only,
+            ApiTokenScope::Group {
+                group: scope_group,
+                write,
+            } => scope_group == group && (write || read_only),
+        });
+
+    if !allowed {
+        return Err(ApiError::Forbidden(
+            "API token lacks the scope for this request".into(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateApiToken {
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// defaults to 90 days, at most a year
+    pub expires_in_days: Option<i64>,
+}
+
+impl Validate for CreateApiToken {
+    fn validate(&self, v: &mut Validator) {
+        v.length("name", &self.name, 1, MAX_NAME_LEN);
+        v.not_empty("scopes", &self.scopes);
+        for (i, scope) in self.scopes.iter().enumerate() {
+            if let Err(message) = scope.parse::<ApiTokenScope>() {
+                v.check(false, &format!("scopes[{}]", i), message);
+            }
+        }
+        if let Some(days) = self.expires_in_days {
+            v.range("expires_in_days", days, 1, MAX_TTL_DAYS);
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreatedApiToken {
+    id: String,
+    name: String,
+    /// only ever shown here
+    token: String,
+    scopes: Vec<String>,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiTokenListItem {
+    id: String,
+    name: String,
+    token_prefix: String,
+    scopes: Vec<String>,
+    expires_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    #[serde(skip)]
+    sort_key: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum ApiTokenSort {
+    #[default]
+    CreatedAt,
+    Name,
+    ExpiresAt,
+}
+
+impl SortKey for ApiTokenSort {
+    /// newest first
+    const DEFAULT_DESCENDING: bool = true;
+}
+
+#[utoipa::path(
+    post,
+    path = "/create",
+    tag = "api-tokens",
+    request_body = CreateApiToken,
+    responses((status = 201, body = CreatedApiToken)),
+)]
+pub async fn create_api_token(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    ValidatedJson(payload): ValidatedJson<CreateApiToken>,
+) -> Result<(StatusCode, Json<CreatedApiToken>), ApiError> {
+    let name = payload.name.trim();
+
+    let mut scopes = payload
+        .scopes
+        .iter()
+        .map(|scope| {
+            scope
+                .parse::<ApiTokenScope>()
+                .map(|scope| scope.to_string())
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ApiError::BadRequest)?;
+    scopes.sort();
+    scopes.dedup();
+
+    let ttl_days = payload.expires_in_days.unwrap_or(DEFAULT_TTL_DAYS);
+    let expires_at = Utc::now() + Duration::days(ttl_days);
+
+    let token = format!("{}{}", TOKEN_PREFIX, generate_token(TOKEN_LEN));
+    let token_prefix: String = token
+        .chars()
+        .take(TOKEN_PREFIX.len() + DISPLAYED_PREFIX_LEN)
+        .collect();
+
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO api_tokens (user_id, name, token_hash, token_prefix, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id
+        "#,
+        auth_ctx.user_id,
+        name,
+        hash_token(&token),
+        token_prefix,
+        &scopes,
+        expires_at
+    )
+    .fetch_one(&state.pg_pool)
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedApiToken {
+            id,
+            name: name.to_string(),
+            token,
+            scopes,
+            expires_at,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/list-own",
+    tag = "api-tokens",
+    params(ListParams),
+    responses((status = 200, body = Paginated<ApiTokenListItem>)),
+)]
+pub async fn list_own_api_tokens(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    ListQuery { page, .. }: ListQuery<ApiTokenSort>,
+) -> Result<(StatusCode, Json<Paginated<ApiTokenListItem>>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let tokens = sqlx::query_as!(
+        ApiTokenListItem,
+        r#"
+        SELECT id AS "id!", name AS "name!", token_prefix AS "token_prefix!",
+            scopes AS "scopes!", expires_at AS "expires_at!", last_used_at,
+            created_at AS "created_at!", sort_key AS "sort_key!"
+        FROM (
+            SELECT id, name, token_prefix, scopes, expires_at, last_used_at, created_at,
+            CASE $2
+                WHEN 'name' THEN name
+                WHEN 'expires_at' THEN to_char(expires_at AT TIME ZONE 'UTC', $3)
+                ELSE to_char(created_at AT TIME ZONE 'UTC', $3)
+            END AS sort_key
+            FROM api_tokens
+            WHERE user_id = $1
+        ) r
+        WHERE ($5::text IS NULL OR CASE WHEN $4
+            THEN (r.sort_key, r.id) < ($5, $6) ELSE (r.sort_key, r.id) > ($5, $6) END)
+        ORDER BY CASE WHEN $4 THEN r.sort_key END DESC, r.sort_key,
+            CASE WHEN $4 THEN r.id END DESC, r.id
+        LIMIT $7
+        "#,
+        auth_ctx.user_id,
+        page.sort(),
+        TIME_KEY_FORMAT,
+        page.descending,
+        page.after_key(),
+        page.after_id(),
+        page.fetch_limit()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"SELECT count(*) AS "count!" FROM api_tokens WHERE user_id = $1"#,
+        auth_ctx.user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let tokens = page.finish(tokens, total, |token| (&token.sort_key, &token.id));
+    Ok((StatusCode::OK, Json(tokens)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/revoke/{id}",
+    tag = "api-tokens",
+    params(("id" = String, Path, description = "token id")),
+    responses((status = 204, description = "token revoked")),
+)]
+pub async fn revoke_api_token(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let deleted = sqlx::query!(
+        r#"DELETE FROM api_tokens WHERE id = $1 AND user_id = $2"#,
+        id,
+        auth_ctx.user_id
+    )
+    .execute(&state.pg_pool)
+    .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("API token not found".into()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
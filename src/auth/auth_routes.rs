@@ -4,44 +4,91 @@ use axum::{
     response::{IntoResponse, Response},
     Extension, Json,
 };
-use axum_extra::extract::cookie::{Cookie, SameSite};
-use log::{debug, error};
+use log::debug;
 use rand::{
     distr::{Alphanumeric, SampleString},
     rng,
 };
 use serde::Deserialize;
-use time::{Duration, OffsetDateTime};
+use sqlx::PgConnection;
+use utoipa::ToSchema;
 
 use crate::{
-    auth::utils::{AuthContext, EXPIRED_EMPTY_COOKIE},
-    entities::{club::create_club, user::UserClean},
-    utils::api::{db_err_to_response, handle_unexpected_db_err, AppState},
+    auth::{
+        email::{parse_email, send_verification_email, set_pending_email},
+        login_throttle::{
+            begin_login_attempt, clear_failed_logins, end_login_attempt, normalize_identifier,
+            record_failed_login,
+        },
+        password::MAX_PASSWORD_LEN,
+        two_factor::{create_two_factor_challenge, two_factor_enabled},
+        utils::{passwords_match, session_cookie, AuthContext},
+    },
+    config::SessionConfig,
+    entities::club::create_club,
+    utils::{
+        api::AppState,
+        client_ip::ClientIp,
+        error::ApiError,
+        validation::{Validate, ValidatedJson, Validator, MAX_NAME_LEN, MAX_TEXT_LEN},
+    },
 };
 
+#[allow(dead_code)]
 #[derive(sqlx::FromRow)]
 pub struct SessionModel {
     pub id: String,
     pub user_id: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct LoginParams {
+    /// username or verified email address
     pub username: String,
     pub password: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct SignUpWithNewClubParams {
     pub username: String,
     pub password: String,
     pub club_title: String,
+    pub email: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct SignUpViaInviteParams {
     pub username: String,
     pub password: String,
+    pub email: Option<String>,
+}
+
+impl Validate for LoginParams {
+    fn validate(&self, v: &mut Validator) {
+        v.length("username", &self.username, 1, MAX_TEXT_LEN);
+        v.length("password", &self.password, 1, MAX_PASSWORD_LEN);
+    }
+}
+
+impl Validate for SignUpWithNewClubParams {
+    fn validate(&self, v: &mut Validator) {
+        v.username("username", &self.username);
+        v.password("password", &self.password);
+        v.length("club_title", &self.club_title, 1, MAX_NAME_LEN);
+        if let Some(email) = &self.email {
+            v.email("email", email);
+        }
+    }
+}
+
+impl Validate for SignUpViaInviteParams {
+    fn validate(&self, v: &mut Validator) {
+        v.username("username", &self.username);
+        v.password("password", &self.password);
+        if let Some(email) = &self.email {
+            v.email("email", email);
+        }
+    }
 }
 
 struct InviteModel {
@@ -50,16 +97,22 @@ struct InviteModel {
 
 // pub async fn sign_up_via_invite
 
+#[utoipa::path(
+    post,
+    path = "/sign-up-via-invite/{invite_id}",
+    tag = "auth",
+    request_body = SignUpViaInviteParams,
+    params(("invite_id" = String, Path, description = "from the invite link")),
+    responses((status = 201, description = "the new user's id, logged in", body = String)),
+)]
 pub async fn sign_up_via_invite(
     State(state): State<AppState>,
     Path(invite_id): Path<String>,
-    Json(payload): Json<SignUpViaInviteParams>,
-) -> Result<Response, (StatusCode, String)> {
-    let mut tx = state
-        .pg_pool
-        .begin()
-        .await
-        .map_err(handle_unexpected_db_err)?;
+    ValidatedJson(payload): ValidatedJson<SignUpViaInviteParams>,
+) -> Result<Response, ApiError> {
+    let email = payload.email.as_deref().map(parse_email).transpose()?;
+
+    let mut tx = state.pg_pool.begin().await?;
 
     let service_invite = sqlx::query_as!(
         InviteModel,
@@ -67,8 +120,7 @@ pub async fn sign_up_via_invite(
         invite_id,
     )
     .fetch_one(&mut *tx)
-    .await
-    .map_err(handle_unexpected_db_err)?;
+    .await?;
 
     let new_user = sqlx::query!(
         r#"INSERT INTO users (username, password, club_id) VALUES ($1, $2, $3) RETURNING id"#,
@@ -77,52 +129,44 @@ pub async fn sign_up_via_invite(
         service_invite.club_id
     )
     .fetch_one(&mut *tx)
-    .await
-    .map_err(handle_unexpected_db_err)?;
+    .await?;
 
-    // Create new session
-    let session_id = Alphanumeric.sample_string(&mut rng(), 16);
+    let headers = start_session(&mut tx, &new_user.id, &state.config.session).await?;
 
-    // Save to DB
-    // TODO: session TTL in DB same as expires in browser
-    let _ = sqlx::query!(
-        "INSERT INTO sessions (id, user_id) VALUES ($1, $2)",
-        session_id,
-        new_user.id
-    )
-    .execute(&mut *tx)
-    .await
-    .map_err(handle_unexpected_db_err)?;
+    if let Some(email) = &email {
+        set_pending_email(&mut tx, &new_user.id, email).await?;
+    }
 
-    let _ = tx.commit().await.map_err(handle_unexpected_db_err)?;
+    tx.commit().await?;
 
-    let cookie = Cookie::build(("session_id", session_id.clone()))
-        .secure(true)
-        .http_only(true)
-        .same_site(SameSite::Strict)
-        .expires(OffsetDateTime::now_utc() + Duration::days(7));
+    if let Some(email) = &email {
+        send_verification_email(&state, &new_user.id, email);
+    }
 
-    Ok((
-        StatusCode::CREATED,
-        [(SET_COOKIE, cookie.to_string())],
-        Json(new_user.id),
-    )
-        .into_response())
+    Ok((StatusCode::CREATED, headers, Json(new_user.id)).into_response())
 }
 
+#[utoipa::path(
+    post,
+    path = "/sign-up-with-new-club",
+    tag = "auth",
+    request_body = SignUpWithNewClubParams,
+    responses((status = 201, description = "the new user's id, logged in", body = String)),
+)]
 pub async fn sign_up_with_new_club(
     State(state): State<AppState>,
-    Json(payload): Json<SignUpWithNewClubParams>,
-) -> Result<(StatusCode, HeaderMap, Json<String>), (StatusCode, String)> {
-    let mut tx = state
-        .pg_pool
-        .begin()
-        .await
-        .map_err(handle_unexpected_db_err)?;
+    ValidatedJson(payload): ValidatedJson<SignUpWithNewClubParams>,
+) -> Result<(StatusCode, HeaderMap, Json<String>), ApiError> {
+    if !state.config.features.club_sign_up {
+        return Err(ApiError::Forbidden(
+            "Signing up with a new club is disabled".into(),
+        ));
+    }
+    let email = payload.email.as_deref().map(parse_email).transpose()?;
 
-    let created_club_id = create_club(&mut tx, &payload.club_title)
-        .await
-        .map_err(handle_unexpected_db_err)?;
+    let mut tx = state.pg_pool.begin().await?;
+
+    let created_club_id = create_club(&mut tx, &payload.club_title).await?;
 
     let new_user = sqlx::query!(
         r#"INSERT INTO users (username, password, club_id) VALUES ($1, $2, $3) RETURNING id"#,
@@ -131,46 +175,36 @@ pub async fn sign_up_with_new_club(
         created_club_id
     )
     .fetch_one(&mut *tx)
-    .await
-    .map_err(handle_unexpected_db_err)?;
+    .await?;
 
     let _ = sqlx::query!(
         r#"INSERT INTO role_assignments (user_id, role) VALUES ($1, 'club_admin') RETURNING id"#,
         new_user.id,
     )
     .fetch_one(&mut *tx)
-    .await
-    .map_err(handle_unexpected_db_err)?;
+    .await?;
 
-    // Create new session
-    let session_id = Alphanumeric.sample_string(&mut rng(), 16);
+    let headers = start_session(&mut tx, &new_user.id, &state.config.session).await?;
 
-    // Save to DB
-    // TODO: session TTL in DB same as expires in browser
-    let _ = sqlx::query!(
-        "INSERT INTO sessions (id, user_id) VALUES ($1, $2)",
-        session_id,
-        new_user.id
-    )
-    .execute(&mut *tx)
-    .await
-    .map_err(handle_unexpected_db_err)?;
-
-    let _ = tx.commit().await.map_err(handle_unexpected_db_err)?;
-    let cookie = Cookie::build(("session_id", session_id.clone()))
-        .secure(true)
-        .http_only(true)
-        .same_site(SameSite::None)
-        .domain("localhost")
-        // .domain("localhost:3333")
-        // .domain("localhost:5173")
-        .expires(OffsetDateTime::now_utc() + Duration::days(7));
-    let mut headers = HeaderMap::new();
-    headers.insert(SET_COOKIE, cookie.to_string().parse().unwrap());
+    if let Some(email) = &email {
+        set_pending_email(&mut tx, &new_user.id, email).await?;
+    }
+
+    tx.commit().await?;
+
+    if let Some(email) = &email {
+        send_verification_email(&state, &new_user.id, email);
+    }
 
     Ok((StatusCode::CREATED, headers, Json(new_user.id)))
 }
 
+#[utoipa::path(
+    post,
+    path = "/log-out",
+    tag = "account",
+    responses((status = 204, description = "logged out")),
+)]
 pub async fn log_out(
     State(state): State<AppState>,
     auth_ctx: Extension<AuthContext>,
@@ -178,77 +212,116 @@ pub async fn log_out(
     let _ = sqlx::query!("DELETE FROM sessions WHERE id = $1", auth_ctx.session_id,)
         .execute(&state.pg_pool)
         .await
+        // the cookie is cleared either way
         .map_err(|err| {
-            error!("{}", err);
             (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                [(SET_COOKIE, EXPIRED_EMPTY_COOKIE)],
-                "Unexpected Error",
+                [(SET_COOKIE, session_cookie(&state.config.session, None))],
+                ApiError::from(err),
             )
                 .into_response()
         })?;
 
-    Ok((StatusCode::NO_CONTENT, [(SET_COOKIE, EXPIRED_EMPTY_COOKIE)]).into_response())
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(SET_COOKIE, session_cookie(&state.config.session, None))],
+    )
+        .into_response())
 }
 
+#[utoipa::path(
+    post,
+    path = "/log-in",
+    tag = "auth",
+    request_body = LoginParams,
+    responses(
+        (status = 200, description = "logged in, the body is the user id, the session is a cookie", body = String, content_type = "text/plain"),
+        (status = 202, description = "a second factor is needed", body = super::two_factor::TwoFactorChallenge),
+    ),
+)]
 pub async fn log_in(
     State(state): State<AppState>,
-    Json(payload): Json<LoginParams>,
-) -> Result<Response, Response> {
-    let mut tx = state.pg_pool.begin().await.map_err(db_err_to_response)?;
+    ClientIp(ip): ClientIp,
+    ValidatedJson(payload): ValidatedJson<LoginParams>,
+) -> Result<Response, ApiError> {
+    let identifier = normalize_identifier(&payload.username);
+    begin_login_attempt(&state.pg_pool, &identifier, ip).await?;
+
+    let mut tx = state.pg_pool.begin().await?;
 
     debug!("logging in");
-    let username = payload.username;
-    let password = payload.password;
-    let user = sqlx::query_as!(
-        UserClean,
-        r#"SELECT id, username FROM users WHERE username = $1 AND password = $2"#,
-        username,
-        password
+    // an email address wins over a username spelled like it, new usernames can't contain `@`
+    // but older ones may
+    let user = sqlx::query!(
+        r#"
+        SELECT id, club_id, password FROM users
+        WHERE username = $1 OR lower(email) = lower($1)
+        ORDER BY lower(email) = lower($1) IS TRUE DESC
+        LIMIT 1
+        "#,
+        payload.username
     )
-    .fetch_one(&mut *tx)
-    .await
-    .map_err(|err| {
-        error!("Log in error, failed to get user w/ password: {:?}", err);
-        return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
-    })?;
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let password_matches = passwords_match(
+        user.as_ref().map(|user| user.password.as_str()),
+        &payload.password,
+    );
+
+    let user = match user {
+        Some(user) if password_matches => user,
+        user => {
+            let known_user = user
+                .as_ref()
+                .map(|user| (user.id.as_str(), user.club_id.as_str()));
+            record_failed_login(&mut tx, &identifier, ip, known_user).await?;
+            tx.commit().await?;
+
+            debug!("Log in error, wrong username or password");
+            return Err(ApiError::Unauthorized("Unauthorized".into()));
+        }
+    };
+
+    end_login_attempt(&mut tx, ip).await?;
+
+    // the identifier's failures are only forgotten once the second factor is right as well
+    if two_factor_enabled(&mut tx, &user.id).await? {
+        let challenge = create_two_factor_challenge(&mut tx, &user.id, Some(&identifier)).await?;
+        tx.commit().await?;
+
+        debug!("password ok, waiting for second factor of user {}", user.id);
+        return Ok((StatusCode::ACCEPTED, Json(challenge)).into_response());
+    }
+
+    clear_failed_logins(&mut tx, &identifier).await?;
+
+    let headers = start_session(&mut tx, &user.id, &state.config.session).await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, headers, user.id).into_response())
+}
 
-    // Create new session
+/// Stores a new session and returns the headers setting its cookie.
+pub async fn start_session(
+    conn: &mut PgConnection,
+    user_id: &str,
+    config: &SessionConfig,
+) -> Result<HeaderMap, ApiError> {
     let session_id = Alphanumeric.sample_string(&mut rng(), 16);
-    let cookie = Cookie::build(("session_id", session_id.clone()))
-        .secure(true)
-        // .domain("localhost")
-        .path("/")
-        .http_only(true)
-        .same_site(SameSite::Strict)
-        .expires(OffsetDateTime::now_utc() + Duration::days(7));
-    // Save to DB
-    // TODO: session TTL in DB same as expires in browser
     let _ = sqlx::query!(
-        "INSERT INTO sessions (id, user_id) VALUES ($1, $2)",
+        "INSERT INTO sessions (id, user_id, expires_at) VALUES ($1, $2, now() + make_interval(days => $3))",
         session_id,
-        user.id
+        user_id,
+        config.lifetime_days as i32
     )
-    .execute(&mut *tx)
-    .await
-    .map_err(|err| {
-        let error_response = serde_json::json!({
-        "status": "error",
-        "message": format!("Database error: { }", err),
-        })
-        .to_string();
-        error!("Log in error - failed to start session: {}", error_response);
-
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Unexpected error with login",
-        )
-            .into_response();
-    })?;
-
-    let _ = tx.commit().await.map_err(db_err_to_response)?;
+    .execute(&mut *conn)
+    .await?;
 
     let mut headers = HeaderMap::new();
-    headers.insert(SET_COOKIE, cookie.to_string().parse().unwrap());
-    return Ok((StatusCode::OK, headers, user.id).into_response());
+    headers.insert(
+        SET_COOKIE,
+        session_cookie(config, Some(session_id)).parse().unwrap(),
+    );
+    Ok(headers)
 }
@@ -0,0 +1,109 @@
+//! CSRF protection for cookie-authenticated requests.
+//!
+//! Every session has a synchronizer token (`GET /api/user/csrf-token`), which state-changing
+//! requests send back in the `X-CSRF-Token` header. Requests without the header are only accepted
+//! when the browser marked them as coming from an allowed origin.
+
+use axum::{
+    extract::State,
+    http::{
+        header::{ORIGIN, REFERER},
+        request::Parts,
+        Method, StatusCode,
+    },
+    Extension, Json,
+};
+use log::debug;
+use serde::Serialize;
+use subtle::ConstantTimeEq;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::utils::AuthContext,
+    config::origin_of,
+    utils::{api::AppState, error::ApiError},
+};
+
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+#[derive(Serialize, ToSchema)]
+pub struct CsrfToken {
+    csrf_token: String,
+}
+
+/// Origin of the page that sent the request, if the browser told us. Falls back to the Referer,
+/// which some browsers send instead of Origin on same-origin requests.
+fn request_origin(req: &Parts) -> Option<String> {
+    if let Some(origin) = req.headers.get(ORIGIN) {
+        // opaque origins (sandboxed iframes, file://) are sent as "null"
+        return Some(origin.to_str().unwrap_or("null").to_string());
+    }
+    req.headers
+        .get(REFERER)
+        .and_then(|referer| referer.to_str().ok())
+        .and_then(origin_of)
+}
+
+fn csrf_rejection(reason: &str) -> ApiError {
+    ApiError::Forbidden(reason.to_string())
+}
+
+pub fn check_csrf(
+    req: &Parts,
+    session_csrf_token: &str,
+    allowed_origins: &[String],
+) -> Result<(), ApiError> {
+    if matches!(req.method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return Ok(());
+    }
+
+    let origin = request_origin(req);
+    let origin_allowed = origin
+        .as_ref()
+        .map(|origin| allowed_origins.contains(origin));
+
+    if origin_allowed == Some(false) {
+        debug!("blocked cross-site request from {:?}", origin);
+        return Err(csrf_rejection("Cross-site request blocked"));
+    }
+
+    match req.headers.get(CSRF_HEADER) {
+        Some(token) => {
+            let token_matches: bool = token.as_bytes().ct_eq(session_csrf_token.as_bytes()).into();
+            if !token_matches {
+                return Err(csrf_rejection("Invalid CSRF token"));
+            }
+        }
+        // the fallback only works for browsers, other clients have to send the token
+        None if origin_allowed == Some(true) => {}
+        None => return Err(csrf_rejection("Missing CSRF token")),
+    }
+
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/csrf-token",
+    tag = "account",
+    responses((status = 200, body = CsrfToken)),
+)]
+pub async fn get_csrf_token(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+) -> Result<(StatusCode, Json<CsrfToken>), ApiError> {
+    let Some(session_id) = &auth_ctx.session_id else {
+        return Err(ApiError::BadRequest(
+            "Only sessions have a CSRF token".into(),
+        ));
+    };
+
+    let csrf_token = sqlx::query_scalar!(
+        r#"SELECT csrf_token FROM sessions WHERE id = $1"#,
+        session_id
+    )
+    .fetch_one(&state.pg_pool)
+    .await?;
+
+    Ok((StatusCode::OK, Json(CsrfToken { csrf_token })))
+}
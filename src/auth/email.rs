@@ -0,0 +1,269 @@
+//! Email addresses of users. A new address only becomes usable (log-in, password resets)
+//! after its owner followed the signed verification link sent to it.
+
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{signed_token, utils::AuthContext},
+    notifications::send_email_in_background,
+    utils::{
+        api::AppState,
+        error::ApiError,
+        validation::{Validate, ValidatedJson, Validator, MAX_TOKEN_LEN},
+    },
+};
+
+const VERIFY_EMAIL_PURPOSE: &str = "verify-email";
+const VERIFICATION_TTL_HOURS: i64 = 24;
+/// RFC 5321 limit for a forward-path
+const MAX_EMAIL_LEN: usize = 254;
+
+#[derive(Serialize, Deserialize)]
+struct VerifyEmailClaims {
+    user_id: String,
+    email: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ChangeEmail {
+    pub email: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct VerifyEmail {
+    pub token: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OwnEmail {
+    /// verified address, used for log-in and password resets
+    email: Option<String>,
+    verified_at: Option<DateTime<Utc>>,
+    /// requested address still waiting for verification
+    pending_email: Option<String>,
+}
+
+impl Validate for ChangeEmail {
+    fn validate(&self, v: &mut Validator) {
+        v.email("email", &self.email);
+    }
+}
+
+impl Validate for VerifyEmail {
+    fn validate(&self, v: &mut Validator) {
+        v.length("token", &self.token, 1, MAX_TOKEN_LEN);
+    }
+}
+
+/// syntax only, surrounding whitespace is ignored
+pub fn is_valid_email(email: &str) -> bool {
+    let email = email.trim();
+    email.len() <= MAX_EMAIL_LEN && email.parse::<lettre::Address>().is_ok()
+}
+
+/// Trims and syntax-checks an address. Case is kept, comparisons are case-insensitive.
+pub fn parse_email(email: &str) -> Result<String, ApiError> {
+    if !is_valid_email(email) {
+        return Err(ApiError::BadRequest("Invalid email address".into()));
+    }
+    Ok(email.trim().to_string())
+}
+
+/// Remembers `email` as the user's pending address. Send the link with [`send_verification_email`]
+/// once the surrounding transaction is committed.
+pub async fn set_pending_email(
+    conn: &mut PgConnection,
+    user_id: &str,
+    email: &str,
+) -> Result<(), ApiError> {
+    let taken = sqlx::query_scalar!(
+        r#"SELECT id FROM users WHERE lower(email) = lower($1) AND id <> $2"#,
+        email,
+        user_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if taken.is_some() {
+        return Err(ApiError::Conflict("Email address is already in use".into()));
+    }
+
+    sqlx::query!(
+        r#"UPDATE users SET pending_email = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2"#,
+        email,
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub fn send_verification_email(state: &AppState, user_id: &str, email: &str) {
+    let token = signed_token::sign(
+        &state.token_secret,
+        VERIFY_EMAIL_PURPOSE,
+        VerifyEmailClaims {
+            user_id: user_id.to_string(),
+            email: email.to_string(),
+        },
+        Duration::hours(VERIFICATION_TTL_HOURS),
+    );
+
+    send_email_in_background(
+        state,
+        email.to_string(),
+        "Confirm your email address".to_string(),
+        format!(
+            "Hi,\n\nplease confirm that this is your email address by opening this link:\n\n{}/verify-email?token={}\n\nThe link expires in {} hours. If you didn't add this address, just ignore this mail.\n",
+            state.config.server.public_url.trim_end_matches('/'),
+            token,
+            VERIFICATION_TTL_HOURS
+        ),
+    );
+}
+
+#[utoipa::path(
+    get,
+    path = "/email/get-own",
+    tag = "account",
+    responses((status = 200, body = OwnEmail)),
+)]
+pub async fn get_own_email(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+) -> Result<(StatusCode, Json<OwnEmail>), ApiError> {
+    let own_email = sqlx::query_as!(
+        OwnEmail,
+        r#"SELECT email, email_verified_at AS verified_at, pending_email FROM users WHERE id = $1"#,
+        auth_ctx.user_id
+    )
+    .fetch_one(&state.pg_pool)
+    .await?;
+
+    Ok((StatusCode::OK, Json(own_email)))
+}
+
+/// The current address stays in use until the new one is verified.
+#[utoipa::path(
+    post,
+    path = "/email/change",
+    tag = "account",
+    request_body = ChangeEmail,
+    responses((status = 202, description = "a verification link is sent")),
+)]
+pub async fn change_email(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    ValidatedJson(payload): ValidatedJson<ChangeEmail>,
+) -> Result<StatusCode, ApiError> {
+    let email = parse_email(&payload.email)?;
+
+    let mut tx = state.pg_pool.begin().await?;
+    set_pending_email(&mut tx, &auth_ctx.user_id, &email).await?;
+    tx.commit().await?;
+
+    send_verification_email(&state, &auth_ctx.user_id, &email);
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[utoipa::path(
+    post,
+    path = "/email/resend-verification",
+    tag = "account",
+    responses((status = 202, description = "a verification link is sent")),
+)]
+pub async fn resend_email_verification(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+) -> Result<StatusCode, ApiError> {
+    let pending_email = sqlx::query_scalar!(
+        r#"SELECT pending_email FROM users WHERE id = $1"#,
+        auth_ctx.user_id
+    )
+    .fetch_one(&state.pg_pool)
+    .await?;
+
+    let Some(pending_email) = pending_email else {
+        return Err(ApiError::NotFound(
+            "No email address awaiting verification".into(),
+        ));
+    };
+
+    send_verification_email(&state, &auth_ctx.user_id, &pending_email);
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/email/remove",
+    tag = "account",
+    responses((status = 204, description = "address removed")),
+)]
+pub async fn remove_email(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+) -> Result<StatusCode, ApiError> {
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET email = NULL, email_verified_at = NULL, pending_email = NULL, updated_at = CURRENT_TIMESTAMP
+        WHERE id = $1
+        "#,
+        auth_ctx.user_id
+    )
+    .execute(&state.pg_pool)
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Unauthenticated - the signed token is proof enough, and the link may be opened on another device.
+#[utoipa::path(
+    post,
+    path = "/verify-email",
+    tag = "auth",
+    request_body = VerifyEmail,
+    responses((status = 204, description = "address verified")),
+)]
+pub async fn verify_email(
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<VerifyEmail>,
+) -> Result<StatusCode, ApiError> {
+    let invalid_token = || ApiError::BadRequest("Invalid or expired verification link".into());
+
+    let claims: VerifyEmailClaims =
+        signed_token::verify(&state.token_secret, VERIFY_EMAIL_PURPOSE, &payload.token)
+            .ok_or_else(invalid_token)?;
+
+    // links for an address that was replaced or removed in the meantime are dead
+    let verified = sqlx::query!(
+        r#"
+        UPDATE users
+        SET email = pending_email, email_verified_at = now(), pending_email = NULL, updated_at = CURRENT_TIMESTAMP
+        WHERE id = $1 AND lower(pending_email) = lower($2)
+        "#,
+        claims.user_id,
+        claims.email
+    )
+    .execute(&state.pg_pool)
+    .await
+    .map_err(|err| match err.as_database_error() {
+        Some(db_err) if db_err.is_unique_violation() => {
+            ApiError::Conflict("Email address is already in use".into())
+        }
+        _ => ApiError::from(err),
+    })?;
+
+    if verified.rows_affected() == 0 {
+        return Err(invalid_token());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,336 @@
+//! Lets admins see the app as one of their users, for support. The admin's own session switches
+//! to the user for a limited time: handlers see the user in `AuthContext::user_id`, while
+//! `impersonator_user_id` keeps the admin. Every request made that way is written to the audit
+//! log, and responses carry the `X-Impersonated-By` header.
+
+use axum::{
+    extract::{Path, State},
+    http::{Method, StatusCode},
+    Extension, Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+use serde::Serialize;
+use serde_json::json;
+use sqlx::PgConnection;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{
+        middlewares::RequestMeta,
+        permissions::{actions::UserImpersonate, Authorized},
+        roles::GlobalRole,
+        utils::AuthContext,
+    },
+    entities::audit_log::{record_audit_event, AuditAction, AuditEvent},
+    utils::{api::AppState, error::ApiError},
+};
+
+/// hard limit, starting anew is needed afterwards
+pub const IMPERSONATION_MINUTES: i64 = 30;
+
+/// set on every response to an impersonated request, holds the admin's user id
+pub const IMPERSONATED_BY_HEADER: &str = "x-impersonated-by";
+
+/// Changes to the account itself are the user's alone. Matched as leading path segments, for all
+/// but reads.
+const IMPERSONATION_BLOCKED_PATHS: &[&str] = &[
+    "/password",
+    "/email",
+    "/2fa",
+    "/api-tokens",
+    "/push-subscriptions",
+    "/oidc",
+    "/users/delete-own",
+    "/clubs/delete-own",
+    "/impersonation/start",
+];
+
+#[derive(Serialize, ToSchema)]
+pub struct ImpersonationStatus {
+    impersonated_user_id: String,
+    impersonated_username: String,
+    expires_at: DateTime<Utc>,
+}
+
+pub fn check_impersonation_restrictions(method: &Method, path: &str) -> Result<(), ApiError> {
+    let read_only = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+    if !read_only
+        && IMPERSONATION_BLOCKED_PATHS
+            .iter()
+            .any(|blocked| starts_with_segments(path, blocked))
+    {
+        return Err(ApiError::Forbidden(
+            "Not allowed while impersonating".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// whether the segments of `prefix` are the first ones of `path`, so `/email` matches
+/// `/email/change` but not `/emails`
+fn starts_with_segments(path: &str, prefix: &str) -> bool {
+    let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+    prefix
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .all(|expected| segments.next() == Some(expected))
+}
+
+/// Written before the handler runs, so no impersonated action goes unrecorded.
+pub async fn record_impersonated_request(
+    conn: &mut PgConnection,
+    auth_ctx: &AuthContext,
+    method: &Method,
+    path: &str,
+) -> Result<(), sqlx::Error> {
+    record_audit_event(
+        conn,
+        AuditEvent {
+            details: json!({ "method": method.as_str(), "path": path }),
+            ..AuditEvent::by(
+                auth_ctx,
+                AuditAction::ImpersonatedRequest,
+                "user",
+                &auth_ctx.user_id,
+            )
+        },
+    )
+    .await
+}
+
+/// Ends an impersonation that ran out of time, called by the auth middleware.
+pub async fn expire_impersonation(
+    conn: &mut PgConnection,
+    session_id: &str,
+    admin_user_id: &str,
+    admin_club_id: &str,
+    impersonated_user_id: &str,
+    meta: &RequestMeta,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE sessions SET impersonated_user_id = NULL, impersonation_expires_at = NULL
+        WHERE id = $1
+        "#,
+        session_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    record_audit_event(
+        conn,
+        AuditEvent {
+            actor_user_id: Some(admin_user_id),
+            club_id: Some(admin_club_id),
+            action: AuditAction::ImpersonationStopped,
+            target_type: Some("user"),
+            target_id: Some(impersonated_user_id),
+            details: json!({ "reason": "expired" }),
+            before: None,
+            after: None,
+            request_id: Some(&meta.request_id),
+            ip: Some(&meta.ip),
+        },
+    )
+    .await
+}
+
+/// Global admins may impersonate anyone, super admins the users of their club. Only users with
+/// fewer rights than the admin can be impersonated, anything else would hand out rights: nobody
+/// can impersonate global admins, and super admins can't impersonate other super admins.
+async fn check_can_impersonate(
+    conn: &mut PgConnection,
+    auth_ctx: &AuthContext,
+    user_id: &str,
+) -> Result<(), ApiError> {
+    let is_global_admin = auth_ctx.global_roles.contains(&GlobalRole::Admin);
+    if user_id == auth_ctx.user_id {
+        return Err(ApiError::BadRequest(
+            "You can't impersonate yourself".into(),
+        ));
+    }
+
+    let target = sqlx::query!(
+        r#"
+        SELECT u.club_id, EXISTS (
+            SELECT 1 FROM global_role_assignments gra
+            WHERE gra.user_id = u.id AND gra.role = 'admin'
+        ) AS "is_global_admin!", EXISTS (
+            SELECT 1 FROM role_assignments ra
+            WHERE ra.user_id = u.id AND ra.role = 'super_admin'
+        ) AS "is_super_admin!"
+        FROM users u WHERE u.id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match target {
+        Some(target) if is_global_admin || target.club_id == auth_ctx.club_id => {
+            if target.is_global_admin {
+                return Err(ApiError::Forbidden(
+                    "Global admins can't be impersonated".into(),
+                ));
+            }
+            if target.is_super_admin && !is_global_admin {
+                return Err(ApiError::Forbidden(
+                    "Super admins can only be impersonated by global admins".into(),
+                ));
+            }
+            Ok(())
+        }
+        // users of other clubs don't exist, as far as club admins are concerned
+        _ => Err(ApiError::NotFound("User not found".into())),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/impersonation/start/{user_id}",
+    tag = "impersonation",
+    params(("user_id" = String, Path)),
+    responses((status = 201, body = ImpersonationStatus)),
+)]
+pub async fn start_impersonation(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<UserImpersonate>,
+    Path(user_id): Path<String>,
+) -> Result<(StatusCode, Json<ImpersonationStatus>), ApiError> {
+    let Some(session_id) = &auth_ctx.session_id else {
+        return Err(ApiError::Forbidden(
+            "Impersonation needs a regular log-in".into(),
+        ));
+    };
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    check_can_impersonate(&mut tx, &auth_ctx, &user_id).await?;
+
+    let expires_at = Utc::now() + Duration::minutes(IMPERSONATION_MINUTES);
+    sqlx::query!(
+        r#"
+        UPDATE sessions SET impersonated_user_id = $1, impersonation_expires_at = $2
+        WHERE id = $3
+        "#,
+        user_id,
+        expires_at,
+        session_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            details: json!({ "expires_at": expires_at }),
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::ImpersonationStarted,
+                "user",
+                &user_id,
+            )
+        },
+    )
+    .await?;
+
+    let impersonated_username =
+        sqlx::query_scalar!(r#"SELECT username FROM users WHERE id = $1"#, user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+    tx.commit().await?;
+
+    info!(
+        "user {} started impersonating {}",
+        auth_ctx.user_id, user_id
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ImpersonationStatus {
+            impersonated_user_id: user_id,
+            impersonated_username,
+            expires_at,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/impersonation/stop",
+    tag = "impersonation",
+    responses((status = 204, description = "back to the own account")),
+)]
+pub async fn stop_impersonation(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+) -> Result<StatusCode, ApiError> {
+    let (Some(session_id), Some(_)) = (&auth_ctx.session_id, &auth_ctx.impersonator_user_id) else {
+        return Err(ApiError::BadRequest("Not impersonating anyone".into()));
+    };
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE sessions SET impersonated_user_id = NULL, impersonation_expires_at = NULL
+        WHERE id = $1
+        "#,
+        session_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            details: json!({ "reason": "stopped" }),
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::ImpersonationStopped,
+                "user",
+                &auth_ctx.user_id,
+            )
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `null` when the session isn't impersonating anyone
+#[utoipa::path(
+    get,
+    path = "/impersonation/status",
+    tag = "impersonation",
+    responses(
+        (status = 200, description = "null when not impersonating", body = Option<ImpersonationStatus>),
+    ),
+)]
+pub async fn get_impersonation_status(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+) -> Result<(StatusCode, Json<Option<ImpersonationStatus>>), ApiError> {
+    if auth_ctx.impersonator_user_id.is_none() {
+        return Ok((StatusCode::OK, Json(None)));
+    }
+
+    let status = sqlx::query_as!(
+        ImpersonationStatus,
+        r#"
+        SELECT u.id AS impersonated_user_id, u.username AS impersonated_username,
+        s.impersonation_expires_at AS "expires_at!"
+        FROM sessions s JOIN users u ON u.id = s.impersonated_user_id
+        WHERE s.id = $1
+        "#,
+        auth_ctx.session_id
+    )
+    .fetch_optional(&state.pg_pool)
+    .await?;
+
+    Ok((StatusCode::OK, Json(status)))
+}
@@ -0,0 +1,378 @@
+//! Brute-force protection for `log_in`. Failures are counted per submitted username/email and per
+//! client IP - for unknown usernames as well, so the answers don't reveal which accounts exist.
+//! After a few failures every further attempt has to wait exponentially longer,
+//! and at a threshold the key is locked for a while. Wrong codes of the second factor count as
+//! failed log-ins too, until it succeeds, and so do wrong passwords or codes of logged-in users
+//! who have to confirm a sensitive change.
+
+use std::net::IpAddr;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension,
+};
+use chrono::{DateTime, Duration, Utc};
+use log::warn;
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
+
+use crate::{
+    auth::{password::check_can_manage_user, utils::AuthContext},
+    entities::audit_log::{record_audit_event, AuditAction, AuditEvent},
+    utils::{api::AppState, error::ApiError},
+};
+
+const IDENTIFIER_KEY: &str = "identifier";
+const IP_KEY: &str = "ip";
+
+/// failures older than this are forgotten
+const FAILURE_WINDOW_MINUTES: i64 = 15;
+const LOCKOUT_MINUTES: i64 = 15;
+const BASE_DELAY_SECONDS: i64 = 1;
+const MAX_DELAY_SECONDS: i64 = 60;
+
+struct ThrottlePolicy {
+    key_kind: &'static str,
+    /// failures before delays kick in
+    delay_after: i32,
+    /// failures that lock the key for `LOCKOUT_MINUTES`
+    lock_after: i32,
+}
+
+const IDENTIFIER_POLICY: ThrottlePolicy = ThrottlePolicy {
+    key_kind: IDENTIFIER_KEY,
+    delay_after: 3,
+    lock_after: 10,
+};
+
+/// looser, since clubs often share one IP (club house wifi, mobile carrier NAT)
+const IP_POLICY: ThrottlePolicy = ThrottlePolicy {
+    key_kind: IP_KEY,
+    delay_after: 20,
+    lock_after: 100,
+};
+
+fn policy_for(key_kind: &str) -> &'static ThrottlePolicy {
+    if key_kind == IP_KEY {
+        &IP_POLICY
+    } else {
+        &IDENTIFIER_POLICY
+    }
+}
+
+/// 1s, 2s, 4s, ... up to a minute
+fn delay_after_failures(policy: &ThrottlePolicy, failure_count: i32) -> Duration {
+    let exponent = (failure_count - policy.delay_after).clamp(0, 16) as u32;
+    Duration::seconds((BASE_DELAY_SECONDS << exponent).min(MAX_DELAY_SECONDS))
+}
+
+pub fn normalize_identifier(identifier: &str) -> String {
+    identifier.trim().to_lowercase()
+}
+
+/// Counts the attempt for the username/email and the IP before the password is checked, in one
+/// statement, so concurrent guesses can't slip past the limits. Rejects it with 429 while either
+/// key is delayed or locked. A success takes the attempt back via `end_login_attempt` and
+/// `clear_failed_logins`.
+pub async fn begin_login_attempt(
+    pool: &PgPool,
+    identifier: &str,
+    ip: IpAddr,
+) -> Result<(), ApiError> {
+    let counted = sqlx::query_scalar!(
+        r#"
+        INSERT INTO failed_logins (key_kind, key, failure_count, last_failed_at)
+        VALUES ($1, $2, 1, now()), ($3, $4, 1, now())
+        ON CONFLICT (key_kind, key) DO UPDATE SET
+            failure_count = CASE
+                WHEN failed_logins.last_failed_at < now() - make_interval(mins => $5) THEN 1
+                ELSE failed_logins.failure_count + 1
+            END,
+            last_failed_at = now()
+        WHERE NOT (
+            COALESCE(failed_logins.locked_until > now(), false)
+            OR (
+                failed_logins.last_failed_at >= now() - make_interval(mins => $5)
+                AND (
+                    failed_logins.failure_count >= CASE failed_logins.key_kind
+                        WHEN $3 THEN $8::int ELSE $9::int END
+                    OR (
+                        failed_logins.failure_count >= CASE failed_logins.key_kind
+                            WHEN $3 THEN $6::int ELSE $7::int END
+                        AND failed_logins.last_failed_at + make_interval(secs => LEAST(
+                            $10::float8 * power(2::float8, LEAST(
+                                failed_logins.failure_count - CASE failed_logins.key_kind
+                                    WHEN $3 THEN $6::int ELSE $7::int END,
+                                16
+                            )),
+                            $11::float8
+                        )) > now()
+                    )
+                )
+            )
+        )
+        RETURNING key_kind
+        "#,
+        IDENTIFIER_KEY,
+        identifier,
+        IP_KEY,
+        ip.to_string(),
+        FAILURE_WINDOW_MINUTES as i32,
+        IP_POLICY.delay_after,
+        IDENTIFIER_POLICY.delay_after,
+        IP_POLICY.lock_after,
+        IDENTIFIER_POLICY.lock_after,
+        BASE_DELAY_SECONDS as f64,
+        MAX_DELAY_SECONDS as f64
+    )
+    .fetch_all(pool)
+    .await?;
+
+    if counted.len() == 2 {
+        return Ok(());
+    }
+    // the key that refused the attempt is unchanged, its entry tells how long to wait
+    let retry_after = retry_after(pool, identifier, ip)
+        .await?
+        .unwrap_or_else(|| Duration::seconds(BASE_DELAY_SECONDS));
+    Err(too_many_attempts(retry_after))
+}
+
+/// `begin_login_attempt` for a logged-in user who has to prove who they are again, e.g. before
+/// switching 2FA off. Counts against their username, so a stolen session can't be used to guess
+/// passwords or codes faster than a log-in could. Returns the identifier for
+/// `record_failed_login`/`clear_failed_logins`.
+pub async fn begin_reauthentication(
+    pool: &PgPool,
+    user_id: &str,
+    ip: IpAddr,
+) -> Result<String, ApiError> {
+    let identifier = sqlx::query_scalar!(
+        r#"SELECT lower(username) AS "identifier!" FROM users WHERE id = $1"#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    begin_login_attempt(pool, &identifier, ip).await?;
+
+    Ok(identifier)
+}
+
+/// how long until neither the username/email nor the IP is delayed or locked
+async fn retry_after(
+    pool: &PgPool,
+    identifier: &str,
+    ip: IpAddr,
+) -> Result<Option<Duration>, ApiError> {
+    let entries = sqlx::query!(
+        r#"
+        SELECT key_kind, failure_count, last_failed_at, locked_until FROM failed_logins
+        WHERE (key_kind = $1 AND key = $2) OR (key_kind = $3 AND key = $4)
+        "#,
+        IDENTIFIER_KEY,
+        identifier,
+        IP_KEY,
+        ip.to_string()
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let now = Utc::now();
+    let retry_at = entries
+        .iter()
+        .filter_map(|entry| {
+            if let Some(locked_until) = entry.locked_until.filter(|until| *until > now) {
+                return Some(locked_until);
+            }
+            let policy = policy_for(&entry.key_kind);
+            let forgotten = entry.last_failed_at < now - Duration::minutes(FAILURE_WINDOW_MINUTES);
+            if forgotten || entry.failure_count < policy.delay_after {
+                return None;
+            }
+            Some(entry.last_failed_at + delay_after_failures(policy, entry.failure_count))
+        })
+        .filter(|retry_at| *retry_at > now)
+        .max();
+
+    Ok(retry_at.map(|retry_at| retry_at - now))
+}
+
+fn too_many_attempts(retry_after: Duration) -> ApiError {
+    ApiError::TooManyRequests {
+        message: "Too many failed log-in attempts, please try again later".into(),
+        // rounded up, so clients retrying right on time aren't rejected again
+        retry_after_seconds: (retry_after.num_milliseconds() + 999) / 1000,
+    }
+}
+
+/// The attempt turned out to be a failure, it was already counted by `begin_login_attempt`.
+/// Locks whichever key reached its threshold.
+/// `user` is the account the identifier belongs to, if any - it's only used for the audit entry.
+pub async fn record_failed_login(
+    conn: &mut PgConnection,
+    identifier: &str,
+    ip: IpAddr,
+    user: Option<(&str, &str)>,
+) -> Result<(), sqlx::Error> {
+    let ip = ip.to_string();
+
+    for (policy, key) in [(&IDENTIFIER_POLICY, identifier), (&IP_POLICY, ip.as_str())] {
+        let locked_until: DateTime<Utc> = Utc::now() + Duration::minutes(LOCKOUT_MINUTES);
+        let failure_count = sqlx::query_scalar!(
+            r#"
+            UPDATE failed_logins SET locked_until = $1
+            WHERE key_kind = $2 AND key = $3 AND failure_count >= $4
+                AND (locked_until IS NULL OR locked_until < now())
+            RETURNING failure_count
+            "#,
+            locked_until,
+            policy.key_kind,
+            key,
+            policy.lock_after
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        let Some(failure_count) = failure_count else {
+            continue;
+        };
+
+        warn!(
+            "log-in locked until {} for {} {} after {} failures",
+            locked_until, policy.key_kind, key, failure_count
+        );
+
+        let (target_user_id, club_id) = match (policy.key_kind, user) {
+            (IDENTIFIER_KEY, Some((user_id, club_id))) => (Some(user_id), Some(club_id)),
+            _ => (None, None),
+        };
+        record_audit_event(
+            conn,
+            AuditEvent {
+                actor_user_id: None,
+                club_id,
+                action: AuditAction::LoginLocked,
+                target_type: Some(if target_user_id.is_some() {
+                    "user"
+                } else {
+                    policy.key_kind
+                }),
+                target_id: target_user_id.or(Some(key)),
+                details: json!({
+                    "key_kind": policy.key_kind,
+                    "failure_count": failure_count,
+                    "locked_until": locked_until,
+                }),
+                before: None,
+                after: None,
+                request_id: None,
+                ip: Some(&ip),
+            },
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// The attempt turned out not to be a failure, so the IP doesn't count it. Its other failures are
+/// kept, one valid account must not reset the limit for guessing others.
+pub async fn end_login_attempt(conn: &mut PgConnection, ip: IpAddr) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE failed_logins SET failure_count = failure_count - 1
+        WHERE key_kind = $1 AND key = $2 AND failure_count > 0
+        "#,
+        IP_KEY,
+        ip.to_string()
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// A completed log-in - with the second factor, if the user has one - forgets the failures for
+/// the identifier.
+pub async fn clear_failed_logins(
+    conn: &mut PgConnection,
+    identifier: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM failed_logins WHERE key_kind = $1 AND key = $2"#,
+        IDENTIFIER_KEY,
+        identifier
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Forgets the failures for the user's username and email address.
+pub async fn unlock_login(conn: &mut PgConnection, user_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM failed_logins
+        WHERE key_kind = $1 AND key IN (
+            SELECT lower(username) FROM users WHERE id = $2
+            UNION SELECT lower(email) FROM users WHERE id = $2 AND email IS NOT NULL
+        )
+        "#,
+        IDENTIFIER_KEY,
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Failures outside the window that don't lock anything anymore.
+pub async fn purge_stale_failed_logins(conn: &mut PgConnection) -> Result<u64, sqlx::Error> {
+    let purged = sqlx::query!(
+        r#"
+        DELETE FROM failed_logins
+        WHERE last_failed_at < now() - make_interval(mins => $1)
+            AND (locked_until IS NULL OR locked_until < now())
+        "#,
+        FAILURE_WINDOW_MINUTES as i32
+    )
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    Ok(purged)
+}
+
+/// Lifts delays and lockouts on the user's username and email address.
+#[utoipa::path(
+    post,
+    path = "/users/unlock-login/{id}",
+    tag = "users",
+    params(("id" = String, Path, description = "user id")),
+    responses((status = 204, description = "log-in unlocked")),
+)]
+pub async fn unlock_user_login(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    Path(user_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    check_can_manage_user(&state.pg_pool, &auth_ctx, &user_id).await?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    unlock_login(&mut tx, &user_id).await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent::by(&auth_ctx, AuditAction::LoginUnlocked, "user", &user_id),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
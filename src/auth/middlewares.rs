@@ -1,17 +1,34 @@
 use axum::{
     extract::{Request, State},
-    http::{header::SET_COOKIE, HeaderMap, HeaderValue, StatusCode},
+    http::{
+        header::{AUTHORIZATION, SET_COOKIE},
+        request::Parts,
+        HeaderMap, HeaderValue,
+    },
     middleware::Next,
     response::{IntoResponse, Response},
 };
 use axum_extra::extract::cookie::CookieJar;
+use chrono::{DateTime, Utc};
 use log::{debug, error};
 use sqlx::prelude::FromRow;
 
 use crate::{
     auth::{
+        api_tokens::{check_token_scopes, TOKEN_PREFIX},
+        csrf::check_csrf,
+        impersonation::{
+            check_impersonation_restrictions, expire_impersonation, record_impersonated_request,
+            IMPERSONATED_BY_HEADER,
+        },
+        password::PASSWORD_RESET_ALLOWED_PATHS,
         roles::{GlobalRole, Role},
-        utils::{AuthContext, EXPIRED_EMPTY_COOKIE},
+        two_factor::{two_factor_required, TWO_FACTOR_ENROLMENT_ALLOWED_PATHS},
+        utils::{hash_token, session_cookie, AuthContext, SESSION_COOKIE},
+    },
+    utils::{
+        client_ip::ClientIp,
+        error::{ApiError, REQUEST_ID},
     },
     AppState,
 };
@@ -23,71 +40,354 @@ pub struct UserWithSessionModel {
     pub club_id: String,
     pub roles: Option<Vec<Role>>,
     pub global_roles: Option<Vec<GlobalRole>>,
+    pub must_reset_password: bool,
+    pub require_admin_2fa: bool,
+    pub two_factor_enabled: bool,
+    pub csrf_token: String,
+    pub impersonated_user_id: Option<String>,
+    pub impersonation_expires_at: Option<DateTime<Utc>>,
+}
+
+/// the user an admin's session currently acts as
+#[derive(FromRow)]
+pub struct ImpersonatedUserModel {
+    pub user_id: String,
+    pub club_id: String,
+    pub roles: Option<Vec<Role>>,
+    pub global_roles: Option<Vec<GlobalRole>>,
+}
+
+#[derive(FromRow)]
+pub struct UserWithApiTokenModel {
+    pub user_id: String,
+    pub api_token_id: String,
+    pub scopes: Vec<String>,
+    pub club_id: String,
+    pub roles: Option<Vec<Role>>,
+    pub global_roles: Option<Vec<GlobalRole>>,
+    pub must_reset_password: bool,
+    pub require_admin_2fa: bool,
+    pub two_factor_enabled: bool,
+}
+
+/// `Authorization: Bearer spt_...`, cookies of a browser are ignored if it's present
+fn bearer_token(req: &Parts) -> Option<&str> {
+    req.headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| token.starts_with(TOKEN_PREFIX))
+}
+
+/// where a request came from, copied into the `AuthContext`
+pub struct RequestMeta {
+    pub request_id: String,
+    pub ip: String,
 }
 
-pub async fn cookie_auth_middleware(
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Takes the proxy's `X-Request-Id` if it's sane, otherwise makes one up.
+fn request_id(headers: &HeaderMap) -> String {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// Gives every request an id, available via `REQUEST_ID` while it's handled. It ends up in
+/// error bodies, the audit log and the `X-Request-Id` response header.
+pub async fn request_id_middleware(req: Request, next: Next) -> Response {
+    let id = request_id(req.headers());
+    let mut res = REQUEST_ID.scope(id.clone(), next.run(req)).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    res
+}
+
+/// Accepts a personal API token or the session cookie and puts the resulting `AuthContext`
+/// into the request extensions.
+pub async fn auth_middleware(
     State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
     jar: CookieJar,
-    mut req: Request,
+    req: Request,
     next: Next,
-) -> Result<Response, (Response)> {
+) -> Result<Response, Response> {
+    let (mut parts, body) = req.into_parts();
+
+    let meta = RequestMeta {
+        request_id: REQUEST_ID
+            .try_with(String::clone)
+            .unwrap_or_else(|_| request_id(&parts.headers)),
+        ip: client_ip.to_string(),
+    };
+    let auth_context = match bearer_token(&parts) {
+        Some(token) => api_token_auth_context(&state, token, &parts, meta).await?,
+        None => session_auth_context(&state, &jar, &parts, meta).await?,
+    };
+
+    let impersonator = auth_context.impersonator_user_id.clone();
+    match &impersonator {
+        Some(_) => {
+            // blocked attempts are recorded as well
+            let mut conn = state.pg_pool.acquire().await.map_err(ApiError::from)?;
+            record_impersonated_request(&mut conn, &auth_context, &parts.method, parts.uri.path())
+                .await
+                .map_err(ApiError::from)?;
+            check_impersonation_restrictions(&parts.method, parts.uri.path())?;
+        }
+        None => check_account_restrictions(&auth_context, parts.uri.path())?,
+    }
+
+    // the span is opened by `trace_middleware`
+    let span = tracing::Span::current();
+    span.record("user_id", auth_context.user_id.as_str());
+    span.record("club_id", auth_context.club_id.as_str());
+
+    parts.extensions.insert(auth_context);
+    let mut res = next.run(Request::from_parts(parts, body)).await;
+
+    if let Some(value) = impersonator.and_then(|id| HeaderValue::from_str(&id).ok()) {
+        res.headers_mut().insert(IMPERSONATED_BY_HEADER, value);
+    }
+
+    Ok(res)
+}
+
+async fn api_token_auth_context(
+    state: &AppState,
+    token: &str,
+    req: &Parts,
+    meta: RequestMeta,
+) -> Result<AuthContext, ApiError> {
+    debug!("api token auth");
+
+    let user_with_token = sqlx::query_as!(
+        UserWithApiTokenModel,
+        r#"
+        SELECT u.id as user_id, t.id as api_token_id, t.scopes, u.club_id as club_id, u.must_reset_password,
+        c.require_admin_2fa, u.totp_enabled_at IS NOT NULL AS "two_factor_enabled!",
+        COALESCE(array_agg(ra.role) FILTER (WHERE ra.role IS NOT NULL), '{}') AS "roles: Vec<Role>",
+        COALESCE(array_agg(gra.role) FILTER (WHERE gra.role IS NOT NULL), '{}') AS "global_roles: Vec<GlobalRole>"
+        FROM users u
+        LEFT JOIN role_assignments ra on ra.user_id = u.id
+        LEFT JOIN global_role_assignments gra ON gra.user_id = u.id
+        JOIN clubs c ON c.id = u.club_id
+        JOIN api_tokens t ON u.id = t.user_id WHERE t.token_hash = $1 AND t.expires_at > now()
+        GROUP BY (u.id, t.id, c.id)
+        "#,
+        hash_token(token)
+    )
+    .fetch_optional(&state.pg_pool)
+    .await?;
+
+    let Some(user_with_token) = user_with_token else {
+        return Err(ApiError::Unauthorized(
+            "Invalid or expired API token".into(),
+        ));
+    };
+
+    // no CSRF check - browsers never attach bearer tokens on their own
+    check_token_scopes(&user_with_token.scopes, &req.method, req.uri.path())?;
+
+    sqlx::query!(
+        r#"UPDATE api_tokens SET last_used_at = now() WHERE id = $1"#,
+        user_with_token.api_token_id
+    )
+    .execute(&state.pg_pool)
+    .await?;
+
+    let roles = user_with_token.roles.unwrap_or(vec![]);
+    let global_roles = user_with_token.global_roles.unwrap_or(vec![]);
+
+    let must_enrol_two_factor = !user_with_token.two_factor_enabled
+        && two_factor_required(&roles, user_with_token.require_admin_2fa);
+
+    Ok(AuthContext {
+        global_roles,
+        roles,
+        user_id: user_with_token.user_id,
+        impersonator_user_id: None,
+        club_id: user_with_token.club_id,
+        session_id: None,
+        api_token_id: Some(user_with_token.api_token_id),
+        must_reset_password: user_with_token.must_reset_password,
+        must_enrol_two_factor,
+        request_id: meta.request_id,
+        ip: Some(meta.ip),
+    })
+}
+
+async fn session_auth_context(
+    state: &AppState,
+    jar: &CookieJar,
+    req: &Parts,
+    meta: RequestMeta,
+) -> Result<AuthContext, Response> {
     debug!("cookie middleware called");
 
-    let Some(cookie) = jar.get("session_id") else {
+    let Some(cookie) = jar.get(SESSION_COOKIE) else {
         debug!("cookie NOT found");
 
         // err -> 401, not allowed,
         // this is an API middleware
         // for direct client-facing-routes, we may want to redirect to the login page (or we do it in the FE anyway)
 
-        return Err((StatusCode::UNAUTHORIZED, "Not logged in").into_response());
+        return Err(ApiError::Unauthorized("Not logged in".into()).into());
     };
 
-    debug!("cookie found {}", cookie.value());
-
     let user_with_session = sqlx::query_as!(
         UserWithSessionModel,
         r#"
-        SELECT u.id as user_id, s.id as session_id, u.club_id as club_id,
+        SELECT u.id as user_id, s.id as session_id, s.csrf_token, s.impersonated_user_id,
+        s.impersonation_expires_at, u.club_id as club_id, u.must_reset_password,
+        c.require_admin_2fa, u.totp_enabled_at IS NOT NULL AS "two_factor_enabled!",
         COALESCE(array_agg(ra.role) FILTER (WHERE ra.role IS NOT NULL), '{}') AS "roles: Vec<Role>", 
         COALESCE(array_agg(gra.role) FILTER (WHERE gra.role IS NOT NULL), '{}') AS "global_roles: Vec<GlobalRole>"
         FROM users u
         LEFT JOIN role_assignments ra on ra.user_id = u.id
         LEFT JOIN global_role_assignments gra ON gra.user_id = u.id
-        JOIN sessions s ON u.id = s.user_id WHERE s.id = $1
-        GROUP BY (u.id, s.id)
+        JOIN clubs c ON c.id = u.club_id
+        JOIN sessions s ON u.id = s.user_id WHERE s.id = $1 AND s.expires_at > now()
+        GROUP BY (u.id, s.id, c.id)
         ;
         "#,
         cookie.value()
     )
     .fetch_one(&state.pg_pool)
     .await
-    // .map_err(handle_unexpected_db_err)?;
     .map_err(|err| {
         error!("Error in user cookie middleware: {}", err);
         // force-expire given bad cookie
 
-        return (
-            StatusCode::UNAUTHORIZED,
-            [(SET_COOKIE, EXPIRED_EMPTY_COOKIE)],
-            "Unauthorized",
+        (
+            [(SET_COOKIE, session_cookie(&state.config.session, None))],
+            ApiError::Unauthorized("Unauthorized".into()),
         )
-            .into_response();
+            .into_response()
     })?;
 
+    check_csrf(
+        req,
+        &user_with_session.csrf_token,
+        &state.config.allowed_origins(),
+    )?;
+
+    if let Some(impersonated_user_id) = &user_with_session.impersonated_user_id {
+        return impersonation_auth_context(state, &user_with_session, impersonated_user_id, meta)
+            .await
+            .map_err(Response::from);
+    }
+
     let roles = user_with_session.roles.unwrap_or(vec![]);
     let global_roles = user_with_session.global_roles.unwrap_or(vec![]);
 
-    let auth_context = AuthContext {
+    let must_enrol_two_factor = !user_with_session.two_factor_enabled
+        && two_factor_required(&roles, user_with_session.require_admin_2fa);
+
+    Ok(AuthContext {
         global_roles,
         roles,
         user_id: user_with_session.user_id,
+        impersonator_user_id: None,
         club_id: user_with_session.club_id,
-        session_id: user_with_session.session_id,
-    };
+        session_id: Some(user_with_session.session_id),
+        api_token_id: None,
+        must_reset_password: user_with_session.must_reset_password,
+        must_enrol_two_factor,
+        request_id: meta.request_id,
+        ip: Some(meta.ip),
+    })
+}
+
+/// Acts as the impersonated user. Their account restrictions don't apply, the admin can't
+/// change their password or 2FA setup anyway.
+async fn impersonation_auth_context(
+    state: &AppState,
+    user_with_session: &UserWithSessionModel,
+    impersonated_user_id: &str,
+    meta: RequestMeta,
+) -> Result<AuthContext, ApiError> {
+    let expired = user_with_session
+        .impersonation_expires_at
+        .is_none_or(|expires_at| expires_at <= Utc::now());
+    if expired {
+        let mut conn = state.pg_pool.acquire().await?;
+        expire_impersonation(
+            &mut conn,
+            &user_with_session.session_id,
+            &user_with_session.user_id,
+            &user_with_session.club_id,
+            impersonated_user_id,
+            &meta,
+        )
+        .await?;
 
-    req.extensions_mut().insert(auth_context);
-    let res = next.run(req).await;
+        // not silently continuing as the admin, the request was meant for the other user
+        return Err(ApiError::Forbidden("Impersonation has expired".into()));
+    }
 
-    Ok(res)
+    let impersonated_user = sqlx::query_as!(
+        ImpersonatedUserModel,
+        r#"
+        SELECT u.id as user_id, u.club_id as club_id,
+        COALESCE(array_agg(ra.role) FILTER (WHERE ra.role IS NOT NULL), '{}') AS "roles: Vec<Role>",
+        COALESCE(array_agg(gra.role) FILTER (WHERE gra.role IS NOT NULL), '{}') AS "global_roles: Vec<GlobalRole>"
+        FROM users u
+        LEFT JOIN role_assignments ra on ra.user_id = u.id
+        LEFT JOIN global_role_assignments gra ON gra.user_id = u.id
+        WHERE u.id = $1
+        GROUP BY u.id
+        "#,
+        impersonated_user_id
+    )
+    .fetch_one(&state.pg_pool)
+    .await?;
+
+    Ok(AuthContext {
+        global_roles: impersonated_user.global_roles.unwrap_or(vec![]),
+        roles: impersonated_user.roles.unwrap_or(vec![]),
+        user_id: impersonated_user.user_id,
+        impersonator_user_id: Some(user_with_session.user_id.clone()),
+        club_id: impersonated_user.club_id,
+        session_id: Some(user_with_session.session_id.clone()),
+        api_token_id: None,
+        must_reset_password: false,
+        must_enrol_two_factor: false,
+        request_id: meta.request_id,
+        ip: Some(meta.ip),
+    })
+}
+
+/// Users an admin flagged, or who still have to set up required 2FA, may only fix that.
+fn check_account_restrictions(auth_context: &AuthContext, path: &str) -> Result<(), ApiError> {
+    // the password comes first, 2FA enrolment is enforced once it's changed
+    if auth_context.must_reset_password {
+        if !PASSWORD_RESET_ALLOWED_PATHS.contains(&path) {
+            debug!(
+                "blocked request, user {} must reset password",
+                auth_context.user_id
+            );
+            return Err(ApiError::Forbidden("Password change required".into()));
+        }
+    } else if auth_context.must_enrol_two_factor
+        && !TWO_FACTOR_ENROLMENT_ALLOWED_PATHS.contains(&path)
+    {
+        debug!(
+            "blocked request, user {} must set up two-factor authentication",
+            auth_context.user_id
+        );
+        return Err(ApiError::Forbidden(
+            "Two-factor authentication setup required".into(),
+        ));
+    }
+
+    Ok(())
 }
@@ -1,4 +1,16 @@
+pub mod api_tokens;
 pub mod auth_routes;
+pub mod csrf;
+pub mod email;
+pub mod impersonation;
+pub mod login_throttle;
 pub mod middlewares;
+pub mod oidc;
+pub mod password;
+pub mod permissions;
 pub mod roles;
+pub mod sealed;
+pub mod signed_token;
+pub mod totp;
+pub mod two_factor;
 pub mod utils;
@@ -0,0 +1,855 @@
+//! Optional single sign-on via a club's own OpenID Connect provider, next to the regular
+//! password log-in. Authorization code flow with PKCE; the flow state (state, nonce, code
+//! verifier) lives in a short-lived signed cookie, so nothing is stored before the callback.
+//!
+//! IdP accounts are mapped to local users by an explicit link, by the verified email address
+//! (not for admins, they have to link it themselves), or - if the club allows it - by creating a
+//! new user on the first log-in.
+//!
+//! Issuers are set by club admins, so they and the endpoints they announce are only called via
+//! `utils::outbound`, which keeps requests out of the server's own network. Client secrets are
+//! stored sealed with `app_secret`.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{
+        header::{LOCATION, SET_COOKIE},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use log::{debug, error, info};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::PgConnection;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    auth::{
+        auth_routes::start_session,
+        permissions::{actions::ClubManage, Authorized},
+        roles::{GlobalRole, Role},
+        sealed, signed_token,
+        two_factor::{challenge_cookie, create_two_factor_challenge, two_factor_enabled},
+        utils::{generate_token, AuthContext},
+    },
+    entities::audit_log::{record_audit_event, AuditAction, AuditEvent},
+    utils::{
+        api::AppState,
+        error::ApiError,
+        outbound,
+        validation::{Validate, ValidatedJson, Validator, MAX_TEXT_LEN, MAX_TOKEN_LEN},
+    },
+};
+
+const FLOW_COOKIE: &str = "oidc_flow";
+const FLOW_COOKIE_PATH: &str = "/api/auth/oidc";
+const FLOW_PURPOSE: &str = "oidc-flow";
+const FLOW_TTL_MINUTES: i64 = 10;
+const CODE_VERIFIER_LEN: usize = 64;
+const MAX_USERNAME_ATTEMPTS: usize = 5;
+const CLIENT_SECRET_PURPOSE: &str = "oidc-client-secret";
+/// users with these roles aren't matched by their email address
+const NO_AUTO_LINK_ROLES: &[Role] = &[Role::SuperAdmin, Role::ClubAdmin];
+
+#[derive(Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+/// carried from the start of the flow to the callback in the signed cookie
+#[derive(Serialize, Deserialize)]
+struct FlowClaims {
+    club_id: String,
+    state: String,
+    nonce: String,
+    code_verifier: String,
+    /// set when a logged-in user links their IdP account instead of logging in
+    link_user_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    email_verified: Option<bool>,
+    preferred_username: Option<String>,
+}
+
+struct ClubProvider {
+    issuer_url: String,
+    client_id: String,
+    /// sealed, see [`sealed`]
+    client_secret: String,
+    jit_provisioning: bool,
+    default_role: Role,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OidcCallback {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OidcLinkStart {
+    /// the browser has to navigate here
+    authorization_url: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OidcProviderSettings {
+    issuer_url: String,
+    client_id: String,
+    jit_provisioning: bool,
+    default_role: Role,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetOidcProvider {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    #[serde(default)]
+    pub jit_provisioning: bool,
+    pub default_role: Option<Role>,
+}
+
+impl Validate for SetOidcProvider {
+    fn validate(&self, v: &mut Validator) {
+        let issuer_url = self.issuer_url.trim();
+        if let Err(problem) = outbound::check_url(issuer_url) {
+            v.check(false, "issuer_url", problem);
+        }
+        v.length("issuer_url", issuer_url, 1, MAX_TEXT_LEN);
+        v.length("client_id", &self.client_id, 1, MAX_TEXT_LEN);
+        v.length("client_secret", &self.client_secret, 1, MAX_TOKEN_LEN);
+        v.check(
+            !matches!(self.default_role, Some(Role::ClubAdmin | Role::SuperAdmin)),
+            "default_role",
+            "Admins can't be created by single sign-on",
+        );
+    }
+}
+
+fn redirect_uri(state: &AppState) -> String {
+    format!(
+        "{}/api/auth/oidc/callback",
+        state.config.server.public_url.trim_end_matches('/')
+    )
+}
+
+fn idp_error(message: &str) -> ApiError {
+    ApiError::BadGateway(message.to_string())
+}
+
+async fn load_provider(conn: &mut PgConnection, club_id: &str) -> Result<ClubProvider, ApiError> {
+    let provider = sqlx::query_as!(
+        ClubProvider,
+        r#"
+        SELECT issuer_url, client_id, client_secret, jit_provisioning, default_role AS "default_role: Role"
+        FROM club_oidc_providers WHERE club_id = $1
+        "#,
+        club_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    provider
+        .ok_or_else(|| ApiError::NotFound("Single sign-on is not configured for this club".into()))
+}
+
+// TODO: cache discovery documents and JWKS instead of fetching them for every log-in
+async fn discover(state: &AppState, issuer_url: &str) -> Result<ProviderMetadata, ApiError> {
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        issuer_url.trim_end_matches('/')
+    );
+    let metadata: ProviderMetadata = state
+        .http_client
+        .get(&url)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(|err| {
+            error!("OIDC discovery at {} failed: {}", url, err);
+            idp_error("Identity provider is unreachable")
+        })?
+        .json()
+        .await
+        .map_err(|err| {
+            error!("invalid OIDC discovery document at {}: {}", url, err);
+            idp_error("Identity provider sent an invalid configuration")
+        })?;
+
+    if metadata.issuer.trim_end_matches('/') != issuer_url.trim_end_matches('/') {
+        error!(
+            "OIDC issuer mismatch: configured {}, provider says {}",
+            issuer_url, metadata.issuer
+        );
+        return Err(idp_error("Identity provider sent an invalid configuration"));
+    }
+    for endpoint in [
+        &metadata.authorization_endpoint,
+        &metadata.token_endpoint,
+        &metadata.jwks_uri,
+    ] {
+        if let Err(problem) = outbound::check_url(endpoint) {
+            error!("OIDC endpoint {} of {} {}", endpoint, issuer_url, problem);
+            return Err(idp_error("Identity provider sent an invalid configuration"));
+        }
+    }
+
+    Ok(metadata)
+}
+
+/// Builds the IdP authorization URL and the cookie remembering the flow.
+async fn begin_flow(
+    state: &AppState,
+    club_id: &str,
+    link_user_id: Option<String>,
+) -> Result<(String, Cookie<'static>), ApiError> {
+    let provider = load_provider(&mut *state.pg_pool.acquire().await?, club_id).await?;
+    // no connection is held while waiting for the IdP
+    let metadata = discover(state, &provider.issuer_url).await?;
+
+    let flow = FlowClaims {
+        club_id: club_id.to_string(),
+        state: generate_token(32),
+        nonce: generate_token(32),
+        code_verifier: generate_token(CODE_VERIFIER_LEN),
+        link_user_id,
+    };
+    let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(flow.code_verifier.as_bytes()));
+
+    let mut authorization_url = Url::parse(&metadata.authorization_endpoint)
+        .map_err(|_| idp_error("Identity provider sent an invalid configuration"))?;
+    authorization_url
+        .query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &provider.client_id)
+        .append_pair("redirect_uri", &redirect_uri(state))
+        .append_pair("scope", "openid profile email")
+        .append_pair("state", &flow.state)
+        .append_pair("nonce", &flow.nonce)
+        .append_pair("code_challenge", &code_challenge)
+        .append_pair("code_challenge_method", "S256");
+
+    let flow_token = signed_token::sign(
+        &state.token_secret,
+        FLOW_PURPOSE,
+        flow,
+        chrono::Duration::minutes(FLOW_TTL_MINUTES),
+    );
+    // Lax, since the IdP's redirect back is a cross-site navigation
+    let cookie = Cookie::build((FLOW_COOKIE, flow_token))
+        .secure(state.config.session.cookie_secure)
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .path(FLOW_COOKIE_PATH)
+        .max_age(time::Duration::minutes(FLOW_TTL_MINUTES))
+        .build();
+
+    Ok((authorization_url.to_string(), cookie))
+}
+
+/// Browser entry point of an SSO log-in, redirects to the club's IdP.
+#[utoipa::path(
+    get,
+    path = "/oidc/{club_id}/start",
+    tag = "auth",
+    params(("club_id" = String, Path)),
+    responses((status = 303, description = "redirect to the club's identity provider")),
+)]
+pub async fn start_oidc_log_in(
+    State(state): State<AppState>,
+    Path(club_id): Path<String>,
+) -> Result<Response, ApiError> {
+    let (authorization_url, cookie) = begin_flow(&state, &club_id, None).await?;
+
+    Ok((
+        StatusCode::SEE_OTHER,
+        [
+            (LOCATION, authorization_url),
+            (SET_COOKIE, cookie.to_string()),
+        ],
+    )
+        .into_response())
+}
+
+/// Links the IdP account to the logged-in user. A POST (and thus CSRF-checked), so other sites
+/// can't make a user link an IdP account of theirs.
+#[utoipa::path(
+    post,
+    path = "/oidc/link",
+    tag = "account",
+    responses((status = 200, body = OidcLinkStart)),
+)]
+pub async fn start_oidc_link(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+) -> Result<Response, ApiError> {
+    let (authorization_url, cookie) =
+        begin_flow(&state, &auth_ctx.club_id, Some(auth_ctx.user_id.clone())).await?;
+
+    Ok((
+        StatusCode::OK,
+        [(SET_COOKIE, cookie.to_string())],
+        Json(OidcLinkStart { authorization_url }),
+    )
+        .into_response())
+}
+
+async fn exchange_code(
+    state: &AppState,
+    provider: &ClubProvider,
+    metadata: &ProviderMetadata,
+    code: &str,
+    flow: &FlowClaims,
+) -> Result<IdTokenClaims, ApiError> {
+    let client_secret = sealed::open(
+        &state.token_secret,
+        CLIENT_SECRET_PURPOSE,
+        &provider.client_secret,
+    )
+    .ok_or_else(|| {
+        error!(
+            "the OIDC client secret of club {} can't be decrypted, was APP_SECRET changed?",
+            flow.club_id
+        );
+        ApiError::ServiceUnavailable("Single sign-on has to be set up again by a club admin".into())
+    })?;
+    let redirect_uri = redirect_uri(state);
+    let token_response: TokenResponse = state
+        .http_client
+        .post(&metadata.token_endpoint)
+        .basic_auth(&provider.client_id, Some(&client_secret))
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("code_verifier", flow.code_verifier.as_str()),
+        ])
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(|err| {
+            error!("OIDC code exchange failed: {}", err);
+            idp_error("Identity provider rejected the log-in")
+        })?
+        .json()
+        .await
+        .map_err(|err| {
+            error!("invalid OIDC token response: {}", err);
+            idp_error("Identity provider sent an invalid response")
+        })?;
+
+    let id_token = token_response.id_token;
+    let invalid_token = |err: jsonwebtoken::errors::Error| {
+        error!("invalid OIDC ID token: {}", err);
+        idp_error("Identity provider sent an invalid ID token")
+    };
+
+    let header = decode_header(&id_token).map_err(invalid_token)?;
+    // symmetric algorithms would make the client secret the verification key
+    if matches!(
+        header.alg,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        return Err(idp_error("Identity provider sent an invalid ID token"));
+    }
+
+    let jwks: JwkSet = state
+        .http_client
+        .get(&metadata.jwks_uri)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(|err| {
+            error!("fetching OIDC JWKS failed: {}", err);
+            idp_error("Identity provider is unreachable")
+        })?
+        .json()
+        .await
+        .map_err(|err| {
+            error!("invalid OIDC JWKS: {}", err);
+            idp_error("Identity provider sent an invalid configuration")
+        })?;
+
+    let jwk = match &header.kid {
+        Some(kid) => jwks.find(kid),
+        None => jwks.keys.first(),
+    }
+    .ok_or_else(|| idp_error("Identity provider sent an invalid ID token"))?;
+    let key = DecodingKey::from_jwk(jwk).map_err(invalid_token)?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_audience(&[&provider.client_id]);
+    validation.set_issuer(&[&metadata.issuer]);
+
+    let claims = decode::<IdTokenClaims>(&id_token, &key, &validation)
+        .map_err(invalid_token)?
+        .claims;
+
+    if claims.nonce.as_deref() != Some(flow.nonce.as_str()) {
+        return Err(idp_error("Identity provider sent an invalid ID token"));
+    }
+
+    Ok(claims)
+}
+
+async fn link_identity(
+    conn: &mut PgConnection,
+    user_id: &str,
+    issuer: &str,
+    subject: &str,
+) -> Result<(), ApiError> {
+    sqlx::query!(
+        r#"INSERT INTO user_oidc_identities (user_id, issuer, subject) VALUES ($1, $2, $3)"#,
+        user_id,
+        issuer,
+        subject
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(|err| match err.as_database_error() {
+        Some(db_err) if db_err.is_unique_violation() => {
+            ApiError::Conflict("This login is already linked to another account".into())
+        }
+        _ => ApiError::from(err),
+    })?;
+
+    Ok(())
+}
+
+/// Creates a user for an unknown IdP account. Its password is random - the user logs in via SSO
+/// or sets one with a password reset.
+async fn provision_user(
+    conn: &mut PgConnection,
+    club_id: &str,
+    role: Role,
+    claims: &IdTokenClaims,
+) -> Result<String, ApiError> {
+    // usernames can't contain `@`, providers often use the email address or UPN
+    let base_username = claims
+        .preferred_username
+        .as_deref()
+        .or(claims.email.as_deref())
+        .and_then(|name| name.split('@').next())
+        .map(str::to_string)
+        .filter(|username| !username.trim().is_empty())
+        .unwrap_or_else(|| "sso-user".to_string());
+
+    for attempt in 0..MAX_USERNAME_ATTEMPTS {
+        let username = match attempt {
+            0 => base_username.clone(),
+            _ => format!("{}-{}", base_username, generate_token(4).to_lowercase()),
+        };
+
+        let user_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO users (username, password, club_id) VALUES ($1, $2, $3)
+            ON CONFLICT (username) DO NOTHING
+            RETURNING id
+            "#,
+            username,
+            generate_token(32),
+            club_id
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        if let Some(user_id) = user_id {
+            sqlx::query!(
+                r#"INSERT INTO role_assignments (user_id, role) VALUES ($1, $2)"#,
+                user_id,
+                role as Role
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            info!("provisioned user {} via single sign-on", username);
+            return Ok(user_id);
+        }
+    }
+
+    Err(ApiError::Conflict(
+        "Could not find a free username for this login".into(),
+    ))
+}
+
+/// Finds (or links, or creates) the local user for the IdP account.
+/// Admin accounts are only linked explicitly: whoever configures the IdP could otherwise log in
+/// as them by asserting their address.
+fn may_auto_link(roles: &[Role], global_roles: &[GlobalRole]) -> bool {
+    !roles.iter().any(|role| NO_AUTO_LINK_ROLES.contains(role))
+        && !global_roles.contains(&GlobalRole::Admin)
+}
+
+async fn resolve_user(
+    conn: &mut PgConnection,
+    flow: &FlowClaims,
+    provider: &ClubProvider,
+    issuer: &str,
+    claims: &IdTokenClaims,
+) -> Result<String, ApiError> {
+    let linked = sqlx::query!(
+        r#"
+        SELECT u.id, u.club_id FROM user_oidc_identities i
+        JOIN users u ON u.id = i.user_id
+        WHERE i.issuer = $1 AND i.subject = $2
+        "#,
+        issuer,
+        claims.sub
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if let Some(linked) = linked {
+        if linked.club_id != flow.club_id {
+            return Err(ApiError::Forbidden(
+                "This login belongs to another club".into(),
+            ));
+        }
+        if flow
+            .link_user_id
+            .as_ref()
+            .is_some_and(|id| *id != linked.id)
+        {
+            return Err(ApiError::Conflict(
+                "This login is already linked to another account".into(),
+            ));
+        }
+        return Ok(linked.id);
+    }
+
+    if let Some(user_id) = &flow.link_user_id {
+        link_identity(conn, user_id, issuer, &claims.sub).await?;
+        return Ok(user_id.clone());
+    }
+
+    // only verified addresses on both sides, `users.email` never holds unverified ones
+    if let (Some(email), Some(true)) = (&claims.email, claims.email_verified) {
+        let user = sqlx::query!(
+            r#"
+            SELECT u.id,
+            ARRAY(SELECT ra.role FROM role_assignments ra WHERE ra.user_id = u.id)
+                AS "roles!: Vec<Role>",
+            ARRAY(SELECT gra.role FROM global_role_assignments gra WHERE gra.user_id = u.id)
+                AS "global_roles!: Vec<GlobalRole>"
+            FROM users u WHERE u.club_id = $1 AND lower(u.email) = lower($2)
+            "#,
+            flow.club_id,
+            email
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        if let Some(user) = user {
+            if !may_auto_link(&user.roles, &user.global_roles) {
+                return Err(ApiError::Forbidden(
+                    "Admins have to link this login in their account settings first".into(),
+                ));
+            }
+            link_identity(conn, &user.id, issuer, &claims.sub).await?;
+            return Ok(user.id);
+        }
+    }
+
+    if provider.jit_provisioning {
+        let user_id = provision_user(conn, &flow.club_id, provider.default_role, claims).await?;
+        link_identity(conn, &user_id, issuer, &claims.sub).await?;
+        return Ok(user_id);
+    }
+
+    Err(ApiError::Forbidden(
+        "No account is linked to this login, ask your club admin".into(),
+    ))
+}
+
+/// The IdP redirects the browser here. Ends on the frontend, logged in or asked for a 2FA code.
+#[utoipa::path(
+    get,
+    path = "/oidc/callback",
+    tag = "auth",
+    params(OidcCallback),
+    responses((status = 303, description = "redirect into the app")),
+)]
+pub async fn oidc_callback(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Query(params): Query<OidcCallback>,
+) -> Result<Response, ApiError> {
+    if let Some(error) = params.error {
+        debug!(
+            "OIDC provider returned an error: {} {:?}",
+            error, params.error_description
+        );
+        return Err(ApiError::Unauthorized(
+            "Single sign-on was cancelled".into(),
+        ));
+    }
+
+    let flow: FlowClaims = jar
+        .get(FLOW_COOKIE)
+        .and_then(|cookie| signed_token::verify(&state.token_secret, FLOW_PURPOSE, cookie.value()))
+        .ok_or_else(|| ApiError::BadRequest("Single sign-on expired, please try again".into()))?;
+
+    let (Some(code), Some(flow_state)) = (params.code, params.state) else {
+        return Err(ApiError::BadRequest(
+            "Invalid single sign-on callback".into(),
+        ));
+    };
+    if flow_state != flow.state {
+        return Err(ApiError::BadRequest(
+            "Invalid single sign-on callback".into(),
+        ));
+    }
+
+    // talk to the IdP first, a slow one must not keep a connection or a transaction open
+    let provider = load_provider(&mut *state.pg_pool.acquire().await?, &flow.club_id).await?;
+    let metadata = discover(&state, &provider.issuer_url).await?;
+    let claims = exchange_code(&state, &provider, &metadata, &code, &flow).await?;
+
+    let mut tx = state.pg_pool.begin().await?;
+    let user_id = resolve_user(&mut tx, &flow, &provider, &metadata.issuer, &claims).await?;
+
+    let mut location = format!("{}/", state.config.server.public_url.trim_end_matches('/'));
+    let clear_flow_cookie = Cookie::build((FLOW_COOKIE, ""))
+        .path(FLOW_COOKIE_PATH)
+        .max_age(time::Duration::ZERO)
+        .build();
+
+    let mut headers = HeaderMap::new();
+    if flow.link_user_id.is_some() {
+        // linking keeps the current session
+    } else if two_factor_enabled(&mut tx, &user_id).await? {
+        let challenge = create_two_factor_challenge(&mut tx, &user_id, None).await?;
+        // the frontend asks for the code, the challenge goes along as a cookie, not in the URL
+        headers.append(
+            SET_COOKIE,
+            challenge_cookie(&state.config.session, Some(challenge.two_factor_challenge))
+                .parse()
+                .expect("cookies are valid header values"),
+        );
+        location = format!(
+            "{}/login?two_factor=1",
+            state.config.server.public_url.trim_end_matches('/')
+        );
+    } else {
+        headers = start_session(&mut tx, &user_id, &state.config.session).await?;
+    }
+
+    tx.commit().await?;
+
+    headers.append(
+        SET_COOKIE,
+        clear_flow_cookie
+            .to_string()
+            .parse()
+            .expect("cookies are valid header values"),
+    );
+    headers.insert(
+        LOCATION,
+        location
+            .parse()
+            .expect("PUBLIC_URL is a valid header value"),
+    );
+
+    Ok((StatusCode::SEE_OTHER, headers).into_response())
+}
+
+/// for audit entries, without the secret
+async fn provider_snapshot(
+    conn: &mut PgConnection,
+    club_id: &str,
+) -> Result<Option<serde_json::Value>, ApiError> {
+    let provider = sqlx::query!(
+        r#"
+        SELECT issuer_url, client_id, jit_provisioning, default_role AS "default_role: Role"
+        FROM club_oidc_providers WHERE club_id = $1
+        "#,
+        club_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(provider.map(|provider| {
+        json!({
+            "oidc_issuer_url": provider.issuer_url,
+            "oidc_client_id": provider.client_id,
+            "oidc_jit_provisioning": provider.jit_provisioning,
+            "oidc_default_role": provider.default_role,
+        })
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/clubs/oidc",
+    tag = "clubs",
+    responses((status = 200, body = OidcProviderSettings)),
+)]
+pub async fn get_oidc_provider(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<ClubManage>,
+) -> Result<(StatusCode, Json<OidcProviderSettings>), ApiError> {
+    let mut conn = state.pg_pool.acquire().await?;
+    let provider = load_provider(&mut conn, &auth_ctx.club_id).await?;
+
+    // the client secret is write-only
+    Ok((
+        StatusCode::OK,
+        Json(OidcProviderSettings {
+            issuer_url: provider.issuer_url,
+            client_id: provider.client_id,
+            jit_provisioning: provider.jit_provisioning,
+            default_role: provider.default_role,
+        }),
+    ))
+}
+
+/// Checks the provider's discovery document before saving.
+#[utoipa::path(
+    post,
+    path = "/clubs/oidc/set",
+    tag = "clubs",
+    request_body = SetOidcProvider,
+    responses((status = 204, description = "provider saved")),
+)]
+pub async fn set_oidc_provider(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<ClubManage>,
+    ValidatedJson(payload): ValidatedJson<SetOidcProvider>,
+) -> Result<StatusCode, ApiError> {
+    let default_role = payload.default_role.unwrap_or(Role::Player);
+    let issuer_url = payload.issuer_url.trim().trim_end_matches('/').to_string();
+    discover(&state, &issuer_url).await?;
+
+    let mut tx = state.pg_pool.begin().await?;
+    let before = provider_snapshot(&mut tx, &auth_ctx.club_id).await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO club_oidc_providers (club_id, issuer_url, client_id, client_secret, jit_provisioning, default_role)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (club_id) DO UPDATE SET
+            issuer_url = EXCLUDED.issuer_url,
+            client_id = EXCLUDED.client_id,
+            client_secret = EXCLUDED.client_secret,
+            jit_provisioning = EXCLUDED.jit_provisioning,
+            default_role = EXCLUDED.default_role,
+            updated_at = now()
+        "#,
+        auth_ctx.club_id,
+        issuer_url,
+        payload.client_id,
+        sealed::seal(
+            &state.token_secret,
+            CLIENT_SECRET_PURPOSE,
+            &payload.client_secret
+        ),
+        payload.jit_provisioning,
+        default_role as Role
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let after = provider_snapshot(&mut tx, &auth_ctx.club_id).await?;
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            before,
+            after,
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::ClubSettingsChanged,
+                "club",
+                &auth_ctx.club_id,
+            )
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Existing links stay, so SSO works again if the provider is set up anew.
+#[utoipa::path(
+    delete,
+    path = "/clubs/oidc/remove",
+    tag = "clubs",
+    responses((status = 204, description = "provider removed")),
+)]
+pub async fn remove_oidc_provider(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<ClubManage>,
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+    let before = provider_snapshot(&mut tx, &auth_ctx.club_id).await?;
+
+    sqlx::query!(
+        r#"DELETE FROM club_oidc_providers WHERE club_id = $1"#,
+        auth_ctx.club_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if before.is_some() {
+        record_audit_event(
+            &mut tx,
+            AuditEvent {
+                before,
+                ..AuditEvent::by(
+                    &auth_ctx,
+                    AuditAction::ClubSettingsChanged,
+                    "club",
+                    &auth_ctx.club_id,
+                )
+            },
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admins_are_not_linked_by_email() {
+        assert!(may_auto_link(&[], &[GlobalRole::User]));
+        assert!(may_auto_link(&[Role::Player, Role::Coach], &[]));
+        assert!(may_auto_link(
+            &[Role::TeamManager, Role::RefereeManager],
+            &[]
+        ));
+        assert!(!may_auto_link(&[Role::Player, Role::SuperAdmin], &[]));
+        assert!(!may_auto_link(&[Role::ClubAdmin], &[]));
+        assert!(!may_auto_link(&[Role::Player], &[GlobalRole::Admin]));
+    }
+}
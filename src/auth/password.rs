@@ -0,0 +1,459 @@
+//! Password change, self-service reset via emailed link, and resets initiated by club admins.
+
+use std::time::Duration as StdDuration;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{
+        login_throttle::{
+            begin_reauthentication, clear_failed_logins, end_login_attempt, record_failed_login,
+        },
+        permissions::{check_permission, Permission},
+        roles::Role,
+        utils::{generate_token, hash_token, passwords_match, AuthContext},
+    },
+    entities::audit_log::{record_audit_event, AuditAction, AuditEvent},
+    notifications::send_email_in_background,
+    utils::{
+        api::AppState,
+        client_ip::ClientIp,
+        error::ApiError,
+        validation::{Validate, ValidatedJson, Validator, MAX_TEXT_LEN, MAX_TOKEN_LEN},
+    },
+};
+
+const RESET_TOKEN_LEN: usize = 32;
+/// self-service links are meant to be opened right away
+const SELF_SERVICE_RESET_TTL_HOURS: i64 = 1;
+/// tokens an admin hands over in person need to survive a few days
+const ADMIN_RESET_TTL_HOURS: i64 = 72;
+/// reset requests are answered no sooner than this, so the response time doesn't tell whether a
+/// user exists - the mail itself is sent in the background
+const RESET_REQUEST_MIN_DURATION: StdDuration = StdDuration::from_millis(300);
+
+pub const MIN_PASSWORD_LEN: usize = 8;
+/// plenty for a passphrase, and nobody can store a megabyte of password in the users table
+pub const MAX_PASSWORD_LEN: usize = 256;
+
+/// paths a user flagged with `must_reset_password` may still call, the CSRF token is needed for
+/// the change itself
+pub const PASSWORD_RESET_ALLOWED_PATHS: &[&str] = &["/password/change", "/log-out", "/csrf-token"];
+
+#[derive(Deserialize, ToSchema)]
+pub struct ChangePassword {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RequestPasswordReset {
+    /// username or verified email address
+    pub username: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ConfirmPasswordReset {
+    pub token: String,
+    pub new_password: String,
+}
+
+/// Returned to an admin once - the plain token is not stored anywhere.
+#[derive(Serialize, ToSchema)]
+pub struct IssuedPasswordReset {
+    pub token: String,
+    pub reset_url: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Validate for ChangePassword {
+    fn validate(&self, v: &mut Validator) {
+        v.length(
+            "current_password",
+            &self.current_password,
+            1,
+            MAX_PASSWORD_LEN,
+        );
+        v.password("new_password", &self.new_password);
+    }
+}
+
+impl Validate for RequestPasswordReset {
+    fn validate(&self, v: &mut Validator) {
+        v.length("username", &self.username, 1, MAX_TEXT_LEN);
+    }
+}
+
+impl Validate for ConfirmPasswordReset {
+    fn validate(&self, v: &mut Validator) {
+        v.length("token", &self.token, 1, MAX_TOKEN_LEN);
+        v.password("new_password", &self.new_password);
+    }
+}
+
+fn reset_url(state: &AppState, token: &str) -> String {
+    format!(
+        "{}/reset-password?token={}",
+        state.config.server.public_url.trim_end_matches('/'),
+        token
+    )
+}
+
+/// Creates a new reset token for the user, invalidating any older ones.
+async fn issue_reset_token(
+    conn: &mut PgConnection,
+    user_id: &str,
+    issued_by: Option<&str>,
+    ttl_hours: i64,
+) -> Result<(String, DateTime<Utc>), ApiError> {
+    let token = generate_token(RESET_TOKEN_LEN);
+    let expires_at = Utc::now() + Duration::hours(ttl_hours);
+
+    sqlx::query!(
+        r#"UPDATE password_reset_tokens SET used_at = now() WHERE user_id = $1 AND used_at IS NULL"#,
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"INSERT INTO password_reset_tokens (user_id, token_hash, issued_by, expires_at) VALUES ($1, $2, $3, $4)"#,
+        user_id,
+        hash_token(&token),
+        issued_by,
+        expires_at
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok((token, expires_at))
+}
+
+/// Admins may only handle users of their own club, and only super admins may touch super admins.
+pub async fn check_can_manage_user(
+    pool: &PgPool,
+    auth_ctx: &AuthContext,
+    user_id: &str,
+) -> Result<(), ApiError> {
+    check_permission(auth_ctx, Permission::UserManage)?;
+
+    let target = sqlx::query!(
+        r#"
+        SELECT
+        COALESCE(array_agg(ra.role) FILTER (WHERE ra.role IS NOT NULL), '{}') AS "roles!: Vec<Role>"
+        FROM users u
+        LEFT JOIN role_assignments ra ON ra.user_id = u.id
+        WHERE u.id = $1 AND u.club_id = $2
+        GROUP BY u.id
+        "#,
+        user_id,
+        auth_ctx.club_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(target) = target else {
+        return Err(ApiError::NotFound("User not found".into()));
+    };
+
+    // only who could make someone a super admin may handle one
+    if target.roles.contains(&Role::SuperAdmin) {
+        check_permission(auth_ctx, Permission::RoleAssign(Role::SuperAdmin))?;
+    }
+
+    Ok(())
+}
+
+/// Wrong current passwords count as failed log-ins.
+#[utoipa::path(
+    post,
+    path = "/password/change",
+    tag = "account",
+    request_body = ChangePassword,
+    responses((status = 204, description = "password changed")),
+)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    ClientIp(ip): ClientIp,
+    ValidatedJson(payload): ValidatedJson<ChangePassword>,
+) -> Result<StatusCode, ApiError> {
+    let identifier = begin_reauthentication(&state.pg_pool, &auth_ctx.user_id, ip).await?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    let stored_password = sqlx::query_scalar!(
+        r#"SELECT password FROM users WHERE id = $1 FOR UPDATE"#,
+        auth_ctx.user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if !passwords_match(Some(&stored_password), &payload.current_password) {
+        record_failed_login(
+            &mut tx,
+            &identifier,
+            ip,
+            Some((&auth_ctx.user_id, &auth_ctx.club_id)),
+        )
+        .await?;
+        tx.commit().await?;
+
+        return Err(ApiError::Forbidden("Current password is incorrect".into()));
+    }
+
+    end_login_attempt(&mut tx, ip).await?;
+    clear_failed_logins(&mut tx, &identifier).await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password = $1, must_reset_password = FALSE, updated_at = CURRENT_TIMESTAMP
+        WHERE id = $2
+        "#,
+        payload.new_password,
+        auth_ctx.user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    // every other device has to log in again with the new password
+    sqlx::query!(
+        r#"DELETE FROM sessions WHERE user_id = $1 AND id <> $2"#,
+        auth_ctx.user_id,
+        auth_ctx.session_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        r#"UPDATE password_reset_tokens SET used_at = now() WHERE user_id = $1 AND used_at IS NULL"#,
+        auth_ctx.user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/password-reset/request",
+    tag = "auth",
+    request_body = RequestPasswordReset,
+    responses((status = 202, description = "a link is sent if the address is known")),
+)]
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<RequestPasswordReset>,
+) -> Result<StatusCode, ApiError> {
+    let answer_at = tokio::time::Instant::now() + RESET_REQUEST_MIN_DURATION;
+
+    let user = sqlx::query!(
+        r#"
+        SELECT id, username, email FROM users
+        WHERE username = $1 OR lower(email) = lower($1)
+        ORDER BY lower(email) = lower($1) IS TRUE DESC
+        LIMIT 1
+        "#,
+        payload.username
+    )
+    .fetch_optional(&state.pg_pool)
+    .await?;
+
+    // the answer is the same whether or not the user exists or has an address,
+    // so this can't be used to probe for usernames
+    match user {
+        Some(user) => match user.email {
+            Some(email) => {
+                let mut tx = state.pg_pool.begin().await?;
+                let (token, expires_at) =
+                    issue_reset_token(&mut tx, &user.id, None, SELF_SERVICE_RESET_TTL_HOURS)
+                        .await?;
+                tx.commit().await?;
+
+                send_email_in_background(
+                    &state,
+                    email,
+                    "Reset your password".to_string(),
+                    format!(
+                        "Hi {},\n\nsomeone asked to reset your password. Use this link to choose a new one:\n\n{}\n\nThe link works once and expires at {} UTC.\nIf you didn't ask for this, just ignore this mail.\n",
+                        user.username,
+                        reset_url(&state, &token),
+                        expires_at.format("%Y-%m-%d %H:%M")
+                    ),
+                );
+            }
+            None => debug!(
+                "password reset requested for user {} without email address",
+                user.id
+            ),
+        },
+        None => debug!("password reset requested for unknown user"),
+    }
+
+    tokio::time::sleep_until(answer_at).await;
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[utoipa::path(
+    post,
+    path = "/password-reset/confirm",
+    tag = "auth",
+    request_body = ConfirmPasswordReset,
+    responses((status = 204, description = "password changed")),
+)]
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<ConfirmPasswordReset>,
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    // consuming the token in the same statement that looks it up keeps it single-use under concurrency
+    let reset = sqlx::query!(
+        r#"
+        UPDATE password_reset_tokens
+        SET used_at = now()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+        RETURNING user_id
+        "#,
+        hash_token(&payload.token)
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(reset) = reset else {
+        return Err(ApiError::BadRequest(
+            "Invalid or expired reset token".into(),
+        ));
+    };
+
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password = $1, must_reset_password = FALSE, updated_at = CURRENT_TIMESTAMP
+        WHERE id = $2
+        "#,
+        payload.new_password,
+        reset.user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    // whoever knew the old password is logged out everywhere
+    sqlx::query!(r#"DELETE FROM sessions WHERE user_id = $1"#, reset.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// For clubs without email: the admin gets the token and passes it on in person.
+#[utoipa::path(
+    post,
+    path = "/users/create-password-reset/{id}",
+    tag = "users",
+    params(("id" = String, Path, description = "user id")),
+    responses((status = 201, body = IssuedPasswordReset)),
+)]
+pub async fn create_password_reset_for_user(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    Path(user_id): Path<String>,
+) -> Result<(StatusCode, Json<IssuedPasswordReset>), ApiError> {
+    check_can_manage_user(&state.pg_pool, &auth_ctx, &user_id).await?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    let (token, expires_at) = issue_reset_token(
+        &mut tx,
+        &user_id,
+        Some(auth_ctx.real_user_id()),
+        ADMIN_RESET_TTL_HOURS,
+    )
+    .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            details: json!({ "expires_at": expires_at }),
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::PasswordResetIssued,
+                "user",
+                &user_id,
+            )
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(IssuedPasswordReset {
+            reset_url: reset_url(&state, &token),
+            token,
+            expires_at,
+        }),
+    ))
+}
+
+/// The user keeps their password, but has to replace it right after the next log-in.
+#[utoipa::path(
+    post,
+    path = "/users/force-password-reset/{id}",
+    tag = "users",
+    params(("id" = String, Path, description = "user id")),
+    responses((status = 204, description = "the user has to pick a new password")),
+)]
+pub async fn force_password_reset(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    Path(user_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    check_can_manage_user(&state.pg_pool, &auth_ctx, &user_id).await?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    sqlx::query!(
+        r#"UPDATE users SET must_reset_password = TRUE, updated_at = CURRENT_TIMESTAMP WHERE id = $1"#,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    // makes the "next log-in" happen right away
+    sqlx::query!(r#"DELETE FROM sessions WHERE user_id = $1"#, user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent::by(
+            &auth_ctx,
+            AuditAction::PasswordResetForced,
+            "user",
+            &user_id,
+        ),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
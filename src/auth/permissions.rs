@@ -0,0 +1,310 @@
+//! Who may do what, in one place. Handlers ask for a `Permission` - either with the
+//! `Authorized<A>` extractor or, when it depends on the payload, with `check_permission`.
+//!
+//! Roles are granted permissions directly and include everything of the roles below them
+//! (`implied_roles`). Every club member has `MEMBER_PERMISSIONS`, even without a role,
+//! and global admins have all permissions.
+
+use std::{collections::BTreeSet, fmt, marker::PhantomData};
+
+use axum::{
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+    Extension, Json,
+};
+use log::debug;
+use serde::{Serialize, Serializer};
+use strum::IntoEnumIterator;
+
+use crate::{
+    auth::{
+        roles::{GlobalRole, Role},
+        utils::AuthContext,
+    },
+    utils::error::ApiError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Permission {
+    UserList,
+    UserCreate,
+    UserDelete,
+    /// password resets, 2FA resets, unlocking log-ins
+    UserManage,
+    UserImpersonate,
+    RoleList,
+    /// assigning and unassigning the given role
+    RoleAssign(Role),
+    TeamList,
+    TeamCreate,
+    TeamUpdate,
+    TeamDelete,
+    GameList,
+    GameCreate,
+    GameDelete,
+    /// everyone's responses to a game invite
+    InviteList,
+    ClubInviteManage,
+    ClubManage,
+    ClubDelete,
+    AuditRead,
+    /// linking parents and minors
+    GuardianshipManage,
+    RefereeAssignmentManage,
+    /// answering game invites for linked minors
+    MinorActOnBehalf,
+}
+
+impl Permission {
+    pub fn all() -> Vec<Permission> {
+        use Permission::*;
+
+        let mut all = vec![
+            UserList,
+            UserCreate,
+            UserDelete,
+            UserManage,
+            UserImpersonate,
+            RoleList,
+            TeamList,
+            TeamCreate,
+            TeamUpdate,
+            TeamDelete,
+            GameList,
+            GameCreate,
+            GameDelete,
+            InviteList,
+            ClubInviteManage,
+            ClubManage,
+            ClubDelete,
+            AuditRead,
+            GuardianshipManage,
+            RefereeAssignmentManage,
+            MinorActOnBehalf,
+        ];
+        all.extend(Role::iter().map(RoleAssign));
+        all
+    }
+}
+
+/// `game.create`, `role.assign:coach`, ... - the names the frontend sees
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Permission::*;
+
+        let name = match self {
+            UserList => "user.list",
+            UserCreate => "user.create",
+            UserDelete => "user.delete",
+            UserManage => "user.manage",
+            UserImpersonate => "user.impersonate",
+            RoleList => "role.list",
+            RoleAssign(role) => return write!(f, "role.assign:{}", role),
+            TeamList => "team.list",
+            TeamCreate => "team.create",
+            TeamUpdate => "team.update",
+            TeamDelete => "team.delete",
+            GameList => "game.list",
+            GameCreate => "game.create",
+            GameDelete => "game.delete",
+            InviteList => "invite.list",
+            ClubInviteManage => "club_invite.manage",
+            ClubManage => "club.manage",
+            ClubDelete => "club.delete",
+            AuditRead => "audit.read",
+            GuardianshipManage => "guardianship.manage",
+            RefereeAssignmentManage => "referee_assignment.manage",
+            MinorActOnBehalf => "minor.act_on_behalf",
+        };
+        f.write_str(name)
+    }
+}
+
+impl Serialize for Permission {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// for every club member, whether or not they have a role
+const MEMBER_PERMISSIONS: &[Permission] = &[Permission::UserList, Permission::TeamList];
+
+/// roles whose permissions a role includes - the hierarchy, top to bottom
+fn implied_roles(role: Role) -> &'static [Role] {
+    match role {
+        Role::SuperAdmin => &[Role::ClubAdmin],
+        Role::ClubAdmin => &[Role::Coach, Role::TeamManager, Role::RefereeManager],
+        Role::Coach => &[Role::Player],
+        Role::TeamManager => &[],
+        Role::RefereeManager => &[Role::Referee],
+        Role::Referee => &[],
+        Role::Player => &[],
+        Role::Parent => &[],
+    }
+}
+
+/// what a role adds on top of the roles it implies
+fn granted_permissions(role: Role) -> &'static [Permission] {
+    use Permission::*;
+
+    match role {
+        Role::SuperAdmin => &[UserImpersonate, RoleAssign(Role::SuperAdmin)],
+        Role::ClubAdmin => &[
+            UserCreate,
+            UserDelete,
+            UserManage,
+            RoleList,
+            RoleAssign(Role::ClubAdmin),
+            RoleAssign(Role::TeamManager),
+            RoleAssign(Role::RefereeManager),
+            RoleAssign(Role::Parent),
+            GuardianshipManage,
+            TeamCreate,
+            TeamUpdate,
+            TeamDelete,
+            ClubInviteManage,
+            ClubManage,
+            ClubDelete,
+            AuditRead,
+        ],
+        Role::Coach => &[
+            RoleAssign(Role::Coach),
+            RoleAssign(Role::Player),
+            GameCreate,
+            GameDelete,
+            InviteList,
+        ],
+        Role::TeamManager => &[GameList, GameCreate, GameDelete, InviteList],
+        Role::RefereeManager => &[RoleAssign(Role::Referee), RefereeAssignmentManage],
+        // the club's whole schedule: teams have no members to narrow it to, and players,
+        // referees and parents need to see the games they may be invited to before they are
+        Role::Referee => &[GameList],
+        Role::Player => &[GameList],
+        Role::Parent => &[GameList, MinorActOnBehalf],
+    }
+}
+
+fn add_role_permissions(role: Role, permissions: &mut BTreeSet<Permission>) {
+    permissions.extend(granted_permissions(role));
+    for implied in implied_roles(role) {
+        add_role_permissions(*implied, permissions);
+    }
+}
+
+pub fn effective_permissions(auth_ctx: &AuthContext) -> BTreeSet<Permission> {
+    if auth_ctx.global_roles.contains(&GlobalRole::Admin) {
+        return Permission::all().into_iter().collect();
+    }
+
+    let mut permissions: BTreeSet<Permission> = MEMBER_PERMISSIONS.iter().copied().collect();
+    for role in &auth_ctx.roles {
+        add_role_permissions(*role, &mut permissions);
+    }
+    permissions
+}
+
+pub fn has_permission(auth_ctx: &AuthContext, permission: Permission) -> bool {
+    effective_permissions(auth_ctx).contains(&permission)
+}
+
+pub fn check_permission(auth_ctx: &AuthContext, permission: Permission) -> Result<(), ApiError> {
+    if has_permission(auth_ctx, permission) {
+        return Ok(());
+    }
+
+    debug!(
+        "permission {} denied for user {} with roles {:?}",
+        permission, auth_ctx.user_id, auth_ctx.roles
+    );
+    Err(ApiError::Forbidden(format!(
+        "Access denied. Missing permission: {}",
+        permission
+    )))
+}
+
+/// A permission as a type, for `Authorized<A>`.
+pub trait Action {
+    const PERMISSION: Permission;
+}
+
+macro_rules! actions {
+    ($($name:ident => $permission:expr),* $(,)?) => {
+        $(
+            pub struct $name;
+
+            impl Action for $name {
+                const PERMISSION: Permission = $permission;
+            }
+        )*
+    };
+}
+
+/// marker types for the permissions handlers check up front
+pub mod actions {
+    use super::{Action, Permission};
+
+    actions! {
+        UserCreate => Permission::UserCreate,
+        UserDelete => Permission::UserDelete,
+        UserImpersonate => Permission::UserImpersonate,
+        RoleList => Permission::RoleList,
+        TeamList => Permission::TeamList,
+        TeamCreate => Permission::TeamCreate,
+        TeamUpdate => Permission::TeamUpdate,
+        TeamDelete => Permission::TeamDelete,
+        GameList => Permission::GameList,
+        GameCreate => Permission::GameCreate,
+        GameDelete => Permission::GameDelete,
+        InviteList => Permission::InviteList,
+        ClubInviteManage => Permission::ClubInviteManage,
+        ClubManage => Permission::ClubManage,
+        ClubDelete => Permission::ClubDelete,
+        AuditRead => Permission::AuditRead,
+        GuardianshipManage => Permission::GuardianshipManage,
+        MinorActOnBehalf => Permission::MinorActOnBehalf,
+        RefereeAssignmentManage => Permission::RefereeAssignmentManage,
+    }
+}
+
+/// The `AuthContext` of a user with the permission of `A`, rejects everyone else with 403.
+/// Only works behind the auth middleware.
+pub struct Authorized<A: Action>(pub AuthContext, PhantomData<A>);
+
+impl<A: Action> std::ops::Deref for Authorized<A> {
+    type Target = AuthContext;
+
+    fn deref(&self) -> &AuthContext {
+        &self.0
+    }
+}
+
+impl<A: Action, S: Send + Sync> FromRequestParts<S> for Authorized<A> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let auth_ctx = parts
+            .extensions
+            .get::<AuthContext>()
+            .cloned()
+            .ok_or_else(|| ApiError::Unauthorized("Not logged in".into()))?;
+
+        check_permission(&auth_ctx, A::PERMISSION)?;
+
+        Ok(Authorized(auth_ctx, PhantomData))
+    }
+}
+
+/// so the frontend can hide what the user can't do anyway
+#[utoipa::path(
+    get,
+    path = "/permissions/list-own",
+    tag = "account",
+    responses(
+        (status = 200, description = "permission names like `game.create`", body = Vec<String>),
+    ),
+)]
+pub async fn list_own_permissions(
+    auth_ctx: Extension<AuthContext>,
+) -> (StatusCode, Json<BTreeSet<Permission>>) {
+    (StatusCode::OK, Json(effective_permissions(&auth_ctx)))
+}
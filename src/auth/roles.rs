@@ -1,20 +1,25 @@
-use std::{collections::HashMap, fmt::Write};
-
-use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    response::{IntoResponse, Response},
-    Extension, Json,
-};
-use log::{debug, error};
+use axum::{extract::State, http::StatusCode, Extension, Json};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use sqlx::{FromRow, Type};
-use strum_macros::{Display, EnumString};
+use strum_macros::{Display, EnumIter, EnumString};
+use utoipa::{IntoParams, ToSchema};
 
 use crate::{
-    auth::{roles, utils::AuthContext},
-    entities::user::UserClean,
-    utils::api::{db_err_to_response, AppState},
+    auth::{
+        permissions::{actions::RoleList, check_permission, Authorized, Permission},
+        utils::AuthContext,
+    },
+    entities::{
+        audit_log::{record_audit_event, AuditAction, AuditEvent},
+        user::UserClean,
+    },
+    utils::{
+        api::AppState,
+        error::ApiError,
+        pagination::{ListParams, ListQuery, Paginated, SortKey},
+        validation::{Validate, ValidatedJson, Validator},
+    },
 };
 
 // TODO: consider a bitmask/bit-flags
@@ -23,7 +28,23 @@ use crate::{
 // TODO: consider saving roles directly into the users table as a special Postgres type
 
 // hardcoding roles, since they shouldn't be adjustable in the UI
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, Display, EnumString)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    Type,
+    Display,
+    EnumString,
+    EnumIter,
+    ToSchema,
+)]
 #[sqlx(type_name = "user_roles", rename_all = "snake_case")] // must match the Postgres type name
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
@@ -33,7 +54,23 @@ pub enum Role {
     ClubAdmin,
 
     Coach,
+    /// organises games for teams, without a say in roles
+    TeamManager,
+    RefereeManager,
+    Referee,
     Player,
+    /// acts on behalf of the minors linked via guardianships
+    Parent,
+}
+
+impl Role {
+    /// who takes part in games and can thus be invited to them
+    pub fn is_invitable(self) -> bool {
+        matches!(
+            self,
+            Role::Coach | Role::TeamManager | Role::Referee | Role::Player
+        )
+    }
 }
 
 // hardcoding roles, since they shouldn't be adjustable in the UI
@@ -48,48 +85,13 @@ pub enum GlobalRole {
     User,
 }
 
-pub fn check_user_roles(auth_ctx: &AuthContext, role_whitelist: &[Role]) -> Result<(), Response> {
-    debug!(
-        "ROLE CHECK, expected {:?} - received {:?}",
-        role_whitelist, auth_ctx.roles
-    );
-    if auth_ctx.global_roles.contains(&GlobalRole::Admin) {
-        debug!("GLOBAL ADMIN - ACCESS ALLOWED");
-        return Ok(());
-    }
-    let roles = &auth_ctx.roles;
-    for r in roles {
-        if role_whitelist.contains(&r) {
-            debug!("ROLE CHECK SUCCEEDED");
-            return Ok(());
-        }
-    }
-    let mut printable_whitelist = String::new();
-
-    for (i, role) in role_whitelist.iter().enumerate() {
-        if i > 0 {
-            printable_whitelist.push_str(", ");
-        }
-        write!(
-            &mut printable_whitelist,
-            "{}",
-            role.to_string().to_lowercase()
-        )
-        .unwrap();
-    }
-
-    let error_text = format!(
-        "Access denied. Needs one of roles: [{}]",
-        printable_whitelist
-    );
-    error!("ROLE CHECK FAILED: {}", error_text);
-    return Err((StatusCode::FORBIDDEN, error_text).into_response());
-}
-
-#[derive(FromRow, Serialize)]
+#[derive(FromRow, Serialize, ToSchema)]
 pub struct SelectRoleAssignments {
     roles: Option<Vec<Role>>,
     user_id: String,
+    username: String,
+    #[serde(skip)]
+    sort_key: String,
 }
 
 #[derive(FromRow, Serialize)]
@@ -99,10 +101,16 @@ pub struct SelectOwnRoleAssignment {
 }
 
 // #[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/roles/list-own",
+    tag = "roles",
+    responses((status = 200, body = Vec<Role>)),
+)]
 pub async fn list_own_role_assignments(
     State(state): State<AppState>,
     auth_ctx: Extension<AuthContext>,
-) -> Result<(StatusCode, Json<Vec<Role>>), Response> {
+) -> Result<(StatusCode, Json<Vec<Role>>), ApiError> {
     let role_assignment = sqlx::query_as!(
         SelectOwnRoleAssignment,
         r#"SELECT
@@ -115,8 +123,7 @@ pub async fn list_own_role_assignments(
         auth_ctx.user_id
     )
     .fetch_optional(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .await?;
 
     match role_assignment {
         Some(ra) => Ok((StatusCode::OK, Json(ra.roles.unwrap_or(vec![])))),
@@ -124,80 +131,129 @@ pub async fn list_own_role_assignments(
     }
 }
 
-#[derive(Deserialize)]
-pub struct Params {
-    user_id: Option<String>,
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum RoleAssignmentSort {
+    #[default]
+    Username,
+}
+
+impl SortKey for RoleAssignmentSort {}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RoleAssignmentFilters {
+    pub user_id: Option<String>,
+    /// users with this role, along with all their other roles
+    pub role: Option<Role>,
+}
+
+impl Validate for RoleAssignmentFilters {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(user_id) = &self.user_id {
+            v.id("user_id", user_id);
+        }
+    }
 }
 
+#[utoipa::path(
+    get,
+    path = "/roles/list",
+    tag = "roles",
+    params(ListParams, RoleAssignmentFilters),
+    responses((status = 200, body = Paginated<SelectRoleAssignments>)),
+)]
 pub async fn list_role_assignments(
     State(state): State<AppState>,
-    auth_ctx: Extension<AuthContext>,
-    Query(params): Query<Params>,
-) -> Result<(StatusCode, Json<HashMap<String, Vec<Role>>>), Response> {
-    let _ = check_user_roles(&auth_ctx, &[Role::ClubAdmin, Role::SuperAdmin])?;
-    let query = match params.user_id {
-        Some(user_id) => {
-            sqlx::query_as!(
-                SelectRoleAssignments,
-                r#"SELECT
-                ra.user_id,
-                COALESCE(array_agg(ra.role) FILTER (WHERE ra.role IS NOT NULL), '{}') AS "roles: Vec<Role>" 
-                FROM role_assignments ra
-                JOIN users u
-                ON ra.user_id = u.id
-                WHERE ra.user_id = $1 AND u.club_id = $2
-                GROUP BY (ra.user_id)
-                "#,
-                user_id,
-                auth_ctx.club_id
-            )
-            .fetch_all(&state.pg_pool)
-            .await
-        }
-        None => {
-            sqlx::query_as!(
-                SelectRoleAssignments,
-                r#"SELECT
-                ra.user_id,
-                COALESCE(array_agg(ra.role) FILTER (WHERE ra.role IS NOT NULL), '{}') AS "roles: Vec<Role>" 
-                FROM role_assignments ra
-                JOIN users u
-                ON ra.user_id = u.id
-                WHERE u.club_id = $1
-                GROUP BY (ra.user_id)
-                "#,
-                auth_ctx.club_id
-            )
-            .fetch_all(&state.pg_pool)
-            .await
-        }
-    };
+    auth_ctx: Authorized<RoleList>,
+    ListQuery { page, filters }: ListQuery<RoleAssignmentSort, RoleAssignmentFilters>,
+) -> Result<(StatusCode, Json<Paginated<SelectRoleAssignments>>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
 
-    let role_assignments = query.map_err(db_err_to_response)?;
+    let role_assignments = sqlx::query_as!(
+        SelectRoleAssignments,
+        r#"
+        SELECT
+        ra.user_id,
+        u.username,
+        COALESCE(array_agg(ra.role) FILTER (WHERE ra.role IS NOT NULL), '{}') AS "roles: Vec<Role>",
+        u.username AS sort_key
+        FROM role_assignments ra
+        JOIN users u
+        ON ra.user_id = u.id
+        WHERE u.club_id = $1
+        AND ($2::text IS NULL OR ra.user_id = $2)
+        AND ($4::text IS NULL OR CASE WHEN $3
+            THEN (u.username, u.id) < ($4, $5) ELSE (u.username, u.id) > ($4, $5) END)
+        GROUP BY (ra.user_id, u.username, u.id)
+        HAVING $6::user_roles IS NULL OR bool_or(ra.role = $6)
+        ORDER BY CASE WHEN $3 THEN u.username END DESC, u.username,
+            CASE WHEN $3 THEN u.id END DESC, u.id
+        LIMIT $7
+        "#,
+        auth_ctx.club_id,
+        filters.user_id,
+        page.descending,
+        page.after_key(),
+        page.after_id(),
+        filters.role as Option<Role>,
+        page.fetch_limit()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
 
-    let mut user_to_role_map: HashMap<String, Vec<Role>> = HashMap::new();
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT count(DISTINCT ra.user_id) AS "count!"
+        FROM role_assignments ra
+        JOIN users u ON ra.user_id = u.id
+        WHERE u.club_id = $1
+        AND ($2::text IS NULL OR ra.user_id = $2)
+        AND ($3::user_roles IS NULL OR ra.role = $3)
+        "#,
+        auth_ctx.club_id,
+        filters.user_id,
+        filters.role as Option<Role>
+    )
+    .fetch_one(&mut *tx)
+    .await?;
 
-    for assignment in role_assignments {
-        user_to_role_map.insert(assignment.user_id, assignment.roles.unwrap_or(vec![]));
-    }
+    tx.commit().await?;
 
-    Ok((StatusCode::CREATED, Json(user_to_role_map)))
+    let role_assignments = page.finish(role_assignments, total, |assignment| {
+        (&assignment.sort_key, &assignment.user_id)
+    });
+    Ok((StatusCode::OK, Json(role_assignments)))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct AssignRole {
     pub user_id: String,
     pub role: Role,
 }
 
+impl Validate for AssignRole {
+    fn validate(&self, v: &mut Validator) {
+        v.id("user_id", &self.user_id);
+    }
+}
+
 // higher roles may assign all lower roles
+#[utoipa::path(
+    post,
+    path = "/roles/assign",
+    tag = "roles",
+    request_body = AssignRole,
+    responses(
+        (status = 201, description = "the assignment id", body = String, content_type = "text/plain"),
+    ),
+)]
 pub async fn assign_role(
     State(state): State<AppState>,
     auth_ctx: Extension<AuthContext>,
-    Json(payload): Json<AssignRole>,
-) -> Result<(StatusCode, String), Response> {
-    let mut tx: sqlx::Transaction<'static, sqlx::Postgres> =
-        state.pg_pool.begin().await.map_err(db_err_to_response)?;
+    ValidatedJson(payload): ValidatedJson<AssignRole>,
+) -> Result<(StatusCode, String), ApiError> {
+    let mut tx: sqlx::Transaction<'static, sqlx::Postgres> = state.pg_pool.begin().await?;
 
     let _ = sqlx::query_as!(
         UserClean,
@@ -205,28 +261,13 @@ pub async fn assign_role(
         auth_ctx.club_id,
         payload.user_id
     )
-    .fetch_one(&mut *tx)
-    .await
-    .map_err(|err| {
-        error!("{}", err);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Given user not present in your club or doesn't exist",
-        )
-            .into_response()
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| {
+        ApiError::NotFound("Given user not present in your club or doesn't exist".into())
     })?;
 
-    // access checks
-    let _ = match payload.role {
-        Role::SuperAdmin => check_user_roles(&auth_ctx, &[Role::SuperAdmin])?,
-        Role::ClubAdmin => check_user_roles(&auth_ctx, &[Role::SuperAdmin, Role::ClubAdmin])?,
-        Role::Coach => {
-            check_user_roles(&auth_ctx, &[Role::SuperAdmin, Role::ClubAdmin, Role::Coach])?
-        }
-        Role::Player => {
-            check_user_roles(&auth_ctx, &[Role::SuperAdmin, Role::ClubAdmin, Role::Coach])?
-        }
-    };
+    check_permission(&auth_ctx, Permission::RoleAssign(payload.role))?;
 
     let new_assignment = sqlx::query!(
         r#"INSERT INTO role_assignments (user_id, role) VALUES ($1, $2) RETURNING id"#,
@@ -234,31 +275,43 @@ pub async fn assign_role(
         payload.role as Role
     )
     .fetch_one(&mut *tx)
-    .await
-    .map_err(db_err_to_response)?;
+    .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            after: Some(json!({ "role": payload.role })),
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::RoleAssigned,
+                "user",
+                &payload.user_id,
+            )
+        },
+    )
+    .await?;
 
-    let _ = tx.commit().await.map_err(db_err_to_response)?;
+    tx.commit().await?;
 
     Ok((StatusCode::CREATED, new_assignment.id.to_string()))
 }
 
 // higher roles may assign all lower roles
+#[utoipa::path(
+    delete,
+    path = "/roles/unassign",
+    tag = "roles",
+    request_body = AssignRole,
+    responses((status = 201, description = "role removed")),
+)]
 pub async fn unassign_role(
     State(state): State<AppState>,
     auth_ctx: Extension<AuthContext>,
-    Json(payload): Json<AssignRole>,
-) -> Result<StatusCode, Response> {
-    // access checks
-    let _ = match payload.role {
-        Role::SuperAdmin => check_user_roles(&auth_ctx, &[Role::SuperAdmin])?,
-        Role::ClubAdmin => check_user_roles(&auth_ctx, &[Role::SuperAdmin, Role::ClubAdmin])?,
-        Role::Coach => {
-            check_user_roles(&auth_ctx, &[Role::SuperAdmin, Role::ClubAdmin, Role::Coach])?
-        }
-        Role::Player => {
-            check_user_roles(&auth_ctx, &[Role::SuperAdmin, Role::ClubAdmin, Role::Coach])?
-        }
-    };
+    ValidatedJson(payload): ValidatedJson<AssignRole>,
+) -> Result<StatusCode, ApiError> {
+    check_permission(&auth_ctx, Permission::RoleAssign(payload.role))?;
+
+    let mut tx = state.pg_pool.begin().await?;
 
     let _ = sqlx::query!(
         r#"
@@ -274,9 +327,24 @@ pub async fn unassign_role(
         auth_ctx.club_id,
         payload.role as Role
     )
-    .fetch_one(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .fetch_one(&mut *tx)
+    .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            before: Some(json!({ "role": payload.role })),
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::RoleUnassigned,
+                "user",
+                &payload.user_id,
+            )
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
 
     Ok(StatusCode::CREATED)
 }
@@ -0,0 +1,47 @@
+//! Secrets the server has to read back, e.g. clubs' single sign-on client secrets, encrypted at
+//! rest with a key derived from `app_secret`.
+//! Format: `sealed1:base64url(nonce + AES-256-GCM ciphertext)`.
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+const PREFIX: &str = "sealed1:";
+const NONCE_LEN: usize = 12;
+
+/// one key per `purpose`, so a value sealed for one use can't be opened for another
+fn cipher(app_secret: &[u8], purpose: &str) -> Aes256Gcm {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(None, app_secret)
+        .expand(purpose.as_bytes(), &mut key)
+        .expect("32 bytes is a valid HKDF output length");
+    Aes256Gcm::new(&key.into())
+}
+
+pub fn seal(app_secret: &[u8], purpose: &str, plaintext: &str) -> String {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher(app_secret, purpose)
+        .encrypt(&nonce, plaintext.as_bytes())
+        .expect("encrypting into a Vec can't fail");
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    format!("{}{}", PREFIX, URL_SAFE_NO_PAD.encode(sealed))
+}
+
+/// `None` for values sealed with another `app_secret` or purpose, or not sealed at all.
+pub fn open(app_secret: &[u8], purpose: &str, sealed: &str) -> Option<String> {
+    let sealed = URL_SAFE_NO_PAD.decode(sealed.strip_prefix(PREFIX)?).ok()?;
+    if sealed.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let plaintext = cipher(app_secret, purpose)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .ok()?;
+    String::from_utf8(plaintext).ok()
+}
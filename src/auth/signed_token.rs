@@ -0,0 +1,69 @@
+//! Stateless HMAC-signed tokens for links sent by email, e.g. email verification.
+//! Format: `base64url(json payload).base64url(HMAC-SHA256 of the first part)`.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use log::warn;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::auth::utils::generate_token;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    /// keeps a token for one use case from being accepted by another
+    purpose: String,
+    /// unix timestamp (seconds)
+    exp: i64,
+    claims: T,
+}
+
+/// The configured `app_secret`. Without it a random secret is used, so links stop working after a
+/// restart.
+pub fn secret_from_config(app_secret: Option<&str>) -> Vec<u8> {
+    match app_secret {
+        Some(secret) => secret.as_bytes().to_vec(),
+        None => {
+            // only debug builds start without one, see `Config::validate`
+            warn!("APP_SECRET is not configured - emailed links and encrypted secrets (SSO, 2FA) will not survive a restart");
+            generate_token(64).into_bytes()
+        }
+    }
+}
+
+fn mac(secret: &[u8], payload: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+pub fn sign<T: Serialize>(secret: &[u8], purpose: &str, claims: T, ttl: Duration) -> String {
+    let envelope = Envelope {
+        purpose: purpose.to_string(),
+        exp: (Utc::now() + ttl).timestamp(),
+        claims,
+    };
+    let payload = URL_SAFE_NO_PAD
+        .encode(serde_json::to_vec(&envelope).expect("token claims are always serializable"));
+    let signature = URL_SAFE_NO_PAD.encode(mac(secret, &payload).finalize().into_bytes());
+
+    format!("{}.{}", payload, signature)
+}
+
+/// `None` for tampered, expired or foreign-purpose tokens - callers don't need to tell them apart.
+pub fn verify<T: DeserializeOwned>(secret: &[u8], purpose: &str, token: &str) -> Option<T> {
+    let (payload, signature) = token.split_once('.')?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+    mac(secret, payload).verify_slice(&signature).ok()?;
+
+    let envelope: Envelope<T> =
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+    if envelope.purpose != purpose || envelope.exp < Utc::now().timestamp() {
+        return None;
+    }
+
+    Some(envelope.claims)
+}
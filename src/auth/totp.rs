@@ -0,0 +1,167 @@
+//! RFC 6238 time-based one-time passwords (HMAC-SHA1, 6 digits, 30s steps) - the defaults
+//! every authenticator app understands.
+
+use chrono::Utc;
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+
+type HmacSha1 = Hmac<Sha1>;
+
+const SECRET_LEN: usize = 20;
+const DIGITS: u32 = 6;
+const STEP_SECONDS: i64 = 30;
+/// steps accepted before/after the current one, to tolerate clock drift
+const ALLOWED_DRIFT_STEPS: i64 = 1;
+const ISSUER: &str = "Sports Planner";
+
+/// Base32 encoded, as expected by authenticator apps.
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; SECRET_LEN];
+    rand::rng().fill_bytes(&mut secret);
+    BASE32_NOPAD.encode(&secret)
+}
+
+pub fn otpauth_uri(secret: &str, account_name: &str) -> String {
+    let label = format!("{}:{}", ISSUER, account_name);
+    format!(
+        "otpauth://totp/{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(&label),
+        secret,
+        percent_encode(ISSUER),
+        DIGITS,
+        STEP_SECONDS
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// RFC 4226 HOTP value for one counter
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    let mut mac = HmacSha1::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let truncated = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    truncated % 10u32.pow(DIGITS)
+}
+
+/// Returns the matched time step, which has to be newer than `last_step` - an accepted code
+/// can't be used a second time.
+pub fn verify_code(secret: &str, code: &str, last_step: Option<i64>) -> Option<i64> {
+    verify_code_at(secret, code, last_step, Utc::now().timestamp())
+}
+
+fn verify_code_at(secret: &str, code: &str, last_step: Option<i64>, now: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+
+    let current_step = now / STEP_SECONDS;
+
+    (current_step - ALLOWED_DRIFT_STEPS..=current_step + ALLOWED_DRIFT_STEPS)
+        .filter(|step| last_step.is_none_or(|last_step| *step > last_step))
+        .find(|step| hotp(&key, *step as u64) == code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the SHA-1 seed of RFC 6238 appendix B, "12345678901234567890"
+    const SEED: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    /// the appendix lists 8 digits, the last 6 are what a 6 digit code shows
+    #[test]
+    fn matches_the_rfc_6238_vectors() {
+        let key = BASE32_NOPAD.decode(SEED.as_bytes()).unwrap();
+        for (time, expected) in [
+            (59, "94287082"),
+            (1111111109, "07081804"),
+            (1111111111, "14050471"),
+            (1234567890, "89005924"),
+            (2000000000, "69279037"),
+            (20000000000, "65353130"),
+        ] {
+            let code = format!("{:06}", hotp(&key, (time / STEP_SECONDS) as u64));
+            assert_eq!(code, expected[2..], "at {}", time);
+            assert_eq!(
+                verify_code_at(SEED, &code, None, time),
+                Some(time / STEP_SECONDS)
+            );
+        }
+    }
+
+    #[test]
+    fn accepts_one_step_of_drift() {
+        let key = BASE32_NOPAD.decode(SEED.as_bytes()).unwrap();
+        let now = 1111111111;
+        let step = now / STEP_SECONDS;
+        let code_at = |step: i64| format!("{:06}", hotp(&key, step as u64));
+
+        assert_eq!(
+            verify_code_at(SEED, &code_at(step - 1), None, now),
+            Some(step - 1)
+        );
+        assert_eq!(
+            verify_code_at(SEED, &code_at(step + 1), None, now),
+            Some(step + 1)
+        );
+        assert_eq!(verify_code_at(SEED, &code_at(step - 2), None, now), None);
+        assert_eq!(verify_code_at(SEED, &code_at(step + 2), None, now), None);
+    }
+
+    #[test]
+    fn rejects_steps_up_to_the_last_accepted_one() {
+        let key = BASE32_NOPAD.decode(SEED.as_bytes()).unwrap();
+        let now = 1111111111;
+        let step = now / STEP_SECONDS;
+        let code_at = |step: i64| format!("{:06}", hotp(&key, step as u64));
+
+        // the same code twice
+        assert_eq!(verify_code_at(SEED, &code_at(step), Some(step), now), None);
+        // an older one after a newer one
+        assert_eq!(
+            verify_code_at(SEED, &code_at(step - 1), Some(step), now),
+            None
+        );
+        assert_eq!(
+            verify_code_at(SEED, &code_at(step), Some(step - 1), now),
+            Some(step)
+        );
+        assert_eq!(
+            verify_code_at(SEED, &code_at(step + 1), Some(step), now),
+            Some(step + 1)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_codes() {
+        let now = 59;
+        assert_eq!(verify_code_at(SEED, " 287082 ", None, now), Some(1));
+        for code in ["28708", "2870820", "28708a", "", "-87082"] {
+            assert_eq!(verify_code_at(SEED, code, None, now), None, "{:?}", code);
+        }
+    }
+}
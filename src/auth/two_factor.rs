@@ -0,0 +1,806 @@
+//! Two-factor authentication: TOTP enrolment, the second log-in step, recovery codes,
+//! and the club setting that makes 2FA mandatory for admins. TOTP secrets are stored sealed with
+//! `app_secret`.
+
+use axum::{
+    extract::{Path, State},
+    http::{header::SET_COOKIE, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use chrono::{Duration, Utc};
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgConnection;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{
+        auth_routes::start_session,
+        login_throttle::{
+            begin_login_attempt, begin_reauthentication, clear_failed_logins, end_login_attempt,
+            record_failed_login,
+        },
+        password::{check_can_manage_user, MAX_PASSWORD_LEN},
+        permissions::{actions::ClubManage, Authorized},
+        roles::Role,
+        sealed, totp,
+        utils::{generate_token, hash_token, passwords_match, AuthContext},
+    },
+    config::SessionConfig,
+    entities::audit_log::{record_audit_event, AuditAction, AuditEvent},
+    utils::{
+        api::AppState,
+        client_ip::ClientIp,
+        error::ApiError,
+        validation::{Validate, ValidatedJson, Validator, MAX_TOKEN_LEN},
+    },
+};
+
+const CHALLENGE_TOKEN_LEN: usize = 32;
+const TOTP_SECRET_PURPOSE: &str = "totp-secret";
+const CHALLENGE_TTL_MINUTES: i64 = 5;
+/// carries the challenge after single sign-on, which ends in a redirect - in the URL it would
+/// end up in the browser history, proxy logs and `Referer` headers
+const CHALLENGE_COOKIE: &str = "two_factor_challenge";
+const CHALLENGE_COOKIE_PATH: &str = "/api/auth/log-in/2fa";
+/// after this many wrong codes the password has to be entered again
+const MAX_CHALLENGE_ATTEMPTS: i32 = 5;
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LEN: usize = 10;
+
+/// roles that have to use 2FA when their club requires it
+pub const TWO_FACTOR_ROLES: &[Role] = &[Role::ClubAdmin, Role::SuperAdmin];
+
+/// paths a user who still has to set up required 2FA may call
+pub const TWO_FACTOR_ENROLMENT_ALLOWED_PATHS: &[&str] = &[
+    "/2fa/status",
+    "/2fa/enrol",
+    "/2fa/activate",
+    "/log-out",
+    "/csrf-token",
+];
+
+#[derive(Serialize, ToSchema)]
+pub struct TwoFactorChallenge {
+    /// exchanged for a session at `/auth/log-in/2fa` together with a code
+    pub two_factor_challenge: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CompleteTwoFactorLogIn {
+    /// omitted after single sign-on, the challenge is in an HttpOnly cookie then
+    pub challenge: Option<String>,
+    /// current TOTP code or an unused recovery code
+    pub code: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TwoFactorCode {
+    pub code: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct DisableTwoFactor {
+    pub current_password: String,
+    /// current TOTP code or an unused recovery code
+    pub code: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetRequireAdminTwoFactor {
+    pub required: bool,
+}
+
+/// recovery codes are longer than TOTP codes, but not by much
+const MAX_CODE_LEN: usize = 64;
+
+impl Validate for CompleteTwoFactorLogIn {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(challenge) = &self.challenge {
+            v.length("challenge", challenge, 1, MAX_TOKEN_LEN);
+        }
+        v.length("code", &self.code, 1, MAX_CODE_LEN);
+    }
+}
+
+impl Validate for TwoFactorCode {
+    fn validate(&self, v: &mut Validator) {
+        v.length("code", &self.code, 1, MAX_CODE_LEN);
+    }
+}
+
+impl Validate for DisableTwoFactor {
+    fn validate(&self, v: &mut Validator) {
+        v.length(
+            "current_password",
+            &self.current_password,
+            1,
+            MAX_PASSWORD_LEN,
+        );
+        v.length("code", &self.code, 1, MAX_CODE_LEN);
+    }
+}
+
+impl Validate for SetRequireAdminTwoFactor {
+    fn validate(&self, _: &mut Validator) {}
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TwoFactorStatus {
+    enabled: bool,
+    /// whether the club makes 2FA mandatory for this user
+    required: bool,
+    recovery_codes_left: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TwoFactorEnrolment {
+    secret: String,
+    otpauth_uri: String,
+}
+
+/// Shown once - only hashes are stored.
+#[derive(Serialize, ToSchema)]
+pub struct RecoveryCodes {
+    recovery_codes: Vec<String>,
+}
+
+pub fn two_factor_required(roles: &[Role], club_requires_admin_2fa: bool) -> bool {
+    club_requires_admin_2fa && roles.iter().any(|role| TWO_FACTOR_ROLES.contains(role))
+}
+
+pub async fn two_factor_enabled(conn: &mut PgConnection, user_id: &str) -> Result<bool, ApiError> {
+    sqlx::query_scalar!(
+        r#"SELECT totp_enabled_at IS NOT NULL AS "enabled!" FROM users WHERE id = $1"#,
+        user_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(ApiError::from)
+}
+
+/// `identifier` is the username/email the password was entered for, wrong codes count as failed
+/// log-ins for it - the username if `None`, e.g. after single sign-on.
+pub async fn create_two_factor_challenge(
+    conn: &mut PgConnection,
+    user_id: &str,
+    identifier: Option<&str>,
+) -> Result<TwoFactorChallenge, ApiError> {
+    let token = generate_token(CHALLENGE_TOKEN_LEN);
+
+    sqlx::query!(
+        r#"
+        INSERT INTO two_factor_challenges (user_id, token_hash, expires_at, login_identifier)
+        SELECT id, $2, $3, COALESCE($4, lower(username)) FROM users WHERE id = $1
+        "#,
+        user_id,
+        hash_token(&token),
+        Utc::now() + Duration::minutes(CHALLENGE_TTL_MINUTES),
+        identifier
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(TwoFactorChallenge {
+        two_factor_challenge: token,
+    })
+}
+
+/// Hands a challenge to `/log-in/2fa` without putting it in a URL, `None` clears it.
+pub fn challenge_cookie(config: &SessionConfig, challenge: Option<String>) -> String {
+    let max_age = match challenge {
+        Some(_) => time::Duration::minutes(CHALLENGE_TTL_MINUTES),
+        None => time::Duration::ZERO,
+    };
+    Cookie::build((CHALLENGE_COOKIE, challenge.unwrap_or_default()))
+        .secure(config.cookie_secure)
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .path(CHALLENGE_COOKIE_PATH)
+        .max_age(max_age)
+        .build()
+        .to_string()
+}
+
+/// dashes and case are only there for readability
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code = generate_token(RECOVERY_CODE_LEN).to_lowercase();
+            let (first, second) = code.split_at(RECOVERY_CODE_LEN / 2);
+            format!("{}-{}", first, second)
+        })
+        .collect()
+}
+
+/// Replaces all recovery codes of the user.
+async fn store_new_recovery_codes(
+    conn: &mut PgConnection,
+    user_id: &str,
+) -> Result<Vec<String>, ApiError> {
+    let codes = generate_recovery_codes();
+    let hashes: Vec<String> = codes
+        .iter()
+        .map(|code| hash_token(&normalize_recovery_code(code)))
+        .collect();
+
+    sqlx::query!(
+        r#"DELETE FROM totp_recovery_codes WHERE user_id = $1"#,
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"INSERT INTO totp_recovery_codes (user_id, code_hash) SELECT $1, UNNEST($2::text[])"#,
+        user_id,
+        &hashes
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(codes)
+}
+
+/// Checks a TOTP code or, failing that, consumes a recovery code.
+async fn verify_second_factor(
+    conn: &mut PgConnection,
+    app_secret: &[u8],
+    user_id: &str,
+    code: &str,
+) -> Result<bool, ApiError> {
+    let user = sqlx::query!(
+        r#"SELECT totp_secret, totp_last_step FROM users WHERE id = $1"#,
+        user_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    // recovery codes still work when the secret can't be opened anymore
+    let secret = user.totp_secret.as_deref().and_then(|sealed| {
+        let secret = sealed::open(app_secret, TOTP_SECRET_PURPOSE, sealed);
+        if secret.is_none() {
+            error!(
+                "the TOTP secret of user {} can't be decrypted, was APP_SECRET changed?",
+                user_id
+            );
+        }
+        secret
+    });
+
+    if let Some(step) = secret
+        .as_deref()
+        .and_then(|secret| totp::verify_code(secret, code, user.totp_last_step))
+    {
+        // the condition makes concurrent use of the same code fail for all but one request
+        let accepted = sqlx::query!(
+            r#"
+            UPDATE users SET totp_last_step = $1
+            WHERE id = $2 AND (totp_last_step IS NULL OR totp_last_step < $1)
+            "#,
+            step,
+            user_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        return Ok(accepted.rows_affected() == 1);
+    }
+
+    let used_recovery_code = sqlx::query!(
+        r#"
+        UPDATE totp_recovery_codes SET used_at = now()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        user_id,
+        hash_token(&normalize_recovery_code(code))
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    if used_recovery_code.rows_affected() == 1 {
+        debug!("user {} logged in with a recovery code", user_id);
+    }
+
+    Ok(used_recovery_code.rows_affected() == 1)
+}
+
+async fn club_requires_admin_2fa(conn: &mut PgConnection, club_id: &str) -> Result<bool, ApiError> {
+    sqlx::query_scalar!(
+        r#"SELECT require_admin_2fa FROM clubs WHERE id = $1"#,
+        club_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(ApiError::from)
+}
+
+/// Second log-in step, the session is only issued here.
+#[utoipa::path(
+    post,
+    path = "/log-in/2fa",
+    tag = "auth",
+    request_body = CompleteTwoFactorLogIn,
+    responses(
+        (status = 200, description = "logged in, the body is the user id", body = String, content_type = "text/plain"),
+    ),
+)]
+pub async fn complete_two_factor_log_in(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    jar: CookieJar,
+    ValidatedJson(payload): ValidatedJson<CompleteTwoFactorLogIn>,
+) -> Result<Response, ApiError> {
+    let challenge_token = payload
+        .challenge
+        .or_else(|| {
+            jar.get(CHALLENGE_COOKIE)
+                .map(|cookie| cookie.value().to_string())
+        })
+        .ok_or_else(expired_challenge)?;
+    let token_hash = hash_token(&challenge_token);
+
+    // counted like a password attempt first, a new challenge for each correct password must not
+    // allow guessing codes without limit
+    let identifier = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(c.login_identifier, lower(u.username)) AS "identifier!"
+        FROM two_factor_challenges c JOIN users u ON u.id = c.user_id
+        WHERE c.token_hash = $1 AND c.expires_at > now()
+        "#,
+        token_hash
+    )
+    .fetch_optional(&state.pg_pool)
+    .await?
+    .ok_or_else(expired_challenge)?;
+    begin_login_attempt(&state.pg_pool, &identifier, ip).await?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    let challenge = sqlx::query!(
+        r#"
+        SELECT c.id, c.user_id, u.club_id FROM two_factor_challenges c
+        JOIN users u ON u.id = c.user_id
+        WHERE c.token_hash = $1 AND c.expires_at > now() AND c.failed_attempts < $2
+        FOR UPDATE OF c
+        "#,
+        token_hash,
+        MAX_CHALLENGE_ATTEMPTS
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(challenge) = challenge else {
+        return Err(expired_challenge());
+    };
+
+    if !verify_second_factor(
+        &mut tx,
+        &state.token_secret,
+        &challenge.user_id,
+        &payload.code,
+    )
+    .await?
+    {
+        sqlx::query!(
+            r#"UPDATE two_factor_challenges SET failed_attempts = failed_attempts + 1 WHERE id = $1"#,
+            challenge.id
+        )
+        .execute(&mut *tx)
+        .await?;
+        record_failed_login(
+            &mut tx,
+            &identifier,
+            ip,
+            Some((&challenge.user_id, &challenge.club_id)),
+        )
+        .await?;
+        tx.commit().await?;
+
+        return Err(ApiError::Unauthorized("Invalid code".into()));
+    }
+
+    sqlx::query!(
+        r#"DELETE FROM two_factor_challenges WHERE id = $1 OR expires_at < now()"#,
+        challenge.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    clear_failed_logins(&mut tx, &identifier).await?;
+    end_login_attempt(&mut tx, ip).await?;
+
+    let mut headers = start_session(&mut tx, &challenge.user_id, &state.config.session).await?;
+    headers.append(
+        SET_COOKIE,
+        challenge_cookie(&state.config.session, None)
+            .parse()
+            .expect("cookies are valid header values"),
+    );
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, headers, challenge.user_id).into_response())
+}
+
+fn expired_challenge() -> ApiError {
+    ApiError::Unauthorized("Log-in expired, please enter your password again".into())
+}
+
+#[utoipa::path(
+    get,
+    path = "/2fa/status",
+    tag = "account",
+    responses((status = 200, body = TwoFactorStatus)),
+)]
+pub async fn get_two_factor_status(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+) -> Result<(StatusCode, Json<TwoFactorStatus>), ApiError> {
+    let mut conn = state.pg_pool.acquire().await?;
+
+    let enabled = two_factor_enabled(&mut conn, &auth_ctx.user_id).await?;
+    let required = two_factor_required(
+        &auth_ctx.roles,
+        club_requires_admin_2fa(&mut conn, &auth_ctx.club_id).await?,
+    );
+    let recovery_codes_left = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM totp_recovery_codes WHERE user_id = $1 AND used_at IS NULL"#,
+        auth_ctx.user_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(TwoFactorStatus {
+            enabled,
+            required,
+            recovery_codes_left,
+        }),
+    ))
+}
+
+/// Starts (or restarts) enrolment. 2FA is only switched on by [`activate_two_factor`].
+#[utoipa::path(
+    post,
+    path = "/2fa/enrol",
+    tag = "account",
+    responses(
+        (status = 201, description = "active once confirmed with a code", body = TwoFactorEnrolment),
+    ),
+)]
+pub async fn enrol_two_factor(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+) -> Result<(StatusCode, Json<TwoFactorEnrolment>), ApiError> {
+    let secret = totp::generate_secret();
+
+    let username = sqlx::query_scalar!(
+        r#"
+        UPDATE users SET totp_pending_secret = $1, updated_at = CURRENT_TIMESTAMP
+        WHERE id = $2 AND totp_enabled_at IS NULL
+        RETURNING username
+        "#,
+        sealed::seal(&state.token_secret, TOTP_SECRET_PURPOSE, &secret),
+        auth_ctx.user_id
+    )
+    .fetch_optional(&state.pg_pool)
+    .await?;
+
+    let Some(username) = username else {
+        return Err(ApiError::Conflict(
+            "Two-factor authentication is already enabled".into(),
+        ));
+    };
+
+    Ok((
+        StatusCode::CREATED,
+        Json(TwoFactorEnrolment {
+            otpauth_uri: totp::otpauth_uri(&secret, &username),
+            secret,
+        }),
+    ))
+}
+
+/// Proves the authenticator app works and switches 2FA on.
+#[utoipa::path(
+    post,
+    path = "/2fa/activate",
+    tag = "account",
+    request_body = TwoFactorCode,
+    responses((status = 200, body = RecoveryCodes)),
+)]
+pub async fn activate_two_factor(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    ValidatedJson(payload): ValidatedJson<TwoFactorCode>,
+) -> Result<(StatusCode, Json<RecoveryCodes>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let pending_secret = sqlx::query_scalar!(
+        r#"SELECT totp_pending_secret FROM users WHERE id = $1 AND totp_enabled_at IS NULL FOR UPDATE"#,
+        auth_ctx.user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .flatten();
+
+    let Some(pending_secret) = pending_secret else {
+        return Err(ApiError::Conflict(
+            "No two-factor enrolment in progress".into(),
+        ));
+    };
+
+    let Some(pending_secret) =
+        sealed::open(&state.token_secret, TOTP_SECRET_PURPOSE, &pending_secret)
+    else {
+        return Err(ApiError::Conflict(
+            "The two-factor enrolment expired, please start again".into(),
+        ));
+    };
+
+    let Some(step) = totp::verify_code(&pending_secret, &payload.code, None) else {
+        return Err(ApiError::BadRequest("Invalid code".into()));
+    };
+
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_secret = totp_pending_secret, totp_pending_secret = NULL, totp_enabled_at = now(),
+            totp_last_step = $1, updated_at = CURRENT_TIMESTAMP
+        WHERE id = $2
+        "#,
+        step,
+        auth_ctx.user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let recovery_codes = store_new_recovery_codes(&mut tx, &auth_ctx.user_id).await?;
+
+    // other devices were logged in with the password only
+    sqlx::query!(
+        r#"DELETE FROM sessions WHERE user_id = $1 AND id <> $2"#,
+        auth_ctx.user_id,
+        auth_ctx.session_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(RecoveryCodes { recovery_codes })))
+}
+
+/// Needs the password as well as a code, and wrong ones count as failed log-ins.
+#[utoipa::path(
+    post,
+    path = "/2fa/disable",
+    tag = "account",
+    request_body = DisableTwoFactor,
+    responses((status = 204, description = "2FA disabled")),
+)]
+pub async fn disable_two_factor(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    ClientIp(ip): ClientIp,
+    ValidatedJson(payload): ValidatedJson<DisableTwoFactor>,
+) -> Result<StatusCode, ApiError> {
+    if two_factor_required(
+        &auth_ctx.roles,
+        club_requires_admin_2fa(&mut *state.pg_pool.acquire().await?, &auth_ctx.club_id).await?,
+    ) {
+        return Err(ApiError::Forbidden(
+            "Your club requires two-factor authentication".into(),
+        ));
+    }
+
+    let identifier = begin_reauthentication(&state.pg_pool, &auth_ctx.user_id, ip).await?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    let stored_password = sqlx::query_scalar!(
+        r#"SELECT password FROM users WHERE id = $1 FOR UPDATE"#,
+        auth_ctx.user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    // the code is only checked with the right password, so guesses don't burn recovery codes
+    let confirmed = passwords_match(Some(&stored_password), &payload.current_password)
+        && verify_second_factor(
+            &mut tx,
+            &state.token_secret,
+            &auth_ctx.user_id,
+            &payload.code,
+        )
+        .await?;
+
+    if !confirmed {
+        record_failed_login(
+            &mut tx,
+            &identifier,
+            ip,
+            Some((&auth_ctx.user_id, &auth_ctx.club_id)),
+        )
+        .await?;
+        tx.commit().await?;
+
+        return Err(ApiError::Forbidden("Invalid password or code".into()));
+    }
+
+    end_login_attempt(&mut tx, ip).await?;
+    clear_failed_logins(&mut tx, &identifier).await?;
+
+    clear_two_factor(&mut tx, &auth_ctx.user_id).await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Wrong codes count as failed log-ins.
+#[utoipa::path(
+    post,
+    path = "/2fa/recovery-codes/regenerate",
+    tag = "account",
+    request_body = TwoFactorCode,
+    responses((status = 200, body = RecoveryCodes)),
+)]
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    ClientIp(ip): ClientIp,
+    ValidatedJson(payload): ValidatedJson<TwoFactorCode>,
+) -> Result<(StatusCode, Json<RecoveryCodes>), ApiError> {
+    let identifier = begin_reauthentication(&state.pg_pool, &auth_ctx.user_id, ip).await?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    if !verify_second_factor(
+        &mut tx,
+        &state.token_secret,
+        &auth_ctx.user_id,
+        &payload.code,
+    )
+    .await?
+    {
+        record_failed_login(
+            &mut tx,
+            &identifier,
+            ip,
+            Some((&auth_ctx.user_id, &auth_ctx.club_id)),
+        )
+        .await?;
+        tx.commit().await?;
+
+        return Err(ApiError::Forbidden("Invalid code".into()));
+    }
+
+    end_login_attempt(&mut tx, ip).await?;
+    clear_failed_logins(&mut tx, &identifier).await?;
+
+    let recovery_codes = store_new_recovery_codes(&mut tx, &auth_ctx.user_id).await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(RecoveryCodes { recovery_codes })))
+}
+
+async fn clear_two_factor(conn: &mut PgConnection, user_id: &str) -> Result<(), ApiError> {
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_secret = NULL, totp_pending_secret = NULL, totp_enabled_at = NULL,
+            totp_last_step = NULL, updated_at = CURRENT_TIMESTAMP
+        WHERE id = $1
+        "#,
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"DELETE FROM totp_recovery_codes WHERE user_id = $1"#,
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// For members who lost their authenticator and their recovery codes.
+/// They have to enrol again if their club requires it.
+#[utoipa::path(
+    post,
+    path = "/users/reset-2fa/{id}",
+    tag = "users",
+    params(("id" = String, Path, description = "user id")),
+    responses((status = 204, description = "2FA removed")),
+)]
+pub async fn reset_two_factor_for_user(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    Path(user_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    check_can_manage_user(&state.pg_pool, &auth_ctx, &user_id).await?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    clear_two_factor(&mut tx, &user_id).await?;
+
+    sqlx::query!(r#"DELETE FROM sessions WHERE user_id = $1"#, user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent::by(&auth_ctx, AuditAction::TwoFactorReset, "user", &user_id),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/clubs/set-require-admin-2fa",
+    tag = "clubs",
+    request_body = SetRequireAdminTwoFactor,
+    responses((status = 204, description = "setting saved")),
+)]
+pub async fn set_require_admin_two_factor(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<ClubManage>,
+    ValidatedJson(payload): ValidatedJson<SetRequireAdminTwoFactor>,
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let was_required = sqlx::query_scalar!(
+        r#"SELECT require_admin_2fa FROM clubs WHERE id = $1 FOR UPDATE"#,
+        auth_ctx.club_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        r#"UPDATE clubs SET require_admin_2fa = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2"#,
+        payload.required,
+        auth_ctx.club_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            before: Some(json!({ "require_admin_2fa": was_required })),
+            after: Some(json!({ "require_admin_2fa": payload.required })),
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::ClubSettingsChanged,
+                "club",
+                &auth_ctx.club_id,
+            )
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
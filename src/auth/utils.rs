@@ -1,16 +1,91 @@
-use crate::auth::roles::{GlobalRole, Role};
+use crate::{
+    auth::roles::{GlobalRole, Role},
+    config::{CookieSameSite, SessionConfig},
+};
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rng,
+};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthContext {
+    /// the effective user - while impersonating, the impersonated one
     pub user_id: String,
-    pub session_id: String,
+    /// the admin acting as `user_id`, see `auth::impersonation`
+    pub impersonator_user_id: Option<String>,
+    /// `None` for requests authenticated with an API token
+    pub session_id: Option<String>,
+    pub api_token_id: Option<String>,
     pub club_id: String,
     pub roles: Vec<Role>,
     pub global_roles: Vec<GlobalRole>,
+    /// set by an admin - everything except changing the password is blocked
+    pub must_reset_password: bool,
+    /// the club requires 2FA for this user, who hasn't set it up yet - only enrolment is allowed
+    pub must_enrol_two_factor: bool,
+    /// `X-Request-Id` of the request, or a generated one - ties audit entries to log lines
+    pub request_id: String,
+    pub ip: Option<String>,
+}
+
+impl AuthContext {
+    /// who actually sent the request, for audit entries
+    pub fn real_user_id(&self) -> &str {
+        self.impersonator_user_id
+            .as_deref()
+            .unwrap_or(&self.user_id)
+    }
 }
 
 // TODO: IMPORTANT! hash passwords
 
-pub const EXPIRED_EMPTY_COOKIE: &str =
-    "session_id=; HttpOnly; SameSite=Strict; Secure; Expires=1 Jan 1970 00:00:00 GMT";
+pub const SESSION_COOKIE: &str = "session_id";
+
+/// the session cookie with the configured attributes, `None` expires it right away
+pub fn session_cookie(config: &SessionConfig, session_id: Option<String>) -> String {
+    let max_age = match session_id {
+        Some(_) => time::Duration::days(config.lifetime_days),
+        None => time::Duration::ZERO,
+    };
+    let mut cookie = Cookie::build((SESSION_COOKIE, session_id.unwrap_or_default()))
+        .secure(config.cookie_secure)
+        .path("/")
+        .http_only(true)
+        .same_site(match config.cookie_same_site {
+            CookieSameSite::Strict => SameSite::Strict,
+            CookieSameSite::Lax => SameSite::Lax,
+        })
+        .max_age(max_age);
+    if let Some(domain) = &config.cookie_domain {
+        cookie = cookie.domain(domain.clone());
+    }
+    cookie.to_string()
+}
+
+/// random secret for links and one-time codes
+pub fn generate_token(len: usize) -> String {
+    Alphanumeric.sample_string(&mut rng(), len)
+}
+
+/// tokens are stored as SHA-256 hex digests, so a DB leak doesn't leak usable tokens
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Compares in constant time. Unknown users (`stored` is `None`) take just as long, so the
+/// response time doesn't reveal which usernames exist.
+pub fn passwords_match(stored: Option<&str>, given: &str) -> bool {
+    // digests have a fixed length, so not even the password length leaks
+    let stored_digest = Sha256::digest(stored.unwrap_or_default().as_bytes());
+    let given_digest = Sha256::digest(given.as_bytes());
+    let digests_match: bool = stored_digest
+        .as_slice()
+        .ct_eq(given_digest.as_slice())
+        .into();
+
+    digests_match && stored.is_some()
+}
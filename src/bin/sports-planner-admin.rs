@@ -0,0 +1,719 @@
+//! Operator CLI, working directly on the database of the config the server uses (`config.toml`,
+//! `CONFIG_FILE`, environment). Results are printed as text, or as JSON with `--json`.
+
+use std::{
+    io::{self, IsTerminal, Read},
+    path::PathBuf,
+    process::ExitCode,
+    str::FromStr,
+};
+
+use clap::{Args, Parser, Subcommand};
+use dotenv::dotenv;
+use rust_rest::{
+    admin::{export_club, import_club, purge_expired, ClubExport},
+    auth::{
+        login_throttle::unlock_login,
+        password::{MAX_PASSWORD_LEN, MIN_PASSWORD_LEN},
+        roles::Role,
+        utils::generate_token,
+    },
+    config::Config,
+    entities::{
+        audit_log::{record_audit_event, AuditAction, AuditEvent},
+        club::create_club,
+    },
+    utils::migrations::{latest_version, migrate},
+};
+use serde_json::{json, Value};
+use sqlx::{postgres::PgPoolOptions, PgConnection, PgPool};
+use strum::IntoEnumIterator;
+
+const GENERATED_PASSWORD_LEN: usize = 16;
+
+#[derive(Parser)]
+#[command(
+    name = "sports-planner-admin",
+    about = "Administrative tasks for operators"
+)]
+struct Cli {
+    /// print results as JSON
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// apply pending migrations
+    Migrate {
+        /// only check that the schema is up to date
+        #[arg(long)]
+        check: bool,
+    },
+    /// create, list, delete, export and import clubs
+    #[command(subcommand)]
+    Clubs(ClubCommand),
+    /// create and list users, reset passwords, unlock log-ins
+    #[command(subcommand)]
+    Users(UserCommand),
+    /// assign and unassign club roles
+    #[command(subcommand)]
+    Roles(RoleCommand),
+    /// list and revoke log-in sessions
+    #[command(subcommand)]
+    Sessions(SessionCommand),
+    /// delete expired sessions, tokens and log-in failures
+    Purge,
+}
+
+/// club ids may start with `-`, hence `allow_hyphen_values`
+#[derive(Subcommand)]
+enum ClubCommand {
+    Create {
+        title: String,
+    },
+    List,
+    /// deletes the club with all its users, teams and games
+    Delete {
+        #[arg(allow_hyphen_values = true)]
+        club_id: String,
+    },
+    /// writes the club's data without secrets as JSON
+    Export {
+        #[arg(allow_hyphen_values = true)]
+        club_id: String,
+        /// file to write, stdout by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// restores an export, its users get new passwords which are printed
+    Import {
+        /// file to read, stdin by default
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+struct PasswordArgs {
+    /// read the password from the first line of stdin, otherwise a random one is generated
+    /// which has to be changed on the first log-in
+    #[arg(long)]
+    password_stdin: bool,
+}
+
+#[derive(Subcommand)]
+enum UserCommand {
+    Create {
+        username: String,
+        #[arg(long, allow_hyphen_values = true)]
+        club: String,
+        /// may be repeated
+        #[arg(long = "role", value_parser = parse_role)]
+        roles: Vec<Role>,
+        #[command(flatten)]
+        password: PasswordArgs,
+    },
+    List {
+        /// only users of this club
+        #[arg(long, allow_hyphen_values = true)]
+        club: Option<String>,
+    },
+    /// sets a new password and logs the user out everywhere
+    ResetPassword {
+        username: String,
+        #[command(flatten)]
+        password: PasswordArgs,
+    },
+    /// lifts log-in delays and lockouts
+    Unlock { username: String },
+}
+
+#[derive(Subcommand)]
+enum RoleCommand {
+    Assign {
+        username: String,
+        #[arg(value_parser = parse_role)]
+        role: Role,
+    },
+    Unassign {
+        username: String,
+        #[arg(value_parser = parse_role)]
+        role: Role,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionCommand {
+    /// active sessions, newest first
+    List {
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// a single session, or all of a user's
+    Revoke {
+        #[arg(required_unless_present = "user", conflicts_with = "user")]
+        session_id: Option<String>,
+        #[arg(long)]
+        user: Option<String>,
+    },
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    // stdout is for the output, logs go to stderr
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("warn")),
+        )
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .init();
+    dotenv().ok();
+    let cli = Cli::parse();
+
+    match run(cli.command).await {
+        Ok(output) => {
+            print_output(&output, cli.json);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            if cli.json {
+                println!("{}", json!({ "error": err }));
+            } else {
+                eprintln!("error: {}", err);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(command: Command) -> Result<Value, String> {
+    let config = Config::load()?;
+    let pool = PgPoolOptions::new()
+        .max_connections(2)
+        .connect(&config.database.url)
+        .await
+        .map_err(|err| format!("could not connect to Postgres: {}", err))?;
+
+    // everything else needs the schema this binary was built for
+    let check_only = !matches!(command, Command::Migrate { check: false });
+    migrate(&pool, !check_only).await?;
+
+    match command {
+        Command::Migrate { .. } => Ok(json!({ "schema_version": latest_version() })),
+        Command::Clubs(command) => run_club_command(&pool, command).await,
+        Command::Users(command) => run_user_command(&pool, command).await,
+        Command::Roles(command) => run_role_command(&pool, command).await,
+        Command::Sessions(command) => run_session_command(&pool, command).await,
+        Command::Purge => {
+            let report = purge_expired(&pool).await.map_err(db_error)?;
+            Ok(json!(report))
+        }
+    }
+}
+
+async fn run_club_command(pool: &PgPool, command: ClubCommand) -> Result<Value, String> {
+    match command {
+        ClubCommand::Create { title } => {
+            let mut tx = pool.begin().await.map_err(db_error)?;
+            let id = create_club(&mut tx, &title).await.map_err(db_error)?;
+            tx.commit().await.map_err(db_error)?;
+            Ok(json!({ "id": id, "title": title }))
+        }
+        ClubCommand::List => {
+            let clubs = sqlx::query!(
+                r#"
+                SELECT c.id, c.title, c.created_at, count(u.id) AS "user_count!"
+                FROM clubs c LEFT JOIN users u ON u.club_id = c.id
+                GROUP BY c.id ORDER BY c.created_at
+                "#
+            )
+            .fetch_all(pool)
+            .await
+            .map_err(db_error)?;
+            Ok(clubs
+                .into_iter()
+                .map(|club| {
+                    json!({
+                        "id": club.id,
+                        "title": club.title,
+                        "created_at": club.created_at,
+                        "user_count": club.user_count,
+                    })
+                })
+                .collect())
+        }
+        ClubCommand::Delete { club_id } => {
+            let mut tx = pool.begin().await.map_err(db_error)?;
+            // events would outlive their games
+            let event_ids = sqlx::query_scalar!(
+                r#"
+                DELETE FROM games g USING teams t
+                WHERE t.id = g.team_id AND t.club_id = $1
+                RETURNING g.event_id
+                "#,
+                club_id
+            )
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(db_error)?;
+            sqlx::query!(r#"DELETE FROM events WHERE id = ANY($1)"#, &event_ids)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+            // users keep their club from being deleted
+            let users = sqlx::query!(r#"DELETE FROM users WHERE club_id = $1"#, club_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?
+                .rows_affected();
+            let deleted = sqlx::query!(r#"DELETE FROM clubs WHERE id = $1"#, club_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?
+                .rows_affected();
+            if deleted == 0 {
+                return Err(format!("no club with id {}", club_id));
+            }
+            record_audit_event(
+                &mut tx,
+                AuditEvent {
+                    details: json!({ "via": "admin_cli", "deleted_users": users }),
+                    ..AuditEvent::by_operator(&club_id, AuditAction::ClubDeleted, "club", &club_id)
+                },
+            )
+            .await
+            .map_err(db_error)?;
+            tx.commit().await.map_err(db_error)?;
+            Ok(json!({ "id": club_id, "deleted_users": users }))
+        }
+        ClubCommand::Export { club_id, output } => {
+            let export = export_club(pool, &club_id, latest_version()).await?;
+            let serialized =
+                serde_json::to_string_pretty(&export).map_err(|err| err.to_string())?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, serialized)
+                        .map_err(|err| format!("could not write {}: {}", path.display(), err))?;
+                    Ok(json!({
+                        "id": club_id,
+                        "file": path,
+                        "rows": export.tables.iter().map(|table| table.rows.len()).sum::<usize>(),
+                    }))
+                }
+                None => {
+                    println!("{}", serialized);
+                    Ok(Value::Null)
+                }
+            }
+        }
+        ClubCommand::Import { file } => {
+            let serialized = match &file {
+                Some(path) => std::fs::read_to_string(path)
+                    .map_err(|err| format!("could not read {}: {}", path.display(), err))?,
+                None => {
+                    let mut serialized = String::new();
+                    io::stdin()
+                        .read_to_string(&mut serialized)
+                        .map_err(|err| format!("could not read stdin: {}", err))?;
+                    serialized
+                }
+            };
+            let export: ClubExport = serde_json::from_str(&serialized)
+                .map_err(|err| format!("not a club export: {}", err))?;
+            let imported = import_club(pool, export, latest_version()).await?;
+            Ok(json!(imported))
+        }
+    }
+}
+
+async fn run_user_command(pool: &PgPool, command: UserCommand) -> Result<Value, String> {
+    match command {
+        UserCommand::Create {
+            username,
+            club,
+            roles,
+            password,
+        } => {
+            if username.trim().is_empty() {
+                return Err("the username must not be empty".to_string());
+            }
+            if username.contains('@') {
+                return Err("the username must not contain @".to_string());
+            }
+            let (password, generated) = read_password(&password)?;
+
+            let mut tx = pool.begin().await.map_err(db_error)?;
+            let user = sqlx::query!(
+                r#"
+                INSERT INTO users (username, password, club_id, must_reset_password)
+                VALUES ($1, $2, $3, $4) RETURNING id
+                "#,
+                username,
+                password,
+                club,
+                generated
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(db_error)?;
+            for role in &roles {
+                sqlx::query!(
+                    r#"INSERT INTO role_assignments (user_id, role) VALUES ($1, $2)"#,
+                    user.id,
+                    *role as Role
+                )
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+            }
+            record_audit_event(
+                &mut tx,
+                AuditEvent {
+                    after: Some(json!({ "id": user.id, "username": username, "roles": roles })),
+                    ..AuditEvent::by_operator(&club, AuditAction::UserCreated, "user", &user.id)
+                },
+            )
+            .await
+            .map_err(db_error)?;
+            tx.commit().await.map_err(db_error)?;
+
+            Ok(json!({
+                "id": user.id,
+                "username": username,
+                "roles": roles,
+                "password": generated.then_some(password),
+            }))
+        }
+        UserCommand::List { club } => {
+            let users = sqlx::query!(
+                r#"
+                SELECT u.id, u.username, u.club_id, u.email, u.created_at,
+                    array_remove(array_agg(ra.role ORDER BY ra.role), NULL) AS "roles!: Vec<Role>"
+                FROM users u LEFT JOIN role_assignments ra ON ra.user_id = u.id
+                WHERE $1::text IS NULL OR u.club_id = $1
+                GROUP BY u.id ORDER BY u.club_id, u.username
+                "#,
+                club
+            )
+            .fetch_all(pool)
+            .await
+            .map_err(db_error)?;
+            Ok(users
+                .into_iter()
+                .map(|user| {
+                    json!({
+                        "id": user.id,
+                        "username": user.username,
+                        "club_id": user.club_id,
+                        "email": user.email,
+                        "roles": user.roles,
+                        "created_at": user.created_at,
+                    })
+                })
+                .collect())
+        }
+        UserCommand::ResetPassword { username, password } => {
+            let (password, generated) = read_password(&password)?;
+
+            let mut tx = pool.begin().await.map_err(db_error)?;
+            let user = find_user(&mut tx, &username).await?;
+            sqlx::query!(
+                r#"UPDATE users SET password = $1, must_reset_password = $2, updated_at = now() WHERE id = $3"#,
+                password,
+                generated,
+                user.id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+            let sessions = sqlx::query!(r#"DELETE FROM sessions WHERE user_id = $1"#, user.id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?
+                .rows_affected();
+            record_audit_event(
+                &mut tx,
+                AuditEvent::by_operator(
+                    &user.club_id,
+                    AuditAction::PasswordResetForced,
+                    "user",
+                    &user.id,
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+            tx.commit().await.map_err(db_error)?;
+
+            Ok(json!({
+                "id": user.id,
+                "username": username,
+                "revoked_sessions": sessions,
+                "password": generated.then_some(password),
+            }))
+        }
+        UserCommand::Unlock { username } => {
+            let mut tx = pool.begin().await.map_err(db_error)?;
+            let user = find_user(&mut tx, &username).await?;
+            unlock_login(&mut tx, &user.id).await.map_err(db_error)?;
+            record_audit_event(
+                &mut tx,
+                AuditEvent::by_operator(
+                    &user.club_id,
+                    AuditAction::LoginUnlocked,
+                    "user",
+                    &user.id,
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+            tx.commit().await.map_err(db_error)?;
+            Ok(json!({ "id": user.id, "username": username }))
+        }
+    }
+}
+
+async fn run_role_command(pool: &PgPool, command: RoleCommand) -> Result<Value, String> {
+    let (username, role, assign) = match command {
+        RoleCommand::Assign { username, role } => (username, role, true),
+        RoleCommand::Unassign { username, role } => (username, role, false),
+    };
+
+    let mut tx = pool.begin().await.map_err(db_error)?;
+    let user = find_user(&mut tx, &username).await?;
+    let changed = if assign {
+        sqlx::query!(
+            r#"INSERT INTO role_assignments (user_id, role) VALUES ($1, $2) ON CONFLICT DO NOTHING"#,
+            user.id,
+            role as Role
+        )
+        .execute(&mut *tx)
+        .await
+    } else {
+        sqlx::query!(
+            r#"DELETE FROM role_assignments WHERE user_id = $1 AND role = $2"#,
+            user.id,
+            role as Role
+        )
+        .execute(&mut *tx)
+        .await
+    }
+    .map_err(db_error)?
+    .rows_affected()
+        > 0;
+    if changed {
+        let (action, before, after) = if assign {
+            (
+                AuditAction::RoleAssigned,
+                None,
+                Some(json!({ "role": role })),
+            )
+        } else {
+            (
+                AuditAction::RoleUnassigned,
+                Some(json!({ "role": role })),
+                None,
+            )
+        };
+        record_audit_event(
+            &mut tx,
+            AuditEvent {
+                before,
+                after,
+                ..AuditEvent::by_operator(&user.club_id, action, "user", &user.id)
+            },
+        )
+        .await
+        .map_err(db_error)?;
+    }
+    tx.commit().await.map_err(db_error)?;
+
+    Ok(json!({ "id": user.id, "username": username, "role": role, "changed": changed }))
+}
+
+async fn run_session_command(pool: &PgPool, command: SessionCommand) -> Result<Value, String> {
+    match command {
+        SessionCommand::List { user } => {
+            let sessions = sqlx::query!(
+                r#"
+                SELECT s.id, u.username, s.created_at, s.expires_at, iu.username AS "impersonating?"
+                FROM sessions s
+                    JOIN users u ON u.id = s.user_id
+                    LEFT JOIN users iu ON iu.id = s.impersonated_user_id
+                        AND s.impersonation_expires_at > now()
+                WHERE s.expires_at > now() AND ($1::text IS NULL OR u.username = $1)
+                ORDER BY s.created_at DESC
+                "#,
+                user
+            )
+            .fetch_all(pool)
+            .await
+            .map_err(db_error)?;
+            Ok(sessions
+                .into_iter()
+                .map(|session| {
+                    json!({
+                        "id": session.id,
+                        "username": session.username,
+                        "created_at": session.created_at,
+                        "expires_at": session.expires_at,
+                        "impersonating": session.impersonating,
+                    })
+                })
+                .collect())
+        }
+        SessionCommand::Revoke { session_id, user } => {
+            let mut tx = pool.begin().await.map_err(db_error)?;
+            let (user, revoked) = match (session_id, user) {
+                (Some(session_id), _) => {
+                    let user = sqlx::query_as!(
+                        FoundUser,
+                        r#"
+                        DELETE FROM sessions s USING users u
+                        WHERE s.id = $1 AND u.id = s.user_id
+                        RETURNING u.id, u.club_id
+                        "#,
+                        session_id
+                    )
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .map_err(db_error)?;
+                    let revoked = u64::from(user.is_some());
+                    (user, revoked)
+                }
+                (None, Some(username)) => {
+                    let user = find_user(&mut tx, &username).await?;
+                    let revoked =
+                        sqlx::query!(r#"DELETE FROM sessions WHERE user_id = $1"#, user.id)
+                            .execute(&mut *tx)
+                            .await
+                            .map_err(db_error)?
+                            .rows_affected();
+                    (Some(user), revoked)
+                }
+                (None, None) => unreachable!("clap requires one of them"),
+            };
+            if let Some(user) = user.filter(|_| revoked > 0) {
+                record_audit_event(
+                    &mut tx,
+                    AuditEvent {
+                        details: json!({ "via": "admin_cli", "revoked_sessions": revoked }),
+                        ..AuditEvent::by_operator(
+                            &user.club_id,
+                            AuditAction::SessionsRevoked,
+                            "user",
+                            &user.id,
+                        )
+                    },
+                )
+                .await
+                .map_err(db_error)?;
+            }
+            tx.commit().await.map_err(db_error)?;
+            Ok(json!({ "revoked_sessions": revoked }))
+        }
+    }
+}
+
+struct FoundUser {
+    id: String,
+    club_id: String,
+}
+
+async fn find_user(conn: &mut PgConnection, username: &str) -> Result<FoundUser, String> {
+    sqlx::query_as!(
+        FoundUser,
+        r#"SELECT id, club_id FROM users WHERE username = $1"#,
+        username
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(db_error)?
+    .ok_or_else(|| format!("no user named {}", username))
+}
+
+/// the password and whether it was generated
+fn read_password(args: &PasswordArgs) -> Result<(String, bool), String> {
+    if !args.password_stdin {
+        return Ok((generate_token(GENERATED_PASSWORD_LEN), true));
+    }
+    let mut line = String::new();
+    io::stdin()
+        .read_line(&mut line)
+        .map_err(|err| format!("could not read the password: {}", err))?;
+    let password = line.trim_end_matches(['\r', '\n']).to_string();
+    let len = password.chars().count();
+    if !(MIN_PASSWORD_LEN..=MAX_PASSWORD_LEN).contains(&len) {
+        return Err(format!(
+            "the password must be {} to {} characters long",
+            MIN_PASSWORD_LEN, MAX_PASSWORD_LEN
+        ));
+    }
+    Ok((password, false))
+}
+
+fn parse_role(value: &str) -> Result<Role, String> {
+    Role::from_str(value).map_err(|_| {
+        let roles: Vec<String> = Role::iter().map(|role| role.to_string()).collect();
+        format!("expected one of {}", roles.join(", "))
+    })
+}
+
+fn db_error(err: sqlx::Error) -> String {
+    match err.as_database_error() {
+        Some(db_err) => db_err.message().to_string(),
+        None => err.to_string(),
+    }
+}
+
+fn print_output(output: &Value, json: bool) {
+    if output.is_null() {
+        return;
+    }
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(output).unwrap_or_default()
+        );
+        return;
+    }
+    match output {
+        Value::Array(items) if items.is_empty() => println!("(none)"),
+        Value::Array(items) => {
+            for item in items {
+                println!("{}", plain_line(item));
+            }
+        }
+        _ => println!("{}", plain_line(output)),
+    }
+}
+
+/// `key=value` pairs, leaving out empty values
+fn plain_line(value: &Value) -> String {
+    let Value::Object(fields) = value else {
+        return plain_value(value);
+    };
+    fields
+        .iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(key, value)| format!("{}={}", key, plain_value(value)))
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+fn plain_value(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.clone(),
+        Value::Array(items) => items.iter().map(plain_value).collect::<Vec<_>>().join(","),
+        Value::Object(_) => {
+            let mut nested = plain_line(value);
+            nested.insert(0, '{');
+            nested.push('}');
+            nested
+        }
+        _ => value.to_string(),
+    }
+}
@@ -0,0 +1,537 @@
+//! Days users can't attend, e.g. holidays. Recording an absence declines their invites and
+//! referee requests for games in it (reason `absent`), new invites for those days start out
+//! declined. Accepted referee requests given up that way are audited and the referee manager who
+//! made them is notified. Coaches see it all in a team's availability grid.
+
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgConnection, Type};
+use strum_macros::{Display, EnumString};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{
+    auth::{
+        permissions::{actions::InviteList, Authorized},
+        utils::AuthContext,
+    },
+    entities::{
+        audit_log::{record_audit_event, AuditAction, AuditEvent},
+        game::{format_start_time, InviteResponse},
+        referee_assignment::RefereeRequestStatus,
+    },
+    notifications::{notify_users, PushMessage},
+    utils::{
+        api::AppState,
+        error::ApiError,
+        pagination::{ListParams, ListQuery, Paginated, SortKey},
+        validation::{Validate, ValidatedJson, ValidatedQuery, Validator, MAX_TEXT_LEN},
+    },
+};
+
+/// `decline_reason` of invites declined because of an absence
+pub const ABSENT_DECLINE_REASON: &str = "absent";
+const MAX_ABSENCE_DAYS: i64 = 366;
+const DEFAULT_GRID_GAMES: i64 = 10;
+const MAX_GRID_GAMES: i64 = 50;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, Display, EnumString, ToSchema,
+)]
+#[sqlx(type_name = "absence_reason", rename_all = "snake_case")] // must match the Postgres type name
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AbsenceReason {
+    Holiday,
+    Illness,
+    Injury,
+    Work,
+    Other,
+}
+
+pub fn absence_router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/create", post(create_absence))
+        .route("/list-own", get(list_own_absences))
+        .route("/delete/{id}", delete(delete_absence))
+        .route("/team-grid/{team_id}", get(get_team_availability_grid))
+        .with_state(state.clone())
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    create_absence,
+    list_own_absences,
+    delete_absence,
+    get_team_availability_grid
+))]
+pub struct AbsenceApi;
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateAbsence {
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    pub reason: AbsenceReason,
+    pub note: Option<String>,
+}
+
+impl Validate for CreateAbsence {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            self.ends_on >= self.starts_on,
+            "ends_on",
+            "must not be before starts_on",
+        );
+        v.check(
+            self.ends_on - self.starts_on < Duration::days(MAX_ABSENCE_DAYS),
+            "ends_on",
+            format!("absences last at most {} days", MAX_ABSENCE_DAYS),
+        );
+        v.check(
+            self.ends_on >= Utc::now().date_naive(),
+            "ends_on",
+            "must not be in the past",
+        );
+        if let Some(note) = &self.note {
+            v.length("note", note.trim(), 0, MAX_TEXT_LEN);
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreatedAbsence {
+    id: String,
+    declined_invites: u64,
+    declined_referee_requests: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Absence {
+    id: String,
+    starts_on: NaiveDate,
+    ends_on: NaiveDate,
+    reason: AbsenceReason,
+    note: Option<String>,
+    #[serde(skip)]
+    sort_key: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum AbsenceSort {
+    #[default]
+    StartsOn,
+    EndsOn,
+}
+
+impl SortKey for AbsenceSort {}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GridParams {
+    /// how many upcoming games, 10 by default
+    pub limit: Option<i64>,
+}
+
+impl Validate for GridParams {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(limit) = self.limit {
+            v.range("limit", limit, 1, MAX_GRID_GAMES);
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GridGame {
+    id: String,
+    opponent: String,
+    start_time: DateTime<Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GridCell {
+    response: InviteResponse,
+    /// set when the member is absent that day
+    absence_reason: Option<AbsenceReason>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GridMember {
+    user_id: String,
+    username: String,
+    /// one entry per game, `null` where not invited
+    games: Vec<Option<GridCell>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AvailabilityGrid {
+    games: Vec<GridGame>,
+    members: Vec<GridMember>,
+}
+
+/// The given users who are absent on the day of `start_time` (UTC, like the rest of the app).
+pub async fn find_absent_user_ids(
+    conn: &mut PgConnection,
+    user_ids: &[String],
+    start_time: DateTime<Utc>,
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT DISTINCT user_id FROM absences
+        WHERE user_id = ANY($1)
+        AND ($2::timestamptz AT TIME ZONE 'UTC')::date BETWEEN starts_on AND ends_on
+        "#,
+        user_ids,
+        start_time
+    )
+    .fetch_all(&mut *conn)
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/create",
+    tag = "absences",
+    request_body = CreateAbsence,
+    responses((status = 201, body = CreatedAbsence)),
+)]
+pub async fn create_absence(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    ValidatedJson(payload): ValidatedJson<CreateAbsence>,
+) -> Result<(StatusCode, Json<CreatedAbsence>), ApiError> {
+    let note = payload
+        .note
+        .as_deref()
+        .map(str::trim)
+        .filter(|note| !note.is_empty());
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO absences (user_id, starts_on, ends_on, reason, note)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+        auth_ctx.user_id,
+        payload.starts_on,
+        payload.ends_on,
+        payload.reason as AbsenceReason,
+        note
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let declined_invites = sqlx::query!(
+        r#"
+        UPDATE game_invites i SET response = 'declined', decline_reason = $4
+        FROM games g JOIN events e ON e.id = g.event_id
+        WHERE g.id = i.game_id
+        AND i.user_id = $1
+        AND i.response <> 'declined'
+        AND e.start_time >= now()
+        AND (e.start_time AT TIME ZONE 'UTC')::date BETWEEN $2 AND $3
+        "#,
+        auth_ctx.user_id,
+        payload.starts_on,
+        payload.ends_on,
+        ABSENT_DECLINE_REASON
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    // frees the slots, referee managers find them among the unfilled ones again
+    let declined_referee_requests = sqlx::query!(
+        r#"
+        UPDATE referee_requests r SET status = 'declined', responded_at = now()
+        FROM referee_requests previous, referee_slots s
+        JOIN games g ON g.id = s.game_id JOIN events e ON e.id = g.event_id
+        WHERE previous.id = r.id AND s.id = r.slot_id
+        AND r.referee_user_id = $1
+        AND r.status <> 'declined'
+        AND e.start_time >= now()
+        AND (e.start_time AT TIME ZONE 'UTC')::date BETWEEN $2 AND $3
+        RETURNING r.id, r.slot_id, r.requested_by_user_id,
+        previous.status AS "previous_status: RefereeRequestStatus", g.opponent, e.start_time
+        "#,
+        auth_ctx.user_id,
+        payload.starts_on,
+        payload.ends_on
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let withdrawn: Vec<_> = declined_referee_requests
+        .iter()
+        .filter(|request| request.previous_status == RefereeRequestStatus::Accepted)
+        .collect();
+    for request in &withdrawn {
+        record_audit_event(
+            &mut tx,
+            AuditEvent {
+                details: json!({ "reason": ABSENT_DECLINE_REASON, "absence_id": id }),
+                before: Some(json!({
+                    "slot_id": request.slot_id,
+                    "referee_user_id": auth_ctx.user_id,
+                    "status": request.previous_status,
+                })),
+                ..AuditEvent::by(
+                    &auth_ctx,
+                    AuditAction::RefereeRequestWithdrawn,
+                    "referee_request",
+                    &request.id,
+                )
+            },
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    for request in withdrawn {
+        if let Some(manager_id) = &request.requested_by_user_id {
+            notify_users(
+                &state,
+                vec![manager_id.clone()],
+                PushMessage {
+                    title: "Referee absent".to_string(),
+                    body: format!(
+                        "vs {} – {}: the slot is open again",
+                        request.opponent,
+                        format_start_time(&request.start_time)
+                    ),
+                    url: Some("/referee-assignments".to_string()),
+                    tag: Some(format!("referee-slot-{}", request.slot_id)),
+                },
+            );
+        }
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedAbsence {
+            id,
+            declined_invites,
+            declined_referee_requests: declined_referee_requests.len() as u64,
+        }),
+    ))
+}
+
+/// current and future absences, soonest first
+#[utoipa::path(
+    get,
+    path = "/list-own",
+    tag = "absences",
+    params(ListParams),
+    responses((status = 200, body = Paginated<Absence>)),
+)]
+pub async fn list_own_absences(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    ListQuery { page, .. }: ListQuery<AbsenceSort>,
+) -> Result<(StatusCode, Json<Paginated<Absence>>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    // dates have no time zone, so the keys don't need `TIME_KEY_FORMAT`
+    let absences = sqlx::query_as!(
+        Absence,
+        r#"
+        SELECT id AS "id!", starts_on AS "starts_on!", ends_on AS "ends_on!",
+            reason AS "reason!: AbsenceReason", note, sort_key AS "sort_key!"
+        FROM (
+            SELECT a.id, a.starts_on, a.ends_on, a.reason, a.note,
+            to_char(CASE $2 WHEN 'ends_on' THEN a.ends_on ELSE a.starts_on END, 'YYYY-MM-DD')
+                AS sort_key
+            FROM absences a
+            WHERE a.user_id = $1 AND a.ends_on >= CURRENT_DATE
+        ) r
+        WHERE ($4::text IS NULL OR CASE WHEN $3
+            THEN (r.sort_key, r.id) < ($4, $5) ELSE (r.sort_key, r.id) > ($4, $5) END)
+        ORDER BY CASE WHEN $3 THEN r.sort_key END DESC, r.sort_key,
+            CASE WHEN $3 THEN r.id END DESC, r.id
+        LIMIT $6
+        "#,
+        auth_ctx.user_id,
+        page.sort(),
+        page.descending,
+        page.after_key(),
+        page.after_id(),
+        page.fetch_limit()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT count(*) AS "count!" FROM absences
+        WHERE user_id = $1 AND ends_on >= CURRENT_DATE
+        "#,
+        auth_ctx.user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let absences = page.finish(absences, total, |absence| (&absence.sort_key, &absence.id));
+    Ok((StatusCode::OK, Json(absences)))
+}
+
+/// Invites declined because of the absence are pending again, unless another absence covers them.
+/// Referee requests stay declined, the slots may have been given to someone else meanwhile.
+#[utoipa::path(
+    delete,
+    path = "/delete/{id}",
+    tag = "absences",
+    params(("id" = String, Path, description = "absence id")),
+    responses((status = 204, description = "absence deleted")),
+)]
+pub async fn delete_absence(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let absence = sqlx::query!(
+        r#"DELETE FROM absences WHERE id = $1 AND user_id = $2 RETURNING starts_on, ends_on"#,
+        id,
+        auth_ctx.user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Absence not found".into()))?;
+
+    sqlx::query!(
+        r#"
+        UPDATE game_invites i SET response = 'pending', decline_reason = NULL
+        FROM games g JOIN events e ON e.id = g.event_id
+        WHERE g.id = i.game_id
+        AND i.user_id = $1
+        AND i.decline_reason = $4
+        AND e.start_time >= now()
+        AND (e.start_time AT TIME ZONE 'UTC')::date BETWEEN $2 AND $3
+        AND NOT EXISTS (
+            SELECT 1 FROM absences a
+            WHERE a.user_id = i.user_id
+            AND (e.start_time AT TIME ZONE 'UTC')::date BETWEEN a.starts_on AND a.ends_on
+        )
+        "#,
+        auth_ctx.user_id,
+        absence.starts_on,
+        absence.ends_on,
+        ABSENT_DECLINE_REASON
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The team's upcoming games against everyone invited to them, with their answers and absences.
+#[utoipa::path(
+    get,
+    path = "/team-grid/{team_id}",
+    tag = "absences",
+    params(
+        ("team_id" = String, Path),
+        GridParams,
+    ),
+    responses((status = 200, body = AvailabilityGrid)),
+)]
+pub async fn get_team_availability_grid(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<InviteList>,
+    Path(team_id): Path<String>,
+    ValidatedQuery(params): ValidatedQuery<GridParams>,
+) -> Result<(StatusCode, Json<AvailabilityGrid>), ApiError> {
+    let limit = params.limit.unwrap_or(DEFAULT_GRID_GAMES);
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    sqlx::query!(
+        "SELECT 1 as ok FROM teams WHERE id = $1 AND club_id = $2",
+        team_id,
+        auth_ctx.club_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Team not found".into()))?;
+
+    let games = sqlx::query_as!(
+        GridGame,
+        r#"
+        SELECT g.id, g.opponent, e.start_time
+        FROM games g JOIN events e ON e.id = g.event_id
+        WHERE g.team_id = $1 AND e.start_time >= now()
+        ORDER BY e.start_time
+        LIMIT $2
+        "#,
+        team_id,
+        limit
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let game_ids: Vec<String> = games.iter().map(|game| game.id.clone()).collect();
+    let cells = sqlx::query!(
+        r#"
+        SELECT i.game_id, u.id AS user_id, u.username, i.response AS "response: InviteResponse",
+        (
+            SELECT a.reason FROM absences a
+            WHERE a.user_id = u.id
+            AND (e.start_time AT TIME ZONE 'UTC')::date BETWEEN a.starts_on AND a.ends_on
+            ORDER BY a.starts_on
+            LIMIT 1
+        ) AS "absence_reason?: AbsenceReason"
+        FROM game_invites i
+        JOIN users u ON u.id = i.user_id
+        JOIN games g ON g.id = i.game_id
+        JOIN events e ON e.id = g.event_id
+        WHERE i.game_id = ANY($1)
+        "#,
+        &game_ids
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    // keyed by username for a stable order, user ids keep namesakes apart
+    let mut members: BTreeMap<(String, String), Vec<Option<GridCell>>> = BTreeMap::new();
+    for cell in cells {
+        let Some(column) = game_ids.iter().position(|id| *id == cell.game_id) else {
+            continue;
+        };
+        let row = members
+            .entry((cell.username, cell.user_id))
+            .or_insert_with(|| (0..game_ids.len()).map(|_| None).collect());
+        row[column] = Some(GridCell {
+            response: cell.response,
+            absence_reason: cell.absence_reason,
+        });
+    }
+
+    let members = members
+        .into_iter()
+        .map(|((username, user_id), games)| GridMember {
+            user_id,
+            username,
+            games,
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(AvailabilityGrid { games, members })))
+}
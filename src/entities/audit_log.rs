@@ -0,0 +1,294 @@
+//! Append-only log of security-relevant events and administrative / data-changing actions.
+//! Entries are written in the same transaction as the change they describe, never updated
+//! or deleted by the app, and outlive their club - only deleted actors are unlinked.
+//! Club admins read them via `GET /api/user/audit`.
+
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{FromRow, PgConnection, Postgres, QueryBuilder};
+use strum_macros::{Display, EnumString};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    auth::{
+        permissions::{actions::AuditRead, Authorized},
+        utils::AuthContext,
+    },
+    utils::{
+        api::AppState,
+        error::ApiError,
+        pagination::{ListParams, ListQuery, Paginated, SortKey, TIME_KEY_FORMAT},
+        validation::{Validate, Validator},
+    },
+};
+
+#[derive(Debug, Clone, Copy, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum AuditAction {
+    /// too many failed log-ins for one username/email or IP
+    LoginLocked,
+    /// an admin lifted a lockout early
+    LoginUnlocked,
+    ImpersonationStarted,
+    /// ended by the admin or by running out of time
+    ImpersonationStopped,
+    /// any request an admin made as another user
+    ImpersonatedRequest,
+    UserCreated,
+    UserDeleted,
+    PasswordResetForced,
+    PasswordResetIssued,
+    /// log-ins of a user ended by an operator
+    SessionsRevoked,
+    TwoFactorReset,
+    RoleAssigned,
+    RoleUnassigned,
+    TeamCreated,
+    TeamUpdated,
+    TeamDeleted,
+    GameCreated,
+    GameDeleted,
+    ServiceInviteCreated,
+    ServiceInviteDeleted,
+    ClubSettingsChanged,
+    ClubDeleted,
+    GuardianshipLinked,
+    GuardianshipUnlinked,
+    RefereeDefaultsChanged,
+    RefereeSlotAdded,
+    RefereeSlotUpdated,
+    RefereeSlotRemoved,
+    RefereeRequested,
+    RefereeRequestCancelled,
+    /// an accepted request the referee gave up by recording an absence
+    RefereeRequestWithdrawn,
+    RefereeLevelChanged,
+}
+
+pub struct AuditEvent<'a> {
+    pub actor_user_id: Option<&'a str>,
+    pub club_id: Option<&'a str>,
+    pub action: AuditAction,
+    pub target_type: Option<&'a str>,
+    pub target_id: Option<&'a str>,
+    pub details: Value,
+    /// the target as it was before and after the change, `None` if it didn't exist
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub request_id: Option<&'a str>,
+    pub ip: Option<&'a str>,
+}
+
+impl<'a> AuditEvent<'a> {
+    /// An action of the requesting user (the admin, when impersonating) in their club.
+    /// Snapshots and details are filled in with struct update syntax.
+    pub fn by(
+        auth_ctx: &'a AuthContext,
+        action: AuditAction,
+        target_type: &'a str,
+        target_id: &'a str,
+    ) -> Self {
+        AuditEvent {
+            actor_user_id: Some(auth_ctx.real_user_id()),
+            club_id: Some(&auth_ctx.club_id),
+            action,
+            target_type: Some(target_type),
+            target_id: Some(target_id),
+            details: json!({}),
+            before: None,
+            after: None,
+            request_id: Some(&auth_ctx.request_id),
+            ip: auth_ctx.ip.as_deref(),
+        }
+    }
+
+    /// An action taken with the admin CLI, which has no user behind it.
+    pub fn by_operator(
+        club_id: &'a str,
+        action: AuditAction,
+        target_type: &'a str,
+        target_id: &'a str,
+    ) -> Self {
+        AuditEvent {
+            actor_user_id: None,
+            club_id: Some(club_id),
+            action,
+            target_type: Some(target_type),
+            target_id: Some(target_id),
+            details: json!({ "via": "admin_cli" }),
+            before: None,
+            after: None,
+            request_id: None,
+            ip: None,
+        }
+    }
+}
+
+pub async fn record_audit_event(
+    conn: &mut PgConnection,
+    event: AuditEvent<'_>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_log
+            (actor_user_id, club_id, action, target_type, target_id, details, before, after, request_id, ip)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#,
+        event.actor_user_id,
+        event.club_id,
+        event.action.to_string(),
+        event.target_type,
+        event.target_id,
+        event.details,
+        event.before,
+        event.after,
+        event.request_id,
+        event.ip
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum AuditLogSort {
+    #[default]
+    CreatedAt,
+}
+
+impl SortKey for AuditLogSort {
+    /// newest first
+    const DEFAULT_DESCENDING: bool = true;
+}
+
+/// entries written in `[since, until)`
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditLogFilters {
+    pub action: Option<String>,
+    pub actor_user_id: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Validate for AuditLogFilters {
+    fn validate(&self, v: &mut Validator) {
+        if let (Some(since), Some(until)) = (self.since, self.until) {
+            v.check(until > since, "until", "must be after since");
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema, FromRow)]
+pub struct AuditLogEntry {
+    id: String,
+    actor_user_id: Option<String>,
+    actor_username: Option<String>,
+    action: String,
+    target_type: Option<String>,
+    target_id: Option<String>,
+    details: Value,
+    before: Option<Value>,
+    after: Option<Value>,
+    request_id: Option<String>,
+    ip: Option<String>,
+    created_at: DateTime<Utc>,
+    #[serde(skip)]
+    sort_key: String,
+}
+
+/// Newest first by default, for the admins of the requesting user's club.
+#[utoipa::path(
+    get,
+    path = "/audit",
+    tag = "clubs",
+    params(ListParams, AuditLogFilters),
+    responses((status = 200, body = Paginated<AuditLogEntry>)),
+)]
+pub async fn list_audit_log(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<AuditRead>,
+    ListQuery { page, filters }: ListQuery<AuditLogSort, AuditLogFilters>,
+) -> Result<(StatusCode, Json<Paginated<AuditLogEntry>>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let mut query = QueryBuilder::new(
+        r#"
+        SELECT * FROM (
+            SELECT a.id, a.actor_user_id, u.username AS actor_username, a.action, a.target_type,
+            a.target_id, a.details, a.before, a.after, a.request_id, a.ip, a.created_at,
+            to_char(a.created_at AT TIME ZONE 'UTC', "#,
+    );
+    query.push_bind(TIME_KEY_FORMAT).push(
+        r#") AS sort_key
+            FROM audit_log a
+            LEFT JOIN users u ON u.id = a.actor_user_id
+        "#,
+    );
+    push_audit_log_filters(&mut query, &auth_ctx.club_id, &filters);
+    query.push(") r");
+    if let (Some(after_key), Some(after_id)) = (page.after_key(), page.after_id()) {
+        let comparison = if page.descending { "<" } else { ">" };
+        query
+            .push(format!(" WHERE (r.sort_key, r.id) {} (", comparison))
+            .push_bind(after_key)
+            .push(", ")
+            .push_bind(after_id)
+            .push(")");
+    }
+    let direction = if page.descending { "DESC" } else { "ASC" };
+    query
+        .push(format!(
+            " ORDER BY r.sort_key {}, r.id {} LIMIT ",
+            direction, direction
+        ))
+        .push_bind(page.fetch_limit());
+    let entries = query
+        .build_query_as::<AuditLogEntry>()
+        .fetch_all(&mut *tx)
+        .await?;
+
+    let mut query = QueryBuilder::new("SELECT count(*) FROM audit_log a");
+    push_audit_log_filters(&mut query, &auth_ctx.club_id, &filters);
+    let total = query
+        .build_query_scalar::<i64>()
+        .fetch_one(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    let entries = page.finish(entries, total, |entry| (&entry.sort_key, &entry.id));
+    Ok((StatusCode::OK, Json(entries)))
+}
+
+/// the `WHERE` clause of the audit log list and its count, on `audit_log a`
+fn push_audit_log_filters<'a>(
+    query: &mut QueryBuilder<'a, Postgres>,
+    club_id: &'a str,
+    params: &'a AuditLogFilters,
+) {
+    query.push(" WHERE a.club_id = ").push_bind(club_id);
+    let text_filters = [
+        ("a.action", &params.action),
+        ("a.actor_user_id", &params.actor_user_id),
+        ("a.target_type", &params.target_type),
+        ("a.target_id", &params.target_id),
+    ];
+    for (column, value) in text_filters {
+        if let Some(value) = value {
+            query.push(format!(" AND {} = ", column)).push_bind(value);
+        }
+    }
+    if let Some(since) = params.since {
+        query.push(" AND a.created_at >= ").push_bind(since);
+    }
+    if let Some(until) = params.until {
+        query.push(" AND a.created_at < ").push_bind(until);
+    }
+}
@@ -1,11 +1,13 @@
 use crate::{
-    auth::utils::AuthContext,
-    utils::api::{handle_unexpected_db_err, AppState, EmptyApiResult},
+    auth::permissions::{actions::ClubDelete, Authorized},
+    entities::audit_log::{record_audit_event, AuditAction, AuditEvent},
+    utils::api::{AppState, EmptyApiResult},
 };
-use axum::{extract::State, http::StatusCode, Extension};
+use axum::{extract::State, http::StatusCode};
 use log::error;
 use nanoid::nanoid;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use sqlx::PgTransaction;
 use tokio::time::{sleep, Duration};
 
@@ -14,7 +16,7 @@ const MAX_RETRIES: usize = 5;
 const RETRY_BACKOFF_MS: u64 = 50;
 
 #[derive(Debug, Deserialize, Serialize, sqlx::FromRow)]
-#[allow(non_snake_case)]
+#[allow(non_snake_case, dead_code)]
 pub struct ClubModel {
     pub id: String,
     pub title: String,
@@ -102,6 +104,7 @@ pub async fn create_club(tx: &mut PgTransaction<'_>, title: &str) -> Result<Stri
 }
 
 // SAMPLE CODE BY KLAUDIUS
+#[allow(dead_code)]
 async fn retry_insert(tx: &mut PgTransaction<'_>, title: &str) -> Result<(), sqlx::Error> {
     let mut retries = 0;
     let max_retries = 5;
@@ -133,27 +136,42 @@ async fn retry_insert(tx: &mut PgTransaction<'_>, title: &str) -> Result<(), sql
 
 // TODO: modify club
 // TODO: more granular checks and readable errors
+#[utoipa::path(
+    delete,
+    path = "/clubs/delete-own",
+    tag = "clubs",
+    responses((status = 204, description = "club deleted")),
+)]
 pub async fn delete_own_club(
     State(state): State<AppState>,
-    auth_ctx: Extension<AuthContext>,
+    auth_ctx: Authorized<ClubDelete>,
 ) -> EmptyApiResult {
-    let mut tx = state
-        .pg_pool
-        .begin()
-        .await
-        .map_err(handle_unexpected_db_err)?;
+    let mut tx = state.pg_pool.begin().await?;
+
+    // the actor is unlinked with the user below, so its id is kept in the details
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            details: json!({ "deleted_by": auth_ctx.real_user_id() }),
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::ClubDeleted,
+                "club",
+                &auth_ctx.club_id,
+            )
+        },
+    )
+    .await?;
 
     let _ = sqlx::query!(r#"DELETE FROM users WHERE id = $1"#, auth_ctx.user_id)
         .execute(&mut *tx)
-        .await
-        .map_err(handle_unexpected_db_err)?;
+        .await?;
 
     let _ = sqlx::query!(r#"DELETE FROM clubs WHERE id = $1"#, auth_ctx.club_id)
         .execute(&mut *tx)
-        .await
-        .map_err(handle_unexpected_db_err)?;
+        .await?;
 
-    let _ = tx.commit().await.map_err(handle_unexpected_db_err)?;
+    tx.commit().await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
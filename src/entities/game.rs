@@ -3,16 +3,31 @@ use std::iter;
 use chrono::{DateTime, Utc};
 use log::debug;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use sqlx::Type;
 use strum_macros::{Display, EnumString};
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
 use crate::{
     auth::{
-        roles::{check_user_roles, Role},
-        utils::AuthContext,
+        permissions::{
+            actions::{GameCreate, GameDelete, GameList},
+            Authorized,
+        },
+        roles::Role,
+    },
+    entities::{
+        absence::{find_absent_user_ids, ABSENT_DECLINE_REASON},
+        audit_log::{record_audit_event, AuditAction, AuditEvent},
+        referee_assignment::create_default_referee_slots,
+    },
+    notifications::{notify_users, PushMessage},
+    utils::{
+        error::ApiError,
+        pagination::{ListParams, ListQuery, Paginated, SortKey, TIME_KEY_FORMAT},
+        validation::{Validate, ValidatedJson, Validator, MAX_NAME_LEN, MAX_TEXT_LEN},
     },
-    utils::api::db_err_to_response,
     AppState, JustId,
 };
 use axum::{
@@ -20,7 +35,7 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
     routing::{delete, get, post},
-    Extension, Json, Router,
+    Json, Router,
 };
 
 // TODO: rewrite game to be from generic perspective - not from your club's
@@ -30,7 +45,7 @@ use axum::{
 // Would possibly need to have both - service may be used by whole leagues and by select teams.
 
 // user from DB wihtout security and unnecessary util fields
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CreateGamePayload {
     pub team_id: String,
     pub opponent: String,
@@ -41,7 +56,32 @@ pub struct CreateGamePayload {
     pub invited_roles: Vec<Role>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, Display, EnumString)]
+impl Validate for CreateGamePayload {
+    fn validate(&self, v: &mut Validator) {
+        v.id("team_id", &self.team_id);
+        v.length("opponent", &self.opponent, 1, MAX_NAME_LEN);
+        v.length("location", &self.location, 0, MAX_TEXT_LEN);
+        if let Some(stop_time) = self.stop_time {
+            v.check(
+                stop_time > self.start_time,
+                "stop_time",
+                "must be after start_time",
+            );
+        }
+        v.not_empty("invited_roles", &self.invited_roles);
+        for (i, role) in self.invited_roles.iter().enumerate() {
+            v.check(
+                role.is_invitable(),
+                &format!("invited_roles[{}]", i),
+                format!("{} can't be invited to games", role),
+            );
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, Display, EnumString, ToSchema,
+)]
 #[sqlx(type_name = "location_kind", rename_all = "snake_case")] // must match the Postgres type name
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
@@ -51,7 +91,9 @@ pub enum LocationKind {
     Other,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, Display, EnumString)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, Display, EnumString, ToSchema,
+)]
 #[sqlx(type_name = "game_invite_response", rename_all = "snake_case")] // must match the Postgres type name
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
@@ -62,7 +104,9 @@ pub enum InviteResponse {
     Unsure,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, Display, EnumString)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, Display, EnumString, ToSchema,
+)]
 #[sqlx(type_name = "game_invite_response", rename_all = "snake_case")] // must match the Postgres type name
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
@@ -96,14 +140,22 @@ pub fn game_router<S>(state: AppState) -> Router<S> {
         .with_state(state.clone())
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(create_game, list_games_for_team, delete_game))]
+pub struct GameApi;
+
+#[utoipa::path(
+    post,
+    path = "/create",
+    tag = "games",
+    request_body = CreateGamePayload,
+    responses((status = 201, description = "the new game's id", body = String)),
+)]
 pub async fn create_game(
     State(state): State<AppState>,
-    auth_ctx: Extension<AuthContext>,
-    Json(payload): Json<CreateGamePayload>,
-) -> Result<Response, Response> {
-    // Only admins/coaches can create games – keep the same guard
-    let _ = check_user_roles(&auth_ctx, &[Role::ClubAdmin, Role::SuperAdmin, Role::Coach])?;
-
+    auth_ctx: Authorized<GameCreate>,
+    ValidatedJson(payload): ValidatedJson<CreateGamePayload>,
+) -> Result<Response, ApiError> {
     // Optional: verify that `payload.team_id` actually belongs to the authenticated club
     sqlx::query!(
         "SELECT 1 as ok FROM teams WHERE id = $1 AND club_id = $2",
@@ -111,10 +163,9 @@ pub async fn create_game(
         auth_ctx.club_id
     )
     .fetch_one(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .await?;
 
-    let mut tx = state.pg_pool.begin().await.map_err(db_err_to_response)?;
+    let mut tx = state.pg_pool.begin().await?;
 
     let new_event = sqlx::query!(
         r#"INSERT INTO events (start_time, stop_time) VALUES ($1::timestamptz, $2::timestamptz) RETURNING id"#,
@@ -122,8 +173,7 @@ pub async fn create_game(
         payload.stop_time,
     )
     .fetch_one(&mut *tx)
-    .await
-    .map_err(db_err_to_response)?;
+    .await?;
 
     let new_game = sqlx::query!(
         r#"INSERT INTO games
@@ -137,8 +187,9 @@ pub async fn create_game(
         payload.team_id
     )
     .fetch_one(&mut *tx)
-    .await
-    .map_err(db_err_to_response)?;
+    .await?;
+
+    create_default_referee_slots(&mut tx, &new_game.id, &payload.team_id).await?;
 
     let users_to_invite = sqlx::query_as!(
         JustId,
@@ -148,78 +199,179 @@ pub async fn create_game(
         AND ra.role = ANY($2)
         ORDER by u.username"#,
         auth_ctx.club_id,
-        payload.invited_roles as Vec<Role>
+        payload.invited_roles.clone() as Vec<Role>
     )
     .fetch_all(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .await?;
 
     // ensuring, one user only gets one invite
     let mut user_ids: Vec<String> = users_to_invite.iter().map(|u| u.id.clone()).collect();
     user_ids.sort_unstable();
     user_ids.dedup();
-    let game_ids: Vec<String> = iter::repeat(new_game.id.clone())
-        .take(user_ids.len())
-        .collect();
-    let statuses: Vec<InviteResponse> = iter::repeat(InviteResponse::Pending)
-        .take(user_ids.len())
-        .collect();
+    let game_ids: Vec<String> = iter::repeat_n(new_game.id.clone(), user_ids.len()).collect();
+
+    // absent users are invited too, but declined right away
+    let absent_user_ids = find_absent_user_ids(&mut tx, &user_ids, payload.start_time).await?;
+    let (statuses, decline_reasons): (Vec<InviteResponse>, Vec<Option<String>>) = user_ids
+        .iter()
+        .map(|user_id| {
+            if absent_user_ids.contains(user_id) {
+                (
+                    InviteResponse::Declined,
+                    Some(ABSENT_DECLINE_REASON.to_string()),
+                )
+            } else {
+                (InviteResponse::Pending, None)
+            }
+        })
+        .unzip();
 
     sqlx::query!(
         r#"
-        INSERT INTO game_invites (user_id, game_id, response)
-        SELECT * FROM UNNEST($1::text[], $2::text[], $3::game_invite_response[])
+        INSERT INTO game_invites (user_id, game_id, response, decline_reason)
+        SELECT * FROM UNNEST($1::text[], $2::text[], $3::game_invite_response[], $4::text[])
     "#,
         &user_ids,
         &game_ids,
-        &statuses as &Vec<InviteResponse>
+        &statuses as &Vec<InviteResponse>,
+        &decline_reasons as &[Option<String>]
     )
     .execute(&mut *tx)
-    .await
-    .map_err(db_err_to_response)?;
-
-    let _ = tx.commit().await.map_err(db_err_to_response)?;
+    .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            after: Some(json!({
+                "team_id": payload.team_id,
+                "opponent": payload.opponent,
+                "start_time": payload.start_time,
+                "stop_time": payload.stop_time,
+                "location": payload.location,
+                "location_kind": payload.location_kind,
+                "invited_roles": payload.invited_roles,
+            })),
+            details: json!({
+                "invited_user_count": user_ids.len(),
+                "absent_user_count": absent_user_ids.len(),
+            }),
+            ..AuditEvent::by(&auth_ctx, AuditAction::GameCreated, "game", &new_game.id)
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+    state.metrics.game_created();
+
+    user_ids.retain(|user_id| !absent_user_ids.contains(user_id));
+    notify_users(
+        &state,
+        user_ids,
+        PushMessage {
+            title: "New game invite".to_string(),
+            body: format!(
+                "vs {} – {} at {}",
+                payload.opponent,
+                format_start_time(&payload.start_time),
+                payload.location
+            ),
+            url: Some("/events".to_string()),
+            tag: Some(format!("game-{}", new_game.id)),
+        },
+    );
 
     Ok((StatusCode::CREATED, Json(&new_game.id)).into_response())
 }
 
+#[utoipa::path(
+    delete,
+    path = "/delete-by-id/{id}",
+    tag = "games",
+    params(("id" = String, Path, description = "game id")),
+    responses((status = 204, description = "game deleted")),
+)]
 pub async fn delete_game(
     State(state): State<AppState>,
-    auth_ctx: Extension<AuthContext>,
+    auth_ctx: Authorized<GameDelete>,
     Path(game_id): Path<String>,
-) -> Result<Response, Response> {
+) -> Result<Response, ApiError> {
     debug!("TRYING TO DELETE GAME {}", game_id);
-    // Only admins/coaches can delete games
-    let _ = check_user_roles(&auth_ctx, &[Role::ClubAdmin, Role::SuperAdmin, Role::Coach])?;
+    let mut tx = state.pg_pool.begin().await?;
 
     // Verify that the game belongs to the authenticated club
-    let game_exists = sqlx::query!(
-        "SELECT 1 as ok FROM games g JOIN teams t ON g.team_id = t.id WHERE g.id = $1 AND t.club_id = $2",
+    let game = sqlx::query!(
+        r#"
+        SELECT g.team_id, g.opponent, e.start_time, e.stop_time, g.location,
+        g.location_kind AS "location_kind: LocationKind", g.invited_roles AS "invited_roles: Vec<Role>"
+        FROM games g JOIN teams t ON g.team_id = t.id JOIN events e ON g.event_id = e.id
+        WHERE g.id = $1 AND t.club_id = $2
+        "#,
         game_id,
         auth_ctx.club_id
     )
-    .fetch_optional(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .fetch_optional(&mut *tx)
+    .await?;
 
-    if game_exists.is_none() {
+    let Some(game) = game else {
         debug!("GAME DOES NOT EXIST!!! {}", game_id);
 
-        return Err((StatusCode::NOT_FOUND, "Game not found").into_response());
-    }
+        return Err(ApiError::NotFound("Game not found".into()));
+    };
 
     debug!("GAME DOES EXIST {}", game_id);
 
+    // invites are gone after the delete, so collect who to tell beforehand
+    let invited_user_ids = sqlx::query_scalar!(
+        "SELECT user_id FROM game_invites WHERE game_id = $1",
+        game_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
     // Delete the game (this will cascade to game_invites due to the foreign key constraint)
     sqlx::query!("DELETE FROM games WHERE id = $1", game_id)
-        .execute(&state.pg_pool)
-        .await
-        .map_err(db_err_to_response)?;
+        .execute(&mut *tx)
+        .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            before: Some(json!({
+                "team_id": game.team_id,
+                "opponent": game.opponent,
+                "start_time": game.start_time,
+                "stop_time": game.stop_time,
+                "location": game.location,
+                "location_kind": game.location_kind,
+                "invited_roles": game.invited_roles,
+            })),
+            details: json!({ "invited_user_count": invited_user_ids.len() }),
+            ..AuditEvent::by(&auth_ctx, AuditAction::GameDeleted, "game", &game_id)
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    notify_users(
+        &state,
+        invited_user_ids,
+        PushMessage {
+            title: "Game cancelled".to_string(),
+            body: format!(
+                "vs {} – {}",
+                game.opponent,
+                format_start_time(&game.start_time)
+            ),
+            url: Some("/events".to_string()),
+            tag: Some(format!("game-{}", game_id)),
+        },
+    );
 
     Ok((StatusCode::NO_CONTENT).into_response())
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 struct GameListItem {
     id: String,
     team_id: String,
@@ -228,16 +380,56 @@ struct GameListItem {
     stop_time: Option<chrono::DateTime<Utc>>,
     location: String,
     location_kind: LocationKind,
+    #[serde(skip)]
+    sort_key: String,
 }
 
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum GameSort {
+    #[default]
+    StartTime,
+    Opponent,
+}
+
+impl SortKey for GameSort {
+    /// latest games first
+    const DEFAULT_DESCENDING: bool = true;
+}
+
+/// games starting in `[from, until)`
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GameFilters {
+    pub from: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Validate for GameFilters {
+    fn validate(&self, v: &mut Validator) {
+        if let (Some(from), Some(until)) = (self.from, self.until) {
+            v.check(until > from, "until", "must be after from");
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/list-for-team/{team_id}",
+    tag = "games",
+    params(
+        ("team_id" = String, Path),
+        ListParams,
+        GameFilters,
+    ),
+    responses((status = 200, body = Paginated<GameListItem>)),
+)]
 pub async fn list_games_for_team(
     State(state): State<AppState>,
-    auth_ctx: Extension<AuthContext>,
+    auth_ctx: Authorized<GameList>,
     Path(team_id): Path<String>,
-) -> Result<Response, Response> {
-    // Only admins/coaches can list games for a team
-    let _ = check_user_roles(&auth_ctx, &[Role::ClubAdmin, Role::SuperAdmin, Role::Coach])?;
-
+    ListQuery { page, filters }: ListQuery<GameSort, GameFilters>,
+) -> Result<Response, ApiError> {
     // Verify that the team belongs to the authenticated club
     let team_exists = sqlx::query!(
         "SELECT 1 as ok FROM teams WHERE id = $1 AND club_id = $2",
@@ -245,34 +437,80 @@ pub async fn list_games_for_team(
         auth_ctx.club_id
     )
     .fetch_optional(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .await?;
 
     if team_exists.is_none() {
-        return Err((StatusCode::NOT_FOUND, "Team not found").into_response());
+        return Err(ApiError::NotFound("Team not found".into()));
     }
 
+    let mut tx = state.pg_pool.begin().await?;
+
     let games = sqlx::query_as!(
         GameListItem,
         r#"
-        SELECT 
-            g.id,
-            g.team_id,
-            g.opponent,
-            e.start_time,
-            e.stop_time,
-            g.location,
-            g.location_kind AS "location_kind: LocationKind"
-        FROM games g
+        SELECT id AS "id!", team_id AS "team_id!", opponent AS "opponent!",
+            start_time AS "start_time!", stop_time, location AS "location!",
+            location_kind AS "location_kind!: LocationKind", sort_key AS "sort_key!"
+        FROM (
+            SELECT
+                g.id,
+                g.team_id,
+                g.opponent,
+                e.start_time,
+                e.stop_time,
+                g.location,
+                g.location_kind,
+                CASE $4
+                    WHEN 'opponent' THEN g.opponent
+                    ELSE to_char(e.start_time AT TIME ZONE 'UTC', $5)
+                END AS sort_key
+            FROM games g
+            JOIN events e ON g.event_id = e.id
+            WHERE g.team_id = $1
+            AND ($2::timestamptz IS NULL OR e.start_time >= $2)
+            AND ($3::timestamptz IS NULL OR e.start_time < $3)
+        ) r
+        WHERE ($7::text IS NULL OR CASE WHEN $6
+            THEN (r.sort_key, r.id) < ($7, $8) ELSE (r.sort_key, r.id) > ($7, $8) END)
+        ORDER BY CASE WHEN $6 THEN r.sort_key END DESC, r.sort_key,
+            CASE WHEN $6 THEN r.id END DESC, r.id
+        LIMIT $9
+        "#,
+        team_id,
+        filters.from,
+        filters.until,
+        page.sort(),
+        TIME_KEY_FORMAT,
+        page.descending,
+        page.after_key(),
+        page.after_id(),
+        page.fetch_limit()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT count(*) AS "count!" FROM games g
         JOIN events e ON g.event_id = e.id
         WHERE g.team_id = $1
-        ORDER BY e.start_time DESC
+        AND ($2::timestamptz IS NULL OR e.start_time >= $2)
+        AND ($3::timestamptz IS NULL OR e.start_time < $3)
         "#,
-        team_id
+        team_id,
+        filters.from,
+        filters.until
     )
-    .fetch_all(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
 
+    let games = page.finish(games, total, |game| (&game.sort_key, &game.id));
     Ok((StatusCode::OK, Json(games)).into_response())
 }
+
+// TODO: use the club's timezone once clubs have one
+pub(crate) fn format_start_time(start_time: &DateTime<Utc>) -> String {
+    start_time.format("%a %d %b, %H:%M UTC").to_string()
+}
@@ -4,108 +4,408 @@ use axum::{
     response::{IntoResponse, Response},
     Extension, Json,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+use utoipa::{IntoParams, ToSchema};
 
 use crate::{
-    auth::utils::AuthContext,
+    auth::{
+        permissions::{
+            actions::{InviteList, MinorActOnBehalf},
+            has_permission, Authorized, Permission,
+        },
+        utils::AuthContext,
+    },
     entities::game::{InviteResponse, InviteResponseFromUser},
-    utils::api::{db_err_to_response, AppState},
+    utils::{
+        api::AppState,
+        error::ApiError,
+        pagination::{ListParams, ListQuery, Paginated, SortKey, TIME_KEY_FORMAT},
+        validation::{Validate, ValidatedJson, Validator},
+    },
 };
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct SelectInvites {
     invite_id: String,
     game_id: String,
+    team_id: String,
     opponent: String,
+    start_time: DateTime<Utc>,
     response: InviteResponse,
+    /// why the app declined the invite, e.g. `absent`
+    decline_reason: Option<String>,
+    #[serde(skip)]
+    sort_key: String,
 }
 
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum InviteSort {
+    #[default]
+    StartTime,
+    Opponent,
+}
+
+impl SortKey for InviteSort {}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct InviteFilters {
+    pub response: Option<InviteResponse>,
+    pub team_id: Option<String>,
+    /// games starting in `[from, until)`
+    pub from: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Validate for InviteFilters {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(team_id) = &self.team_id {
+            v.id("team_id", team_id);
+        }
+        if let (Some(from), Some(until)) = (self.from, self.until) {
+            v.check(until > from, "until", "must be after from");
+        }
+    }
+}
+
+/// soonest games first
+#[utoipa::path(
+    get,
+    path = "/game-invites/list-own",
+    tag = "game-invites",
+    params(ListParams, InviteFilters),
+    responses((status = 200, body = Paginated<SelectInvites>)),
+)]
 pub async fn list_own_game_invites(
     State(state): State<AppState>,
     auth_ctx: Extension<AuthContext>,
-) -> Result<Response, Response> {
+    ListQuery { page, filters }: ListQuery<InviteSort, InviteFilters>,
+) -> Result<Response, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
     let invites = sqlx::query_as!(
         SelectInvites,
         r#"
-        SELECT g.id as game_id, g.opponent as opponent, i.response AS "response: InviteResponse", i.id as invite_id
-        FROM game_invites i 
+        SELECT invite_id AS "invite_id!", game_id AS "game_id!", team_id AS "team_id!",
+            opponent AS "opponent!", start_time AS "start_time!",
+            response AS "response!: InviteResponse", decline_reason, sort_key AS "sort_key!"
+        FROM (
+            SELECT i.id AS invite_id, g.id AS game_id, g.team_id, g.opponent, e.start_time,
+            i.response, i.decline_reason,
+            CASE $6
+                WHEN 'opponent' THEN g.opponent
+                ELSE to_char(e.start_time AT TIME ZONE 'UTC', $7)
+            END AS sort_key
+            FROM game_invites i
+            JOIN games g ON g.id = i.game_id
+            JOIN events e ON e.id = g.event_id
+            WHERE i.user_id = $1
+            AND ($2::game_invite_response IS NULL OR i.response = $2)
+            AND ($3::text IS NULL OR g.team_id = $3)
+            AND ($4::timestamptz IS NULL OR e.start_time >= $4)
+            AND ($5::timestamptz IS NULL OR e.start_time < $5)
+        ) r
+        WHERE ($9::text IS NULL OR CASE WHEN $8
+            THEN (r.sort_key, r.invite_id) < ($9, $10) ELSE (r.sort_key, r.invite_id) > ($9, $10) END)
+        ORDER BY CASE WHEN $8 THEN r.sort_key END DESC, r.sort_key,
+            CASE WHEN $8 THEN r.invite_id END DESC, r.invite_id
+        LIMIT $11
+        "#,
+        auth_ctx.user_id,
+        filters.response as Option<InviteResponse>,
+        filters.team_id,
+        filters.from,
+        filters.until,
+        page.sort(),
+        TIME_KEY_FORMAT,
+        page.descending,
+        page.after_key(),
+        page.after_id(),
+        page.fetch_limit()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT count(*) AS "count!" FROM game_invites i
         JOIN games g ON g.id = i.game_id
-        WHERE user_id = $1
+        JOIN events e ON e.id = g.event_id
+        WHERE i.user_id = $1
+        AND ($2::game_invite_response IS NULL OR i.response = $2)
+        AND ($3::text IS NULL OR g.team_id = $3)
+        AND ($4::timestamptz IS NULL OR e.start_time >= $4)
+        AND ($5::timestamptz IS NULL OR e.start_time < $5)
+        "#,
+        auth_ctx.user_id,
+        filters.response as Option<InviteResponse>,
+        filters.team_id,
+        filters.from,
+        filters.until
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let invites = page.finish(invites, total, |invite| {
+        (&invite.sort_key, &invite.invite_id)
+    });
+    Ok((StatusCode::OK, Json(invites)).into_response())
+}
+
+#[derive(Serialize, ToSchema)]
+struct SelectMinorsInvites {
+    user_id: String,
+    username: String,
+    invite_id: String,
+    game_id: String,
+    opponent: String,
+    start_time: DateTime<Utc>,
+    response: InviteResponse,
+    decline_reason: Option<String>,
+    #[serde(skip)]
+    sort_key: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum MinorsInviteSort {
+    #[default]
+    StartTime,
+    Username,
+}
+
+impl SortKey for MinorsInviteSort {}
+
+/// invites of the minors linked to the requesting parent, soonest games first
+#[utoipa::path(
+    get,
+    path = "/game-invites/list-for-minors",
+    tag = "game-invites",
+    params(ListParams),
+    responses((status = 200, body = Paginated<SelectMinorsInvites>)),
+)]
+pub async fn list_minors_game_invites(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<MinorActOnBehalf>,
+    ListQuery { page, .. }: ListQuery<MinorsInviteSort>,
+) -> Result<Response, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let invites = sqlx::query_as!(
+        SelectMinorsInvites,
+        r#"
+        SELECT user_id AS "user_id!", username AS "username!", invite_id AS "invite_id!",
+            game_id AS "game_id!", opponent AS "opponent!", start_time AS "start_time!",
+            response AS "response!: InviteResponse", decline_reason, sort_key AS "sort_key!"
+        FROM (
+            SELECT u.id AS user_id, u.username, i.id AS invite_id, g.id AS game_id, g.opponent,
+            e.start_time, i.response, i.decline_reason,
+            CASE $2
+                WHEN 'username' THEN u.username
+                ELSE to_char(e.start_time AT TIME ZONE 'UTC', $3)
+            END AS sort_key
+            FROM guardianships gs
+            JOIN users u ON u.id = gs.minor_user_id
+            JOIN game_invites i ON i.user_id = gs.minor_user_id
+            JOIN games g ON g.id = i.game_id
+            JOIN events e ON e.id = g.event_id
+            WHERE gs.parent_user_id = $1
+        ) r
+        WHERE ($5::text IS NULL OR CASE WHEN $4
+            THEN (r.sort_key, r.invite_id) < ($5, $6) ELSE (r.sort_key, r.invite_id) > ($5, $6) END)
+        ORDER BY CASE WHEN $4 THEN r.sort_key END DESC, r.sort_key,
+            CASE WHEN $4 THEN r.invite_id END DESC, r.invite_id
+        LIMIT $7
+        "#,
+        auth_ctx.user_id,
+        page.sort(),
+        TIME_KEY_FORMAT,
+        page.descending,
+        page.after_key(),
+        page.after_id(),
+        page.fetch_limit()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT count(*) AS "count!" FROM guardianships gs
+        JOIN game_invites i ON i.user_id = gs.minor_user_id
+        WHERE gs.parent_user_id = $1
         "#,
         auth_ctx.user_id
     )
-    .fetch_all(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .fetch_one(&mut *tx)
+    .await?;
 
+    tx.commit().await?;
+
+    let invites = page.finish(invites, total, |invite| {
+        (&invite.sort_key, &invite.invite_id)
+    });
     Ok((StatusCode::OK, Json(invites)).into_response())
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct SelectInvitesToGame {
     user_id: String,
     invite_id: String,
     username: String,
     response: InviteResponse,
+    decline_reason: Option<String>,
+    #[serde(skip)]
+    sort_key: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum InviteToGameSort {
+    #[default]
+    Username,
+    Response,
+}
+
+impl SortKey for InviteToGameSort {}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct InviteToGameFilters {
+    pub response: Option<InviteResponse>,
+}
+
+impl Validate for InviteToGameFilters {
+    fn validate(&self, _: &mut Validator) {}
 }
 
+#[utoipa::path(
+    get,
+    path = "/game-invites/list-to-game/{game_id}",
+    tag = "game-invites",
+    params(("game_id" = String, Path), ListParams, InviteToGameFilters),
+    responses((status = 200, body = Paginated<SelectInvitesToGame>)),
+)]
 pub async fn list_invites_to_game(
     State(state): State<AppState>,
-    auth_ctx: Extension<AuthContext>,
+    auth_ctx: Authorized<InviteList>,
     Path(game_id): Path<String>,
-) -> Result<Response, Response> {
+    ListQuery { page, filters }: ListQuery<InviteToGameSort, InviteToGameFilters>,
+) -> Result<Response, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
     let invites = sqlx::query_as!(
         SelectInvitesToGame,
         r#"
-        SELECT
-            u.id       as user_id,
-            u.username as username,
-            i.response AS "response: InviteResponse",
-            i.id       as invite_id
-        FROM game_invites i
-        JOIN users u ON u.id = i.user_id
+        SELECT user_id AS "user_id!", invite_id AS "invite_id!", username AS "username!",
+            response AS "response!: InviteResponse", decline_reason, sort_key AS "sort_key!"
+        FROM (
+            SELECT u.id AS user_id, i.id AS invite_id, u.username, i.response, i.decline_reason,
+            CASE $4 WHEN 'response' THEN i.response::text ELSE u.username END AS sort_key
+            FROM game_invites i
+            JOIN users u ON u.id = i.user_id
+            JOIN games g ON g.id = i.game_id
+            JOIN teams t ON t.id = g.team_id
+            WHERE i.game_id = $1
+            AND t.club_id = $2
+            AND ($3::game_invite_response IS NULL OR i.response = $3)
+        ) r
+        WHERE ($6::text IS NULL OR CASE WHEN $5
+            THEN (r.sort_key, r.invite_id) < ($6, $7) ELSE (r.sort_key, r.invite_id) > ($6, $7) END)
+        ORDER BY CASE WHEN $5 THEN r.sort_key END DESC, r.sort_key,
+            CASE WHEN $5 THEN r.invite_id END DESC, r.invite_id
+        LIMIT $8
+        "#,
+        game_id,
+        auth_ctx.club_id,
+        filters.response as Option<InviteResponse>,
+        page.sort(),
+        page.descending,
+        page.after_key(),
+        page.after_id(),
+        page.fetch_limit()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT count(*) AS "count!" FROM game_invites i
         JOIN games g ON g.id = i.game_id
-        JOIN teams t ON t.id = g.team_id  
+        JOIN teams t ON t.id = g.team_id
         WHERE i.game_id = $1
-          AND t.club_id = $2
+        AND t.club_id = $2
+        AND ($3::game_invite_response IS NULL OR i.response = $3)
         "#,
         game_id,
-        auth_ctx.club_id
+        auth_ctx.club_id,
+        filters.response as Option<InviteResponse>
     )
-    .fetch_all(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
 
+    let invites = page.finish(invites, total, |invite| {
+        (&invite.sort_key, &invite.invite_id)
+    });
     Ok((StatusCode::OK, Json(invites)).into_response())
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct AnswerInviteToGame {
     invite_id: String,
     response: InviteResponseFromUser,
 }
 
+impl Validate for AnswerInviteToGame {
+    fn validate(&self, v: &mut Validator) {
+        v.id("invite_id", &self.invite_id);
+    }
+}
+
+/// for the user's own invites, or - for parents - those of their minors
+#[utoipa::path(
+    post,
+    path = "/game-invites/respond",
+    tag = "game-invites",
+    request_body = AnswerInviteToGame,
+    responses((status = 200, description = "answer saved")),
+)]
 pub async fn answer_invite_to_game(
     State(state): State<AppState>,
     auth_ctx: Extension<AuthContext>,
-    Json(payload): Json<AnswerInviteToGame>,
-) -> Result<Response, Response> {
-    let _ = sqlx::query!(
+    ValidatedJson(payload): ValidatedJson<AnswerInviteToGame>,
+) -> Result<Response, ApiError> {
+    let answered = sqlx::query!(
         r#"
         UPDATE game_invites AS i
-        SET response = $1
-        FROM users AS u
-        WHERE u.id = i.user_id
-        AND i.id = $2
-        AND u.id = $3
+        SET response = $1, decline_reason = NULL
+        WHERE i.id = $2
+        AND (
+            i.user_id = $3
+            OR ($4 AND EXISTS (
+                SELECT 1 FROM guardianships gs
+                WHERE gs.parent_user_id = $3 AND gs.minor_user_id = i.user_id
+            ))
+        )
         "#,
         payload.response as InviteResponseFromUser,
         payload.invite_id,
-        auth_ctx.user_id
+        auth_ctx.user_id,
+        has_permission(&auth_ctx, Permission::MinorActOnBehalf)
     )
     .execute(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .await?;
+    if answered.rows_affected() > 0 {
+        state
+            .metrics
+            .game_invite_answered(&payload.response.to_string());
+    }
 
     Ok((StatusCode::OK).into_response())
 }
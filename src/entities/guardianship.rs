@@ -0,0 +1,342 @@
+//! Links parents to the minors they act for. Club admins manage the links, the parent
+//! needs the `parent` role.
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use strum_macros::{Display, EnumString};
+use utoipa::{OpenApi, ToSchema};
+
+use crate::{
+    auth::{
+        permissions::{actions::GuardianshipManage, Authorized},
+        roles::Role,
+        utils::AuthContext,
+    },
+    entities::audit_log::{record_audit_event, AuditAction, AuditEvent},
+    utils::{
+        api::AppState,
+        error::ApiError,
+        pagination::{ListParams, ListQuery, Paginated, SortKey},
+        validation::{Validate, ValidatedJson, Validator},
+    },
+};
+
+pub fn guardianship_router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/link", post(link_guardianship))
+        .route("/unlink", delete(unlink_guardianship))
+        .route("/list", get(list_guardianships))
+        .route("/list-own-minors", get(list_own_minors))
+        .with_state(state.clone())
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    link_guardianship,
+    unlink_guardianship,
+    list_guardianships,
+    list_own_minors
+))]
+pub struct GuardianshipApi;
+
+#[derive(Deserialize, ToSchema)]
+pub struct GuardianshipPayload {
+    pub parent_user_id: String,
+    pub minor_user_id: String,
+}
+
+impl Validate for GuardianshipPayload {
+    fn validate(&self, v: &mut Validator) {
+        v.id("parent_user_id", &self.parent_user_id);
+        v.id("minor_user_id", &self.minor_user_id);
+        v.check(
+            self.parent_user_id != self.minor_user_id,
+            "minor_user_id",
+            "A user can't be their own parent",
+        );
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GuardianshipListItem {
+    parent_user_id: String,
+    parent_username: String,
+    minor_user_id: String,
+    minor_username: String,
+    #[serde(skip)]
+    sort_key: String,
+    /// the link has no id of its own
+    #[serde(skip)]
+    link_id: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum GuardianshipSort {
+    #[default]
+    ParentUsername,
+    MinorUsername,
+}
+
+impl SortKey for GuardianshipSort {}
+
+#[derive(Serialize, ToSchema)]
+pub struct MinorListItem {
+    id: String,
+    username: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum MinorSort {
+    #[default]
+    Username,
+}
+
+impl SortKey for MinorSort {}
+
+#[utoipa::path(
+    post,
+    path = "/link",
+    tag = "guardianships",
+    request_body = GuardianshipPayload,
+    responses((status = 201, description = "linked")),
+)]
+pub async fn link_guardianship(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<GuardianshipManage>,
+    ValidatedJson(payload): ValidatedJson<GuardianshipPayload>,
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let users = sqlx::query!(
+        r#"
+        SELECT u.id, EXISTS (
+            SELECT 1 FROM role_assignments ra WHERE ra.user_id = u.id AND ra.role = $3
+        ) AS "is_parent!"
+        FROM users u WHERE u.club_id = $1 AND u.id = ANY($2)
+        "#,
+        auth_ctx.club_id,
+        &[
+            payload.parent_user_id.clone(),
+            payload.minor_user_id.clone()
+        ],
+        Role::Parent as Role
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    if users.len() != 2 {
+        return Err(ApiError::NotFound("User not found".into()));
+    }
+    if !users
+        .iter()
+        .any(|user| user.id == payload.parent_user_id && user.is_parent)
+    {
+        return Err(ApiError::BadRequest(
+            "The parent needs the parent role first".into(),
+        ));
+    }
+
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO guardianships (parent_user_id, minor_user_id) VALUES ($1, $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        payload.parent_user_id,
+        payload.minor_user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if inserted.rows_affected() > 0 {
+        record_audit_event(
+            &mut tx,
+            AuditEvent {
+                after: Some(json!({ "parent_user_id": payload.parent_user_id })),
+                ..AuditEvent::by(
+                    &auth_ctx,
+                    AuditAction::GuardianshipLinked,
+                    "user",
+                    &payload.minor_user_id,
+                )
+            },
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(StatusCode::CREATED)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/unlink",
+    tag = "guardianships",
+    request_body = GuardianshipPayload,
+    responses((status = 204, description = "unlinked")),
+)]
+pub async fn unlink_guardianship(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<GuardianshipManage>,
+    ValidatedJson(payload): ValidatedJson<GuardianshipPayload>,
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let deleted = sqlx::query!(
+        r#"
+        DELETE FROM guardianships g
+        USING users u
+        WHERE g.parent_user_id = $1 AND g.minor_user_id = $2
+        AND u.id = g.minor_user_id AND u.club_id = $3
+        "#,
+        payload.parent_user_id,
+        payload.minor_user_id,
+        auth_ctx.club_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Guardianship not found".into()));
+    }
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            before: Some(json!({ "parent_user_id": payload.parent_user_id })),
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::GuardianshipUnlinked,
+                "user",
+                &payload.minor_user_id,
+            )
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/list",
+    tag = "guardianships",
+    params(ListParams),
+    responses((status = 200, body = Paginated<GuardianshipListItem>)),
+)]
+pub async fn list_guardianships(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<GuardianshipManage>,
+    ListQuery { page, .. }: ListQuery<GuardianshipSort>,
+) -> Result<(StatusCode, Json<Paginated<GuardianshipListItem>>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let guardianships = sqlx::query_as!(
+        GuardianshipListItem,
+        r#"
+        SELECT parent_user_id AS "parent_user_id!", parent_username AS "parent_username!",
+            minor_user_id AS "minor_user_id!", minor_username AS "minor_username!",
+            sort_key AS "sort_key!", link_id AS "link_id!"
+        FROM (
+            SELECT p.id AS parent_user_id, p.username AS parent_username,
+            m.id AS minor_user_id, m.username AS minor_username,
+            CASE $2 WHEN 'minor_username' THEN m.username ELSE p.username END AS sort_key,
+            p.id || '/' || m.id AS link_id
+            FROM guardianships g
+            JOIN users p ON p.id = g.parent_user_id
+            JOIN users m ON m.id = g.minor_user_id
+            WHERE m.club_id = $1
+        ) r
+        WHERE ($4::text IS NULL OR CASE WHEN $3
+            THEN (r.sort_key, r.link_id) < ($4, $5) ELSE (r.sort_key, r.link_id) > ($4, $5) END)
+        ORDER BY CASE WHEN $3 THEN r.sort_key END DESC, r.sort_key,
+            CASE WHEN $3 THEN r.link_id END DESC, r.link_id
+        LIMIT $6
+        "#,
+        auth_ctx.club_id,
+        page.sort(),
+        page.descending,
+        page.after_key(),
+        page.after_id(),
+        page.fetch_limit()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT count(*) AS "count!" FROM guardianships g
+        JOIN users m ON m.id = g.minor_user_id
+        WHERE m.club_id = $1
+        "#,
+        auth_ctx.club_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let guardianships = page.finish(guardianships, total, |guardianship| {
+        (&guardianship.sort_key, &guardianship.link_id)
+    });
+    Ok((StatusCode::OK, Json(guardianships)))
+}
+
+/// the minors the requesting user may act for
+#[utoipa::path(
+    get,
+    path = "/list-own-minors",
+    tag = "guardianships",
+    params(ListParams),
+    responses((status = 200, body = Paginated<MinorListItem>)),
+)]
+pub async fn list_own_minors(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    ListQuery { page, .. }: ListQuery<MinorSort>,
+) -> Result<(StatusCode, Json<Paginated<MinorListItem>>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let minors = sqlx::query_as!(
+        MinorListItem,
+        r#"
+        SELECT m.id, m.username
+        FROM guardianships g JOIN users m ON m.id = g.minor_user_id
+        WHERE g.parent_user_id = $1
+        AND ($3::text IS NULL OR CASE WHEN $2
+            THEN (m.username, m.id) < ($3, $4) ELSE (m.username, m.id) > ($3, $4) END)
+        ORDER BY CASE WHEN $2 THEN m.username END DESC, m.username,
+            CASE WHEN $2 THEN m.id END DESC, m.id
+        LIMIT $5
+        "#,
+        auth_ctx.user_id,
+        page.descending,
+        page.after_key(),
+        page.after_id(),
+        page.fetch_limit()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"SELECT count(*) AS "count!" FROM guardianships WHERE parent_user_id = $1"#,
+        auth_ctx.user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let minors = page.finish(minors, total, |minor| (&minor.username, &minor.id));
+    Ok((StatusCode::OK, Json(minors)))
+}
@@ -1,6 +1,12 @@
+pub mod absence;
+pub mod audit_log;
 pub mod club;
 pub mod game;
 pub mod game_invite;
+pub mod guardianship;
+pub mod push_subscription;
+pub mod referee_assignment;
+pub mod referee_auto_assignment;
 pub mod service_invite;
 pub mod team;
 pub mod user;
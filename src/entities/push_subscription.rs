@@ -0,0 +1,241 @@
+//! Browser push subscriptions – one per device a user enabled notifications on.
+
+use axum::{
+    extract::State,
+    http::{header::USER_AGENT, HeaderMap, StatusCode},
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+use utoipa::{OpenApi, ToSchema};
+
+use crate::{
+    auth::utils::AuthContext,
+    notifications::web_push::{is_push_service_endpoint, SubscriptionKeys},
+    utils::{
+        api::AppState,
+        error::ApiError,
+        pagination::{ListParams, ListQuery, Paginated, SortKey, TIME_KEY_FORMAT},
+        validation::{Validate, ValidatedJson, Validator, MAX_TOKEN_LEN},
+    },
+};
+
+pub fn push_subscription_router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/vapid-public-key", get(get_vapid_public_key))
+        .route("/register", post(register_push_subscription))
+        .route("/unregister", delete(unregister_push_subscription))
+        .route("/list-own", get(list_own_push_subscriptions))
+        .with_state(state.clone())
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_vapid_public_key,
+    register_push_subscription,
+    unregister_push_subscription,
+    list_own_push_subscriptions,
+))]
+pub struct PushSubscriptionApi;
+
+/// Shape of `PushSubscription.toJSON()` in the browser
+#[derive(Deserialize, ToSchema)]
+pub struct RegisterPushSubscription {
+    pub endpoint: String,
+    pub keys: PushSubscriptionKeys,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PushSubscriptionKeys {
+    pub p256dh: String,
+    pub auth: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UnregisterPushSubscription {
+    pub endpoint: String,
+}
+
+impl Validate for RegisterPushSubscription {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            is_push_service_endpoint(&self.endpoint),
+            "endpoint",
+            "must be an https URL of a known push service",
+        );
+        v.length("endpoint", &self.endpoint, 1, MAX_TOKEN_LEN);
+        if let Err(err) = SubscriptionKeys::parse(&self.keys.p256dh, &self.keys.auth) {
+            v.check(false, "keys", err.to_string());
+        }
+    }
+}
+
+impl Validate for UnregisterPushSubscription {
+    fn validate(&self, v: &mut Validator) {
+        v.length("endpoint", &self.endpoint, 1, MAX_TOKEN_LEN);
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PushSubscriptionListItem {
+    id: String,
+    user_agent: Option<String>,
+    created_at: chrono::NaiveDateTime,
+    #[serde(skip)]
+    sort_key: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum PushSubscriptionSort {
+    #[default]
+    CreatedAt,
+    UserAgent,
+}
+
+impl SortKey for PushSubscriptionSort {}
+
+/// The application server key the frontend passes to `pushManager.subscribe()`
+#[utoipa::path(
+    get,
+    path = "/vapid-public-key",
+    tag = "push-subscriptions",
+    responses(
+        (status = 200, description = "for `PushManager.subscribe`", body = String, content_type = "text/plain"),
+    ),
+)]
+pub async fn get_vapid_public_key(State(state): State<AppState>) -> Result<String, ApiError> {
+    match &state.web_push {
+        Some(web_push) => Ok(web_push.public_key().to_string()),
+        None => Err(ApiError::ServiceUnavailable(
+            "Push notifications are not configured".into(),
+        )),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/register",
+    tag = "push-subscriptions",
+    request_body = RegisterPushSubscription,
+    responses((status = 201, description = "subscription saved")),
+)]
+pub async fn register_push_subscription(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    headers: HeaderMap,
+    ValidatedJson(payload): ValidatedJson<RegisterPushSubscription>,
+) -> Result<StatusCode, ApiError> {
+    let user_agent = headers
+        .get(USER_AGENT)
+        .and_then(|ua| ua.to_str().ok())
+        .map(|ua| ua.to_string());
+
+    // a device that re-subscribes, or is now used by someone else, keeps a single row
+    sqlx::query!(
+        r#"
+        INSERT INTO push_subscriptions (user_id, session_id, endpoint, p256dh, auth, user_agent)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (endpoint) DO UPDATE
+        SET user_id = EXCLUDED.user_id,
+            session_id = EXCLUDED.session_id,
+            p256dh = EXCLUDED.p256dh,
+            auth = EXCLUDED.auth,
+            user_agent = EXCLUDED.user_agent,
+            updated_at = CURRENT_TIMESTAMP
+        "#,
+        auth_ctx.user_id,
+        auth_ctx.session_id,
+        payload.endpoint,
+        payload.keys.p256dh,
+        payload.keys.auth,
+        user_agent
+    )
+    .execute(&state.pg_pool)
+    .await?;
+
+    Ok(StatusCode::CREATED)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/unregister",
+    tag = "push-subscriptions",
+    request_body = UnregisterPushSubscription,
+    responses((status = 204, description = "subscription removed")),
+)]
+pub async fn unregister_push_subscription(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    ValidatedJson(payload): ValidatedJson<UnregisterPushSubscription>,
+) -> Result<StatusCode, ApiError> {
+    sqlx::query!(
+        r#"DELETE FROM push_subscriptions WHERE endpoint = $1 AND user_id = $2"#,
+        payload.endpoint,
+        auth_ctx.user_id
+    )
+    .execute(&state.pg_pool)
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/list-own",
+    tag = "push-subscriptions",
+    params(ListParams),
+    responses((status = 200, body = Paginated<PushSubscriptionListItem>)),
+)]
+pub async fn list_own_push_subscriptions(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    ListQuery { page, .. }: ListQuery<PushSubscriptionSort>,
+) -> Result<(StatusCode, Json<Paginated<PushSubscriptionListItem>>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let subscriptions = sqlx::query_as!(
+        PushSubscriptionListItem,
+        r#"
+        SELECT id AS "id!", user_agent, created_at AS "created_at!", sort_key AS "sort_key!"
+        FROM (
+            SELECT id, user_agent, created_at,
+            CASE $2
+                WHEN 'user_agent' THEN coalesce(user_agent, '')
+                ELSE to_char(created_at, $3)
+            END AS sort_key
+            FROM push_subscriptions
+            WHERE user_id = $1
+        ) r
+        WHERE ($5::text IS NULL OR CASE WHEN $4
+            THEN (r.sort_key, r.id) < ($5, $6) ELSE (r.sort_key, r.id) > ($5, $6) END)
+        ORDER BY CASE WHEN $4 THEN r.sort_key END DESC, r.sort_key,
+            CASE WHEN $4 THEN r.id END DESC, r.id
+        LIMIT $7
+        "#,
+        auth_ctx.user_id,
+        page.sort(),
+        TIME_KEY_FORMAT,
+        page.descending,
+        page.after_key(),
+        page.after_id(),
+        page.fetch_limit()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"SELECT count(*) AS "count!" FROM push_subscriptions WHERE user_id = $1"#,
+        auth_ctx.user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let subscriptions = page.finish(subscriptions, total, |subscription| {
+        (&subscription.sort_key, &subscription.id)
+    });
+    Ok((StatusCode::OK, Json(subscriptions)))
+}
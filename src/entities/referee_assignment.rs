@@ -0,0 +1,1242 @@
+//! Referees for games. Every game has slots (main, assistant, table official), created from
+//! its team's defaults. Referee managers request a referee per slot, the slot is filled once
+//! the referee accepts. Requests that overlap another of the referee's games are refused.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get, post, put},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgConnection, Type};
+use strum_macros::{Display, EnumString};
+use utoipa::{OpenApi, ToSchema};
+
+use crate::{
+    auth::{
+        permissions::{
+            actions::{GameList, RefereeAssignmentManage},
+            Authorized,
+        },
+        roles::Role,
+        utils::AuthContext,
+    },
+    entities::{
+        absence::find_absent_user_ids,
+        audit_log::{record_audit_event, AuditAction, AuditEvent},
+        game::format_start_time,
+        referee_auto_assignment::{commit_referee_assignments, propose_referee_assignments},
+    },
+    notifications::{notify_users, PushMessage},
+    utils::{
+        api::AppState,
+        error::ApiError,
+        pagination::{ListParams, ListQuery, Paginated, SortKey, TIME_KEY_FORMAT},
+        validation::{Validate, ValidatedJson, Validator},
+    },
+};
+
+/// games without an end time are assumed to take this long
+pub const DEFAULT_GAME_HOURS: i32 = 2;
+const MAX_SLOTS_PER_POSITION: i32 = 10;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, Display, EnumString, ToSchema,
+)]
+#[sqlx(type_name = "referee_position", rename_all = "snake_case")] // must match the Postgres type name
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum RefereePosition {
+    Main,
+    Assistant,
+    TableOfficial,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, Display, EnumString, ToSchema,
+)]
+#[sqlx(type_name = "referee_request_status", rename_all = "snake_case")] // must match the Postgres type name
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum RefereeRequestStatus {
+    Pending,
+    Accepted,
+    Declined,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, Display, EnumString, ToSchema,
+)]
+#[sqlx(type_name = "referee_request_status", rename_all = "snake_case")] // must match the Postgres type name
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+/** A referee may not reset a request back to "pending" */
+pub enum RefereeResponse {
+    Accepted,
+    Declined,
+}
+
+pub fn referee_assignment_router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/defaults/{team_id}", get(get_team_referee_defaults))
+        .route("/defaults/{team_id}", put(set_team_referee_defaults))
+        .route("/slots/for-game/{game_id}", get(list_slots_for_game))
+        .route("/slots/unfilled", get(list_unfilled_slots))
+        .route("/slots/add", post(add_referee_slot))
+        .route(
+            "/slots/set-min-level/{slot_id}",
+            put(set_referee_slot_min_level),
+        )
+        .route("/slots/remove/{slot_id}", delete(remove_referee_slot))
+        .route("/requests/create", post(request_referee))
+        .route("/requests/respond", post(respond_to_referee_request))
+        .route(
+            "/requests/cancel/{request_id}",
+            delete(cancel_referee_request),
+        )
+        .route("/list-own", get(list_own_referee_assignments))
+        .route("/referees", get(list_referees))
+        .route("/referees/set-level/{user_id}", put(set_referee_level))
+        .route("/auto-assign/propose", post(propose_referee_assignments))
+        .route("/auto-assign/commit", post(commit_referee_assignments))
+        .with_state(state.clone())
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_team_referee_defaults,
+    set_team_referee_defaults,
+    list_slots_for_game,
+    list_unfilled_slots,
+    add_referee_slot,
+    set_referee_slot_min_level,
+    remove_referee_slot,
+    request_referee,
+    respond_to_referee_request,
+    cancel_referee_request,
+    list_own_referee_assignments,
+    list_referees,
+    set_referee_level,
+    super::referee_auto_assignment::propose_referee_assignments,
+    super::referee_auto_assignment::commit_referee_assignments,
+))]
+pub struct RefereeAssignmentApi;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RefereeSlotCount {
+    pub position: RefereePosition,
+    pub count: i32,
+    /// level referees need for these slots
+    #[serde(default)]
+    pub min_level: i32,
+}
+
+/// a slot with its game and the referee asked for it, if any
+#[derive(Serialize, ToSchema)]
+pub struct RefereeSlotItem {
+    id: String,
+    position: RefereePosition,
+    min_level: i32,
+    game_id: String,
+    opponent: String,
+    team_name: String,
+    start_time: DateTime<Utc>,
+    request_id: Option<String>,
+    referee_user_id: Option<String>,
+    referee_username: Option<String>,
+    status: Option<RefereeRequestStatus>,
+    #[serde(skip)]
+    sort_key: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum UnfilledSlotSort {
+    #[default]
+    StartTime,
+}
+
+impl SortKey for UnfilledSlotSort {}
+
+#[derive(Serialize, ToSchema)]
+pub struct OwnRefereeAssignment {
+    request_id: String,
+    slot_id: String,
+    position: RefereePosition,
+    status: RefereeRequestStatus,
+    game_id: String,
+    opponent: String,
+    team_name: String,
+    location: String,
+    start_time: DateTime<Utc>,
+    stop_time: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    sort_key: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum OwnAssignmentSort {
+    #[default]
+    StartTime,
+}
+
+impl SortKey for OwnAssignmentSort {}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AddRefereeSlot {
+    pub game_id: String,
+    pub position: RefereePosition,
+    #[serde(default)]
+    pub min_level: i32,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetLevel {
+    pub level: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RefereeListItem {
+    id: String,
+    username: String,
+    level: i32,
+    #[serde(skip)]
+    sort_key: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum RefereeSort {
+    #[default]
+    Level,
+    Username,
+}
+
+impl SortKey for RefereeSort {
+    /// highest level first
+    const DEFAULT_DESCENDING: bool = true;
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RequestReferee {
+    pub slot_id: String,
+    pub referee_user_id: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RespondToRefereeRequest {
+    pub request_id: String,
+    pub response: RefereeResponse,
+}
+
+impl Validate for RefereeSlotCount {
+    fn validate(&self, v: &mut Validator) {
+        v.range("count", self.count, 0, MAX_SLOTS_PER_POSITION);
+        v.check(self.min_level >= 0, "min_level", "must not be negative");
+    }
+}
+
+impl Validate for AddRefereeSlot {
+    fn validate(&self, v: &mut Validator) {
+        v.id("game_id", &self.game_id);
+        v.check(self.min_level >= 0, "min_level", "must not be negative");
+    }
+}
+
+impl Validate for SetLevel {
+    fn validate(&self, v: &mut Validator) {
+        v.check(self.level >= 0, "level", "must not be negative");
+    }
+}
+
+impl Validate for RequestReferee {
+    fn validate(&self, v: &mut Validator) {
+        v.id("slot_id", &self.slot_id);
+        v.id("referee_user_id", &self.referee_user_id);
+    }
+}
+
+impl Validate for RespondToRefereeRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.id("request_id", &self.request_id);
+    }
+}
+
+/// Creates a new game's slots from its team's defaults.
+pub async fn create_default_referee_slots(
+    conn: &mut PgConnection,
+    game_id: &str,
+    team_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO referee_slots (game_id, position, min_level)
+        SELECT $1, d.position, d.min_level
+        FROM team_referee_defaults d CROSS JOIN LATERAL generate_series(1, d.count)
+        WHERE d.team_id = $2
+        "#,
+        game_id,
+        team_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// The referee's games overlapping the given one - including itself, one can't hold
+/// two slots of a game. `ignored_request_id` is the request being checked.
+async fn find_conflict(
+    conn: &mut PgConnection,
+    referee_user_id: &str,
+    game_id: &str,
+    ignored_request_id: Option<&str>,
+) -> Result<Option<ApiError>, ApiError> {
+    let conflict = sqlx::query!(
+        r#"
+        SELECT g2.opponent, e2.start_time
+        FROM games g JOIN events e ON e.id = g.event_id,
+        referee_requests r
+        JOIN referee_slots s ON s.id = r.slot_id
+        JOIN games g2 ON g2.id = s.game_id
+        JOIN events e2 ON e2.id = g2.event_id
+        WHERE g.id = $2
+        AND r.referee_user_id = $1
+        AND r.status <> 'declined'
+        AND ($3::text IS NULL OR r.id <> $3)
+        AND tstzrange(e.start_time, COALESCE(e.stop_time, e.start_time + make_interval(hours => $4)))
+            && tstzrange(e2.start_time, COALESCE(e2.stop_time, e2.start_time + make_interval(hours => $4)))
+        ORDER BY e2.start_time
+        LIMIT 1
+        "#,
+        referee_user_id,
+        game_id,
+        ignored_request_id,
+        DEFAULT_GAME_HOURS
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(conflict.map(|conflict| {
+        ApiError::Conflict(format!(
+            "Conflicts with the game vs {} on {}",
+            conflict.opponent,
+            format_start_time(&conflict.start_time)
+        ))
+    }))
+}
+
+async fn check_team_in_club(
+    conn: &mut PgConnection,
+    team_id: &str,
+    club_id: &str,
+) -> Result<(), ApiError> {
+    sqlx::query!(
+        "SELECT 1 as ok FROM teams WHERE id = $1 AND club_id = $2",
+        team_id,
+        club_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Team not found".into()))?;
+
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/defaults/{team_id}",
+    tag = "referee-assignments",
+    params(("team_id" = String, Path)),
+    responses((status = 200, body = Vec<RefereeSlotCount>)),
+)]
+pub async fn get_team_referee_defaults(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<GameList>,
+    Path(team_id): Path<String>,
+) -> Result<(StatusCode, Json<Vec<RefereeSlotCount>>), ApiError> {
+    let mut conn = state.pg_pool.acquire().await?;
+    check_team_in_club(&mut conn, &team_id, &auth_ctx.club_id).await?;
+
+    let defaults = sqlx::query_as!(
+        RefereeSlotCount,
+        r#"
+        SELECT position AS "position: RefereePosition", count, min_level
+        FROM team_referee_defaults WHERE team_id = $1
+        ORDER BY position
+        "#,
+        team_id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok((StatusCode::OK, Json(defaults)))
+}
+
+/// Replaces the team's defaults, a count of 0 drops the position. Existing games keep their slots.
+#[utoipa::path(
+    put,
+    path = "/defaults/{team_id}",
+    tag = "referee-assignments",
+    request_body = Vec<RefereeSlotCount>,
+    params(("team_id" = String, Path)),
+    responses((status = 204, description = "defaults saved")),
+)]
+pub async fn set_team_referee_defaults(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<RefereeAssignmentManage>,
+    Path(team_id): Path<String>,
+    ValidatedJson(payload): ValidatedJson<Vec<RefereeSlotCount>>,
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+    check_team_in_club(&mut tx, &team_id, &auth_ctx.club_id).await?;
+
+    let before = sqlx::query_as!(
+        RefereeSlotCount,
+        r#"
+        SELECT position AS "position: RefereePosition", count, min_level
+        FROM team_referee_defaults WHERE team_id = $1
+        ORDER BY position
+        "#,
+        team_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "DELETE FROM team_referee_defaults WHERE team_id = $1",
+        team_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let after: Vec<&RefereeSlotCount> = payload.iter().filter(|slot| slot.count > 0).collect();
+    for slot in &after {
+        sqlx::query!(
+            r#"
+            INSERT INTO team_referee_defaults (team_id, position, count, min_level)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (team_id, position) DO UPDATE
+            SET count = EXCLUDED.count, min_level = EXCLUDED.min_level, updated_at = now()
+            "#,
+            team_id,
+            slot.position as RefereePosition,
+            slot.count,
+            slot.min_level
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            before: Some(json!(before)),
+            after: Some(json!(after)),
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::RefereeDefaultsChanged,
+                "team",
+                &team_id,
+            )
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/slots/for-game/{game_id}",
+    tag = "referee-assignments",
+    params(("game_id" = String, Path)),
+    responses((status = 200, body = Vec<RefereeSlotItem>)),
+)]
+pub async fn list_slots_for_game(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<GameList>,
+    Path(game_id): Path<String>,
+) -> Result<(StatusCode, Json<Vec<RefereeSlotItem>>), ApiError> {
+    let slots = sqlx::query_as!(
+        RefereeSlotItem,
+        r#"
+        SELECT s.id, s.position AS "position: RefereePosition", s.min_level,
+        g.id AS game_id, g.opponent,
+        t.name AS team_name, e.start_time, r.id AS "request_id?",
+        r.referee_user_id AS "referee_user_id?", u.username AS "referee_username?",
+        r.status AS "status?: RefereeRequestStatus", s.position::text AS "sort_key!"
+        FROM referee_slots s
+        JOIN games g ON g.id = s.game_id
+        JOIN teams t ON t.id = g.team_id
+        JOIN events e ON e.id = g.event_id
+        LEFT JOIN referee_requests r ON r.slot_id = s.id AND r.status <> 'declined'
+        LEFT JOIN users u ON u.id = r.referee_user_id
+        WHERE g.id = $1 AND t.club_id = $2
+        ORDER BY s.position, s.created_at
+        "#,
+        game_id,
+        auth_ctx.club_id
+    )
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok((StatusCode::OK, Json(slots)))
+}
+
+/// Slots of upcoming games across the club without an accepted referee, soonest first.
+/// Slots with a pending request come with the referee asked.
+#[utoipa::path(
+    get,
+    path = "/slots/unfilled",
+    tag = "referee-assignments",
+    params(ListParams),
+    responses((status = 200, body = Paginated<RefereeSlotItem>)),
+)]
+pub async fn list_unfilled_slots(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<RefereeAssignmentManage>,
+    ListQuery { page, .. }: ListQuery<UnfilledSlotSort>,
+) -> Result<(StatusCode, Json<Paginated<RefereeSlotItem>>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let slots = sqlx::query_as!(
+        RefereeSlotItem,
+        r#"
+        SELECT id AS "id!", position AS "position!: RefereePosition", min_level AS "min_level!",
+            game_id AS "game_id!", opponent AS "opponent!", team_name AS "team_name!",
+            start_time AS "start_time!", request_id AS "request_id?",
+            referee_user_id AS "referee_user_id?", referee_username AS "referee_username?",
+            status AS "status?: RefereeRequestStatus", sort_key AS "sort_key!"
+        FROM (
+            SELECT s.id, s.position, s.min_level, g.id AS game_id, g.opponent,
+            t.name AS team_name, e.start_time, r.id AS request_id,
+            r.referee_user_id, u.username AS referee_username, r.status,
+            to_char(e.start_time AT TIME ZONE 'UTC', $2) AS sort_key
+            FROM referee_slots s
+            JOIN games g ON g.id = s.game_id
+            JOIN teams t ON t.id = g.team_id
+            JOIN events e ON e.id = g.event_id
+            LEFT JOIN referee_requests r ON r.slot_id = s.id AND r.status = 'pending'
+            LEFT JOIN users u ON u.id = r.referee_user_id
+            WHERE t.club_id = $1
+            AND e.start_time >= now()
+            AND NOT EXISTS (
+                SELECT 1 FROM referee_requests a WHERE a.slot_id = s.id AND a.status = 'accepted'
+            )
+        ) r
+        WHERE ($4::text IS NULL OR CASE WHEN $3
+            THEN (r.sort_key, r.id) < ($4, $5) ELSE (r.sort_key, r.id) > ($4, $5) END)
+        ORDER BY CASE WHEN $3 THEN r.sort_key END DESC, r.sort_key,
+            CASE WHEN $3 THEN r.id END DESC, r.id
+        LIMIT $6
+        "#,
+        auth_ctx.club_id,
+        TIME_KEY_FORMAT,
+        page.descending,
+        page.after_key(),
+        page.after_id(),
+        page.fetch_limit()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT count(*) AS "count!" FROM referee_slots s
+        JOIN games g ON g.id = s.game_id
+        JOIN teams t ON t.id = g.team_id
+        JOIN events e ON e.id = g.event_id
+        WHERE t.club_id = $1
+        AND e.start_time >= now()
+        AND NOT EXISTS (
+            SELECT 1 FROM referee_requests a WHERE a.slot_id = s.id AND a.status = 'accepted'
+        )
+        "#,
+        auth_ctx.club_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let slots = page.finish(slots, total, |slot| (&slot.sort_key, &slot.id));
+    Ok((StatusCode::OK, Json(slots)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/slots/add",
+    tag = "referee-assignments",
+    request_body = AddRefereeSlot,
+    responses((status = 201, description = "the new slot's id", body = String)),
+)]
+pub async fn add_referee_slot(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<RefereeAssignmentManage>,
+    ValidatedJson(payload): ValidatedJson<AddRefereeSlot>,
+) -> Result<(StatusCode, Json<String>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let slot_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO referee_slots (game_id, position, min_level)
+        SELECT g.id, $3, $4 FROM games g JOIN teams t ON t.id = g.team_id
+        WHERE g.id = $1 AND t.club_id = $2
+        RETURNING id
+        "#,
+        payload.game_id,
+        auth_ctx.club_id,
+        payload.position as RefereePosition,
+        payload.min_level.max(0)
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Game not found".into()))?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            after: Some(json!({
+                "game_id": payload.game_id,
+                "position": payload.position,
+                "min_level": payload.min_level.max(0),
+            })),
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::RefereeSlotAdded,
+                "referee_slot",
+                &slot_id,
+            )
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(slot_id)))
+}
+
+/// a referee already asked keeps the slot, even if below the new level
+#[utoipa::path(
+    put,
+    path = "/slots/set-min-level/{slot_id}",
+    tag = "referee-assignments",
+    request_body = SetLevel,
+    params(("slot_id" = String, Path)),
+    responses((status = 204, description = "level saved")),
+)]
+pub async fn set_referee_slot_min_level(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<RefereeAssignmentManage>,
+    Path(slot_id): Path<String>,
+    ValidatedJson(payload): ValidatedJson<SetLevel>,
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let before = sqlx::query_scalar!(
+        r#"
+        SELECT s.min_level FROM referee_slots s
+        JOIN games g ON g.id = s.game_id JOIN teams t ON t.id = g.team_id
+        WHERE s.id = $1 AND t.club_id = $2
+        FOR UPDATE OF s
+        "#,
+        slot_id,
+        auth_ctx.club_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Referee slot not found".into()))?;
+
+    sqlx::query!(
+        "UPDATE referee_slots SET min_level = $1 WHERE id = $2",
+        payload.level,
+        slot_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            before: Some(json!({ "min_level": before })),
+            after: Some(json!({ "min_level": payload.level })),
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::RefereeSlotUpdated,
+                "referee_slot",
+                &slot_id,
+            )
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// drops the slot's requests too, the referee asked is told
+#[utoipa::path(
+    delete,
+    path = "/slots/remove/{slot_id}",
+    tag = "referee-assignments",
+    params(("slot_id" = String, Path)),
+    responses((status = 204, description = "slot removed")),
+)]
+pub async fn remove_referee_slot(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<RefereeAssignmentManage>,
+    Path(slot_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let slot = sqlx::query!(
+        r#"
+        SELECT s.game_id, s.position AS "position: RefereePosition", g.opponent, e.start_time,
+        r.referee_user_id AS "referee_user_id?"
+        FROM referee_slots s
+        JOIN games g ON g.id = s.game_id
+        JOIN teams t ON t.id = g.team_id
+        JOIN events e ON e.id = g.event_id
+        LEFT JOIN referee_requests r ON r.slot_id = s.id AND r.status <> 'declined'
+        WHERE s.id = $1 AND t.club_id = $2
+        "#,
+        slot_id,
+        auth_ctx.club_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Referee slot not found".into()))?;
+
+    sqlx::query!("DELETE FROM referee_slots WHERE id = $1", slot_id)
+        .execute(&mut *tx)
+        .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            before: Some(json!({
+                "game_id": slot.game_id,
+                "position": slot.position,
+                "referee_user_id": slot.referee_user_id,
+            })),
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::RefereeSlotRemoved,
+                "referee_slot",
+                &slot_id,
+            )
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    if let Some(referee_user_id) = slot.referee_user_id {
+        notify_users(
+            &state,
+            vec![referee_user_id],
+            PushMessage {
+                title: "Referee assignment cancelled".to_string(),
+                body: format!(
+                    "vs {} – {}",
+                    slot.opponent,
+                    format_start_time(&slot.start_time)
+                ),
+                url: Some("/referee-assignments".to_string()),
+                tag: Some(format!("referee-slot-{}", slot_id)),
+            },
+        );
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// a request that was written, for telling the referee after the commit
+pub struct CreatedRefereeRequest {
+    pub id: String,
+    pub referee_user_id: String,
+    pub message: PushMessage,
+}
+
+/// Asks a referee for a free slot after checking role, level and conflicts.
+/// Runs in the caller's transaction, earlier requests of it count as conflicts.
+pub async fn create_referee_request(
+    conn: &mut PgConnection,
+    auth_ctx: &AuthContext,
+    slot_id: &str,
+    referee_user_id: &str,
+) -> Result<CreatedRefereeRequest, ApiError> {
+    let slot = sqlx::query!(
+        r#"
+        SELECT s.game_id, s.position AS "position: RefereePosition", s.min_level,
+        g.opponent, e.start_time,
+        EXISTS (
+            SELECT 1 FROM referee_requests r WHERE r.slot_id = s.id AND r.status <> 'declined'
+        ) AS "taken!"
+        FROM referee_slots s
+        JOIN games g ON g.id = s.game_id
+        JOIN teams t ON t.id = g.team_id
+        JOIN events e ON e.id = g.event_id
+        WHERE s.id = $1 AND t.club_id = $2
+        "#,
+        slot_id,
+        auth_ctx.club_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Referee slot not found".into()))?;
+
+    if slot.taken {
+        return Err(ApiError::Conflict(
+            "A referee was already asked for this slot".into(),
+        ));
+    }
+
+    let referee_level = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(rp.level, 0) AS "level!"
+        FROM users u
+        JOIN role_assignments ra ON ra.user_id = u.id AND ra.role = $3
+        LEFT JOIN referee_profiles rp ON rp.user_id = u.id
+        WHERE u.id = $1 AND u.club_id = $2
+        "#,
+        referee_user_id,
+        auth_ctx.club_id,
+        Role::Referee as Role
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| ApiError::BadRequest("User is not a referee of your club".into()))?;
+
+    if referee_level < slot.min_level {
+        return Err(ApiError::BadRequest(format!(
+            "The slot needs a referee of level {} or above",
+            slot.min_level
+        )));
+    }
+
+    let absent =
+        find_absent_user_ids(&mut *conn, &[referee_user_id.to_string()], slot.start_time).await?;
+    if !absent.is_empty() {
+        return Err(ApiError::Conflict(
+            "The referee is absent on that day".into(),
+        ));
+    }
+
+    if let Some(conflict) = find_conflict(&mut *conn, referee_user_id, &slot.game_id, None).await? {
+        return Err(conflict);
+    }
+
+    let request_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO referee_requests (slot_id, referee_user_id, requested_by_user_id)
+        VALUES ($1, $2, $3)
+        RETURNING id
+        "#,
+        slot_id,
+        referee_user_id,
+        auth_ctx.user_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    record_audit_event(
+        &mut *conn,
+        AuditEvent {
+            after: Some(json!({
+                "slot_id": slot_id,
+                "game_id": slot.game_id,
+                "position": slot.position,
+                "referee_user_id": referee_user_id,
+            })),
+            ..AuditEvent::by(
+                auth_ctx,
+                AuditAction::RefereeRequested,
+                "referee_request",
+                &request_id,
+            )
+        },
+    )
+    .await?;
+
+    Ok(CreatedRefereeRequest {
+        id: request_id,
+        referee_user_id: referee_user_id.to_string(),
+        message: PushMessage {
+            title: "Referee request".to_string(),
+            body: format!(
+                "{} vs {} – {}",
+                slot.position,
+                slot.opponent,
+                format_start_time(&slot.start_time)
+            ),
+            url: Some("/referee-assignments".to_string()),
+            tag: Some(format!("referee-slot-{}", slot_id)),
+        },
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/requests/create",
+    tag = "referee-assignments",
+    request_body = RequestReferee,
+    responses((status = 201, description = "the new request's id", body = String)),
+)]
+pub async fn request_referee(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<RefereeAssignmentManage>,
+    ValidatedJson(payload): ValidatedJson<RequestReferee>,
+) -> Result<(StatusCode, Json<String>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let request = create_referee_request(
+        &mut tx,
+        &auth_ctx,
+        &payload.slot_id,
+        &payload.referee_user_id,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    notify_users(&state, vec![request.referee_user_id], request.message);
+
+    Ok((StatusCode::CREATED, Json(request.id)))
+}
+
+/// for the referee asked, accepting checks for conflicts once more
+#[utoipa::path(
+    post,
+    path = "/requests/respond",
+    tag = "referee-assignments",
+    request_body = RespondToRefereeRequest,
+    responses((status = 200, description = "answer saved")),
+)]
+pub async fn respond_to_referee_request(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    ValidatedJson(payload): ValidatedJson<RespondToRefereeRequest>,
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let game_id = sqlx::query_scalar!(
+        r#"
+        SELECT s.game_id FROM referee_requests r JOIN referee_slots s ON s.id = r.slot_id
+        WHERE r.id = $1 AND r.referee_user_id = $2 AND r.status = 'pending'
+        "#,
+        payload.request_id,
+        auth_ctx.user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Referee request not found".into()))?;
+
+    if payload.response == RefereeResponse::Accepted {
+        if let Some(conflict) = find_conflict(
+            &mut tx,
+            &auth_ctx.user_id,
+            &game_id,
+            Some(&payload.request_id),
+        )
+        .await?
+        {
+            return Err(conflict);
+        }
+    }
+
+    sqlx::query!(
+        r#"UPDATE referee_requests SET status = $1, responded_at = now() WHERE id = $2"#,
+        payload.response as RefereeResponse,
+        payload.request_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::OK)
+}
+
+/// frees the slot, whether the request was accepted yet or not
+#[utoipa::path(
+    delete,
+    path = "/requests/cancel/{request_id}",
+    tag = "referee-assignments",
+    params(("request_id" = String, Path)),
+    responses((status = 204, description = "request cancelled")),
+)]
+pub async fn cancel_referee_request(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<RefereeAssignmentManage>,
+    Path(request_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let request = sqlx::query!(
+        r#"
+        DELETE FROM referee_requests r
+        USING referee_slots s, games g, teams t
+        WHERE r.id = $1
+        AND s.id = r.slot_id AND g.id = s.game_id AND t.id = g.team_id
+        AND t.club_id = $2
+        RETURNING r.slot_id, r.referee_user_id, r.status AS "status: RefereeRequestStatus",
+        g.opponent
+        "#,
+        request_id,
+        auth_ctx.club_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Referee request not found".into()))?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            before: Some(json!({
+                "slot_id": request.slot_id,
+                "referee_user_id": request.referee_user_id,
+                "status": request.status,
+            })),
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::RefereeRequestCancelled,
+                "referee_request",
+                &request_id,
+            )
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    if request.status != RefereeRequestStatus::Declined {
+        notify_users(
+            &state,
+            vec![request.referee_user_id],
+            PushMessage {
+                title: "Referee assignment cancelled".to_string(),
+                body: format!("vs {}", request.opponent),
+                url: Some("/referee-assignments".to_string()),
+                tag: Some(format!("referee-slot-{}", request.slot_id)),
+            },
+        );
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// the club's referees with their qualification level, highest first
+#[utoipa::path(
+    get,
+    path = "/referees",
+    tag = "referee-assignments",
+    params(ListParams),
+    responses((status = 200, body = Paginated<RefereeListItem>)),
+)]
+pub async fn list_referees(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<RefereeAssignmentManage>,
+    ListQuery { page, .. }: ListQuery<RefereeSort>,
+) -> Result<(StatusCode, Json<Paginated<RefereeListItem>>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    // levels aren't negative, zero-padded they sort like numbers
+    let referees = sqlx::query_as!(
+        RefereeListItem,
+        r#"
+        SELECT id AS "id!", username AS "username!", level AS "level!", sort_key AS "sort_key!"
+        FROM (
+            SELECT u.id, u.username, COALESCE(rp.level, 0) AS level,
+            CASE $3
+                WHEN 'username' THEN u.username
+                ELSE lpad(COALESCE(rp.level, 0)::text, 10, '0')
+            END AS sort_key
+            FROM users u
+            JOIN role_assignments ra ON ra.user_id = u.id AND ra.role = $2
+            LEFT JOIN referee_profiles rp ON rp.user_id = u.id
+            WHERE u.club_id = $1
+        ) r
+        WHERE ($5::text IS NULL OR CASE WHEN $4
+            THEN (r.sort_key, r.id) < ($5, $6) ELSE (r.sort_key, r.id) > ($5, $6) END)
+        ORDER BY CASE WHEN $4 THEN r.sort_key END DESC, r.sort_key,
+            CASE WHEN $4 THEN r.id END DESC, r.id
+        LIMIT $7
+        "#,
+        auth_ctx.club_id,
+        Role::Referee as Role,
+        page.sort(),
+        page.descending,
+        page.after_key(),
+        page.after_id(),
+        page.fetch_limit()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT count(*) AS "count!" FROM users u
+        JOIN role_assignments ra ON ra.user_id = u.id AND ra.role = $2
+        WHERE u.club_id = $1
+        "#,
+        auth_ctx.club_id,
+        Role::Referee as Role
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let referees = page.finish(referees, total, |referee| (&referee.sort_key, &referee.id));
+    Ok((StatusCode::OK, Json(referees)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/referees/set-level/{user_id}",
+    tag = "referee-assignments",
+    request_body = SetLevel,
+    params(("user_id" = String, Path)),
+    responses((status = 204, description = "level saved")),
+)]
+pub async fn set_referee_level(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<RefereeAssignmentManage>,
+    Path(user_id): Path<String>,
+    ValidatedJson(payload): ValidatedJson<SetLevel>,
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let before = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(rp.level, 0) AS "level!"
+        FROM users u
+        JOIN role_assignments ra ON ra.user_id = u.id AND ra.role = $3
+        LEFT JOIN referee_profiles rp ON rp.user_id = u.id
+        WHERE u.id = $1 AND u.club_id = $2
+        "#,
+        user_id,
+        auth_ctx.club_id,
+        Role::Referee as Role
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Referee not found".into()))?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO referee_profiles (user_id, level) VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE SET level = EXCLUDED.level, updated_at = now()
+        "#,
+        user_id,
+        payload.level
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            before: Some(json!({ "referee_level": before })),
+            after: Some(json!({ "referee_level": payload.level })),
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::RefereeLevelChanged,
+                "user",
+                &user_id,
+            )
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// pending and accepted assignments of upcoming games, soonest first
+#[utoipa::path(
+    get,
+    path = "/list-own",
+    tag = "referee-assignments",
+    params(ListParams),
+    responses((status = 200, body = Paginated<OwnRefereeAssignment>)),
+)]
+pub async fn list_own_referee_assignments(
+    State(state): State<AppState>,
+    auth_ctx: Extension<AuthContext>,
+    ListQuery { page, .. }: ListQuery<OwnAssignmentSort>,
+) -> Result<(StatusCode, Json<Paginated<OwnRefereeAssignment>>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let assignments = sqlx::query_as!(
+        OwnRefereeAssignment,
+        r#"
+        SELECT request_id AS "request_id!", slot_id AS "slot_id!",
+            position AS "position!: RefereePosition", status AS "status!: RefereeRequestStatus",
+            game_id AS "game_id!", opponent AS "opponent!", team_name AS "team_name!",
+            location AS "location!", start_time AS "start_time!", stop_time,
+            sort_key AS "sort_key!"
+        FROM (
+            SELECT r.id AS request_id, s.id AS slot_id, s.position, r.status, g.id AS game_id,
+            g.opponent, t.name AS team_name, g.location, e.start_time, e.stop_time,
+            to_char(e.start_time AT TIME ZONE 'UTC', $3) AS sort_key
+            FROM referee_requests r
+            JOIN referee_slots s ON s.id = r.slot_id
+            JOIN games g ON g.id = s.game_id
+            JOIN teams t ON t.id = g.team_id
+            JOIN events e ON e.id = g.event_id
+            WHERE r.referee_user_id = $1
+            AND r.status <> 'declined'
+            AND COALESCE(e.stop_time, e.start_time + make_interval(hours => $2)) >= now()
+        ) r
+        WHERE ($5::text IS NULL OR CASE WHEN $4
+            THEN (r.sort_key, r.request_id) < ($5, $6)
+            ELSE (r.sort_key, r.request_id) > ($5, $6) END)
+        ORDER BY CASE WHEN $4 THEN r.sort_key END DESC, r.sort_key,
+            CASE WHEN $4 THEN r.request_id END DESC, r.request_id
+        LIMIT $7
+        "#,
+        auth_ctx.user_id,
+        DEFAULT_GAME_HOURS,
+        TIME_KEY_FORMAT,
+        page.descending,
+        page.after_key(),
+        page.after_id(),
+        page.fetch_limit()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT count(*) AS "count!" FROM referee_requests r
+        JOIN referee_slots s ON s.id = r.slot_id
+        JOIN games g ON g.id = s.game_id
+        JOIN events e ON e.id = g.event_id
+        WHERE r.referee_user_id = $1
+        AND r.status <> 'declined'
+        AND COALESCE(e.stop_time, e.start_time + make_interval(hours => $2)) >= now()
+        "#,
+        auth_ctx.user_id,
+        DEFAULT_GAME_HOURS
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let assignments = page.finish(assignments, total, |assignment| {
+        (&assignment.sort_key, &assignment.request_id)
+    });
+    Ok((StatusCode::OK, Json(assignments)))
+}
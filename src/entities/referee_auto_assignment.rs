@@ -0,0 +1,719 @@
+//! Proposes referees for the open slots of a date range, for referee managers to tweak and
+//! then commit as regular referee requests.
+//!
+//! Greedy: the slots with the fewest eligible referees go first, each to the eligible
+//! referee with the fewest assignments in the range so far. Eligible means qualified for the
+//! slot, not absent, below the daily maximum and free around the game - with time to travel
+//! when the venue differs from their other games.
+
+use std::collections::HashMap;
+
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{
+        permissions::{actions::RefereeAssignmentManage, Authorized},
+        roles::Role,
+    },
+    entities::referee_assignment::{create_referee_request, RefereePosition, DEFAULT_GAME_HOURS},
+    notifications::notify_users,
+    utils::{
+        api::AppState,
+        error::ApiError,
+        validation::{Validate, ValidatedJson, Validator},
+    },
+};
+
+const MAX_RANGE_DAYS: i64 = 31;
+const DEFAULT_MAX_GAMES_PER_DAY: i64 = 2;
+const DEFAULT_TRAVEL_GAP_MINUTES: i64 = 60;
+const MAX_COMMITTED_ASSIGNMENTS: usize = 200;
+
+#[derive(Deserialize, ToSchema)]
+pub struct ProposeAssignments {
+    pub from: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub max_games_per_day: Option<i64>,
+    /// between games at different venues
+    pub travel_gap_minutes: Option<i64>,
+    /// only consider these referees, all of the club otherwise
+    pub referee_user_ids: Option<Vec<String>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProposedAssignment {
+    slot_id: String,
+    game_id: String,
+    position: RefereePosition,
+    min_level: i32,
+    opponent: String,
+    location: String,
+    start_time: DateTime<Utc>,
+    referee_user_id: Option<String>,
+    referee_username: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RefereeLoad {
+    referee_user_id: String,
+    username: String,
+    level: i32,
+    /// games in the range, the ones already assigned included
+    assignments: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AssignmentProposal {
+    assignments: Vec<ProposedAssignment>,
+    /// slots no referee was eligible for
+    unfilled: Vec<ProposedAssignment>,
+    referee_load: Vec<RefereeLoad>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CommitAssignment {
+    pub slot_id: String,
+    pub referee_user_id: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CommitAssignments {
+    pub assignments: Vec<CommitAssignment>,
+}
+
+impl Validate for ProposeAssignments {
+    fn validate(&self, v: &mut Validator) {
+        v.check(self.until > self.from, "until", "must be after from");
+        v.check(
+            self.until - self.from <= Duration::days(MAX_RANGE_DAYS),
+            "until",
+            format!("the range spans at most {} days", MAX_RANGE_DAYS),
+        );
+        if let Some(max_games_per_day) = self.max_games_per_day {
+            v.range("max_games_per_day", max_games_per_day, 1, 10);
+        }
+        if let Some(travel_gap_minutes) = self.travel_gap_minutes {
+            v.range("travel_gap_minutes", travel_gap_minutes, 0, 24 * 60);
+        }
+        for (i, id) in self.referee_user_ids.iter().flatten().enumerate() {
+            v.id(&format!("referee_user_ids[{}]", i), id);
+        }
+    }
+}
+
+impl Validate for CommitAssignment {
+    fn validate(&self, v: &mut Validator) {
+        v.id("slot_id", &self.slot_id);
+        v.id("referee_user_id", &self.referee_user_id);
+    }
+}
+
+impl Validate for CommitAssignments {
+    fn validate(&self, v: &mut Validator) {
+        v.range(
+            "assignments",
+            self.assignments.len(),
+            1,
+            MAX_COMMITTED_ASSIGNMENTS,
+        );
+        let mut assignments = Validator::default();
+        self.assignments.validate(&mut assignments);
+        v.nested("assignments", assignments);
+    }
+}
+
+struct OpenSlot {
+    id: String,
+    game_id: String,
+    position: RefereePosition,
+    min_level: i32,
+    opponent: String,
+    location: String,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+}
+
+struct Referee {
+    id: String,
+    username: String,
+    level: i32,
+}
+
+/// days a referee can't be asked for, both ends included
+struct AbsentDays {
+    starts_on: NaiveDate,
+    ends_on: NaiveDate,
+}
+
+/// a game a referee is (or would be) busy with
+#[derive(Clone)]
+struct Commitment {
+    game_id: String,
+    location: String,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+}
+
+struct SolverOptions {
+    max_games_per_day: usize,
+    travel_gap: Duration,
+}
+
+fn same_venue(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+fn is_eligible(
+    slot: &OpenSlot,
+    referee: &Referee,
+    absences: &[AbsentDays],
+    commitments: &[Commitment],
+    options: &SolverOptions,
+) -> bool {
+    if referee.level < slot.min_level {
+        return false;
+    }
+
+    let game_day = slot.start_time.date_naive();
+    if absences
+        .iter()
+        .any(|absence| (absence.starts_on..=absence.ends_on).contains(&game_day))
+    {
+        return false;
+    }
+
+    let games_that_day = commitments
+        .iter()
+        .filter(|c| c.start_time.date_naive() == slot.start_time.date_naive())
+        .count();
+    if games_that_day >= options.max_games_per_day {
+        return false;
+    }
+
+    commitments.iter().all(|c| {
+        if c.game_id == slot.game_id {
+            return false;
+        }
+        let gap = if same_venue(&c.location, &slot.location) {
+            Duration::zero()
+        } else {
+            options.travel_gap
+        };
+        slot.start_time >= c.end_time + gap || c.start_time >= slot.end_time + gap
+    })
+}
+
+/// The referee index per slot, `None` where nobody is eligible.
+/// `in_range` counts each referee's existing assignments in the requested range.
+fn solve(
+    slots: &[OpenSlot],
+    referees: &[Referee],
+    absences: &[Vec<AbsentDays>],
+    commitments: &mut [Vec<Commitment>],
+    in_range: &mut [usize],
+    options: &SolverOptions,
+) -> Vec<Option<usize>> {
+    let eligible_count = |slot: &OpenSlot, commitments: &[Vec<Commitment>]| {
+        (0..referees.len())
+            .filter(|&r| is_eligible(slot, &referees[r], &absences[r], &commitments[r], options))
+            .count()
+    };
+
+    // most constrained first, so scarce referees aren't used up on easy slots
+    let mut order: Vec<usize> = (0..slots.len()).collect();
+    order.sort_by_key(|&i| {
+        (
+            eligible_count(&slots[i], commitments),
+            slots[i].start_time,
+            slots[i].position as u8,
+        )
+    });
+
+    let mut chosen = vec![None; slots.len()];
+    for i in order {
+        let slot = &slots[i];
+        let best = (0..referees.len())
+            .filter(|&r| is_eligible(slot, &referees[r], &absences[r], &commitments[r], options))
+            // fewest games first, then the least overqualified to keep the best ones free
+            .min_by_key(|&r| {
+                (
+                    in_range[r],
+                    referees[r].level - slot.min_level,
+                    &referees[r].username,
+                )
+            });
+
+        if let Some(r) = best {
+            commitments[r].push(Commitment {
+                game_id: slot.game_id.clone(),
+                location: slot.location.clone(),
+                start_time: slot.start_time,
+                end_time: slot.end_time,
+            });
+            in_range[r] += 1;
+            chosen[i] = Some(r);
+        }
+    }
+    chosen
+}
+
+/// Nothing is written, the proposal can be changed freely before committing it.
+#[utoipa::path(
+    post,
+    path = "/auto-assign/propose",
+    tag = "referee-assignments",
+    request_body = ProposeAssignments,
+    responses((status = 200, body = AssignmentProposal)),
+)]
+pub async fn propose_referee_assignments(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<RefereeAssignmentManage>,
+    ValidatedJson(payload): ValidatedJson<ProposeAssignments>,
+) -> Result<(StatusCode, Json<AssignmentProposal>), ApiError> {
+    let max_games_per_day = payload
+        .max_games_per_day
+        .unwrap_or(DEFAULT_MAX_GAMES_PER_DAY);
+    let travel_gap_minutes = payload
+        .travel_gap_minutes
+        .unwrap_or(DEFAULT_TRAVEL_GAP_MINUTES);
+    let options = SolverOptions {
+        max_games_per_day: max_games_per_day as usize,
+        travel_gap: Duration::minutes(travel_gap_minutes),
+    };
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    let slots = sqlx::query_as!(
+        OpenSlot,
+        r#"
+        SELECT s.id, s.game_id, s.position AS "position: RefereePosition", s.min_level,
+        g.opponent, g.location, e.start_time,
+        COALESCE(e.stop_time, e.start_time + make_interval(hours => $4)) AS "end_time!"
+        FROM referee_slots s
+        JOIN games g ON g.id = s.game_id
+        JOIN teams t ON t.id = g.team_id
+        JOIN events e ON e.id = g.event_id
+        WHERE t.club_id = $1 AND e.start_time >= $2 AND e.start_time < $3
+        AND NOT EXISTS (
+            SELECT 1 FROM referee_requests r WHERE r.slot_id = s.id AND r.status <> 'declined'
+        )
+        ORDER BY e.start_time, s.position, s.created_at
+        "#,
+        auth_ctx.club_id,
+        payload.from,
+        payload.until,
+        DEFAULT_GAME_HOURS
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let referees = sqlx::query_as!(
+        Referee,
+        r#"
+        SELECT u.id, u.username, COALESCE(rp.level, 0) AS "level!"
+        FROM users u
+        JOIN role_assignments ra ON ra.user_id = u.id AND ra.role = $2
+        LEFT JOIN referee_profiles rp ON rp.user_id = u.id
+        WHERE u.club_id = $1
+        AND ($3::text[] IS NULL OR u.id = ANY($3))
+        ORDER BY u.username
+        "#,
+        auth_ctx.club_id,
+        Role::Referee as Role,
+        payload.referee_user_ids.as_deref()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    // a day around the range, for the daily maximum and travel gaps at its edges
+    let existing = sqlx::query!(
+        r#"
+        SELECT r.referee_user_id, g.id AS game_id, g.location, e.start_time,
+        COALESCE(e.stop_time, e.start_time + make_interval(hours => $4)) AS "end_time!"
+        FROM referee_requests r
+        JOIN referee_slots s ON s.id = r.slot_id
+        JOIN games g ON g.id = s.game_id
+        JOIN events e ON e.id = g.event_id
+        WHERE r.referee_user_id = ANY($1)
+        AND r.status <> 'declined'
+        AND e.start_time >= $2::timestamptz - interval '1 day'
+        AND e.start_time < $3::timestamptz + interval '1 day'
+        "#,
+        &referees.iter().map(|r| r.id.clone()).collect::<Vec<_>>(),
+        payload.from,
+        payload.until,
+        DEFAULT_GAME_HOURS
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let absent_days = sqlx::query!(
+        r#"
+        SELECT user_id, starts_on, ends_on FROM absences
+        WHERE user_id = ANY($1)
+        AND ends_on >= ($2::timestamptz AT TIME ZONE 'UTC')::date
+        AND starts_on <= ($3::timestamptz AT TIME ZONE 'UTC')::date
+        "#,
+        &referees.iter().map(|r| r.id.clone()).collect::<Vec<_>>(),
+        payload.from,
+        payload.until
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let index: HashMap<&str, usize> = referees
+        .iter()
+        .enumerate()
+        .map(|(i, r)| (r.id.as_str(), i))
+        .collect();
+    let mut commitments = vec![Vec::new(); referees.len()];
+    let mut in_range = vec![0; referees.len()];
+    for row in existing {
+        let Some(&i) = index.get(row.referee_user_id.as_str()) else {
+            continue;
+        };
+        if row.start_time >= payload.from && row.start_time < payload.until {
+            in_range[i] += 1;
+        }
+        commitments[i].push(Commitment {
+            game_id: row.game_id,
+            location: row.location,
+            start_time: row.start_time,
+            end_time: row.end_time,
+        });
+    }
+
+    let mut absences: Vec<Vec<AbsentDays>> = referees.iter().map(|_| Vec::new()).collect();
+    for row in absent_days {
+        if let Some(&i) = index.get(row.user_id.as_str()) {
+            absences[i].push(AbsentDays {
+                starts_on: row.starts_on,
+                ends_on: row.ends_on,
+            });
+        }
+    }
+
+    let chosen = solve(
+        &slots,
+        &referees,
+        &absences,
+        &mut commitments,
+        &mut in_range,
+        &options,
+    );
+
+    let mut assignments = Vec::new();
+    let mut unfilled = Vec::new();
+    for (slot, referee) in slots.into_iter().zip(chosen) {
+        let referee = referee.map(|r| &referees[r]);
+        let item = ProposedAssignment {
+            slot_id: slot.id,
+            game_id: slot.game_id,
+            position: slot.position,
+            min_level: slot.min_level,
+            opponent: slot.opponent,
+            location: slot.location,
+            start_time: slot.start_time,
+            referee_user_id: referee.map(|r| r.id.clone()),
+            referee_username: referee.map(|r| r.username.clone()),
+        };
+        if referee.is_some() {
+            assignments.push(item);
+        } else {
+            unfilled.push(item);
+        }
+    }
+
+    let referee_load = referees
+        .iter()
+        .zip(in_range)
+        .map(|(referee, assignments)| RefereeLoad {
+            referee_user_id: referee.id.clone(),
+            username: referee.username.clone(),
+            level: referee.level,
+            assignments,
+        })
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(AssignmentProposal {
+            assignments,
+            unfilled,
+            referee_load,
+        }),
+    ))
+}
+
+/// All or nothing: creates a referee request per assignment, with the same checks as
+/// asking a referee by hand. Returns the ids of the requests.
+#[utoipa::path(
+    post,
+    path = "/auto-assign/commit",
+    tag = "referee-assignments",
+    request_body = CommitAssignments,
+    responses((status = 201, description = "ids of the new requests", body = Vec<String>)),
+)]
+pub async fn commit_referee_assignments(
+    State(state): State<AppState>,
+    auth_ctx: Authorized<RefereeAssignmentManage>,
+    ValidatedJson(payload): ValidatedJson<CommitAssignments>,
+) -> Result<(StatusCode, Json<Vec<String>>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let mut requests = Vec::with_capacity(payload.assignments.len());
+    for assignment in &payload.assignments {
+        requests.push(
+            create_referee_request(
+                &mut tx,
+                &auth_ctx,
+                &assignment.slot_id,
+                &assignment.referee_user_id,
+            )
+            .await?,
+        );
+    }
+
+    tx.commit().await?;
+
+    let ids = requests.iter().map(|request| request.id.clone()).collect();
+    for request in requests {
+        notify_users(&state, vec![request.referee_user_id], request.message);
+    }
+
+    Ok((StatusCode::CREATED, Json(ids)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn slot(game_id: &str, location: &str, start: &str, min_level: i32) -> OpenSlot {
+        OpenSlot {
+            id: format!("{}-slot", game_id),
+            game_id: game_id.to_string(),
+            position: RefereePosition::Main,
+            min_level,
+            opponent: "Opponent".to_string(),
+            location: location.to_string(),
+            start_time: at(start),
+            end_time: at(start) + Duration::hours(2),
+        }
+    }
+
+    fn busy(game_id: &str, location: &str, start: &str) -> Commitment {
+        Commitment {
+            game_id: game_id.to_string(),
+            location: location.to_string(),
+            start_time: at(start),
+            end_time: at(start) + Duration::hours(2),
+        }
+    }
+
+    fn referee(username: &str, level: i32) -> Referee {
+        Referee {
+            id: username.to_string(),
+            username: username.to_string(),
+            level,
+        }
+    }
+
+    fn options() -> SolverOptions {
+        SolverOptions {
+            max_games_per_day: 2,
+            travel_gap: Duration::minutes(60),
+        }
+    }
+
+    fn eligible(slot: &OpenSlot, absences: &[AbsentDays], commitments: &[Commitment]) -> bool {
+        is_eligible(slot, &referee("ref", 2), absences, commitments, &options())
+    }
+
+    #[test]
+    fn needs_the_level() {
+        let game = slot("g", "Hall A", "2026-05-02T10:00:00Z", 2);
+        assert!(is_eligible(&game, &referee("ref", 2), &[], &[], &options()));
+        assert!(is_eligible(&game, &referee("ref", 3), &[], &[], &options()));
+        assert!(!is_eligible(
+            &game,
+            &referee("ref", 1),
+            &[],
+            &[],
+            &options()
+        ));
+    }
+
+    #[test]
+    fn absences_include_both_ends_in_utc_days() {
+        let away = [AbsentDays {
+            starts_on: "2026-05-01".parse().unwrap(),
+            ends_on: "2026-05-02".parse().unwrap(),
+        }];
+        assert!(eligible(
+            &slot("g", "A", "2026-04-30T23:30:00Z", 1),
+            &away,
+            &[]
+        ));
+        assert!(!eligible(
+            &slot("g", "A", "2026-05-01T00:00:00Z", 1),
+            &away,
+            &[]
+        ));
+        assert!(!eligible(
+            &slot("g", "A", "2026-05-02T23:30:00Z", 1),
+            &away,
+            &[]
+        ));
+        assert!(eligible(
+            &slot("g", "A", "2026-05-03T00:00:00Z", 1),
+            &away,
+            &[]
+        ));
+    }
+
+    #[test]
+    fn daily_maximum_counts_games_per_utc_day() {
+        let day = [
+            busy("g1", "Hall A", "2026-05-02T10:00:00Z"),
+            busy("g2", "Hall A", "2026-05-02T14:00:00Z"),
+        ];
+        assert!(eligible(
+            &slot("g", "Hall A", "2026-05-02T18:00:00Z", 1),
+            &[],
+            &day[..1]
+        ));
+        assert!(!eligible(
+            &slot("g", "Hall A", "2026-05-02T18:00:00Z", 1),
+            &[],
+            &day
+        ));
+        assert!(!eligible(
+            &slot("g", "Hall A", "2026-05-02T23:00:00Z", 1),
+            &[],
+            &day
+        ));
+        // past midnight UTC it's the next day, whatever the club's local time
+        assert!(eligible(
+            &slot("g", "Hall A", "2026-05-03T00:30:00Z", 1),
+            &[],
+            &day
+        ));
+
+        let late = [
+            busy("g1", "Hall A", "2026-05-02T20:00:00Z"),
+            busy("g2", "Hall A", "2026-05-02T22:00:00Z"),
+        ];
+        assert!(eligible(
+            &slot("g", "Hall A", "2026-05-03T00:00:00Z", 1),
+            &[],
+            &late
+        ));
+    }
+
+    #[test]
+    fn one_position_per_game() {
+        // even with the other slot's times not overlapping
+        let other_position = [busy("g", "Hall A", "2026-05-01T10:00:00Z")];
+        assert!(!eligible(
+            &slot("g", "Hall A", "2026-05-02T10:00:00Z", 1),
+            &[],
+            &other_position
+        ));
+    }
+
+    #[test]
+    fn needs_travel_time_between_venues_only() {
+        let game = [busy("g1", "Hall A", "2026-05-02T10:00:00Z")];
+        let candidate = |location, start| eligible(&slot("g", location, start, 1), &[], &game);
+
+        // back to back at the same venue, compared case- and whitespace-insensitively
+        assert!(candidate(" hall a ", "2026-05-02T12:00:00Z"));
+        assert!(candidate("Hall A", "2026-05-02T08:00:00Z"));
+        assert!(!candidate("Hall A", "2026-05-02T11:59:00Z"));
+
+        assert!(!candidate("Hall B", "2026-05-02T12:00:00Z"));
+        assert!(!candidate("Hall B", "2026-05-02T12:59:00Z"));
+        assert!(candidate("Hall B", "2026-05-02T13:00:00Z"));
+        assert!(!candidate("Hall B", "2026-05-02T07:01:00Z"));
+        assert!(candidate("Hall B", "2026-05-02T07:00:00Z"));
+    }
+
+    fn run(slots: &[OpenSlot], referees: &[Referee], in_range: &mut [usize]) -> Vec<Option<usize>> {
+        let absences: Vec<Vec<AbsentDays>> = referees.iter().map(|_| vec![]).collect();
+        let mut commitments: Vec<Vec<Commitment>> = referees.iter().map(|_| vec![]).collect();
+        solve(
+            slots,
+            referees,
+            &absences,
+            &mut commitments,
+            in_range,
+            &options(),
+        )
+    }
+
+    #[test]
+    fn spreads_games_then_spares_the_best_referees() {
+        let referees = [referee("anna", 3), referee("bert", 1), referee("carl", 1)];
+
+        // fewest games in the range first
+        let chosen = run(
+            &[slot("g", "Hall A", "2026-05-02T10:00:00Z", 1)],
+            &referees,
+            &mut [0, 1, 1],
+        );
+        assert_eq!(chosen, vec![Some(0)]);
+
+        // then the least overqualified, then by name
+        let chosen = run(
+            &[slot("g", "Hall A", "2026-05-02T10:00:00Z", 1)],
+            &referees,
+            &mut [0, 0, 0],
+        );
+        assert_eq!(chosen, vec![Some(1)]);
+
+        // what's assigned counts for the next slot
+        let mut in_range = [0, 0, 0];
+        let chosen = run(
+            &[
+                slot("g1", "Hall A", "2026-05-02T10:00:00Z", 1),
+                slot("g2", "Hall A", "2026-05-03T10:00:00Z", 1),
+                slot("g3", "Hall A", "2026-05-04T10:00:00Z", 1),
+            ],
+            &referees,
+            &mut in_range,
+        );
+        assert_eq!(chosen, vec![Some(1), Some(2), Some(0)]);
+        assert_eq!(in_range, [1, 1, 1]);
+    }
+
+    #[test]
+    fn fills_the_most_constrained_slots_first() {
+        let referees = [referee("anna", 3), referee("bert", 1)];
+        // the easy slot comes first and would take anna, who has fewer games
+        let chosen = run(
+            &[
+                slot("easy", "Hall A", "2026-05-02T10:00:00Z", 1),
+                slot("hard", "Hall B", "2026-05-02T10:00:00Z", 3),
+            ],
+            &referees,
+            &mut [0, 1],
+        );
+        assert_eq!(chosen, vec![Some(1), Some(0)]);
+
+        let chosen = run(
+            &[slot("g", "Hall A", "2026-05-02T10:00:00Z", 4)],
+            &referees,
+            &mut [0, 0],
+        );
+        assert_eq!(chosen, vec![None]);
+    }
+}
@@ -1,8 +1,6 @@
 use axum::{
     extract::{Path, State},
     http::StatusCode,
-    response::Response,
-    Extension,
 };
 use rand::{
     distr::{Alphanumeric, SampleString},
@@ -10,46 +8,87 @@ use rand::{
 };
 
 use crate::{
-    auth::{
-        roles::{check_user_roles, Role},
-        utils::AuthContext,
-    },
-    utils::api::{db_err_to_response, AppState},
+    auth::permissions::{actions::ClubInviteManage, Authorized},
+    entities::audit_log::{record_audit_event, AuditAction, AuditEvent},
+    utils::{api::AppState, error::ApiError},
 };
 
+#[utoipa::path(
+    post,
+    path = "/invites-to-club/create",
+    tag = "clubs",
+    responses(
+        (status = 200, description = "the invite id", body = String, content_type = "text/plain"),
+    ),
+)]
 pub async fn create_service_invite(
     State(state): State<AppState>,
-    auth_ctx: Extension<AuthContext>,
-) -> Result<String, Response> {
-    let _ = check_user_roles(&auth_ctx, &[Role::ClubAdmin, Role::SuperAdmin])?;
+    auth_ctx: Authorized<ClubInviteManage>,
+) -> Result<String, ApiError> {
     let id = Alphanumeric.sample_string(&mut rng(), 16);
 
+    let mut tx = state.pg_pool.begin().await?;
+
     let result = sqlx::query!(
         r#"INSERT INTO service_invites (id, club_id) VALUES ($1, $2) RETURNING id"#,
         id,
         auth_ctx.club_id
     )
-    .fetch_one(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .fetch_one(&mut *tx)
+    .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent::by(
+            &auth_ctx,
+            AuditAction::ServiceInviteCreated,
+            "service_invite",
+            &result.id,
+        ),
+    )
+    .await?;
+
+    tx.commit().await?;
 
     Ok(result.id)
 }
 
+#[utoipa::path(
+    delete,
+    path = "/invites-to-club/delete-by-id/{id}",
+    tag = "clubs",
+    params(("id" = String, Path, description = "invite id")),
+    responses((status = 204, description = "invite deleted")),
+)]
 pub async fn delete_service_invite_by_id(
     State(state): State<AppState>,
-    auth_ctx: Extension<AuthContext>,
+    auth_ctx: Authorized<ClubInviteManage>,
     Path(id): Path<String>,
-) -> Result<StatusCode, Response> {
-    let _ = check_user_roles(&auth_ctx, &[Role::ClubAdmin, Role::SuperAdmin])?;
-    let _ = sqlx::query!(
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let deleted = sqlx::query!(
         r#"DELETE FROM service_invites WHERE club_id = $1 AND id = $2"#,
         &auth_ctx.club_id,
         id
     )
-    .execute(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .execute(&mut *tx)
+    .await?;
+
+    if deleted.rows_affected() > 0 {
+        record_audit_event(
+            &mut tx,
+            AuditEvent::by(
+                &auth_ctx,
+                AuditAction::ServiceInviteDeleted,
+                "service_invite",
+                &id,
+            ),
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
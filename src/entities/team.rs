@@ -2,28 +2,34 @@
 //! Team entity – a concrete team inside an club
 //! (e.g. “men's senior football team”)
 
-use log::debug;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use sqlx::FromRow;
+use strum_macros::{Display, EnumString};
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
 use crate::{
-    auth::{
-        roles::{check_user_roles, Role},
-        utils::AuthContext,
+    auth::permissions::{
+        actions::{TeamCreate, TeamDelete, TeamList, TeamUpdate},
+        Authorized,
     },
-    utils::api::db_err_to_response,
-    AppState, JustId,
+    entities::audit_log::{record_audit_event, AuditAction, AuditEvent},
+    utils::{
+        error::ApiError,
+        pagination::{ListParams, ListQuery, Paginated, SortKey},
+        validation::{Validate, ValidatedJson, Validator, MAX_NAME_LEN},
+    },
+    AppState,
 };
 use axum::{
     extract::{Path, State},
     http::StatusCode,
-    response::{IntoResponse, Response},
     routing::{delete, get, post, put},
-    Extension, Json, Router,
+    Json, Router,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
-#[allow(non_snake_case)]
+#[allow(non_snake_case, dead_code)]
 pub struct TeamModel {
     pub id: String,
     /// The club that owns this team
@@ -38,7 +44,7 @@ pub struct TeamModel {
 }
 
 /// Core representation of a *team* used across the API.
-#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, ToSchema)]
 pub struct Team {
     pub id: String,
     pub club_id: String,
@@ -56,62 +62,174 @@ pub fn team_router<S>(state: AppState) -> Router<S> {
         .with_state(state.clone())
 }
 
-/// ---------- CREATE ----------------------------------------------------------
-#[derive(Debug, Clone, Deserialize)]
+#[derive(OpenApi)]
+#[openapi(paths(create_team, get_team, list_teams, update_team, delete_team))]
+pub struct TeamApi;
+
+// ---------- CREATE ----------------------------------------------------------
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct CreateTeamPayload {
     pub name: String,
     pub slug: String,
 }
 
+impl Validate for CreateTeamPayload {
+    fn validate(&self, v: &mut Validator) {
+        v.length("name", &self.name, 1, MAX_NAME_LEN);
+        v.slug("slug", &self.slug);
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/create",
+    tag = "teams",
+    request_body = CreateTeamPayload,
+    responses((status = 201, description = "the new team's id", body = String)),
+)]
 pub async fn create_team(
     State(state): State<AppState>,
-    auth_ctx: Extension<AuthContext>,
-    Json(payload): Json<CreateTeamPayload>,
-) -> Result<(StatusCode, Json<String>), Response> {
-    // clubAdmin or SuperAdmin can create a team
-    check_user_roles(&auth_ctx, &[Role::SuperAdmin, Role::ClubAdmin])?;
+    auth_ctx: Authorized<TeamCreate>,
+    ValidatedJson(payload): ValidatedJson<CreateTeamPayload>,
+) -> Result<(StatusCode, Json<String>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
 
-    let new_id = sqlx::query!(
+    let team = sqlx::query_as!(
+        Team,
         r#"INSERT INTO teams (id, club_id, name, slug) 
            VALUES ($1, $2, $3, $4) 
-           RETURNING id"#,
+           RETURNING id, club_id, name, slug"#,
         uuid::Uuid::new_v4().to_string(),
         auth_ctx.club_id,
         payload.name,
         payload.slug
     )
-    .fetch_one(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .fetch_one(&mut *tx)
+    .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            after: Some(json!(team)),
+            ..AuditEvent::by(&auth_ctx, AuditAction::TeamCreated, "team", &team.id)
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(team.id)))
+}
 
-    Ok((StatusCode::CREATED, Json(new_id.id)))
+// ---------- READ ALL --------------------------------------------------------
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum TeamSort {
+    #[default]
+    Name,
+    Slug,
 }
 
-/// ---------- READ ALL --------------------------------------------------------
+impl SortKey for TeamSort {}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TeamFilters {
+    /// part of the name or slug, case-insensitive
+    pub q: Option<String>,
+}
+
+impl Validate for TeamFilters {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(q) = &self.q {
+            v.length("q", q, 0, MAX_NAME_LEN);
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TeamListItem {
+    id: String,
+    club_id: String,
+    name: String,
+    slug: String,
+    #[serde(skip)]
+    sort_key: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/list",
+    tag = "teams",
+    params(ListParams, TeamFilters),
+    responses((status = 200, body = Paginated<TeamListItem>)),
+)]
 pub async fn list_teams(
     State(state): State<AppState>,
-    auth_ctx: Extension<AuthContext>,
-) -> Result<(StatusCode, Json<Vec<Team>>), Response> {
+    auth_ctx: Authorized<TeamList>,
+    ListQuery { page, filters }: ListQuery<TeamSort, TeamFilters>,
+) -> Result<(StatusCode, Json<Paginated<TeamListItem>>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
     let teams = sqlx::query_as!(
-        Team,
-        r#"SELECT id, club_id, name, slug 
-           FROM teams 
-           WHERE club_id = $1"#,
-        auth_ctx.club_id
+        TeamListItem,
+        r#"
+        SELECT id AS "id!", club_id AS "club_id!", name AS "name!", slug AS "slug!",
+        sort_key AS "sort_key!" FROM (
+            SELECT t.id, t.club_id, t.name, t.slug,
+            CASE $3 WHEN 'slug' THEN t.slug ELSE t.name END AS sort_key
+            FROM teams t
+            WHERE t.club_id = $1
+            AND ($2::text IS NULL OR t.name ILIKE '%' || $2 || '%' OR t.slug ILIKE '%' || $2 || '%')
+        ) r
+        WHERE ($5::text IS NULL OR CASE WHEN $4
+            THEN (r.sort_key, r.id) < ($5, $6) ELSE (r.sort_key, r.id) > ($5, $6) END)
+        ORDER BY CASE WHEN $4 THEN r.sort_key END DESC, r.sort_key,
+            CASE WHEN $4 THEN r.id END DESC, r.id
+        LIMIT $7
+        "#,
+        auth_ctx.club_id,
+        filters.q,
+        page.sort(),
+        page.descending,
+        page.after_key(),
+        page.after_id(),
+        page.fetch_limit()
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT count(*) AS "count!" FROM teams t
+        WHERE t.club_id = $1
+        AND ($2::text IS NULL OR t.name ILIKE '%' || $2 || '%' OR t.slug ILIKE '%' || $2 || '%')
+        "#,
+        auth_ctx.club_id,
+        filters.q
     )
-    .fetch_all(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
 
+    let teams = page.finish(teams, total, |team| (&team.sort_key, &team.id));
     Ok((StatusCode::OK, Json(teams)))
 }
 
-/// ---------- READ ONE --------------------------------------------------------
+// ---------- READ ONE --------------------------------------------------------
+#[utoipa::path(
+    get,
+    path = "/get/{id}",
+    tag = "teams",
+    params(("id" = String, Path, description = "team id")),
+    responses((status = 200, body = Team)),
+)]
 pub async fn get_team(
     State(state): State<AppState>,
-    auth_ctx: Extension<AuthContext>,
+    auth_ctx: Authorized<TeamList>,
     Path(team_id): Path<String>,
-) -> Result<(StatusCode, Json<Team>), Response> {
+) -> Result<(StatusCode, Json<Team>), ApiError> {
     let team = sqlx::query_as!(
         Team,
         r#"SELECT id, club_id, name, slug 
@@ -121,27 +239,54 @@ pub async fn get_team(
         auth_ctx.club_id
     )
     .fetch_one(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .await?;
 
     Ok((StatusCode::OK, Json(team)))
 }
 
-/// ---------- UPDATE ---------------------------------------------------------
-#[derive(Debug, Clone, Deserialize)]
+// ---------- UPDATE ---------------------------------------------------------
+#[derive(Debug, Clone, Deserialize, ToSchema)]
 pub struct UpdateTeamPayload {
     pub name: Option<String>,
     pub slug: Option<String>,
 }
 
+impl Validate for UpdateTeamPayload {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(name) = &self.name {
+            v.length("name", name, 1, MAX_NAME_LEN);
+        }
+        if let Some(slug) = &self.slug {
+            v.slug("slug", slug);
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/update/{id}",
+    tag = "teams",
+    request_body = UpdateTeamPayload,
+    params(("id" = String, Path, description = "team id")),
+    responses((status = 200, body = Team)),
+)]
 pub async fn update_team(
     State(state): State<AppState>,
-    auth_ctx: Extension<AuthContext>,
+    auth_ctx: Authorized<TeamUpdate>,
     Path(team_id): Path<String>,
-    Json(payload): Json<UpdateTeamPayload>,
-) -> Result<(StatusCode, Json<Team>), Response> {
-    // Only ClubAdmin / SuperAdmin may change team data
-    check_user_roles(&auth_ctx, &[Role::SuperAdmin, Role::ClubAdmin])?;
+    ValidatedJson(payload): ValidatedJson<UpdateTeamPayload>,
+) -> Result<(StatusCode, Json<Team>), ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let before = sqlx::query_as!(
+        Team,
+        r#"SELECT id, club_id, name, slug FROM teams WHERE id = $1 AND club_id = $2 FOR UPDATE"#,
+        team_id,
+        auth_ctx.club_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Team not found".into()))?;
 
     // Update name & slug (short_name) only if provided
     let updated = sqlx::query_as!(
@@ -159,28 +304,60 @@ pub async fn update_team(
         team_id,                 // $3
         auth_ctx.club_id         // $4
     )
-    .fetch_one(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .fetch_one(&mut *tx)
+    .await?;
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            before: Some(json!(before)),
+            after: Some(json!(updated)),
+            ..AuditEvent::by(&auth_ctx, AuditAction::TeamUpdated, "team", &team_id)
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
 
     Ok((StatusCode::OK, Json(updated)))
 }
-/// ---------- DELETE ---------------------------------------------------------
+// ---------- DELETE ---------------------------------------------------------
+#[utoipa::path(
+    delete,
+    path = "/delete-by-id/{id}",
+    tag = "teams",
+    params(("id" = String, Path, description = "team id")),
+    responses((status = 204, description = "team deleted")),
+)]
 pub async fn delete_team(
     State(state): State<AppState>,
-    auth_ctx: Extension<AuthContext>,
+    auth_ctx: Authorized<TeamDelete>,
     Path(team_id): Path<String>,
-) -> Result<StatusCode, Response> {
-    check_user_roles(&auth_ctx, &[Role::ClubAdmin, Role::SuperAdmin])?;
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
 
-    sqlx::query!(
-        r#"DELETE FROM teams WHERE id = $1 AND club_id = $2"#,
+    let deleted = sqlx::query_as!(
+        Team,
+        r#"DELETE FROM teams WHERE id = $1 AND club_id = $2 RETURNING id, club_id, name, slug"#,
         team_id,
         auth_ctx.club_id
     )
-    .execute(&state.pg_pool)
-    .await
-    .map_err(db_err_to_response)?;
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    // deleting twice is fine, but only the first time is worth an entry
+    if let Some(deleted) = deleted {
+        record_audit_event(
+            &mut tx,
+            AuditEvent {
+                before: Some(json!(deleted)),
+                ..AuditEvent::by(&auth_ctx, AuditAction::TeamDeleted, "team", &team_id)
+            },
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
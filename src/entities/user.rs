@@ -1,25 +1,50 @@
 use log::debug;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use strum_macros::{Display, EnumString};
+use utoipa::{IntoParams, ToSchema};
 
 use crate::{
     auth::{
-        roles::{check_user_roles, Role},
+        email::{parse_email, send_verification_email, set_pending_email},
+        permissions::{
+            actions::{UserCreate, UserDelete},
+            Authorized,
+        },
+        roles::Role,
         utils::AuthContext,
     },
-    utils::api::ApiResult,
+    entities::audit_log::{record_audit_event, AuditAction, AuditEvent},
+    utils::{
+        api::ApiResult,
+        error::ApiError,
+        pagination::{ListParams, ListQuery, Paginated, SortKey, TIME_KEY_FORMAT},
+        validation::{Validate, ValidatedJson, Validator, MAX_NAME_LEN},
+    },
     AppState,
 };
 use axum::{
     extract::{Path, State},
     http::StatusCode,
-    response::{IntoResponse, Response},
     Extension, Json,
 };
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CreateUser {
     pub username: String,
     pub password: String,
+    /// the new member gets a verification link
+    pub email: Option<String>,
+}
+
+impl Validate for CreateUser {
+    fn validate(&self, v: &mut Validator) {
+        v.username("username", &self.username);
+        v.password("password", &self.password);
+        if let Some(email) = &self.email {
+            v.email("email", email);
+        }
+    }
 }
 
 // user from DB wihtout security and unnecessary util fields
@@ -29,136 +54,259 @@ pub struct UserClean {
     pub username: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/users/create",
+    tag = "users",
+    request_body = CreateUser,
+    responses((status = 201, description = "the new user's id", body = String)),
+)]
 pub async fn create_user(
     State(state): State<AppState>,
-    auth_ctx: Extension<AuthContext>,
-    Json(payload): Json<CreateUser>,
-) -> Result<Response, Response> {
+    auth_ctx: Authorized<UserCreate>,
+    ValidatedJson(payload): ValidatedJson<CreateUser>,
+) -> Result<(StatusCode, Json<String>), ApiError> {
     let username = payload.username;
     let password = payload.password;
+    let email = payload.email.as_deref().map(parse_email).transpose()?;
 
-    let _ = check_user_roles(&auth_ctx, &[Role::ClubAdmin, Role::SuperAdmin])?;
+    let mut tx = state.pg_pool.begin().await?;
 
-    let query_result = sqlx::query!(
+    // a taken username is a 409 on `username`
+    let record = sqlx::query!(
         r#"INSERT INTO users (username, password, club_id) VALUES ($1, $2, $3) RETURNING id"#,
         username,
         password,
         auth_ctx.club_id
     )
-    .fetch_one(&state.pg_pool)
-    .await;
-
-    match query_result {
-        Err(e) => {
-            let error_response = serde_json::json!({
-            "status": "error",
-            "message": format!("Database error: { }", e),
-            })
-            .to_string();
-            Err((StatusCode::INTERNAL_SERVER_ERROR, error_response).into_response())
-        }
-        Ok(record) => Ok((StatusCode::CREATED, Json(record.id)).into_response()),
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if let Some(email) = &email {
+        set_pending_email(&mut tx, &record.id, email).await?;
+    }
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            after: Some(json!({ "id": record.id, "username": username })),
+            ..AuditEvent::by(&auth_ctx, AuditAction::UserCreated, "user", &record.id)
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    if let Some(email) = &email {
+        send_verification_email(&state, &record.id, email);
     }
+
+    Ok((StatusCode::CREATED, Json(record.id)))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/users/delete-by-id/{id}",
+    tag = "users",
+    params(("id" = String, Path, description = "user id")),
+    responses((status = 204, description = "user deleted")),
+)]
 pub async fn delete_user_by_id(
     State(state): State<AppState>,
     Path(id): Path<String>,
-    auth_ctx: Extension<AuthContext>,
-) -> Result<StatusCode, Response> {
+    auth_ctx: Authorized<UserDelete>,
+) -> Result<StatusCode, ApiError> {
     debug!("delete user by id called");
     debug!("{}", id);
     debug!(
-        "delete_user_by_id, auth ctx - user: {} session: {}, club: {}",
+        "delete_user_by_id, auth ctx - user: {} session: {:?}, club: {}",
         auth_ctx.user_id, auth_ctx.session_id, auth_ctx.club_id
     );
 
-    let _ = check_user_roles(&auth_ctx, &[Role::ClubAdmin, Role::SuperAdmin])?;
+    let mut tx = state.pg_pool.begin().await?;
 
-    let query_result = sqlx::query!(r#"DELETE FROM users WHERE id = $1"#, id)
-        .execute(&state.pg_pool)
-        .await;
+    let deleted = sqlx::query_as!(
+        UserClean,
+        r#"DELETE FROM users WHERE id = $1 AND club_id = $2 RETURNING id, username"#,
+        id,
+        auth_ctx.club_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
 
-    match query_result {
-        Err(e) => {
-            let error_response = serde_json::json!({
-            "status": "error",
-            "message": format!("Database error: { }", e),
-            })
-            .to_string();
-            Err((StatusCode::INTERNAL_SERVER_ERROR, error_response).into_response())
-        }
-        Ok(result_info) => {
-            if result_info.rows_affected() == 0 {
-                Err((
-                    StatusCode::NOT_ACCEPTABLE,
-                    "User with given ID does not exist - possibly already deleted",
-                )
-                    .into_response())
-            } else {
-                Ok(StatusCode::NO_CONTENT)
-            }
-        }
-    }
+    let Some(deleted) = deleted else {
+        return Err(ApiError::NotFound(
+            "User with given ID does not exist - possibly already deleted".into(),
+        ));
+    };
+
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            before: Some(json!(deleted)),
+            ..AuditEvent::by(&auth_ctx, AuditAction::UserDeleted, "user", &id)
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
 }
 
+#[utoipa::path(
+    delete,
+    path = "/users/delete-own",
+    tag = "users",
+    responses((status = 204, description = "user deleted")),
+)]
 pub async fn delete_own_user(
     State(state): State<AppState>,
     auth_ctx: Extension<AuthContext>,
-) -> Result<StatusCode, Response> {
-    let query_result = sqlx::query!(r#"DELETE FROM users WHERE id = $1"#, auth_ctx.user_id)
-        .execute(&state.pg_pool)
-        .await;
-
-    match query_result {
-        Err(e) => {
-            let error_response = serde_json::json!({
-            "status": "error",
-            "message": format!("Database error: { }", e),
-            })
-            .to_string();
-            Err((StatusCode::INTERNAL_SERVER_ERROR, error_response).into_response())
-        }
-        Ok(result_info) => {
-            if result_info.rows_affected() == 0 {
-                Err((
-                    StatusCode::NOT_ACCEPTABLE,
-                    "User with given ID does not exist - possibly already deleted",
-                )
-                    .into_response())
-            } else {
-                Ok(StatusCode::NO_CONTENT)
-            }
+) -> Result<StatusCode, ApiError> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let username = sqlx::query_scalar!(
+        r#"SELECT username FROM users WHERE id = $1"#,
+        auth_ctx.user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(username) = username else {
+        return Err(ApiError::NotFound(
+            "User with given ID does not exist - possibly already deleted".into(),
+        ));
+    };
+
+    // written first, the actor reference is cleared by the delete
+    record_audit_event(
+        &mut tx,
+        AuditEvent {
+            before: Some(json!({ "id": auth_ctx.user_id, "username": username })),
+            ..AuditEvent::by(
+                &auth_ctx,
+                AuditAction::UserDeleted,
+                "user",
+                &auth_ctx.user_id,
+            )
+        },
+    )
+    .await?;
+
+    sqlx::query!(r#"DELETE FROM users WHERE id = $1"#, auth_ctx.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Clone, Copy, Default, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserSort {
+    #[default]
+    Username,
+    CreatedAt,
+}
+
+impl SortKey for UserSort {}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserFilters {
+    /// part of the username, case-insensitive
+    pub q: Option<String>,
+    pub role: Option<Role>,
+}
+
+impl Validate for UserFilters {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(q) = &self.q {
+            v.length("q", q, 0, MAX_NAME_LEN);
         }
     }
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct UserListItem {
+    id: String,
+    username: String,
+    #[serde(skip)]
+    sort_key: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/list",
+    tag = "users",
+    params(ListParams, UserFilters),
+    responses((status = 200, body = Paginated<UserListItem>)),
+)]
 pub async fn list_users(
     State(state): State<AppState>,
     auth_ctx: Extension<AuthContext>,
-) -> ApiResult<Vec<UserClean>> {
+    ListQuery { page, filters }: ListQuery<UserSort, UserFilters>,
+) -> ApiResult<Paginated<UserListItem>> {
     debug!(
-        "list_users, auth ctx - user: {} session: {}, club: {}, roles: {:?}",
+        "list_users, auth ctx - user: {} session: {:?}, club: {}, roles: {:?}",
         auth_ctx.user_id, auth_ctx.session_id, auth_ctx.club_id, auth_ctx.roles
     );
 
-    let query_result = sqlx::query_as!(
-        UserClean,
-        r#"SELECT id, username FROM users WHERE club_id = $1 ORDER by id"#,
-        auth_ctx.club_id
+    let mut tx = state.pg_pool.begin().await?;
+
+    let users = sqlx::query_as!(
+        UserListItem,
+        r#"
+        SELECT id AS "id!", username AS "username!", sort_key AS "sort_key!" FROM (
+            SELECT u.id, u.username,
+            -- a plain timestamp, AT TIME ZONE 'UTC' would make it depend on the session's zone
+            CASE $4 WHEN 'created_at' THEN to_char(u.created_at, $5) ELSE u.username END AS sort_key
+            FROM users u
+            WHERE u.club_id = $1
+            AND ($2::text IS NULL OR u.username ILIKE '%' || $2 || '%')
+            AND ($3::user_roles IS NULL OR EXISTS (
+                SELECT 1 FROM role_assignments ra WHERE ra.user_id = u.id AND ra.role = $3
+            ))
+        ) r
+        WHERE ($7::text IS NULL OR CASE WHEN $6
+            THEN (r.sort_key, r.id) < ($7, $8) ELSE (r.sort_key, r.id) > ($7, $8) END)
+        ORDER BY CASE WHEN $6 THEN r.sort_key END DESC, r.sort_key,
+            CASE WHEN $6 THEN r.id END DESC, r.id
+        LIMIT $9
+        "#,
+        auth_ctx.club_id,
+        filters.q,
+        filters.role as Option<Role>,
+        page.sort(),
+        TIME_KEY_FORMAT,
+        page.descending,
+        page.after_key(),
+        page.after_id(),
+        page.fetch_limit()
     )
-    .fetch_all(&state.pg_pool)
-    .await;
-
-    match query_result {
-        Err(e) => {
-            let error_response = serde_json::json!({
-            "status": "error",
-            "message": format!("Database error: { }", e),
-            })
-            .to_string();
-            Err((StatusCode::INTERNAL_SERVER_ERROR, error_response))
-        }
-        Ok(users) => Ok((StatusCode::OK, Json(users))),
-    }
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT count(*) AS "count!" FROM users u
+        WHERE u.club_id = $1
+        AND ($2::text IS NULL OR u.username ILIKE '%' || $2 || '%')
+        AND ($3::user_roles IS NULL OR EXISTS (
+            SELECT 1 FROM role_assignments ra WHERE ra.user_id = u.id AND ra.role = $3
+        ))
+        "#,
+        auth_ctx.club_id,
+        filters.q,
+        filters.role as Option<Role>
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let users = page.finish(users, total, |user| (&user.sort_key, &user.id));
+    Ok((StatusCode::OK, Json(users)))
 }
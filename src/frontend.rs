@@ -0,0 +1,214 @@
+//! The web frontend, served next to the API for everything outside of `/api`.
+//!
+//! Production builds serve the built app (`npm run build` in `ts-frontend`), either from
+//! `frontend.dir` (`FRONTEND_DIR`) or, with the `web` feature, embedded into the binary. Debug
+//! builds without either forward to the Vite dev server instead.
+//!
+//! Vite puts content-hashed files into `assets/`, those are cached for good; everything else,
+//! `index.html` in particular, is revalidated on every load so deployments show up right away.
+//! Precompressed `.br` and `.gz` variants next to a file are sent to clients accepting them.
+//! Other paths without a file are client-side routes and get `index.html`, also when their last
+//! segment has a dot (`/users/jane.doe`). Only missing files under `assets/` are a 404, a
+//! browser asking for an old script must not get a page instead.
+
+use std::{borrow::Cow, path::PathBuf, sync::Arc};
+
+use axum::{
+    extract::State,
+    http::{
+        header::{ACCEPT_ENCODING, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE, VARY},
+        HeaderMap, HeaderValue, Uri,
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use log::{info, warn};
+
+use crate::{config::FrontendConfig, utils::error::ApiError};
+
+const INDEX: &str = "index.html";
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const REVALIDATE: &str = "no-cache";
+
+#[cfg(feature = "web")]
+#[derive(rust_embed::RustEmbed)]
+#[folder = "ts-frontend/dist"]
+struct EmbeddedFrontend;
+
+pub enum Frontend {
+    /// the built app in a directory
+    Dir(PathBuf),
+    /// the built app, compiled into the binary
+    #[cfg(feature = "web")]
+    Embedded,
+    /// forwards to the Vite dev server
+    #[cfg(all(not(feature = "web"), debug_assertions))]
+    DevServer(String),
+    /// API only
+    #[cfg(all(not(feature = "web"), not(debug_assertions)))]
+    None,
+}
+
+impl Frontend {
+    /// `dir` is checked when the config is loaded
+    pub fn from_config(config: &FrontendConfig) -> Self {
+        if let Some(dir) = &config.dir {
+            info!("serving the frontend from {}", dir.display());
+            return Frontend::Dir(dir.clone());
+        }
+        #[cfg(feature = "web")]
+        {
+            info!("serving the embedded frontend");
+            Frontend::Embedded
+        }
+        #[cfg(all(not(feature = "web"), debug_assertions))]
+        {
+            info!(
+                "forwarding frontend requests to the dev server at {}",
+                config.dev_server
+            );
+            Frontend::DevServer(config.dev_server.clone())
+        }
+        #[cfg(all(not(feature = "web"), not(debug_assertions)))]
+        {
+            warn!("neither FRONTEND_DIR nor the `web` feature is set - serving the API only");
+            Frontend::None
+        }
+    }
+
+    /// a router with only a fallback, to be merged into the app after all other routes
+    pub fn router<S: Clone + Send + Sync + 'static>(self) -> Router<S> {
+        let files = match self {
+            Frontend::Dir(dir) => Files::Dir(dir),
+            #[cfg(feature = "web")]
+            Frontend::Embedded => Files::Embedded,
+            #[cfg(all(not(feature = "web"), debug_assertions))]
+            Frontend::DevServer(url) => {
+                return Router::new()
+                    .fallback_service(axum_reverse_proxy::ReverseProxy::new("/", &url));
+            }
+            #[cfg(all(not(feature = "web"), not(debug_assertions)))]
+            Frontend::None => return Router::new(),
+        };
+        Router::new().fallback_service(get(serve_frontend).with_state(Arc::new(files)))
+    }
+}
+
+enum Files {
+    Dir(PathBuf),
+    #[cfg(feature = "web")]
+    Embedded,
+}
+
+impl Files {
+    /// `path` is relative and free of `..`
+    async fn read(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        match self {
+            Files::Dir(dir) => tokio::fs::read(dir.join(path)).await.ok().map(Cow::Owned),
+            #[cfg(feature = "web")]
+            Files::Embedded => EmbeddedFrontend::get(path).map(|file| file.data),
+        }
+    }
+
+    /// the precompressed variant the client prefers, if there is one
+    async fn read_encoded(
+        &self,
+        path: &str,
+        accepted: &[&'static str],
+    ) -> Option<(Cow<'static, [u8]>, Option<&'static str>)> {
+        for (encoding, extension) in [("br", "br"), ("gzip", "gz")] {
+            if !accepted.contains(&encoding) {
+                continue;
+            }
+            if let Some(bytes) = self.read(&format!("{}.{}", path, extension)).await {
+                return Some((bytes, Some(encoding)));
+            }
+        }
+        self.read(path).await.map(|bytes| (bytes, None))
+    }
+}
+
+/// `br` and `gzip`, if the `Accept-Encoding` header allows them
+fn accepted_encodings(headers: &HeaderMap) -> Vec<&'static str> {
+    let Some(header) = headers.get(ACCEPT_ENCODING).and_then(|h| h.to_str().ok()) else {
+        return vec![];
+    };
+    let mut accepted = vec![];
+    for part in header.split(',') {
+        let mut params = part.split(';').map(str::trim);
+        let coding = params.next().unwrap_or_default();
+        let refused = params.any(|param| {
+            param
+                .strip_prefix("q=")
+                .and_then(|q| q.parse::<f32>().ok())
+                .is_some_and(|q| q == 0.0)
+        });
+        if refused {
+            continue;
+        }
+        match coding {
+            "br" => accepted.push("br"),
+            "gzip" => accepted.push("gzip"),
+            _ => {}
+        }
+    }
+    accepted
+}
+
+async fn serve_frontend(
+    State(files): State<Arc<Files>>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Result<Response, ApiError> {
+    let not_found = || ApiError::NotFound("no such file".to_string());
+
+    let path = uri.path().trim_start_matches('/');
+    if path
+        .split('/')
+        .any(|segment| segment == ".." || segment.contains('\\'))
+    {
+        return Err(not_found());
+    }
+    let path = if path.is_empty() { INDEX } else { path };
+    let accepted = accepted_encodings(&headers);
+
+    let (path, (bytes, encoding)) = match files.read_encoded(path, &accepted).await {
+        Some(file) => (path, file),
+        // a client-side route, e.g. `/teams/abc`
+        None if !path.starts_with("assets/") => {
+            let file = files.read_encoded(INDEX, &accepted).await.ok_or_else(|| {
+                warn!("the frontend has no {}", INDEX);
+                not_found()
+            })?;
+            (INDEX, file)
+        }
+        None => return Err(not_found()),
+    };
+
+    let content_type = mime_guess::from_path(path).first_or_octet_stream();
+    let cache_control = if path.starts_with("assets/") {
+        IMMUTABLE
+    } else {
+        REVALIDATE
+    };
+    let mut response = (
+        [
+            (
+                CONTENT_TYPE,
+                HeaderValue::from_str(content_type.as_ref())
+                    .unwrap_or(HeaderValue::from_static("application/octet-stream")),
+            ),
+            (CACHE_CONTROL, HeaderValue::from_static(cache_control)),
+            (VARY, HeaderValue::from_static("accept-encoding")),
+        ],
+        bytes.into_owned(),
+    )
+        .into_response();
+    if let Some(encoding) = encoding {
+        response
+            .headers_mut()
+            .insert(CONTENT_ENCODING, HeaderValue::from_static(encoding));
+    }
+    Ok(response)
+}
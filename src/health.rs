@@ -0,0 +1,51 @@
+//! Probes for the container orchestrator: `/healthz` answers as long as the process serves
+//! requests, `/readyz` only while the database is reachable and its schema is current.
+//! Probes are public, so the problem they report is generic; the details are logged.
+
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::utils::{api::AppState, migrations::check_schema};
+
+/// a probe hanging on an exhausted pool is as bad as a failed one
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+pub struct Readiness {
+    ready: bool,
+    /// what failed, `None` if ready
+    problem: Option<&'static str>,
+}
+
+pub async fn healthz() -> &'static str {
+    "ok"
+}
+
+pub async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<Readiness>) {
+    let problem = match tokio::time::timeout(CHECK_TIMEOUT, check_schema(&state.pg_pool)).await {
+        Ok(Ok(())) => None,
+        Ok(Err(err)) => {
+            warn!(error = %err, "not ready");
+            Some("the database is unreachable or its schema isn't current")
+        }
+        Err(_) => {
+            warn!(timeout = ?CHECK_TIMEOUT, "not ready: the database didn't answer in time");
+            Some("the database didn't answer in time")
+        }
+    };
+    let status = if problem.is_none() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(Readiness {
+            ready: problem.is_none(),
+            problem,
+        }),
+    )
+}
@@ -0,0 +1,26 @@
+//! The backend, shared by the server (`main.rs`) and the admin CLI
+//! (`bin/sports-planner-admin.rs`).
+
+pub mod admin;
+pub mod auth;
+pub mod config;
+pub mod entities;
+pub mod frontend;
+pub mod health;
+pub mod metrics;
+pub mod notifications;
+pub mod openapi;
+pub mod telemetry;
+pub mod utils;
+
+// TODO: soft-deletes via deleted_at (not super high-prio now)
+
+use serde::Deserialize;
+
+use crate::utils::api::AppState;
+
+// the input to our `create_user` handler
+#[derive(Deserialize, sqlx::FromRow)]
+pub struct JustId {
+    pub id: String,
+}
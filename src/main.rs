@@ -1,42 +1,77 @@
+use std::{net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
+
 use axum::{
     extract::Request,
-    middleware::{self, Next},
-    response::Response,
+    http::{header::CONTENT_TYPE, HeaderName, HeaderValue, Method},
+    middleware,
     routing::{delete, get, post},
     Router, ServiceExt,
 };
-use axum_reverse_proxy::ReverseProxy;
 use dotenv::dotenv;
-use log::{error, info};
-use serde::Deserialize;
-use sqlx::postgres::PgPoolOptions;
-use tower::ServiceBuilder;
-use tower_http::cors::CorsLayer;
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    ConnectOptions,
+};
+use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::normalize_path::NormalizePathLayer;
 use tower_layer::Layer;
+use tracing::{error, info, level_filters::LevelFilter};
+use tracing_log::AsLog;
 
-mod auth;
-mod entities;
-mod utils;
-
-// TODO: don't expose internals in error responses (though they are helpful in the early stages of dev)
-// TODO: soft-deletes via deleted_at (not super high-prio now)
-
-use crate::{
+use rust_rest::{
     auth::{
+        api_tokens::api_token_router,
         auth_routes::{log_in, log_out, sign_up_via_invite, sign_up_with_new_club},
-        middlewares::cookie_auth_middleware,
+        csrf::{get_csrf_token, CSRF_HEADER},
+        email::{
+            change_email, get_own_email, remove_email, resend_email_verification, verify_email,
+        },
+        impersonation::{
+            get_impersonation_status, start_impersonation, stop_impersonation,
+            IMPERSONATED_BY_HEADER,
+        },
+        login_throttle::unlock_user_login,
+        middlewares::{auth_middleware, request_id_middleware, REQUEST_ID_HEADER},
+        oidc::{
+            get_oidc_provider, oidc_callback, remove_oidc_provider, set_oidc_provider,
+            start_oidc_link, start_oidc_log_in,
+        },
+        password::{
+            change_password, confirm_password_reset, create_password_reset_for_user,
+            force_password_reset, request_password_reset,
+        },
+        permissions::list_own_permissions,
         roles::{assign_role, list_own_role_assignments, list_role_assignments, unassign_role},
+        signed_token,
+        two_factor::{
+            activate_two_factor, complete_two_factor_log_in, disable_two_factor, enrol_two_factor,
+            get_two_factor_status, regenerate_recovery_codes, reset_two_factor_for_user,
+            set_require_admin_two_factor,
+        },
     },
+    config::Config,
     entities::{
+        absence::absence_router,
+        audit_log::list_audit_log,
         club::delete_own_club,
         game::game_router,
-        game_invite::{answer_invite_to_game, list_invites_to_game, list_own_game_invites},
+        game_invite::{
+            answer_invite_to_game, list_invites_to_game, list_minors_game_invites,
+            list_own_game_invites,
+        },
+        guardianship::guardianship_router,
+        push_subscription::push_subscription_router,
+        referee_assignment::referee_assignment_router,
         service_invite::{create_service_invite, delete_service_invite_by_id},
         team::team_router,
         user::{create_user, delete_own_user, delete_user_by_id, list_users},
     },
-    utils::{api::AppState, initial_setup::initial_setup},
+    frontend::Frontend,
+    health::{healthz, readyz},
+    metrics::{metrics_middleware, serve_metrics, Metrics},
+    notifications::{email::Mailer, web_push::WebPush},
+    openapi, telemetry,
+    utils::{api::AppState, error::ApiError, initial_setup::initial_setup, migrations, outbound},
 };
 
 #[derive(sqlx::FromRow)]
@@ -46,55 +81,153 @@ pub struct ConfigModel {
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() {
-    // needed so that logs are actually printed to the console
-    env_logger::init();
-
     dotenv().ok();
-    let postgres_url = dotenv::var("DATABASE_URL").expect("DATABASE_URL is not configured");
+    let config = match Config::load() {
+        Ok(config) => Arc::new(config),
+        Err(err) => {
+            // logging is configured there as well, so fall back to the defaults
+            let _ = telemetry::init(&Default::default());
+            error!(error = %err, "invalid configuration");
+            std::process::exit(1);
+        }
+    };
+    if let Err(err) = telemetry::init(&config.logging) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 
+    let connect_options = match PgConnectOptions::from_str(&config.database.url) {
+        Ok(options) => options
+            .log_statements(LevelFilter::DEBUG.as_log())
+            .log_slow_statements(
+                LevelFilter::WARN.as_log(),
+                Duration::from_millis(config.database.slow_query_ms),
+            ),
+        Err(err) => {
+            error!(error = %err, "invalid database URL");
+            std::process::exit(1);
+        }
+    };
     let pool = match PgPoolOptions::new()
-        .max_connections(3)
-        .connect(&postgres_url)
+        .max_connections(config.database.max_connections)
+        .min_connections(config.database.min_connections)
+        .acquire_timeout(Duration::from_secs(config.database.acquire_timeout_seconds))
+        .connect_with(connect_options)
         .await
     {
         Ok(pool) => {
-            info!("Connected to Postgres");
+            info!("connected to Postgres");
             pool
         }
         Err(err) => {
-            error!("Failed to connect to Postgres: {:?}", err);
+            error!(error = ?err, "failed to connect to Postgres");
             std::process::exit(1);
         }
     };
 
+    if let Err(err) = migrations::migrate(&pool, config.database.run_migrations).await {
+        error!(error = %err, "could not migrate the database");
+        std::process::exit(1);
+    }
+
     // load persistent config
-    let Ok(persistent_config) = sqlx::query_as!(ConfigModel, "SELECT * FROM config",)
+    let persistent_config = match sqlx::query_as!(ConfigModel, "SELECT * FROM config",)
         .fetch_one(&pool)
         .await
-    else {
-        panic!("could not get persistent config")
+    {
+        Ok(persistent_config) => persistent_config,
+        Err(err) => {
+            error!(error = %err, "failed to load the persistent config");
+            std::process::exit(1);
+        }
     };
 
     // check if app has already been initialized
     if !persistent_config.is_initialized {
-        initial_setup(&pool).await
+        initial_setup(&pool, &config.initial).await
     }
 
-    let state = AppState { pg_pool: pool };
+    let web_push = match WebPush::from_config(&config.web_push) {
+        Ok(web_push) => web_push,
+        Err(err) => {
+            error!(error = %err, "failed to set up web push");
+            std::process::exit(1);
+        }
+    };
+
+    let mailer = match Mailer::from_config(&config.email) {
+        Ok(mailer) => mailer,
+        Err(err) => {
+            error!(error = %err, "failed to set up the mailer");
+            std::process::exit(1);
+        }
+    };
+
+    let token_secret = signed_token::secret_from_config(config.server.app_secret.as_deref());
+    let frontend = Frontend::from_config(&config.frontend);
+
+    let allowed_origins = config.allowed_origins();
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::list(allowed_origins.iter().map(|origin| {
+            HeaderValue::from_str(origin).expect("origins are validated on startup")
+        })))
+        .allow_credentials(true)
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+        .allow_headers([CONTENT_TYPE, HeaderName::from_static(CSRF_HEADER)])
+        .expose_headers([
+            HeaderName::from_static(IMPERSONATED_BY_HEADER),
+            HeaderName::from_static(REQUEST_ID_HEADER),
+        ]);
+
+    let state = AppState {
+        pg_pool: pool,
+        web_push,
+        mailer,
+        token_secret,
+        http_client: outbound::http_client(),
+        config: config.clone(),
+        metrics: Metrics::new(),
+    };
 
     // build our application with a route
     let app = Router::new()
         .nest("/api", api_routes(state.clone()))
-        .fallback_service(ReverseProxy::new("/", "http://localhost:5173")) // FWds reqs to the dev server of the FE - TODO: to be replaced with compiled static frontend assets later
-        .layer(ServiceBuilder::new().layer(middleware::from_fn(logging_middleware)))
-        .layer(CorsLayer::permissive()) // TODO: replace with real strict CORS config
+        .merge(if config.features.api_docs {
+            openapi::docs_router()
+        } else {
+            Router::new()
+        })
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route(
+            "/metrics",
+            if config.features.metrics {
+                get(serve_metrics)
+            } else {
+                get(|| async { ApiError::NotFound("metrics are disabled".to_string()) })
+            },
+        )
+        .merge(frontend.router())
+        .layer(middleware::from_fn(telemetry::trace_middleware))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            metrics_middleware,
+        ))
+        .layer(middleware::from_fn(request_id_middleware))
+        .layer(cors)
         .with_state(state);
 
     fn unprotected_api_routes<S>(state: AppState) -> Router<S> {
         Router::new()
             .route("/log-in", post(log_in))
+            .route("/log-in/2fa", post(complete_two_factor_log_in))
             .route("/sign-up-with-new-club", post(sign_up_with_new_club))
             .route("/sign-up-via-invite/{invite_id}", post(sign_up_via_invite))
+            .route("/password-reset/request", post(request_password_reset))
+            .route("/password-reset/confirm", post(confirm_password_reset))
+            .route("/verify-email", post(verify_email))
+            .route("/oidc/{club_id}/start", get(start_oidc_log_in))
+            .route("/oidc/callback", get(oidc_callback))
             .with_state(state)
     }
 
@@ -109,32 +242,89 @@ async fn main() {
     fn protected_api_routes<S>(state: AppState) -> Router<S> {
         Router::new()
             .route("/log-out", post(log_out))
+            .route("/csrf-token", get(get_csrf_token))
+            .route("/password/change", post(change_password))
+            .route("/email/get-own", get(get_own_email))
+            .route("/email/change", post(change_email))
+            .route(
+                "/email/resend-verification",
+                post(resend_email_verification),
+            )
+            .route("/email/remove", delete(remove_email))
+            .route("/2fa/status", get(get_two_factor_status))
+            .route("/2fa/enrol", post(enrol_two_factor))
+            .route("/2fa/activate", post(activate_two_factor))
+            .route("/2fa/disable", post(disable_two_factor))
+            .route(
+                "/2fa/recovery-codes/regenerate",
+                post(regenerate_recovery_codes),
+            )
             .route("/users/list", get(list_users))
             .route("/users/create", post(create_user))
             .route("/users/delete-by-id/{id}", delete(delete_user_by_id))
             .route("/users/delete-own", delete(delete_own_user))
+            .route(
+                "/users/create-password-reset/{id}",
+                post(create_password_reset_for_user),
+            )
+            .route(
+                "/users/force-password-reset/{id}",
+                post(force_password_reset),
+            )
+            .route("/users/reset-2fa/{id}", post(reset_two_factor_for_user))
+            .route("/users/unlock-login/{id}", post(unlock_user_login))
+            .route("/impersonation/start/{user_id}", post(start_impersonation))
+            .route("/impersonation/stop", post(stop_impersonation))
+            .route("/impersonation/status", get(get_impersonation_status))
             .route("/invites-to-club/create", post(create_service_invite))
             .route(
                 "/invites-to-club/delete-by-id/{id}",
                 delete(delete_service_invite_by_id),
             )
             .route("/clubs/delete-own", delete(delete_own_club))
+            .route(
+                "/clubs/set-require-admin-2fa",
+                post(set_require_admin_two_factor),
+            )
+            .route("/audit", get(list_audit_log))
+            .route("/clubs/oidc", get(get_oidc_provider))
+            .route("/clubs/oidc/set", post(set_oidc_provider))
+            .route("/clubs/oidc/remove", delete(remove_oidc_provider))
+            .route("/oidc/link", post(start_oidc_link))
             //
             .route("/roles/list", get(list_role_assignments))
             .route("/roles/list-own", get(list_own_role_assignments))
             .route("/roles/assign", post(assign_role))
             .route("/roles/unassign", delete(unassign_role))
+            .route("/permissions/list-own", get(list_own_permissions))
             //
             .nest("/teams", team_router(state.clone()))
             //
             .nest("/games", game_router(state.clone()))
             //
             .route("/game-invites/list-own", get(list_own_game_invites))
+            .route(
+                "/game-invites/list-for-minors",
+                get(list_minors_game_invites),
+            )
             .route(
                 "/game-invites/list-to-game/{game_id}",
                 get(list_invites_to_game),
             )
             .route("/game-invites/respond", post(answer_invite_to_game))
+            .nest("/guardianships", guardianship_router(state.clone()))
+            .nest("/absences", absence_router(state.clone()))
+            .nest(
+                "/referee-assignments",
+                referee_assignment_router(state.clone()),
+            )
+            //
+            .nest("/api-tokens", api_token_router(state.clone()))
+            //
+            .nest(
+                "/push-subscriptions",
+                push_subscription_router(state.clone()),
+            )
             //
             .with_state(state)
     }
@@ -145,7 +335,7 @@ async fn main() {
                 "/user",
                 protected_api_routes(state.clone()).layer(middleware::from_fn_with_state(
                     state.clone(),
-                    cookie_auth_middleware,
+                    auth_middleware,
                 )),
             )
             // .nest(
@@ -156,30 +346,24 @@ async fn main() {
             //     )),
             // )
             .nest("/auth", unprotected_api_routes(state.clone()))
+            // so that unknown API routes don't end up at the frontend
+            .fallback(|| async { ApiError::NotFound("no such route".to_string()) })
             .with_state(state)
     }
 
     // two lines below and their respective imports are necessary to remove trailing slashes from URLs (otherwise routes with and without them are treated as separate)
     // see https://github.com/tokio-rs/axum/issues/2659
     let app = NormalizePathLayer::trim_trailing_slash().layer(app);
-    let app = ServiceExt::<Request>::into_make_service(app);
+    // client IPs are needed for log-in throttling
+    let app = ServiceExt::<Request>::into_make_service_with_connect_info::<SocketAddr>(app);
 
-    info!("running rust server on localhost:3333");
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3333").await.unwrap();
+    info!(address = %config.server.bind_address, "running rust server");
+    let listener = match tokio::net::TcpListener::bind(config.server.bind_address).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!(address = %config.server.bind_address, error = %err, "failed to bind");
+            std::process::exit(1);
+        }
+    };
     axum::serve(listener, app).await.unwrap();
 }
-
-async fn logging_middleware(req: Request, next: Next) -> Response {
-    info!(
-        "request received, path: {}",
-        req.uri().path_and_query().unwrap()
-    );
-
-    return next.run(req).await;
-}
-
-// the input to our `create_user` handler
-#[derive(Deserialize, sqlx::FromRow)]
-pub struct JustId {
-    pub id: String,
-}
@@ -0,0 +1,220 @@
+//! Prometheus metrics, served in the text format at `/metrics`.
+//!
+//! Requests are labelled with their route template (`/api/user/games/delete-by-id/{id}`), never
+//! the concrete path, so the number of series stays bounded. Pool and session gauges are read
+//! when scraped, domain counters are bumped by the handlers via `AppState::metrics`.
+
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use subtle::ConstantTimeEq;
+
+use crate::utils::{api::AppState, error::ApiError};
+
+const NAMESPACE: &str = "sports_planner";
+/// requests no route matched, e.g. frontend files
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Cheap to clone, all clones count into the same registry.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    db_pool_connections: IntGaugeVec,
+    db_pool_max_connections: IntGauge,
+    active_sessions: IntGauge,
+    games_created: IntCounter,
+    game_invites_answered: IntCounterVec,
+    notifications_sent: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let opts = |name: &str, help: &str| Opts::new(name, help).namespace(NAMESPACE);
+
+        let http_requests = IntCounterVec::new(
+            opts("http_requests_total", "HTTP requests by route and status"),
+            &["method", "route", "status"],
+        )
+        .expect("valid metric");
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "time to the response head by route and status",
+            )
+            .namespace(NAMESPACE),
+            &["method", "route", "status"],
+        )
+        .expect("valid metric");
+        let db_pool_connections = IntGaugeVec::new(
+            opts("db_pool_connections", "open database connections by state"),
+            &["state"],
+        )
+        .expect("valid metric");
+        let db_pool_max_connections = IntGauge::with_opts(opts(
+            "db_pool_max_connections",
+            "configured limit of database connections",
+        ))
+        .expect("valid metric");
+        let active_sessions = IntGauge::with_opts(opts(
+            "sessions_active",
+            "log-in sessions that haven't expired",
+        ))
+        .expect("valid metric");
+        let games_created = IntCounter::with_opts(opts("games_created_total", "games created"))
+            .expect("valid metric");
+        let game_invites_answered = IntCounterVec::new(
+            opts("game_invites_answered_total", "answers to game invites"),
+            &["response"],
+        )
+        .expect("valid metric");
+        let notifications_sent = IntCounterVec::new(
+            opts(
+                "notifications_sent_total",
+                "emails and push messages by outcome",
+            ),
+            &["channel", "outcome"],
+        )
+        .expect("valid metric");
+
+        for collector in [
+            Box::new(http_requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(http_request_duration.clone()),
+            Box::new(db_pool_connections.clone()),
+            Box::new(db_pool_max_connections.clone()),
+            Box::new(active_sessions.clone()),
+            Box::new(games_created.clone()),
+            Box::new(game_invites_answered.clone()),
+            Box::new(notifications_sent.clone()),
+        ] {
+            registry
+                .register(collector)
+                .expect("metric names are unique");
+        }
+
+        Metrics {
+            registry,
+            http_requests,
+            http_request_duration,
+            db_pool_connections,
+            db_pool_max_connections,
+            active_sessions,
+            games_created,
+            game_invites_answered,
+            notifications_sent,
+        }
+    }
+
+    pub fn game_created(&self) {
+        self.games_created.inc();
+    }
+
+    pub fn game_invite_answered(&self, response: &str) {
+        self.game_invites_answered
+            .with_label_values(&[response])
+            .inc();
+    }
+
+    /// `channel` is `email` or `web_push`
+    pub fn notification_sent(&self, channel: &str, outcome: &str) {
+        self.notifications_sent
+            .with_label_values(&[channel, outcome])
+            .inc();
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+/// Counts and times every request, installed on the whole app.
+pub async fn metrics_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+    let method = request.method().to_string();
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    let status = response.status().as_u16().to_string();
+    let labels = [method.as_str(), route.as_str(), status.as_str()];
+    state.metrics.http_requests.with_label_values(&labels).inc();
+    state
+        .metrics
+        .http_request_duration
+        .with_label_values(&labels)
+        .observe(started.elapsed().as_secs_f64());
+    response
+}
+
+pub async fn serve_metrics(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    if let Some(token) = &state.config.server.metrics_token {
+        let given = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        if !bool::from(given.as_bytes().ct_eq(token.as_bytes())) {
+            return Err(ApiError::Unauthorized("metrics token required".to_string()));
+        }
+    }
+
+    let metrics = &state.metrics;
+    let pool = &state.pg_pool;
+    let idle = pool.num_idle() as i64;
+    metrics
+        .db_pool_connections
+        .with_label_values(&["idle"])
+        .set(idle);
+    metrics
+        .db_pool_connections
+        .with_label_values(&["in_use"])
+        .set(i64::from(pool.size()) - idle);
+    metrics
+        .db_pool_max_connections
+        .set(i64::from(state.config.database.max_connections));
+    let active_sessions = sqlx::query_scalar!(
+        r#"SELECT count(*) AS "count!" FROM sessions WHERE expires_at > now()"#
+    )
+    .fetch_one(pool)
+    .await?;
+    metrics.active_sessions.set(active_sessions);
+
+    let encoder = TextEncoder::new();
+    let mut body = vec![];
+    encoder
+        .encode(&metrics.registry.gather(), &mut body)
+        .map_err(|err| ApiError::Internal(format!("failed to encode metrics: {}", err)))?;
+    Ok((
+        StatusCode::OK,
+        [(CONTENT_TYPE, encoder.format_type().to_string())],
+        body,
+    )
+        .into_response())
+}
@@ -0,0 +1,95 @@
+//! Outgoing email. Without SMTP configuration mails are only written to the log,
+//! which is enough for local development. Bodies hold reset and verification links, so they are
+//! only logged in debug builds.
+
+use std::fmt;
+
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use log::{debug, info, warn};
+
+use crate::config::EmailConfig;
+
+#[derive(Debug)]
+pub enum MailError {
+    InvalidConfig(String),
+    InvalidAddress(String),
+    Send(String),
+}
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MailError::InvalidConfig(reason) => write!(f, "invalid mail config: {}", reason),
+            MailError::InvalidAddress(address) => write!(f, "invalid email address: {}", address),
+            MailError::Send(reason) => write!(f, "failed to send email: {}", reason),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum Mailer {
+    Smtp {
+        transport: Box<AsyncSmtpTransport<Tokio1Executor>>,
+        from: Mailbox,
+    },
+    /// dev fallback - prints mails instead of sending them
+    Log,
+}
+
+impl Mailer {
+    pub fn from_config(config: &EmailConfig) -> Result<Self, MailError> {
+        let Some(smtp_url) = &config.smtp_url else {
+            warn!("SMTP_URL is not configured - emails will only be logged");
+            return Ok(Mailer::Log);
+        };
+        let from = config
+            .mail_from
+            .as_ref()
+            .ok_or_else(|| MailError::InvalidConfig("MAIL_FROM is not configured".into()))?;
+        let from = from
+            .parse::<Mailbox>()
+            .map_err(|_| MailError::InvalidConfig(format!("MAIL_FROM is invalid: {}", from)))?;
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::from_url(smtp_url)
+            .map_err(|err| MailError::InvalidConfig(format!("SMTP_URL is invalid: {}", err)))?
+            .build();
+        let transport = Box::new(transport);
+
+        Ok(Mailer::Smtp { transport, from })
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, body: String) -> Result<(), MailError> {
+        match self {
+            Mailer::Log => {
+                info!(
+                    "email to {} not sent, SMTP is not configured: {}",
+                    to, subject
+                );
+                if cfg!(debug_assertions) {
+                    debug!("body of the email to {}:\n\n{}", to, body);
+                }
+                Ok(())
+            }
+            Mailer::Smtp { transport, from } => {
+                let to = to
+                    .parse::<Mailbox>()
+                    .map_err(|_| MailError::InvalidAddress(to.to_string()))?;
+                let message = Message::builder()
+                    .from(from.clone())
+                    .to(to)
+                    .subject(subject)
+                    .header(ContentType::TEXT_PLAIN)
+                    .body(body)
+                    .map_err(|err| MailError::Send(err.to_string()))?;
+
+                transport
+                    .send(message)
+                    .await
+                    .map_err(|err| MailError::Send(err.to_string()))?;
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+pub mod email;
+pub mod web_push;
+
+use std::sync::Arc;
+
+use log::{debug, error, warn};
+use serde::Serialize;
+use sqlx::PgPool;
+use tokio::{sync::Semaphore, task::JoinSet};
+use tracing::Instrument;
+
+use crate::{
+    metrics::Metrics,
+    notifications::web_push::{DeliveryOutcome, SubscriptionKeys, WebPush},
+    utils::api::AppState,
+};
+
+/// push services contacted at the same time per notification
+const MAX_CONCURRENT_DELIVERIES: usize = 8;
+
+/// What the service worker receives and shows on the device.
+#[derive(Debug, Clone, Serialize)]
+pub struct PushMessage {
+    pub title: String,
+    pub body: String,
+    /// frontend route to open when the notification is clicked
+    pub url: Option<String>,
+    /// notifications sharing a tag replace each other instead of stacking up
+    pub tag: Option<String>,
+}
+
+struct PushSubscriptionRow {
+    id: String,
+    endpoint: String,
+    p256dh: String,
+    auth: String,
+}
+
+/// Sends `message` to every registered device of the given users.
+/// Delivery runs in the background, so a slow or failing push service never fails the calling request.
+pub fn notify_users(state: &AppState, user_ids: Vec<String>, message: PushMessage) {
+    let Some(web_push) = state.web_push.clone() else {
+        return;
+    };
+    if user_ids.is_empty() {
+        return;
+    }
+
+    let pool = state.pg_pool.clone();
+    let metrics = state.metrics.clone();
+    // still logged under the request that caused it
+    tokio::spawn(
+        async move { deliver(pool, web_push, metrics, user_ids, message).await }.in_current_span(),
+    );
+}
+
+async fn deliver(
+    pool: PgPool,
+    web_push: WebPush,
+    metrics: Metrics,
+    user_ids: Vec<String>,
+    message: PushMessage,
+) {
+    let subscriptions = match sqlx::query_as!(
+        PushSubscriptionRow,
+        r#"SELECT id, endpoint, p256dh, auth FROM push_subscriptions WHERE user_id = ANY($1)"#,
+        &user_ids
+    )
+    .fetch_all(&pool)
+    .await
+    {
+        Ok(subscriptions) => subscriptions,
+        Err(err) => {
+            error!("failed to load push subscriptions: {}", err);
+            return;
+        }
+    };
+
+    let payload = match serde_json::to_vec(&message) {
+        Ok(payload) => payload,
+        Err(err) => {
+            error!("failed to serialize push message: {}", err);
+            return;
+        }
+    };
+
+    // one slow push service must not hold up the other devices
+    let payload = Arc::new(payload);
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_DELIVERIES));
+    let mut deliveries = JoinSet::new();
+    for subscription in subscriptions {
+        let web_push = web_push.clone();
+        let payload = payload.clone();
+        let permits = permits.clone();
+        deliveries.spawn(
+            async move {
+                let _permit = permits.acquire_owned().await;
+                let outcome =
+                    match SubscriptionKeys::parse(&subscription.p256dh, &subscription.auth) {
+                        Ok(keys) => web_push.send(&subscription.endpoint, &keys, &payload).await,
+                        Err(err) => Err(err),
+                    };
+                (subscription, outcome)
+            }
+            .in_current_span(),
+        );
+    }
+
+    while let Some(delivery) = deliveries.join_next().await {
+        let (subscription, outcome) = match delivery {
+            Ok(delivery) => delivery,
+            Err(err) => {
+                error!("push delivery task failed: {}", err);
+                continue;
+            }
+        };
+
+        let label = match &outcome {
+            Ok(DeliveryOutcome::Delivered) => "sent",
+            Ok(DeliveryOutcome::Rejected(_)) => "rejected",
+            Ok(DeliveryOutcome::Gone) => "gone",
+            Err(_) => "failed",
+        };
+        metrics.notification_sent("web_push", label);
+
+        match outcome {
+            Ok(DeliveryOutcome::Delivered) | Ok(DeliveryOutcome::Rejected(_)) => {}
+            Ok(DeliveryOutcome::Gone) => {
+                debug!("push subscription {} expired, removing it", subscription.id);
+                prune_subscription(&pool, &subscription.id).await;
+            }
+            Err(err) => warn!(
+                "push delivery to subscription {} failed: {}",
+                subscription.id, err
+            ),
+        }
+    }
+}
+
+async fn prune_subscription(pool: &PgPool, subscription_id: &str) {
+    if let Err(err) = sqlx::query!(
+        "DELETE FROM push_subscriptions WHERE id = $1",
+        subscription_id
+    )
+    .execute(pool)
+    .await
+    {
+        error!(
+            "failed to remove push subscription {}: {}",
+            subscription_id, err
+        );
+    }
+}
+
+/// Sends an email without holding up the request - failures are only logged.
+pub fn send_email_in_background(state: &AppState, to: String, subject: String, body: String) {
+    let mailer = state.mailer.clone();
+    let metrics = state.metrics.clone();
+    tokio::spawn(
+        async move {
+            match mailer.send(&to, &subject, body).await {
+                Ok(()) => metrics.notification_sent("email", "sent"),
+                Err(err) => {
+                    metrics.notification_sent("email", "failed");
+                    error!("{}", err);
+                }
+            }
+        }
+        .in_current_span(),
+    );
+}
@@ -0,0 +1,436 @@
+//! Web Push delivery (RFC 8030) with VAPID authentication (RFC 8292)
+//! and `aes128gcm` payload encryption (RFC 8291, RFC 8188).
+
+use std::{fmt, time::Duration};
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, OsRng},
+    Aes128Gcm, Nonce,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hkdf::Hkdf;
+use log::{info, warn};
+use p256::{
+    ecdh::diffie_hellman,
+    ecdsa::{signature::Signer, Signature, SigningKey},
+    elliptic_curve::sec1::ToEncodedPoint,
+    PublicKey, SecretKey,
+};
+use rand::RngCore;
+use reqwest::{StatusCode, Url};
+use sha2::Sha256;
+
+use crate::config::WebPushConfig;
+
+/// size of the single record we send - payload, padding delimiter and auth tag must fit
+const RECORD_SIZE: u32 = 4096;
+/// salt (16) + record size (4) + key id length (1) + uncompressed P-256 key (65)
+const HEADER_LEN: usize = 86;
+const TAG_LEN: usize = 16;
+/// push services reject bodies above 4096 bytes, see RFC 8291 section 4
+pub const MAX_PAYLOAD_LEN: usize = RECORD_SIZE as usize - HEADER_LEN - TAG_LEN - 1;
+
+/// how long the push service keeps an undelivered message around
+const TTL_SECONDS: u32 = 24 * 60 * 60;
+/// VAPID tokens may be valid for at most 24h, stay well below that
+const VAPID_TOKEN_VALIDITY_SECONDS: i64 = 12 * 60 * 60;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// a push service that takes longer is treated as failed, the message is not retried
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+/// enough of a rejection to see why, the rest of whatever the push service sends is dropped
+const MAX_LOGGED_BODY_LEN: usize = 256;
+
+/// Hosts of the push services browsers subscribe at, subdomains included. Endpoints elsewhere are
+/// refused, so subscriptions can't make the server send requests to arbitrary hosts.
+const PUSH_SERVICE_HOSTS: &[&str] = &[
+    // Chrome, Edge, Opera and other Chromium browsers
+    "fcm.googleapis.com",
+    "android.googleapis.com",
+    // Firefox
+    "push.services.mozilla.com",
+    // Safari
+    "push.apple.com",
+    // legacy Edge
+    "notify.windows.com",
+];
+
+/// Whether `endpoint` is an https URL of a known push service.
+pub fn is_push_service_endpoint(endpoint: &str) -> bool {
+    let Ok(url) = Url::parse(endpoint) else {
+        return false;
+    };
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    url.scheme() == "https"
+        && PUSH_SERVICE_HOSTS.iter().any(|service| {
+            host == *service
+                || host
+                    .strip_suffix(service)
+                    .is_some_and(|subdomain| subdomain.ends_with('.'))
+        })
+}
+
+#[derive(Debug)]
+pub enum WebPushError {
+    /// VAPID private key missing or malformed
+    InvalidVapidKey(String),
+    /// subscription keys from the browser are malformed
+    InvalidSubscription(String),
+    PayloadTooLarge(usize),
+    Encryption,
+    Http(reqwest::Error),
+}
+
+impl fmt::Display for WebPushError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebPushError::InvalidVapidKey(reason) => write!(f, "invalid VAPID key: {}", reason),
+            WebPushError::InvalidSubscription(reason) => {
+                write!(f, "invalid push subscription: {}", reason)
+            }
+            WebPushError::PayloadTooLarge(len) => write!(
+                f,
+                "push payload of {} bytes exceeds the maximum of {}",
+                len, MAX_PAYLOAD_LEN
+            ),
+            WebPushError::Encryption => write!(f, "failed to encrypt push payload"),
+            WebPushError::Http(err) => write!(f, "push service request failed: {}", err),
+        }
+    }
+}
+
+/// Keys of a browser `PushSubscription`, decoded and checked.
+pub struct SubscriptionKeys {
+    ua_public: PublicKey,
+    auth_secret: [u8; 16],
+}
+
+impl SubscriptionKeys {
+    /// Parses the base64url `p256dh` and `auth` values exactly as `PushSubscription.toJSON()` returns them.
+    pub fn parse(p256dh: &str, auth: &str) -> Result<Self, WebPushError> {
+        let p256dh = URL_SAFE_NO_PAD
+            .decode(p256dh.trim_end_matches('='))
+            .map_err(|_| WebPushError::InvalidSubscription("p256dh is not base64url".into()))?;
+        let ua_public = PublicKey::from_sec1_bytes(&p256dh).map_err(|_| {
+            WebPushError::InvalidSubscription("p256dh is not a P-256 public key".into())
+        })?;
+
+        let auth = URL_SAFE_NO_PAD
+            .decode(auth.trim_end_matches('='))
+            .map_err(|_| WebPushError::InvalidSubscription("auth is not base64url".into()))?;
+        let auth_secret: [u8; 16] = auth
+            .try_into()
+            .map_err(|_| WebPushError::InvalidSubscription("auth must be 16 bytes".into()))?;
+
+        Ok(SubscriptionKeys {
+            ua_public,
+            auth_secret,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DeliveryOutcome {
+    Delivered,
+    /// the push service no longer knows the subscription (404/410) - it should be deleted
+    Gone,
+    Rejected(StatusCode),
+}
+
+/// Application server identity plus HTTP client used to talk to push services.
+#[derive(Clone)]
+pub struct WebPush {
+    signing_key: SigningKey,
+    /// uncompressed public key, base64url - handed to browsers as `applicationServerKey`
+    public_key: String,
+    /// contact for push service operators, `mailto:` or `https:` URL
+    subject: String,
+    http: reqwest::Client,
+}
+
+impl WebPush {
+    /// `private_key` is the raw 32-byte P-256 scalar, base64url-encoded
+    /// (the format produced by e.g. `npx web-push generate-vapid-keys`).
+    pub fn new(private_key: &str, subject: String) -> Result<Self, WebPushError> {
+        let raw = URL_SAFE_NO_PAD
+            .decode(private_key.trim().trim_end_matches('='))
+            .map_err(|_| WebPushError::InvalidVapidKey("not base64url".into()))?;
+        let signing_key = SigningKey::from_slice(&raw)
+            .map_err(|_| WebPushError::InvalidVapidKey("not a P-256 private key".into()))?;
+
+        if !(subject.starts_with("mailto:") || subject.starts_with("https://")) {
+            return Err(WebPushError::InvalidVapidKey(
+                "subject must be a mailto: or https: URL".into(),
+            ));
+        }
+
+        let public_key = URL_SAFE_NO_PAD.encode(
+            signing_key
+                .verifying_key()
+                .to_encoded_point(false)
+                .as_bytes(),
+        );
+
+        let http = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            // a push service never redirects, anything else could point the request elsewhere
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(WebPushError::Http)?;
+
+        Ok(WebPush {
+            signing_key,
+            public_key,
+            subject,
+            http,
+        })
+    }
+
+    /// Web Push stays disabled when no key is configured, a configured but broken key is a startup
+    /// error.
+    pub fn from_config(config: &WebPushConfig) -> Result<Option<Self>, WebPushError> {
+        let Some(private_key) = &config.vapid_private_key else {
+            info!("VAPID_PRIVATE_KEY is not configured - web push notifications are disabled");
+            return Ok(None);
+        };
+        let subject = config.vapid_subject.clone().ok_or_else(|| {
+            WebPushError::InvalidVapidKey("VAPID_SUBJECT is not configured".into())
+        })?;
+
+        WebPush::new(private_key, subject).map(Some)
+    }
+
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    pub async fn send(
+        &self,
+        endpoint: &str,
+        keys: &SubscriptionKeys,
+        payload: &[u8],
+    ) -> Result<DeliveryOutcome, WebPushError> {
+        if !is_push_service_endpoint(endpoint) {
+            return Err(WebPushError::InvalidSubscription(
+                "endpoint is not at a known push service".into(),
+            ));
+        }
+        let url = Url::parse(endpoint)
+            .map_err(|_| WebPushError::InvalidSubscription("endpoint is not a URL".into()))?;
+        let body = encrypt(keys, payload)?;
+        let authorization = format!(
+            "vapid t={}, k={}",
+            self.vapid_token(&url.origin().ascii_serialization()),
+            self.public_key
+        );
+
+        let response = self
+            .http
+            .post(url)
+            .header("Authorization", authorization)
+            .header("Content-Encoding", "aes128gcm")
+            .header("Content-Type", "application/octet-stream")
+            .header("TTL", TTL_SECONDS.to_string())
+            .body(body)
+            .send()
+            .await
+            .map_err(WebPushError::Http)?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(DeliveryOutcome::Delivered);
+        }
+        if status == StatusCode::NOT_FOUND || status == StatusCode::GONE {
+            return Ok(DeliveryOutcome::Gone);
+        }
+
+        warn!(
+            "push service rejected message with {}: {}",
+            status,
+            body_excerpt(response).await
+        );
+        Ok(DeliveryOutcome::Rejected(status))
+    }
+
+    /// ES256-signed JWT as described in RFC 8292 section 2
+    fn vapid_token(&self, audience: &str) -> String {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"typ":"JWT","alg":"ES256"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(
+            serde_json::json!({
+                "aud": audience,
+                "exp": chrono::Utc::now().timestamp() + VAPID_TOKEN_VALIDITY_SECONDS,
+                "sub": self.subject,
+            })
+            .to_string(),
+        );
+        let signing_input = format!("{}.{}", header, claims);
+        let signature: Signature = self.signing_key.sign(signing_input.as_bytes());
+
+        format!(
+            "{}.{}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        )
+    }
+}
+
+/// The start of the response body, without reading more than `MAX_LOGGED_BODY_LEN` of it.
+async fn body_excerpt(mut response: reqwest::Response) -> String {
+    let mut body = Vec::new();
+    while body.len() < MAX_LOGGED_BODY_LEN {
+        match response.chunk().await {
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+            _ => break,
+        }
+    }
+    body.truncate(MAX_LOGGED_BODY_LEN);
+    String::from_utf8_lossy(&body).into_owned()
+}
+
+/// Encrypts `payload` into a single `aes128gcm` record, see RFC 8291 section 3.4.
+fn encrypt(keys: &SubscriptionKeys, payload: &[u8]) -> Result<Vec<u8>, WebPushError> {
+    let mut salt = [0u8; 16];
+    rand::rng().fill_bytes(&mut salt);
+    encrypt_with(keys, payload, &SecretKey::random(&mut OsRng), salt)
+}
+
+/// [`encrypt`] with a given key pair and salt, both must be fresh for every message
+fn encrypt_with(
+    keys: &SubscriptionKeys,
+    payload: &[u8],
+    as_secret: &SecretKey,
+    salt: [u8; 16],
+) -> Result<Vec<u8>, WebPushError> {
+    if payload.len() > MAX_PAYLOAD_LEN {
+        return Err(WebPushError::PayloadTooLarge(payload.len()));
+    }
+
+    let as_public = as_secret.public_key().to_encoded_point(false);
+    let ua_public = keys.ua_public.to_encoded_point(false);
+    let ecdh_secret = diffie_hellman(as_secret.to_nonzero_scalar(), keys.ua_public.as_affine());
+
+    // combine the ECDH secret with the subscription's auth secret
+    let mut key_info = Vec::with_capacity(144);
+    key_info.extend_from_slice(b"WebPush: info\0");
+    key_info.extend_from_slice(ua_public.as_bytes());
+    key_info.extend_from_slice(as_public.as_bytes());
+    let mut ikm = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&keys.auth_secret), ecdh_secret.raw_secret_bytes())
+        .expand(&key_info, &mut ikm)
+        .map_err(|_| WebPushError::Encryption)?;
+
+    // derive content encryption key and nonce from the salt
+    let prk = Hkdf::<Sha256>::new(Some(&salt), &ikm);
+    let mut cek = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    prk.expand(b"Content-Encoding: aes128gcm\0", &mut cek)
+        .map_err(|_| WebPushError::Encryption)?;
+    prk.expand(b"Content-Encoding: nonce\0", &mut nonce)
+        .map_err(|_| WebPushError::Encryption)?;
+
+    // 0x02 marks the last (and only) record, no further padding
+    let mut plaintext = Vec::with_capacity(payload.len() + 1);
+    plaintext.extend_from_slice(payload);
+    plaintext.push(0x02);
+
+    let ciphertext = Aes128Gcm::new_from_slice(&cek)
+        .map_err(|_| WebPushError::Encryption)?
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| WebPushError::Encryption)?;
+
+    let mut body = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(as_public.as_bytes().len() as u8);
+    body.extend_from_slice(as_public.as_bytes());
+    body.extend_from_slice(&ciphertext);
+
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use p256::ecdsa::{signature::Verifier, VerifyingKey};
+
+    use super::*;
+
+    fn decode(value: &str) -> Vec<u8> {
+        URL_SAFE_NO_PAD.decode(value).unwrap()
+    }
+
+    /// the example of RFC 8291 section 5
+    #[test]
+    fn encrypts_the_rfc_8291_example() {
+        let keys = SubscriptionKeys::parse(
+            "BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4",
+            "BTBZMqHH6r4Tts7J_aSIgg",
+        )
+        .unwrap();
+        let as_secret =
+            SecretKey::from_slice(&decode("yfWPiYE-n46HLnH0KqZOF1fJJU3MYrct3AELtAQ-oRw")).unwrap();
+        let salt = decode("DGv6ra1nlYgDCS1FRnbzlw").try_into().unwrap();
+
+        let body = encrypt_with(
+            &keys,
+            b"When I grow up, I want to be a watermelon",
+            &as_secret,
+            salt,
+        )
+        .unwrap();
+
+        assert_eq!(
+            URL_SAFE_NO_PAD.encode(body),
+            "DGv6ra1nlYgDCS1FRnbzlwAAEABBBP4z9KsN6nGRTbVYI_c7VJSPQTBtkgcy27mlmlMoZIIgDll6e3vCYLocInmYWAmS6TlzAC8wEqKK6PBru3jl7A_yl95bQpu6cVPTpK4Mqgkf1CXztLVBSt2Ks3oZwbuwXPXLWyouBWLVWGNWQexSgSxsj_Qulcy4a-fN"
+        );
+    }
+
+    #[test]
+    fn signs_vapid_tokens_for_the_push_service() {
+        let web_push = WebPush::new(
+            "yfWPiYE-n46HLnH0KqZOF1fJJU3MYrct3AELtAQ-oRw",
+            "mailto:admin@example.com".to_string(),
+        )
+        .unwrap();
+
+        let token = web_push.vapid_token("https://fcm.googleapis.com");
+        let (signing_input, signature) = token.rsplit_once('.').unwrap();
+        let claims: serde_json::Value =
+            serde_json::from_slice(&decode(signing_input.split_once('.').unwrap().1)).unwrap();
+        assert_eq!(claims["aud"], "https://fcm.googleapis.com");
+        assert_eq!(claims["sub"], "mailto:admin@example.com");
+
+        let verifying_key = VerifyingKey::from_sec1_bytes(&decode(web_push.public_key())).unwrap();
+        let signature = Signature::from_slice(&decode(signature)).unwrap();
+        assert!(verifying_key
+            .verify(signing_input.as_bytes(), &signature)
+            .is_ok());
+    }
+
+    #[test]
+    fn only_accepts_known_push_services() {
+        assert!(is_push_service_endpoint(
+            "https://fcm.googleapis.com/fcm/send/abc"
+        ));
+        assert!(is_push_service_endpoint(
+            "https://updates.push.services.mozilla.com/wpush/v2/abc"
+        ));
+        assert!(is_push_service_endpoint("https://web.push.apple.com/abc"));
+        assert!(!is_push_service_endpoint("http://fcm.googleapis.com/abc"));
+        assert!(!is_push_service_endpoint("https://evilpush.apple.com/abc"));
+        assert!(!is_push_service_endpoint("https://127.0.0.1/abc"));
+        assert!(!is_push_service_endpoint("https://169.254.169.254/latest"));
+    }
+
+    #[tokio::test]
+    async fn logs_only_the_start_of_a_rejection() {
+        let response = reqwest::Response::from(axum::http::Response::new("x".repeat(10_000)));
+
+        assert_eq!(
+            body_excerpt(response).await,
+            "x".repeat(MAX_LOGGED_BODY_LEN)
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! The OpenAPI document of the API, served at `/api/openapi.json` with a docs UI at `/api/docs`.
+//!
+//! Handlers carry `#[utoipa::path]` with paths relative to the router they're mounted in, the
+//! `*Api` structs here and next to the nested routers mirror that nesting. `openapi.json` at the
+//! repository root is the checked-in copy the TS client is generated from, the test below fails
+//! when it's outdated; `UPDATE_OPENAPI=1 cargo test` rewrites it.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::Path,
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+    Router,
+};
+use utoipa::{
+    openapi::{
+        security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+        ContentBuilder, HttpMethod, RefOr, ResponseBuilder, SecurityRequirement,
+    },
+    Modify, OpenApi,
+};
+use utoipa_swagger_ui::Config;
+
+use crate::{
+    auth::{
+        api_tokens::ApiTokenApi, auth_routes, csrf, email, impersonation, login_throttle, oidc,
+        password, permissions, roles, two_factor, utils::SESSION_COOKIE,
+    },
+    entities::{
+        absence::AbsenceApi, audit_log, club, game::GameApi, game_invite,
+        guardianship::GuardianshipApi, push_subscription::PushSubscriptionApi,
+        referee_assignment::RefereeAssignmentApi, service_invite, team::TeamApi, user,
+    },
+    utils::error::{ApiError, ErrorBody},
+};
+
+const SPEC_PATH: &str = "/api/openapi.json";
+const PROTECTED_PREFIX: &str = "/api/user/";
+
+/// `/api/auth`, no session needed
+#[derive(OpenApi)]
+#[openapi(paths(
+    auth_routes::log_in,
+    two_factor::complete_two_factor_log_in,
+    auth_routes::sign_up_with_new_club,
+    auth_routes::sign_up_via_invite,
+    password::request_password_reset,
+    password::confirm_password_reset,
+    email::verify_email,
+    oidc::start_oidc_log_in,
+    oidc::oidc_callback,
+))]
+struct AuthApi;
+
+/// `/api/user`, behind the session or an API token
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        auth_routes::log_out,
+        csrf::get_csrf_token,
+        password::change_password,
+        email::get_own_email,
+        email::change_email,
+        email::resend_email_verification,
+        email::remove_email,
+        two_factor::get_two_factor_status,
+        two_factor::enrol_two_factor,
+        two_factor::activate_two_factor,
+        two_factor::disable_two_factor,
+        two_factor::regenerate_recovery_codes,
+        user::list_users,
+        user::create_user,
+        user::delete_user_by_id,
+        user::delete_own_user,
+        password::create_password_reset_for_user,
+        password::force_password_reset,
+        two_factor::reset_two_factor_for_user,
+        login_throttle::unlock_user_login,
+        impersonation::start_impersonation,
+        impersonation::stop_impersonation,
+        impersonation::get_impersonation_status,
+        service_invite::create_service_invite,
+        service_invite::delete_service_invite_by_id,
+        club::delete_own_club,
+        two_factor::set_require_admin_two_factor,
+        audit_log::list_audit_log,
+        oidc::get_oidc_provider,
+        oidc::set_oidc_provider,
+        oidc::remove_oidc_provider,
+        oidc::start_oidc_link,
+        roles::list_role_assignments,
+        roles::list_own_role_assignments,
+        roles::assign_role,
+        roles::unassign_role,
+        permissions::list_own_permissions,
+        game_invite::list_own_game_invites,
+        game_invite::list_minors_game_invites,
+        game_invite::list_invites_to_game,
+        game_invite::answer_invite_to_game,
+    ),
+    nest(
+        (path = "/teams", api = TeamApi),
+        (path = "/games", api = GameApi),
+        (path = "/guardianships", api = GuardianshipApi),
+        (path = "/absences", api = AbsenceApi),
+        (path = "/referee-assignments", api = RefereeAssignmentApi),
+        (path = "/api-tokens", api = ApiTokenApi),
+        (path = "/push-subscriptions", api = PushSubscriptionApi),
+    )
+)]
+struct UserApi;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Sports Planner API", description = "Teams, games, invites and referees of sports clubs."),
+    nest(
+        (path = "/api/auth", api = AuthApi),
+        (path = "/api/user", api = UserApi),
+    ),
+    components(schemas(ErrorBody)),
+    modifiers(&SecurityAndErrors)
+)]
+pub struct ApiDoc;
+
+/// Adds how to authenticate, and the error body every operation may answer with.
+struct SecurityAndErrors;
+
+impl Modify for SecurityAndErrors {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "session",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(SESSION_COOKIE))),
+        );
+        components.add_security_scheme(
+            "csrf",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(csrf::CSRF_HEADER))),
+        );
+        components.add_security_scheme(
+            "api_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        let error = ResponseBuilder::new()
+            .description("an error, see `code`")
+            .content(
+                "application/json",
+                ContentBuilder::new()
+                    .schema(Some(RefOr::Ref(utoipa::openapi::Ref::from_schema_name(
+                        "ErrorBody",
+                    ))))
+                    .build(),
+            )
+            .build();
+
+        for (path, item) in openapi.paths.paths.iter_mut() {
+            let protected = path.starts_with(PROTECTED_PREFIX);
+            let operations = [
+                (HttpMethod::Get, item.get.as_mut()),
+                (HttpMethod::Post, item.post.as_mut()),
+                (HttpMethod::Put, item.put.as_mut()),
+                (HttpMethod::Delete, item.delete.as_mut()),
+            ];
+            for (method, operation) in operations {
+                let Some(operation) = operation else {
+                    continue;
+                };
+                operation
+                    .responses
+                    .responses
+                    .insert("default".to_string(), RefOr::T(error.clone()));
+                if protected {
+                    // sessions need the CSRF token for anything but reads, tokens don't
+                    let session = match method {
+                        HttpMethod::Get => {
+                            SecurityRequirement::new("session", Vec::<String>::new())
+                        }
+                        _ => SecurityRequirement::new("session", Vec::<String>::new())
+                            .add("csrf", Vec::<String>::new()),
+                    };
+                    operation.security = Some(vec![
+                        session,
+                        SecurityRequirement::new("api_token", Vec::<String>::new()),
+                    ]);
+                }
+            }
+        }
+    }
+}
+
+/// The document and the docs UI, outside of the auth middleware. The UI's assets are linked
+/// relative to `/api/docs/`, which the trailing slash trimming of the app would turn into
+/// `/api/docs`, so that redirects to the index page instead.
+pub fn docs_router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    let spec = ApiDoc::openapi()
+        .to_json()
+        .expect("the OpenAPI document serializes");
+    let config = Arc::new(Config::new([SPEC_PATH]));
+    Router::new()
+        .route(
+            SPEC_PATH,
+            get(|| async move { ([(CONTENT_TYPE, "application/json")], spec) }),
+        )
+        .route(
+            "/api/docs",
+            get(|| async { Redirect::to("/api/docs/index.html") }),
+        )
+        .route(
+            "/api/docs/{*file}",
+            get(|Path(file): Path<String>| async move { serve_docs_file(&file, config) }),
+        )
+}
+
+fn serve_docs_file(file: &str, config: Arc<Config<'static>>) -> Result<Response, ApiError> {
+    let file = utoipa_swagger_ui::serve(file, config)
+        .map_err(|err| ApiError::Internal(format!("serving the docs UI failed: {err}")))?
+        .ok_or_else(|| ApiError::NotFound("no such file".to_string()))?;
+    Ok(([(CONTENT_TYPE, file.content_type)], file.bytes.into_owned()).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHECKED_IN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+
+    #[test]
+    fn checked_in_spec_is_up_to_date() {
+        let spec = ApiDoc::openapi()
+            .to_pretty_json()
+            .expect("the spec serializes")
+            + "\n";
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            std::fs::write(CHECKED_IN, &spec).expect("openapi.json is writable");
+            return;
+        }
+        let checked_in = std::fs::read_to_string(CHECKED_IN).unwrap_or_default();
+        assert!(
+            checked_in == spec,
+            "openapi.json is outdated, regenerate it with `UPDATE_OPENAPI=1 cargo test`"
+        );
+    }
+}
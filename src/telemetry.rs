@@ -0,0 +1,70 @@
+//! Structured logs via `tracing`, as text or one JSON object per line.
+//!
+//! Every request runs in a `request` span carrying its id, route and - once authenticated - the
+//! user and club, so everything logged while handling it (including slow statements from sqlx) can
+//! be matched to the response.
+
+use std::{io::IsTerminal, time::Instant};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{field::Empty, info, info_span, warn, Instrument};
+use tracing_subscriber::EnvFilter;
+
+use crate::{
+    config::{LogFormat, LoggingConfig},
+    utils::error::REQUEST_ID,
+};
+
+/// Installs the global subscriber, fails if there already is one.
+pub fn init(config: &LoggingConfig) -> Result<(), String> {
+    let filter = EnvFilter::try_new(&config.filter)
+        .map_err(|err| format!("invalid log filter {}: {}", config.filter, err))?;
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal());
+    match config.format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .try_init(),
+    }
+    .map_err(|err| format!("could not set up logging: {}", err))
+}
+
+/// Wraps a request in its span and logs the outcome. Runs inside `request_id_middleware`.
+pub async fn trace_middleware(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let span = info_span!(
+        "request",
+        request_id = REQUEST_ID.try_with(String::clone).unwrap_or_default(),
+        method = %request.method(),
+        route,
+        user_id = Empty,
+        club_id = Empty,
+    );
+    let started = Instant::now();
+
+    let response = next.run(request).instrument(span.clone()).await;
+
+    let status = response.status().as_u16();
+    let latency_ms = started.elapsed().as_millis() as u64;
+    span.in_scope(|| {
+        if response.status().is_server_error() {
+            warn!(status, latency_ms, "request failed");
+        } else {
+            info!(status, latency_ms, "request handled");
+        }
+    });
+    response
+}
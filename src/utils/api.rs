@@ -1,35 +1,31 @@
-use axum::{
-    http::StatusCode,
-    response::{IntoResponse, Response},
-    Json,
+use std::sync::Arc;
+
+use axum::{http::StatusCode, Json};
+use sqlx::PgPool;
+
+use crate::{
+    config::Config,
+    metrics::Metrics,
+    notifications::{email::Mailer, web_push::WebPush},
+    utils::error::ApiError,
 };
-use log::error;
-use sqlx::{Error, PgPool};
 
-pub type ApiResult<T> = Result<(StatusCode, Json<T>), (StatusCode, String)>;
+pub type ApiResult<T> = Result<(StatusCode, Json<T>), ApiError>;
 
-pub type EmptyApiResult = Result<StatusCode, (StatusCode, String)>;
+pub type EmptyApiResult = Result<StatusCode, ApiError>;
 
 #[derive(Clone)]
 pub struct AppState {
     // FYI: no Arc+Mutex necessary, because pool implements
     // clone and send+sync
     pub pg_pool: PgPool,
-}
-
-pub fn handle_unexpected_db_err(err: Error) -> (StatusCode, String) {
-    error!("{}", err);
-    (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        "Unexpected Error".to_string(),
-    )
-}
-
-pub fn db_err_to_response(err: Error) -> Response {
-    error!("{}", err);
-    (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected Error").into_response()
-}
-
-pub struct JustId {
-    id: String,
+    /// `None` when no VAPID key is configured
+    pub web_push: Option<WebPush>,
+    pub mailer: Mailer,
+    /// signs stateless tokens in emailed links
+    pub token_secret: Vec<u8>,
+    /// for outgoing calls, e.g. to single sign-on providers
+    pub http_client: reqwest::Client,
+    pub config: Arc<Config>,
+    pub metrics: Metrics,
 }
@@ -0,0 +1,105 @@
+//! The address of the client behind the request. Behind a reverse proxy every connection comes
+//! from the proxy, so the client is taken from `X-Forwarded-For` - but only when the connection
+//! comes from one of `server.trusted_proxies`, anyone else could send any address in it.
+
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap},
+};
+use ipnet::IpNet;
+
+use crate::utils::{api::AppState, error::ApiError};
+
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+
+pub struct ClientIp(pub IpAddr);
+
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let ConnectInfo(peer) = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .ok_or_else(|| ApiError::Internal("no connect info on the request".into()))?;
+
+        Ok(ClientIp(client_ip(
+            peer.ip(),
+            &parts.headers,
+            &state.config.server.trusted_proxies,
+        )))
+    }
+}
+
+/// Walks `X-Forwarded-For` from the right, the nearest hop first, and stops at the first address
+/// that isn't a trusted proxy. Entries left of it may be made up by the client.
+pub fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpNet]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+    if !is_trusted(&peer) {
+        return peer;
+    }
+
+    let hops: Vec<&str> = headers
+        .get_all(FORWARDED_FOR_HEADER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+
+    let mut client = peer;
+    for hop in hops.into_iter().rev() {
+        let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+            // a garbled entry ends the chain, whoever wrote it isn't trusted
+            break;
+        };
+        client = ip;
+        if !is_trusted(&ip) {
+            break;
+        }
+    }
+    client
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(peer: &str, forwarded_for: &[&str]) -> String {
+        let mut headers = HeaderMap::new();
+        for value in forwarded_for {
+            headers.append(FORWARDED_FOR_HEADER, value.parse().unwrap());
+        }
+        let trusted = ["10.0.0.0/8".parse().unwrap(), "::1/128".parse().unwrap()];
+        client_ip(peer.parse().unwrap(), &headers, &trusted).to_string()
+    }
+
+    #[test]
+    fn believes_only_trusted_proxies() {
+        // anyone else may send the header
+        assert_eq!(resolve("203.0.113.7", &["198.51.100.1"]), "203.0.113.7");
+        assert_eq!(resolve("10.0.0.1", &[]), "10.0.0.1");
+        assert_eq!(resolve("10.0.0.1", &["198.51.100.1"]), "198.51.100.1");
+        assert_eq!(resolve("::1", &["198.51.100.1, 10.0.0.2"]), "198.51.100.1");
+        assert_eq!(
+            resolve("10.0.0.1", &["198.51.100.1", "10.0.0.2"]),
+            "198.51.100.1"
+        );
+    }
+
+    #[test]
+    fn ignores_what_the_client_put_in_front() {
+        assert_eq!(
+            resolve("10.0.0.1", &["1.2.3.4, 198.51.100.1"]),
+            "198.51.100.1"
+        );
+        assert_eq!(
+            resolve("10.0.0.1", &["1.2.3.4, garbage, 10.0.0.2"]),
+            "10.0.0.2"
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! The error type of all handlers. Every error reaches the client as the same JSON body:
+//!
+//! ```json
+//! { "code": "already_exists", "message": "username is already taken",
+//!   "field_errors": [{ "field": "username", "message": "is already taken" }], "request_id": "..." }
+//! ```
+//!
+//! `code` is stable and meant for programs, `message` for people. Internals (database messages
+//! and the like) are only logged, together with the request id.
+
+use axum::{
+    http::{header::RETRY_AFTER, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use log::{error, warn};
+use serde::Serialize;
+use sqlx::{error::ErrorKind, postgres::PgDatabaseError};
+use utoipa::ToSchema;
+
+tokio::task_local! {
+    /// set by the request id middleware for the duration of a request
+    pub static REQUEST_ID: String;
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    /// a unique constraint was hit, `field` is the clashing input when it's known
+    AlreadyExists {
+        field: Option<String>,
+    },
+    /// the input is well-formed, but its values aren't acceptable
+    Validation {
+        message: String,
+        field_errors: Vec<FieldError>,
+    },
+    /// the input points at something that doesn't exist
+    InvalidReference {
+        field: Option<String>,
+    },
+    TooManyRequests {
+        message: String,
+        retry_after_seconds: i64,
+    },
+    BadGateway(String),
+    ServiceUnavailable(String),
+    /// the string is logged, clients only learn that something went wrong
+    Internal(String),
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    code: &'static str,
+    message: String,
+    field_errors: Vec<FieldError>,
+    request_id: Option<String>,
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) | ApiError::AlreadyExists { .. } => StatusCode::CONFLICT,
+            ApiError::Validation { .. } | ApiError::InvalidReference { .. } => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            ApiError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            ApiError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::AlreadyExists { .. } => "already_exists",
+            ApiError::Validation { .. } => "validation_failed",
+            ApiError::InvalidReference { .. } => "invalid_reference",
+            ApiError::TooManyRequests { .. } => "too_many_requests",
+            ApiError::BadGateway(_) => "bad_gateway",
+            ApiError::ServiceUnavailable(_) => "service_unavailable",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    fn message_and_field_errors(self) -> (String, Vec<FieldError>) {
+        let field_error = |field: Option<String>, message: &str| {
+            field
+                .map(|field| FieldError {
+                    field,
+                    message: message.to_string(),
+                })
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message)
+            | ApiError::BadGateway(message)
+            | ApiError::ServiceUnavailable(message)
+            | ApiError::TooManyRequests { message, .. } => (message, vec![]),
+            ApiError::AlreadyExists { field } => (
+                match &field {
+                    Some(field) => format!("{} is already taken", field),
+                    None => "Already exists".to_string(),
+                },
+                field_error(field, "is already taken"),
+            ),
+            ApiError::Validation {
+                message,
+                field_errors,
+            } => (message, field_errors),
+            ApiError::InvalidReference { field } => (
+                match &field {
+                    Some(field) => format!("{} refers to something that doesn't exist", field),
+                    None => "Refers to something that doesn't exist".to_string(),
+                },
+                field_error(field, "doesn't exist"),
+            ),
+            ApiError::Internal(_) => ("Unexpected Error".to_string(), vec![]),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let request_id = REQUEST_ID.try_with(|id| id.clone()).ok();
+        let status = self.status();
+        let code = self.code();
+        let retry_after = match &self {
+            ApiError::TooManyRequests {
+                retry_after_seconds,
+                ..
+            } => Some(*retry_after_seconds),
+            _ => None,
+        };
+
+        if let ApiError::Internal(detail) = &self {
+            error!(
+                "request {}: {}",
+                request_id.as_deref().unwrap_or("-"),
+                detail
+            );
+        }
+
+        let (message, field_errors) = self.message_and_field_errors();
+        let body = Json(ErrorBody {
+            code,
+            message,
+            field_errors,
+            request_id,
+        });
+
+        match retry_after {
+            Some(seconds) => (status, [(RETRY_AFTER, seconds.to_string())], body).into_response(),
+            None => (status, body).into_response(),
+        }
+    }
+}
+
+/// For the `?` in functions that build their own responses, e.g. middlewares.
+impl From<ApiError> for Response {
+    fn from(err: ApiError) -> Self {
+        err.into_response()
+    }
+}
+
+/// The column a constraint violation is about, read from Postgres' detail message, e.g.
+/// `Key (club_id, slug)=(...) already exists.` gives `slug`. Composite keys are scoped by their
+/// leading columns, so the last one is what the user chose.
+fn violated_field(err: &PgDatabaseError) -> Option<String> {
+    let detail = err.detail()?;
+    let columns = detail.strip_prefix("Key (")?;
+    let columns = &columns[..columns.find(")=(")?];
+    let last = columns.rsplit(", ").next()?;
+    // expression indexes, e.g. `lower(email::text)`
+    let last = last.rsplit('(').next()?;
+    let field = last.split([':', ')']).next()?.trim();
+    (!field.is_empty()).then(|| field.to_string())
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = err {
+            return ApiError::NotFound("Not found".to_string());
+        }
+
+        let Some(db_err) = err.as_database_error() else {
+            return ApiError::Internal(err.to_string());
+        };
+        let field = db_err
+            .try_downcast_ref::<PgDatabaseError>()
+            .and_then(violated_field);
+
+        match db_err.kind() {
+            ErrorKind::UniqueViolation => {
+                warn!("unique violation: {}", db_err);
+                ApiError::AlreadyExists { field }
+            }
+            ErrorKind::ForeignKeyViolation => {
+                warn!("foreign key violation: {}", db_err);
+                ApiError::InvalidReference { field }
+            }
+            ErrorKind::CheckViolation | ErrorKind::NotNullViolation => {
+                warn!("check violation: {}", db_err);
+                ApiError::Validation {
+                    message: "Invalid value".to_string(),
+                    field_errors: vec![],
+                }
+            }
+            _ => ApiError::Internal(err.to_string()),
+        }
+    }
+}
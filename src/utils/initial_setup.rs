@@ -1,20 +1,20 @@
 use log::error;
 use sqlx::{Pool, Postgres};
 
-use crate::entities::club::create_club;
+use crate::{config::InitialConfig, entities::club::create_club};
 
-pub async fn initial_setup(pool: &Pool<Postgres>) {
-    let initial_club_title = dotenv::var("INITIAL_CLUB").expect("INITIAL_CLUB is not configured");
-    let initial_user_name =
-        dotenv::var("INITIAL_USER").expect("INITIAL_USERNAME is not configured");
-    let initial_user_password =
-        dotenv::var("INITIAL_PASSWORD").expect("INITIAL_PASSWORD is not configured");
+pub async fn initial_setup(pool: &Pool<Postgres>, initial: &InitialConfig) {
+    let (Some(initial_club_title), Some(initial_user_name), Some(initial_user_password)) =
+        (&initial.club, &initial.user, &initial.password)
+    else {
+        panic!("INITIAL_CLUB, INITIAL_USER and INITIAL_PASSWORD are not configured")
+    };
 
     let Ok(mut tx) = pool.begin().await else {
         panic!("could not start init transaction")
     };
 
-    let Ok(created_club_id) = create_club(&mut tx, &initial_club_title).await else {
+    let Ok(created_club_id) = create_club(&mut tx, initial_club_title).await else {
         panic!("could not create initial club")
     };
 
@@ -0,0 +1,214 @@
+//! The schema migrations in `migrations/`, compiled into the binary and applied on startup.
+//!
+//! Instances starting at the same time wait for each other on an advisory lock, so only one of
+//! them migrates. A database migrated by a newer binary is refused rather than used with a schema
+//! this binary doesn't know.
+
+use log::{info, warn};
+use sqlx::{migrate::Migrator, PgConnection, Pool, Postgres};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// arbitrary, but the same for every instance
+const MIGRATION_LOCK_KEY: i64 = 0x7370_6f72_7473;
+
+/// the single `100_create_initial` migration, split into versions 1 to 5 since
+const LEGACY_INITIAL_VERSION: i64 = 100;
+
+const LEGACY_HISTORY_PROBLEM: &str =
+    "the migration history still records the legacy initial migration 100";
+
+#[derive(sqlx::FromRow)]
+struct AppliedMigration {
+    version: i64,
+    success: bool,
+}
+
+/// the newest migration compiled into the binary
+pub fn latest_version() -> i64 {
+    MIGRATOR
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or_default()
+}
+
+/// applies pending migrations, or with `run_migrations` off, only checks that there are none
+pub async fn migrate(pool: &Pool<Postgres>, run_migrations: bool) -> Result<(), String> {
+    let mut conn = pool
+        .acquire()
+        .await
+        .map_err(|err| format!("could not connect to run migrations: {}", err))?;
+
+    sqlx::query("SELECT pg_advisory_lock($1)")
+        .bind(MIGRATION_LOCK_KEY)
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| format!("could not take the migration lock: {}", err))?;
+
+    let result = migrate_locked(&mut conn, run_migrations).await;
+
+    if let Err(err) = sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(MIGRATION_LOCK_KEY)
+        .execute(&mut *conn)
+        .await
+    {
+        // the lock goes away with the connection
+        warn!("could not release the migration lock: {}", err);
+        conn.detach();
+    }
+    result
+}
+
+async fn migrate_locked(conn: &mut PgConnection, run_migrations: bool) -> Result<(), String> {
+    if has_legacy_history(conn).await? {
+        if !run_migrations {
+            return Err(format!(
+                "{} - run `sports-planner-admin migrate`, or start the server with database.run_migrations (RUN_MIGRATIONS) on",
+                LEGACY_HISTORY_PROBLEM
+            ));
+        }
+        adopt_legacy_history(conn).await?;
+    }
+
+    let applied = applied_migrations(conn).await?;
+    if applied.is_empty() && table_exists(conn, "config").await? {
+        return Err(
+            "the database has tables but no migration history, it was not set up by migrations - \
+             use an empty database"
+                .to_string(),
+        );
+    }
+    let pending = pending_versions(&applied)?;
+    let latest_known = latest_version();
+    if pending.is_empty() {
+        info!("database schema is up to date at version {}", latest_known);
+        return Ok(());
+    }
+    if !run_migrations {
+        return Err(format!(
+            "{} migration(s) pending ({:?}) - run `sports-planner-admin migrate`, or start the server with database.run_migrations (RUN_MIGRATIONS) on",
+            pending.len(),
+            pending
+        ));
+    }
+
+    info!("applying {} migration(s): {:?}", pending.len(), pending);
+    MIGRATOR
+        .run(&mut *conn)
+        .await
+        .map_err(|err| format!("migrating the database failed: {}", err))?;
+    info!("database schema migrated to version {}", latest_known);
+    Ok(())
+}
+
+/// Whether the schema is exactly the one of this binary, without waiting for a running migration.
+pub async fn check_schema(pool: &Pool<Postgres>) -> Result<(), String> {
+    let mut conn = pool
+        .acquire()
+        .await
+        .map_err(|err| format!("could not connect: {}", err))?;
+    if has_legacy_history(&mut conn).await? {
+        return Err(LEGACY_HISTORY_PROBLEM.to_string());
+    }
+    let pending = pending_versions(&applied_migrations(&mut conn).await?)?;
+    if !pending.is_empty() {
+        return Err(format!("{} migration(s) pending", pending.len()));
+    }
+    Ok(())
+}
+
+/// the versions still to apply, refusing failed and unknown newer ones
+fn pending_versions(applied: &[AppliedMigration]) -> Result<Vec<i64>, String> {
+    if let Some(dirty) = applied.iter().find(|migration| !migration.success) {
+        return Err(format!(
+            "migration {} failed partway before, fix the database by hand and remove its row from _sqlx_migrations",
+            dirty.version
+        ));
+    }
+
+    let latest_known = latest_version();
+    if let Some(latest_applied) = applied.iter().map(|migration| migration.version).max() {
+        if latest_applied > latest_known {
+            return Err(format!(
+                "the database schema is at version {}, newer than this binary's {} - update the binary",
+                latest_applied, latest_known
+            ));
+        }
+    }
+
+    Ok(MIGRATOR
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .map(|migration| migration.version)
+        .filter(|version| {
+            !applied
+                .iter()
+                .any(|migration| migration.version == *version)
+        })
+        .collect())
+}
+
+/// whether the database was set up with the old single initial migration
+async fn has_legacy_history(conn: &mut PgConnection) -> Result<bool, String> {
+    if !table_exists(conn, "_sqlx_migrations").await? {
+        return Ok(false);
+    }
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM _sqlx_migrations WHERE version = $1 AND success)",
+    )
+    .bind(LEGACY_INITIAL_VERSION)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|err| format!("could not read the migration history: {}", err))
+}
+
+/// the legacy initial migration's replacements are recorded as applied, the schema is the same -
+/// only done when migrations may run, it rewrites the history
+async fn adopt_legacy_history(conn: &mut PgConnection) -> Result<(), String> {
+    info!("replacing the legacy initial migration in the migration history");
+    let history_error =
+        |err: sqlx::Error| format!("could not rewrite the migration history: {}", err);
+    let mut tx = sqlx::Connection::begin(&mut *conn)
+        .await
+        .map_err(history_error)?;
+    sqlx::query("DELETE FROM _sqlx_migrations WHERE version = $1")
+        .bind(LEGACY_INITIAL_VERSION)
+        .execute(&mut *tx)
+        .await
+        .map_err(history_error)?;
+    for migration in MIGRATOR.iter().filter(|migration| {
+        migration.migration_type.is_up_migration() && migration.version < LEGACY_INITIAL_VERSION
+    }) {
+        sqlx::query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+            VALUES ($1, $2, TRUE, $3, 0)",
+        )
+        .bind(migration.version)
+        .bind(&*migration.description)
+        .bind(&*migration.checksum)
+        .execute(&mut *tx)
+        .await
+        .map_err(history_error)?;
+    }
+    tx.commit().await.map_err(history_error)
+}
+
+async fn applied_migrations(conn: &mut PgConnection) -> Result<Vec<AppliedMigration>, String> {
+    if !table_exists(conn, "_sqlx_migrations").await? {
+        return Ok(vec![]);
+    }
+    sqlx::query_as::<_, AppliedMigration>("SELECT version, success FROM _sqlx_migrations")
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|err| format!("could not read the migration history: {}", err))
+}
+
+async fn table_exists(conn: &mut PgConnection, table: &str) -> Result<bool, String> {
+    sqlx::query_scalar::<_, bool>("SELECT to_regclass($1) IS NOT NULL")
+        .bind(table)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|err| format!("could not inspect the database: {}", err))
+}
@@ -1,2 +1,8 @@
 pub mod api;
+pub mod client_ip;
+pub mod error;
 pub mod initial_setup;
+pub mod migrations;
+pub mod outbound;
+pub mod pagination;
+pub mod validation;
@@ -0,0 +1,115 @@
+//! The HTTP client for calls to hosts configured by users, i.e. clubs' single sign-on providers.
+//! Those hosts may be slow or never answer, so every call is bounded.
+//!
+//! Users must not be able to reach the server's own network through it: only https URLs are
+//! called, and never private, link-local or loopback addresses - checked for the URL itself, for
+//! every address its host resolves to, and for every redirect. Debug builds allow http and
+//! loopback, for an IdP running next to the dev server.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect, Url,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// for the whole call, including reading the body
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_REDIRECTS: usize = 5;
+
+pub fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+        .dns_resolver(std::sync::Arc::new(PublicResolver))
+        .redirect(redirect::Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if let Err(problem) = check_url(attempt.url().as_str()) {
+                attempt.error(problem)
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()
+        .expect("the TLS backend initializes")
+}
+
+/// Whether outgoing calls may go to `url`, the problem otherwise. Host names are checked when
+/// they are resolved.
+pub fn check_url(url: &str) -> Result<Url, String> {
+    let url = Url::parse(url).map_err(|_| "must be a URL".to_string())?;
+    let scheme_allowed =
+        url.scheme() == "https" || (url.scheme() == "http" && cfg!(debug_assertions));
+    if !scheme_allowed {
+        return Err("must be an https URL".to_string());
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| "must have a host".to_string())?;
+    // IPv6 hosts come in brackets
+    let ip = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .ok();
+    if ip.is_some_and(|ip| !is_public(ip)) {
+        return Err("must not point to a private or local address".to_string());
+    }
+    Ok(url)
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    // 100.64.0.0/10, carrier-grade NAT
+    let shared = a == 100 && (b & 0b1100_0000) == 64;
+    !(ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || shared
+        || a == 0
+        || (ip.is_loopback() && !cfg!(debug_assertions)))
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    !(ip.is_unique_local()
+        || ip.is_unicast_link_local()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || (ip.is_loopback() && !cfg!(debug_assertions)))
+}
+
+/// the system resolver, leaving out addresses calls must not go to
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
@@ -0,0 +1,197 @@
+//! The query parameters of list routes: `?limit=50&sort=-start_time&cursor=...&<filters>`.
+//!
+//! Pagination is keyset-based. Rows are ordered by the chosen sort key, then by id, and the
+//! cursor holds both of the last row of a page, so the next page starts right after it even when
+//! rows were added or removed in between. Queries compare the sort key as text, so they select
+//! it as `sort_key` (timestamps via [`TIME_KEY_FORMAT`]) and filter with
+//!
+//! ```sql
+//! AND ($after_key::text IS NULL OR CASE WHEN $descending
+//!     THEN (sort_key, id) < ($after_key, $after_id) ELSE (sort_key, id) > ($after_key, $after_id) END)
+//! ORDER BY CASE WHEN $descending THEN sort_key END DESC, sort_key,
+//!     CASE WHEN $descending THEN id END DESC, id
+//! LIMIT $fetch_limit
+//! ```
+//!
+//! `timestamptz` columns are formatted `AT TIME ZONE 'UTC'`, otherwise `to_char` follows the
+//! session's time zone and cursors change with it. Plain `timestamp` columns are formatted as
+//! they are, `AT TIME ZONE` would turn them into `timestamptz`.
+
+use std::{fmt::Display, str::FromStr};
+
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::utils::{
+    error::ApiError,
+    validation::{query_rejection, Validate, Validator},
+};
+
+pub const DEFAULT_LIMIT: i64 = 50;
+pub const MAX_LIMIT: i64 = 200;
+/// `to_char` format of timestamp sort keys, sorts like the timestamps themselves
+pub const TIME_KEY_FORMAT: &str = r#"YYYY-MM-DD"T"HH24:MI:SS.US"#;
+
+/// What a list can be sorted by, e.g. a strum enum of column names.
+pub trait SortKey: FromStr + Display + Default + Copy {
+    /// the direction when `sort` isn't given
+    const DEFAULT_DESCENDING: bool = false;
+}
+
+#[derive(Deserialize, Default)]
+pub struct NoFilters {}
+
+impl Validate for NoFilters {
+    fn validate(&self, _: &mut Validator) {}
+}
+
+/// The query parameters every list route takes, besides its filters.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListParams {
+    /// page size, 1 to 200, defaults to 50
+    limit: Option<i64>,
+    /// a sort key, descending with a leading `-`
+    sort: Option<String>,
+    /// `next_cursor` of the previous page
+    cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    sort: String,
+    descending: bool,
+    key: String,
+    id: String,
+}
+
+pub struct Page<S> {
+    pub limit: i64,
+    pub sort: S,
+    pub descending: bool,
+    after: Option<Cursor>,
+}
+
+impl<S: SortKey> Page<S> {
+    pub fn sort(&self) -> String {
+        self.sort.to_string()
+    }
+
+    /// sort key of the last row of the previous page
+    pub fn after_key(&self) -> Option<&str> {
+        self.after.as_ref().map(|cursor| cursor.key.as_str())
+    }
+
+    pub fn after_id(&self) -> Option<&str> {
+        self.after.as_ref().map(|cursor| cursor.id.as_str())
+    }
+
+    /// one more row than asked for tells whether there's a next page
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit + 1
+    }
+
+    /// `key` gives a row's sort key and id
+    pub fn finish<T>(
+        &self,
+        mut rows: Vec<T>,
+        total: i64,
+        key: impl Fn(&T) -> (&str, &str),
+    ) -> Paginated<T> {
+        let mut next_cursor = None;
+        if rows.len() as i64 > self.limit {
+            rows.truncate(self.limit as usize);
+            next_cursor = rows.last().map(|row| {
+                let (key, id) = key(row);
+                let cursor = Cursor {
+                    sort: self.sort(),
+                    descending: self.descending,
+                    key: key.to_string(),
+                    id: id.to_string(),
+                };
+                URL_SAFE_NO_PAD.encode(serde_json::to_vec(&cursor).unwrap_or_default())
+            });
+        }
+        Paginated {
+            items: rows,
+            next_cursor,
+            total,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    /// `cursor` of the next page, none on the last one
+    pub next_cursor: Option<String>,
+    /// items matching the filters, across all pages
+    pub total: i64,
+}
+
+/// Pagination, sorting and the route's filters `F`, with all problems reported as a 422.
+pub struct ListQuery<S, F = NoFilters> {
+    pub page: Page<S>,
+    pub filters: F,
+}
+
+fn decode_cursor(cursor: &str) -> Option<Cursor> {
+    let bytes = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+impl<S, F, St> FromRequestParts<St> for ListQuery<S, F>
+where
+    S: SortKey,
+    F: DeserializeOwned + Validate,
+    St: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _: &St) -> Result<Self, Self::Rejection> {
+        let Query(params) =
+            Query::<ListParams>::try_from_uri(&parts.uri).map_err(query_rejection)?;
+        let Query(filters) = Query::<F>::try_from_uri(&parts.uri).map_err(query_rejection)?;
+
+        let mut v = Validator::default();
+        let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+        v.range("limit", limit, 1, MAX_LIMIT);
+
+        let (sort, descending) = match params.sort.as_deref() {
+            None => (Some(S::default()), S::DEFAULT_DESCENDING),
+            Some(sort) => match sort.strip_prefix('-') {
+                Some(sort) => (sort.parse().ok(), true),
+                None => (sort.parse().ok(), false),
+            },
+        };
+        v.check(sort.is_some(), "sort", "is not a sort key of this list");
+
+        let after = params.cursor.as_deref().map(decode_cursor);
+        let cursor_ok = match (&after, &sort) {
+            (Some(Some(cursor)), Some(sort)) => {
+                cursor.sort == sort.to_string() && cursor.descending == descending
+            }
+            (Some(None), _) => false,
+            _ => true,
+        };
+        v.check(cursor_ok, "cursor", "is invalid or for another sort order");
+
+        filters.validate(&mut v);
+        v.finish()?;
+
+        Ok(ListQuery {
+            page: Page {
+                limit,
+                sort: sort.unwrap_or_default(),
+                descending,
+                after: after.flatten(),
+            },
+            filters,
+        })
+    }
+}
@@ -0,0 +1,218 @@
+//! Declarative checks of request bodies and query strings. Handlers take `ValidatedJson<T>`
+//! instead of `Json<T>` (or `ValidatedQuery<T>` instead of `Query<T>`), `T` lists its rules in
+//! `Validate::validate`, and every violated rule comes back at once as a 422 with one field error
+//! each.
+
+use std::fmt::Display;
+
+use axum::{
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        FromRequest, FromRequestParts, Query, Request,
+    },
+    http::request::Parts,
+    Json,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    auth::{
+        email::is_valid_email,
+        password::{MAX_PASSWORD_LEN, MIN_PASSWORD_LEN},
+    },
+    utils::error::{ApiError, FieldError},
+};
+
+/// user names, team names, opponents and other short labels
+pub const MAX_NAME_LEN: usize = 100;
+pub const MAX_SLUG_LEN: usize = 64;
+/// free text like notes and locations
+pub const MAX_TEXT_LEN: usize = 500;
+/// tokens and codes sent back to us, generous but bounded
+pub const MAX_TOKEN_LEN: usize = 2048;
+const MAX_ID_LEN: usize = 64;
+
+pub trait Validate {
+    fn validate(&self, v: &mut Validator);
+}
+
+/// Collects the field errors of one payload.
+#[derive(Default)]
+pub struct Validator {
+    field_errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn check(&mut self, ok: bool, field: &str, message: impl Into<String>) {
+        if !ok {
+            self.field_errors.push(FieldError {
+                field: field.to_string(),
+                message: message.into(),
+            });
+        }
+    }
+
+    /// in characters, not bytes
+    pub fn length(&mut self, field: &str, value: &str, min: usize, max: usize) {
+        let len = value.chars().count();
+        let message = match min {
+            0 => format!("must be at most {} characters long", max),
+            1 if value.trim().is_empty() => "must not be empty".to_string(),
+            _ => format!("must be {} to {} characters long", min, max),
+        };
+        self.check(
+            (min..=max).contains(&len) && (min == 0 || !value.trim().is_empty()),
+            field,
+            message,
+        );
+    }
+
+    /// ids of our own records
+    pub fn id(&mut self, field: &str, value: &str) {
+        self.length(field, value, 1, MAX_ID_LEN);
+    }
+
+    pub fn range<T: PartialOrd + Display>(&mut self, field: &str, value: T, min: T, max: T) {
+        let ok = value >= min && value <= max;
+        self.check(ok, field, format!("must be between {} and {}", min, max));
+    }
+
+    pub fn not_empty<T>(&mut self, field: &str, items: &[T]) {
+        self.check(!items.is_empty(), field, "must not be empty");
+    }
+
+    /// lowercase letters, digits and single dashes between them, as used in URLs
+    pub fn slug(&mut self, field: &str, value: &str) {
+        let ok = !value.is_empty()
+            && value.len() <= MAX_SLUG_LEN
+            && value.split('-').all(|part| {
+                !part.is_empty()
+                    && part
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+            });
+        self.check(
+            ok,
+            field,
+            format!(
+                "must be at most {} lowercase letters, digits and dashes",
+                MAX_SLUG_LEN
+            ),
+        );
+    }
+
+    /// `@` is left to email addresses, which are accepted for log-ins as well
+    pub fn username(&mut self, field: &str, value: &str) {
+        self.length(field, value, 1, MAX_NAME_LEN);
+        self.check(!value.contains('@'), field, "must not contain @");
+    }
+
+    /// the policy for new passwords, log-ins accept whatever was set before
+    pub fn password(&mut self, field: &str, value: &str) {
+        self.length(field, value, MIN_PASSWORD_LEN, MAX_PASSWORD_LEN);
+    }
+
+    pub fn email(&mut self, field: &str, value: &str) {
+        self.check(
+            is_valid_email(value),
+            field,
+            "must be a valid email address",
+        );
+    }
+
+    /// the errors of a nested payload, e.g. `assignments[0].slot_id`
+    pub fn nested(&mut self, field: &str, nested: Validator) {
+        self.field_errors
+            .extend(nested.field_errors.into_iter().map(|error| FieldError {
+                field: format!("{}{}", field, error.field),
+                ..error
+            }));
+    }
+
+    pub fn finish(self) -> Result<(), ApiError> {
+        if self.field_errors.is_empty() {
+            return Ok(());
+        }
+        Err(ApiError::Validation {
+            message: "Invalid input".to_string(),
+            field_errors: self.field_errors,
+        })
+    }
+}
+
+impl<T: Validate> Validate for Vec<T> {
+    fn validate(&self, v: &mut Validator) {
+        let mut items = Validator::default();
+        for (i, item) in self.iter().enumerate() {
+            item.validate(&mut items);
+            v.field_errors
+                .extend(items.field_errors.drain(..).map(|error| FieldError {
+                    field: format!("[{}].{}", i, error.field),
+                    ..error
+                }));
+        }
+    }
+}
+
+/// `Json<T>` that also runs `T`'s rules.
+pub struct ValidatedJson<T>(pub T);
+
+fn json_rejection(rejection: JsonRejection) -> ApiError {
+    match rejection {
+        // wrong types, missing fields - serde says which
+        JsonRejection::JsonDataError(err) => ApiError::Validation {
+            message: err.body_text(),
+            field_errors: vec![],
+        },
+        other => ApiError::BadRequest(other.body_text()),
+    }
+}
+
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(payload) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(json_rejection)?;
+
+        let mut validator = Validator::default();
+        payload.validate(&mut validator);
+        validator.finish()?;
+
+        Ok(ValidatedJson(payload))
+    }
+}
+
+/// `Query<T>` that also runs `T`'s rules.
+pub struct ValidatedQuery<T>(pub T);
+
+/// unknown values and wrong types, serde says which
+pub fn query_rejection(rejection: QueryRejection) -> ApiError {
+    ApiError::Validation {
+        message: rejection.body_text(),
+        field_errors: vec![],
+    }
+}
+
+impl<T, S> FromRequestParts<S> for ValidatedQuery<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let Query(params) = Query::<T>::try_from_uri(&parts.uri).map_err(query_rejection)?;
+
+        let mut validator = Validator::default();
+        params.validate(&mut validator);
+        validator.finish()?;
+
+        Ok(ValidatedQuery(params))
+    }
+}
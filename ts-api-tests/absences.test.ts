@@ -0,0 +1,224 @@
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+
+const { testId } = makeTestId();
+
+const inDays = (days: number) => new Date(Date.now() + days * 24 * 3600 * 1000);
+const dayString = (days: number) => inDays(days).toISOString().slice(0, 10);
+
+describe(__filename, () => {
+  it("declines invites while absent and reverts them afterwards", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const playerId = await adminClient.createUser({
+      username: `player-${testId}`,
+      password: `player-pass-${testId}`,
+    });
+    await adminClient.assignRole({ user_id: playerId, role: "player" });
+    const playerClient = new TestClient({
+      ...(await testAuthUtils.logIn({
+        username: `player-${testId}`,
+        password: `player-pass-${testId}`,
+      })),
+      testId,
+    });
+
+    const teamId = await adminClient.createTeam({
+      name: `team-${testId}`,
+      slug: `t-${testId}`,
+    });
+    await adminClient.createGame({
+      team_id: teamId,
+      opponent: "before",
+      start_time: inDays(5),
+      location: "pitch",
+      location_kind: "home",
+      invited_roles: ["player"],
+    });
+
+    const absence = await playerClient.createAbsence({
+      starts_on: dayString(4),
+      ends_on: dayString(10),
+      reason: "holiday",
+    });
+    expect(absence.declined_invites).toBe(1);
+
+    // games created during the absence are declined right away
+    const laterGameId = await adminClient.createGame({
+      team_id: teamId,
+      opponent: "during",
+      start_time: inDays(8),
+      location: "pitch",
+      location_kind: "home",
+      invited_roles: ["player"],
+    });
+    const { items: laterInvites } =
+      await adminClient.listInvitesToGame(laterGameId);
+    expect(laterInvites).toMatchObject([
+      { user_id: playerId, response: "declined", decline_reason: "absent" },
+    ]);
+
+    const grid = await adminClient.getTeamAvailabilityGrid(teamId);
+    expect(grid.games.map((game) => game.opponent)).toEqual([
+      "before",
+      "during",
+    ]);
+    expect(grid.members).toMatchObject([
+      {
+        user_id: playerId,
+        games: [
+          { response: "declined", absence_reason: "holiday" },
+          { response: "declined", absence_reason: "holiday" },
+        ],
+      },
+    ]);
+    await expect(
+      adminClient.getTeamAvailabilityGrid(teamId, 0),
+    ).rejects.toMatchObject({
+      response: { status: 422, data: { field_errors: [{ field: "limit" }] } },
+    });
+
+    expect((await playerClient.listOwnAbsences()).items).toMatchObject([
+      { id: absence.id, reason: "holiday" },
+    ]);
+    await playerClient.deleteAbsence(absence.id);
+
+    const invites = (await playerClient.listOwnInvites()).items;
+    expect(invites).toHaveLength(2);
+    expect(invites).toMatchObject(
+      invites.map(() => ({ response: "pending", decline_reason: null })),
+    );
+  });
+
+  it("keeps absent referees out of assignments", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin2-${testId}`,
+      password: `admin2-pass-${testId}`,
+      clubTitle: `test-club2-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const refereeId = await adminClient.createUser({
+      username: `referee-${testId}`,
+      password: `referee-pass-${testId}`,
+    });
+    await adminClient.assignRole({ user_id: refereeId, role: "referee" });
+    const refereeClient = new TestClient({
+      ...(await testAuthUtils.logIn({
+        username: `referee-${testId}`,
+        password: `referee-pass-${testId}`,
+      })),
+      testId,
+    });
+    await refereeClient.createAbsence({
+      starts_on: dayString(6),
+      ends_on: dayString(6),
+      reason: "work",
+    });
+
+    const teamId = await adminClient.createTeam({
+      name: `team2-${testId}`,
+      slug: `t2-${testId}`,
+    });
+    const gameId = await adminClient.createGame({
+      team_id: teamId,
+      opponent: "opp",
+      start_time: inDays(6),
+      location: "pitch",
+      location_kind: "home",
+      invited_roles: ["player"],
+    });
+    const slotId = await adminClient.addRefereeSlot({
+      game_id: gameId,
+      position: "main",
+    });
+
+    await expect(
+      adminClient.requestReferee({
+        slot_id: slotId,
+        referee_user_id: refereeId,
+      }),
+    ).rejects.toMatchObject({ response: { status: 409 } });
+
+    const proposal = await adminClient.proposeRefereeAssignments({
+      from: inDays(5),
+      until: inDays(7),
+    });
+    expect(proposal.assignments).toEqual([]);
+    expect(proposal.unfilled).toMatchObject([{ slot_id: slotId }]);
+  });
+
+  it("audits accepted referee requests given up by an absence", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin3-${testId}`,
+      password: `admin3-pass-${testId}`,
+      clubTitle: `test-club3-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const refereeId = await adminClient.createUser({
+      username: `referee3-${testId}`,
+      password: `referee3-pass-${testId}`,
+    });
+    await adminClient.assignRole({ user_id: refereeId, role: "referee" });
+    const refereeClient = new TestClient({
+      ...(await testAuthUtils.logIn({
+        username: `referee3-${testId}`,
+        password: `referee3-pass-${testId}`,
+      })),
+      testId,
+    });
+
+    const teamId = await adminClient.createTeam({
+      name: `team3-${testId}`,
+      slug: `t3-${testId}`,
+    });
+    const gameId = await adminClient.createGame({
+      team_id: teamId,
+      opponent: "opp",
+      start_time: inDays(6),
+      location: "pitch",
+      location_kind: "home",
+      invited_roles: ["player"],
+    });
+    const slotId = await adminClient.addRefereeSlot({
+      game_id: gameId,
+      position: "main",
+    });
+    const requestId = await adminClient.requestReferee({
+      slot_id: slotId,
+      referee_user_id: refereeId,
+    });
+    await refereeClient.respondToRefereeRequest({
+      request_id: requestId,
+      response: "accepted",
+    });
+
+    const absence = await refereeClient.createAbsence({
+      starts_on: dayString(6),
+      ends_on: dayString(6),
+      reason: "illness",
+    });
+    expect(absence.declined_referee_requests).toBe(1);
+
+    const unfilled = await adminClient.listUnfilledRefereeSlots();
+    expect(unfilled.items.map((slot) => slot.id)).toContain(slotId);
+    const { items: entries } = await adminClient.listAuditLog({
+      action: "referee_request_withdrawn",
+    });
+    expect(entries).toMatchObject([
+      {
+        actor_user_id: refereeId,
+        target_id: requestId,
+        details: { reason: "absent", absence_id: absence.id },
+        before: { slot_id: slotId, status: "accepted" },
+      },
+    ]);
+  });
+});
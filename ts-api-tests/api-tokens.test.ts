@@ -0,0 +1,90 @@
+import axios from "axios";
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+import { API_URL } from "./utils/env";
+
+const { testId } = makeTestId();
+
+describe(__filename, () => {
+  it("lets scoped bearer tokens act as their owner", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    await expect(
+      adminClient.createApiToken({ name: "bad", scopes: ["clubs:write"] }),
+    ).rejects.toMatchObject({ response: { status: 422 } });
+
+    const { id, token } = await adminClient.createApiToken({
+      name: `sheet-${testId}`,
+      scopes: ["read", "teams:write"],
+      expires_in_days: 7,
+    });
+
+    const withToken = (method: string, url: string, data?: unknown) =>
+      axios({
+        method,
+        url: API_URL + "/user" + url,
+        data,
+        headers: { Authorization: `Bearer ${token}` },
+        validateStatus: () => true,
+      });
+
+    expect((await withToken("GET", "/users/list")).status).toBe(200);
+    const team = await withToken("POST", "/teams/create", {
+      name: `team-${testId}`,
+      slug: `team-${testId}`,
+    });
+    expect(team.status).toBe(201);
+    // out of scope
+    expect(
+      (
+        await withToken("POST", "/users/create", {
+          username: `user-${testId}`,
+          password: `pass-${testId}`,
+        })
+      ).status,
+    ).toBe(403);
+    // account management needs a real log-in
+    expect((await withToken("GET", "/api-tokens/list-own")).status).toBe(403);
+
+    const { token: usersToken } = await adminClient.createApiToken({
+      name: `users-${testId}`,
+      scopes: ["users:write"],
+    });
+    const withUsersToken = (method: string, url: string) =>
+      axios({
+        method,
+        url: API_URL + "/user" + url,
+        headers: { Authorization: `Bearer ${usersToken}` },
+        validateStatus: () => true,
+      });
+    for (const [method, url] of [
+      ["DELETE", "/users/delete-own"],
+      ["POST", `/users/reset-2fa/${adminClient.ownId}`],
+      ["POST", `/users/force-password-reset/${adminClient.ownId}`],
+      ["POST", `/users/create-password-reset/${adminClient.ownId}`],
+      ["POST", `/users/unlock-login/${adminClient.ownId}`],
+    ]) {
+      expect((await withUsersToken(method, url)).status).toBe(403);
+    }
+    // the account still exists
+    expect((await withUsersToken("GET", "/users/list")).status).toBe(200);
+
+    const [listed] = (await adminClient.listOwnApiTokens()).items;
+    expect(listed).toMatchObject({ id, scopes: ["read", "teams:write"] });
+    expect(listed.last_used_at).not.toBeNull();
+    expect(token.startsWith(listed.token_prefix)).toBe(true);
+
+    await adminClient.revokeApiToken(id);
+    expect((await withToken("GET", "/users/list")).status).toBe(401);
+
+    // ---- Cleanup -----------------------------------------------------------
+    await adminClient.deleteTeamById(team.data);
+    await adminClient.deleteOwnclub();
+  });
+});
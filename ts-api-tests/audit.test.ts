@@ -0,0 +1,86 @@
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+
+const { testId } = makeTestId();
+
+describe(__filename, () => {
+  it("records changes with before/after snapshots", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const teamId = await adminClient.createTeam({
+      name: `team-${testId}`,
+      slug: `t-${testId}`,
+    });
+    await adminClient.updateTeam(teamId, { name: `renamed-${testId}` });
+    await adminClient.deleteTeamById(teamId);
+
+    const { items: entries, total } = await adminClient.listAuditLog({
+      target_type: "team",
+      target_id: teamId,
+    });
+    expect(total).toBe(3);
+    expect(entries.map((entry) => entry.action)).toEqual([
+      "team_deleted",
+      "team_updated",
+      "team_created",
+    ]);
+
+    const [deleted, updated] = entries;
+    expect(deleted).toMatchObject({
+      actor_user_id: adminDetails.ownId,
+      actor_username: `admin-${testId}`,
+      before: { name: `renamed-${testId}` },
+      after: null,
+    });
+    expect(updated).toMatchObject({
+      before: { name: `team-${testId}` },
+      after: { name: `renamed-${testId}` },
+    });
+    expect(deleted.request_id).toBeTruthy();
+
+    const firstPage = await adminClient.listAuditLog({
+      target_id: teamId,
+      limit: 1,
+    });
+    const secondPage = await adminClient.listAuditLog({
+      target_id: teamId,
+      limit: 1,
+      cursor: firstPage.next_cursor!,
+    });
+    expect(firstPage.items[0].id).toBe(deleted.id);
+    expect(secondPage.items[0].id).toBe(updated.id);
+    expect(secondPage.total).toBe(3);
+  });
+
+  it("is only readable by club admins", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin2-${testId}`,
+      password: `admin2-pass-${testId}`,
+      clubTitle: `test-club2-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const playerId = await adminClient.createUser({
+      username: `player-${testId}`,
+      password: `player-pass-${testId}`,
+    });
+    await adminClient.assignRole({ user_id: playerId, role: "player" });
+    const playerClient = new TestClient({
+      ...(await testAuthUtils.logIn({
+        username: `player-${testId}`,
+        password: `player-pass-${testId}`,
+      })),
+      testId,
+    });
+
+    await expect(playerClient.listAuditLog()).rejects.toMatchObject({
+      response: { status: 403 },
+    });
+  });
+});
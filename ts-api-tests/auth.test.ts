@@ -36,7 +36,7 @@ describe(__filename, () => {
       await expect(method()).rejects.toMatchObject({
         response: {
           status: 401,
-          data: "Not logged in",
+          data: { code: "unauthorized", message: "Not logged in" },
         },
       });
     }
@@ -86,7 +86,10 @@ describe(__filename, () => {
     log({ status, data, headers });
 
     expect(status).toEqual(401);
-    expect(data).toEqual("Unauthorized");
+    expect(data).toMatchObject({
+      code: "unauthorized",
+      message: "Unauthorized",
+    });
 
     const cookies = headers["set-cookie"];
 
@@ -34,9 +34,13 @@ describe(__filename, () => {
     expect(ownRolesOfAdmin).toEqual(["club_admin"]);
 
     const allRoleAssignments = await clubAdminClient.listRoles();
-    expect(allRoleAssignments).toEqual({
-      [clubAdminClient.ownId]: ["club_admin"],
-    });
+    expect(allRoleAssignments.items).toEqual([
+      {
+        user_id: clubAdminClient.ownId,
+        username: adminUsername,
+        roles: ["club_admin"],
+      },
+    ]);
 
     const newUserId = await clubAdminClient.createUser({
       username: regularUserName,
@@ -63,7 +67,10 @@ describe(__filename, () => {
     ).rejects.toMatchObject({
       response: {
         status: 403,
-        data: "Access denied. Needs one of roles: [club_admin, super_admin]",
+        data: {
+          code: "forbidden",
+          message: "Access denied. Missing permission: user.create",
+        },
       },
     });
 
@@ -72,12 +79,15 @@ describe(__filename, () => {
     ).rejects.toMatchObject({
       response: {
         status: 403,
-        data: "Access denied. Needs one of roles: [club_admin, super_admin]",
+        data: {
+          code: "forbidden",
+          message: "Access denied. Missing permission: user.delete",
+        },
       },
     });
 
-    const users = await clubAdminClient.listUsers();
-    expect(users.length).toEqual(2);
+    const { items: users, total } = await clubAdminClient.listUsers();
+    expect(total).toEqual(2);
     expect(users).toEqual(
       expect.arrayContaining([
         {
@@ -98,9 +108,14 @@ describe(__filename, () => {
 
     expect(regularUserClient.listOwnRoles()).resolves.toEqual(["club_admin"]);
 
-    expect(clubAdminClient.listRoles()).resolves.toEqual({
-      [clubAdminClient.ownId]: ["club_admin"],
-      [regularUserClient.ownId]: ["club_admin"],
+    expect(
+      clubAdminClient.listRoles({ role: "club_admin" }),
+    ).resolves.toMatchObject({
+      items: expect.arrayContaining([
+        { user_id: clubAdminClient.ownId, roles: ["club_admin"] },
+        { user_id: regularUserClient.ownId, roles: ["club_admin"] },
+      ]),
+      total: 2,
     });
 
     const id = await regularUserClient.createUser({
@@ -116,8 +131,8 @@ describe(__filename, () => {
 
     expect(regularUserClient.listOwnRoles()).resolves.toEqual([]);
     expect(clubAdminClient.listRoles()).resolves.toMatchObject({
-      [clubAdminClient.ownId]: ["club_admin"],
-      // [regularUserClient.ownId]: NONE,
+      items: [{ user_id: clubAdminClient.ownId, roles: ["club_admin"] }],
+      // regularUserClient: NONE,
     });
 
     await expect(
@@ -128,7 +143,10 @@ describe(__filename, () => {
     ).rejects.toMatchObject({
       response: {
         status: 403,
-        data: "Access denied. Needs one of roles: [club_admin, super_admin]",
+        data: {
+          code: "forbidden",
+          message: "Access denied. Missing permission: user.create",
+        },
       },
     });
 
@@ -137,7 +155,10 @@ describe(__filename, () => {
     ).rejects.toMatchObject({
       response: {
         status: 403,
-        data: "Access denied. Needs one of roles: [club_admin, super_admin]",
+        data: {
+          code: "forbidden",
+          message: "Access denied. Missing permission: user.delete",
+        },
       },
     });
 
@@ -161,7 +182,7 @@ describe(__filename, () => {
     await expect(regularUserClient.listUsers()).rejects.toMatchObject({
       response: {
         status: 401,
-        data: "Unauthorized",
+        data: { code: "unauthorized", message: "Unauthorized" },
       },
     });
 
@@ -188,7 +209,7 @@ describe(__filename, () => {
       testId,
     });
 
-    let regularUserInvites = await regularUserClient.listOwnInvites();
+    let regularUserInvites = (await regularUserClient.listOwnInvites()).items;
 
     expect(regularUserInvites).toEqual([]);
 
@@ -206,21 +227,21 @@ describe(__filename, () => {
       team_id,
       opponent: "some-opp",
       start_time: new Date(),
-      stop_time: new Date(),
+      stop_time: new Date(Date.now() + 2 * 3600 * 1000),
       location: "some place with address",
       location_kind: "home",
       invited_roles: ["player", "coach"],
     });
 
     // Test listing games for the team
-    const games = await clubAdminClient.listGamesForTeam(team_id);
+    const { items: games } = await clubAdminClient.listGamesForTeam(team_id);
     expect(games).toHaveLength(1);
     expect(games[0].id).toBe(newGameId);
     expect(games[0].opponent).toBe("some-opp");
     expect(games[0].location).toBe("some place with address");
     expect(games[0].location_kind).toBe("home");
 
-    regularUserInvites = await regularUserClient.listOwnInvites();
+    regularUserInvites = (await regularUserClient.listOwnInvites()).items;
     expect(regularUserInvites.length).toEqual(1);
     expect(regularUserInvites).toMatchObject([
       {
@@ -234,12 +255,13 @@ describe(__filename, () => {
     const invitesToFirstGame =
       await clubAdminClient.listInvitesToGame(newGameId);
 
-    expect(invitesToFirstGame).toEqual([
+    expect(invitesToFirstGame.items).toEqual([
       {
         invite_id: firstInviteId,
         user_id: regularUserClient.ownId,
         username: regularUserName,
         response: "pending",
+        decline_reason: null,
       },
     ]);
 
@@ -248,48 +270,45 @@ describe(__filename, () => {
       response: "unsure",
     });
 
-    await expect(clubAdminClient.listInvitesToGame(newGameId)).resolves.toEqual(
-      [
-        {
-          invite_id: firstInviteId,
-          user_id: regularUserClient.ownId,
-          username: regularUserName,
-          response: "unsure",
-        },
-      ],
-    );
+    expect((await clubAdminClient.listInvitesToGame(newGameId)).items).toEqual([
+      {
+        invite_id: firstInviteId,
+        user_id: regularUserClient.ownId,
+        username: regularUserName,
+        response: "unsure",
+        decline_reason: null,
+      },
+    ]);
 
     await regularUserClient.respondToInvite({
       invite_id: firstInviteId,
       response: "declined",
     });
 
-    await expect(clubAdminClient.listInvitesToGame(newGameId)).resolves.toEqual(
-      [
-        {
-          invite_id: firstInviteId,
-          user_id: regularUserClient.ownId,
-          username: regularUserName,
-          response: "declined",
-        },
-      ],
-    );
+    expect((await clubAdminClient.listInvitesToGame(newGameId)).items).toEqual([
+      {
+        invite_id: firstInviteId,
+        user_id: regularUserClient.ownId,
+        username: regularUserName,
+        response: "declined",
+        decline_reason: null,
+      },
+    ]);
 
     await regularUserClient.respondToInvite({
       invite_id: firstInviteId,
       response: "accepted",
     });
 
-    await expect(clubAdminClient.listInvitesToGame(newGameId)).resolves.toEqual(
-      [
-        {
-          invite_id: firstInviteId,
-          user_id: regularUserClient.ownId,
-          username: regularUserName,
-          response: "accepted",
-        },
-      ],
-    );
+    expect((await clubAdminClient.listInvitesToGame(newGameId)).items).toEqual([
+      {
+        invite_id: firstInviteId,
+        user_id: regularUserClient.ownId,
+        username: regularUserName,
+        response: "accepted",
+        decline_reason: null,
+      },
+    ]);
 
     // await expect(
     //   regularUserClient.respondToInvite({
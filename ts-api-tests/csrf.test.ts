@@ -0,0 +1,41 @@
+import axios from "axios";
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+import { API_URL } from "./utils/env";
+
+const { testId } = makeTestId();
+
+describe(__filename, () => {
+  it("rejects state-changing requests without the session's CSRF token", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const post = (headers: Record<string, string>) =>
+      axios({
+        method: "POST",
+        url: API_URL + "/user/invites-to-club/create",
+        headers: { Cookie: adminDetails.cookie, ...headers },
+        validateStatus: () => true,
+      });
+
+    expect((await post({})).status).toBe(403);
+    expect((await post({ "x-csrf-token": "forged" })).status).toBe(403);
+    expect(
+      (await post({ Origin: "https://evil.example" })).status,
+    ).toBe(403);
+
+    // reads don't need the token
+    await adminClient.listTeams();
+    // the client fetches and sends it on its own
+    const inviteId = await adminClient.createServiceInvite();
+    await adminClient.deleteServiceInviteById(inviteId);
+
+    // ---- Cleanup -----------------------------------------------------------
+    await adminClient.deleteOwnclub();
+  });
+});
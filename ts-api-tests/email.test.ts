@@ -0,0 +1,60 @@
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+
+const { testId } = makeTestId();
+
+describe(__filename, () => {
+  it("keeps new email addresses pending until verified", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const username = `member-${testId}`;
+    const password = `member-pass-${testId}`;
+    const email = `member-${testId}@example.com`;
+
+    await expect(
+      adminClient.createUser({ username, password, email: "not-an-email" }),
+    ).rejects.toMatchObject({ response: { status: 422 } });
+
+    await adminClient.createUser({ username, password, email });
+    const memberClient = new TestClient({
+      ...(await testAuthUtils.logIn({ username, password })),
+      testId,
+    });
+
+    expect(await memberClient.getOwnEmail()).toMatchObject({
+      email: null,
+      verified_at: null,
+      pending_email: email,
+    });
+
+    // unverified addresses can't be used to log in
+    await expect(
+      testAuthUtils.logIn({ username: email, password }),
+    ).rejects.toThrow();
+
+    await expect(
+      testAuthUtils.verifyEmail({ token: "forged.token" }),
+    ).rejects.toMatchObject({ response: { status: 400 } });
+
+    await memberClient.resendEmailVerification();
+
+    await memberClient.removeEmail();
+    expect(await memberClient.getOwnEmail()).toMatchObject({
+      email: null,
+      pending_email: null,
+    });
+    await expect(
+      memberClient.resendEmailVerification(),
+    ).rejects.toMatchObject({ response: { status: 404 } });
+
+    // ---- Cleanup -----------------------------------------------------------
+    await memberClient.deleteOwnUser();
+    await adminClient.deleteOwnclub();
+  });
+});
@@ -0,0 +1,68 @@
+import axios from "axios";
+import { apiErrorSchema } from "ts-shared";
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+import { API_URL } from "./utils/env";
+
+const { testId } = makeTestId();
+
+describe(__filename, () => {
+  it("answers errors with a code, field errors and the request id", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    await adminClient.createUser({
+      username: `member-${testId}`,
+      password: `member-pass-${testId}`,
+    });
+    const duplicate = await adminClient
+      .createUser({
+        username: `member-${testId}`,
+        password: `member-pass-${testId}`,
+      })
+      .catch((err) => err.response);
+    expect(duplicate.status).toBe(409);
+    expect(apiErrorSchema.parse(duplicate.data)).toMatchObject({
+      code: "already_exists",
+      field_errors: [{ field: "username" }],
+    });
+    // no database internals
+    expect(duplicate.data.message).not.toMatch(/constraint|duplicate key/);
+
+    await adminClient.createTeam({ name: `team-${testId}`, slug: testId });
+    await expect(
+      adminClient.createTeam({ name: `other-${testId}`, slug: testId }),
+    ).rejects.toMatchObject({
+      response: {
+        status: 409,
+        data: { code: "already_exists", field_errors: [{ field: "slug" }] },
+      },
+    });
+
+    await expect(adminClient.deleteUserById("missing")).rejects.toMatchObject({
+      response: { status: 404, data: { code: "not_found" } },
+    });
+  });
+
+  it("echoes the request id", async () => {
+    const res = await axios({
+      method: "GET",
+      url: API_URL + "/user/users/list",
+      headers: { "x-request-id": `req-${testId}` },
+      validateStatus: () => true,
+    });
+    expect(res.status).toBe(401);
+    expect(res.headers["x-request-id"]).toBe(`req-${testId}`);
+    expect(res.data).toEqual({
+      code: "unauthorized",
+      message: "Not logged in",
+      field_errors: [],
+      request_id: `req-${testId}`,
+    });
+  });
+});
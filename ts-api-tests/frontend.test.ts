@@ -0,0 +1,43 @@
+import axios from "axios";
+import { BACKEND_URL } from "./utils/env";
+
+// set when the server serves a built frontend (FRONTEND_DIR or the `web` feature) rather than
+// forwarding to the Vite dev server
+const FRONTEND_BUILT = process.env.FRONTEND_BUILT === "true";
+
+const get = (path: string) =>
+  axios.get<string>(BACKEND_URL + path, {
+    responseType: "text",
+    validateStatus: () => true,
+  });
+
+describe(__filename, () => {
+  (FRONTEND_BUILT ? it : it.skip)(
+    "serves client-side routes and caches hashed assets",
+    async () => {
+      const index = await get("/");
+      expect(index.status).toBe(200);
+      expect(index.headers["content-type"]).toMatch(/^text\/html/);
+      expect(index.headers["cache-control"]).toBe("no-cache");
+
+      // routes get the app, also with a dot in the last segment
+      for (const route of ["/teams/abc", "/users/jane.doe"]) {
+        const page = await get(route);
+        expect(page.status).toBe(200);
+        expect(page.data).toBe(index.data);
+        expect(page.headers["cache-control"]).toBe("no-cache");
+      }
+
+      const asset = index.data.match(/\/assets\/[^"]+\.js/)?.[0];
+      expect(asset).toBeDefined();
+      const script = await get(asset!);
+      expect(script.status).toBe(200);
+      expect(script.headers["cache-control"]).toBe(
+        "public, max-age=31536000, immutable",
+      );
+
+      // a missing asset is not a page
+      expect((await get("/assets/missing-0000.js")).status).toBe(404);
+    },
+  );
+});
@@ -0,0 +1,87 @@
+import axios from "axios";
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+import { BACKEND_URL } from "./utils/env";
+
+const { testId } = makeTestId();
+
+// set when the server runs with FEATURE_METRICS=true, plus METRICS_TOKEN if it requires one
+const METRICS_ENABLED = process.env.METRICS_ENABLED === "true";
+const METRICS_TOKEN = process.env.METRICS_TOKEN;
+
+const scrape = async () => {
+  const res = await axios.get<string>(BACKEND_URL + "/metrics", {
+    headers: METRICS_TOKEN ? { Authorization: `Bearer ${METRICS_TOKEN}` } : {},
+  });
+  expect(res.headers["content-type"]).toMatch(/^text\/plain/);
+  return res.data;
+};
+
+/** the value of a series, 0 before it was first touched */
+const sample = (metrics: string, series: string) => {
+  const line = metrics
+    .split("\n")
+    .find((line) => line.startsWith(series + " "));
+  return line ? Number(line.slice(series.length + 1)) : 0;
+};
+
+describe(__filename, () => {
+  it("reports liveness and readiness", async () => {
+    const health = await axios.get(BACKEND_URL + "/healthz");
+    expect(health.status).toBe(200);
+
+    const ready = await axios.get(BACKEND_URL + "/readyz");
+    expect(ready.data).toEqual({ ready: true, problem: null });
+  });
+
+  (METRICS_ENABLED ? it : it.skip)(
+    "counts requests per route template and created games",
+    async () => {
+      const adminDetails = await testAuthUtils.signUpWithNewClub({
+        username: `admin-${testId}`,
+        password: `admin-pass-${testId}`,
+        clubTitle: `test-club-${testId}`,
+      });
+      const adminClient = new TestClient({ ...adminDetails, testId });
+      const teamId = await adminClient.createTeam({
+        name: `team-${testId}`,
+        slug: testId,
+      });
+
+      const gamesSeries = "sports_planner_games_created_total";
+      const requestSeries = `sports_planner_http_requests_total{method="POST",route="/api/user/games/create",status="201"}`;
+      const before = await scrape();
+
+      await adminClient.createGame({
+        team_id: teamId,
+        opponent: `opponent-${testId}`,
+        start_time: new Date(Date.now() + 24 * 60 * 60 * 1000),
+        location: "home ground",
+        location_kind: "home",
+        invited_roles: ["player"],
+      });
+
+      // other test files may create games meanwhile
+      const after = await scrape();
+      expect(sample(after, gamesSeries)).toBeGreaterThan(
+        sample(before, gamesSeries),
+      );
+      expect(sample(after, requestSeries)).toBeGreaterThan(
+        sample(before, requestSeries),
+      );
+      // templates, not concrete ids
+      expect(after).not.toContain(teamId);
+      expect(
+        sample(after, "sports_planner_sessions_active"),
+      ).toBeGreaterThan(0);
+    },
+  );
+
+  (METRICS_ENABLED ? it.skip : it)("serves no metrics by default", async () => {
+    const res = await axios.get(BACKEND_URL + "/metrics", {
+      validateStatus: () => true,
+    });
+    expect(res.status).toBe(404);
+  });
+});
@@ -0,0 +1,107 @@
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+
+const { testId } = makeTestId();
+
+describe(__filename, () => {
+  it("lets super admins act as a user of their club", async () => {
+    const { cookie } = await testAuthUtils.logInConductorUser();
+    const conductorClient = new TestClient({ cookie, testId });
+
+    const playerId = await conductorClient.createUser({
+      username: `player-${testId}`,
+      password: `player-pass-${testId}`,
+    });
+    await conductorClient.assignRole({ user_id: playerId, role: "player" });
+
+    expect(await conductorClient.getImpersonationStatus()).toBeNull();
+
+    const status = await conductorClient.startImpersonation(playerId);
+    expect(status).toMatchObject({
+      impersonated_user_id: playerId,
+      impersonated_username: `player-${testId}`,
+    });
+    expect(status.expires_at.getTime()).toBeGreaterThan(Date.now());
+
+    expect(await conductorClient.listOwnRoles()).toEqual(["player"]);
+
+    // the account itself stays the user's
+    await expect(
+      conductorClient.changePassword({
+        currentPassword: `player-pass-${testId}`,
+        newPassword: `hijacked-${testId}`,
+      }),
+    ).rejects.toMatchObject({ response: { status: 403 } });
+    // the user's notifications must not end up on the admin's device
+    await expect(
+      conductorClient.registerPushSubscription({
+        endpoint: `https://fcm.googleapis.com/fcm/send/${testId}`,
+        keys: {
+          p256dh:
+            "BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4",
+          auth: "BTBZMqHH6r4Tts7J_aSIgg",
+        },
+      }),
+    ).rejects.toMatchObject({ response: { status: 403 } });
+    await expect(
+      conductorClient.startImpersonation(playerId),
+    ).rejects.toMatchObject({ response: { status: 403 } });
+
+    await conductorClient.stopImpersonation();
+    expect(await conductorClient.getImpersonationStatus()).toBeNull();
+    expect(await conductorClient.listOwnRoles()).toContain("super_admin");
+  });
+
+  it("is limited to users with fewer rights", async () => {
+    const { cookie } = await testAuthUtils.logInConductorUser();
+    const conductorClient = new TestClient({ cookie, testId });
+
+    const superAdminIds: string[] = [];
+    for (const name of ["first", "second"]) {
+      const userId = await conductorClient.createUser({
+        username: `${name}-super-admin-${testId}`,
+        password: `${name}-super-admin-pass-${testId}`,
+      });
+      await conductorClient.assignRole({
+        user_id: userId,
+        role: "super_admin",
+      });
+      superAdminIds.push(userId);
+    }
+    const superAdminClient = new TestClient({
+      ...(await testAuthUtils.logIn({
+        username: `first-super-admin-${testId}`,
+        password: `first-super-admin-pass-${testId}`,
+      })),
+      testId,
+    });
+
+    await expect(
+      superAdminClient.startImpersonation(superAdminIds[1]),
+    ).rejects.toMatchObject({ response: { status: 403 } });
+    expect(await superAdminClient.getImpersonationStatus()).toBeNull();
+
+    // global admins outrank them
+    await conductorClient.startImpersonation(superAdminIds[1]);
+    await conductorClient.stopImpersonation();
+  });
+
+  it("is limited to super admins", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const coachId = await adminClient.createUser({
+      username: `coach-${testId}`,
+      password: `coach-pass-${testId}`,
+    });
+
+    await expect(adminClient.startImpersonation(coachId)).rejects.toMatchObject(
+      { response: { status: 403 } },
+    );
+  });
+});
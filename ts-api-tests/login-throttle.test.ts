@@ -0,0 +1,145 @@
+import { TwoFactorRequiredError } from "ts-shared";
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+import { totpCode } from "./utils/totp";
+
+const { testId } = makeTestId();
+
+const failLogIn = (username: string) =>
+  expect(
+    testAuthUtils.logIn({ username, password: "wrong-password" }),
+  ).rejects.toThrow();
+
+describe(__filename, () => {
+  it("delays repeated failures, whether or not the user exists", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const username = `member-${testId}`;
+    const password = `member-pass-${testId}`;
+    const memberId = await adminClient.createUser({ username, password });
+
+    for (const name of [username, `nobody-${testId}`]) {
+      await failLogIn(name);
+      await failLogIn(name);
+      await failLogIn(name);
+
+      // even the right password has to wait now
+      const { status, headers } = await testAuthUtils.axios({
+        method: "POST",
+        url: "/log-in",
+        data: { username: name, password },
+        validateStatus: () => true,
+      });
+      expect(status).toBe(429);
+      expect(Number(headers["retry-after"])).toBeGreaterThan(0);
+    }
+
+    await adminClient.unlockUserLogin(memberId);
+    const memberClient = new TestClient({
+      ...(await testAuthUtils.logIn({ username, password })),
+      testId,
+    });
+
+    // ---- Cleanup -----------------------------------------------------------
+    await memberClient.deleteOwnUser();
+    await adminClient.deleteOwnclub();
+  });
+
+  it("counts wrong second-factor codes as failed log-ins", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `tfa-admin-${testId}`,
+      password: `tfa-admin-pass-${testId}`,
+      clubTitle: `tfa-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const username = `tfa-member-${testId}`;
+    const password = `tfa-member-pass-${testId}`;
+    await adminClient.createUser({ username, password });
+    const memberClient = new TestClient({
+      ...(await testAuthUtils.logIn({ username, password })),
+      testId,
+    });
+    const { secret } = await memberClient.enrolTwoFactor();
+    await memberClient.activateTwoFactor(totpCode(secret));
+
+    // the correct password counts until the code is right as well
+    const challenge = await testAuthUtils
+      .logIn({ username, password })
+      .then(() => Promise.reject(new Error("expected a challenge")))
+      .catch((err) => {
+        if (err instanceof TwoFactorRequiredError) return err.challenge;
+        throw err;
+      });
+    for (const code of ["000000", "000001"]) {
+      await expect(
+        testAuthUtils.completeTwoFactorLogIn({ challenge, code }),
+      ).rejects.toMatchObject({ response: { status: 401 } });
+    }
+
+    const { status } = await testAuthUtils.axios({
+      method: "POST",
+      url: "/log-in",
+      data: { username, password },
+      validateStatus: () => true,
+    });
+    expect(status).toBe(429);
+
+    // ---- Cleanup -----------------------------------------------------------
+    await memberClient.deleteOwnUser();
+    await adminClient.deleteOwnclub();
+  });
+
+  it("counts wrong passwords and codes of logged-in users as failed log-ins", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `session-admin-${testId}`,
+      password: `session-admin-pass-${testId}`,
+      clubTitle: `session-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const username = `session-member-${testId}`;
+    const password = `session-member-pass-${testId}`;
+    await adminClient.createUser({ username, password });
+    const memberClient = new TestClient({
+      ...(await testAuthUtils.logIn({ username, password })),
+      testId,
+    });
+    const { secret } = await memberClient.enrolTwoFactor();
+    await memberClient.activateTwoFactor(totpCode(secret));
+
+    await expect(memberClient.regenerateRecoveryCodes("000000")).rejects.toMatchObject(
+      { response: { status: 403 } },
+    );
+    await expect(
+      memberClient.disableTwoFactor({
+        currentPassword: "wrong-password",
+        code: totpCode(secret, 1),
+      }),
+    ).rejects.toMatchObject({ response: { status: 403 } });
+    await expect(
+      memberClient.disableTwoFactor({
+        currentPassword: password,
+        code: "000000",
+      }),
+    ).rejects.toMatchObject({ response: { status: 403 } });
+
+    // a stolen session doesn't get more guesses than a log-in would
+    await expect(
+      memberClient.disableTwoFactor({
+        currentPassword: password,
+        code: totpCode(secret, 1),
+      }),
+    ).rejects.toMatchObject({ response: { status: 429 } });
+
+    // ---- Cleanup -----------------------------------------------------------
+    await memberClient.deleteOwnUser();
+    await adminClient.deleteOwnclub();
+  });
+});
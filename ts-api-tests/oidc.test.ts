@@ -0,0 +1,123 @@
+import axios from "axios";
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+import { API_URL } from "./utils/env";
+
+const { testId } = makeTestId();
+
+// e.g. the mock provider from pg/docker-compose.yaml: http://localhost:8080/default
+const OIDC_ISSUER_URL = process.env.OIDC_ISSUER_URL;
+
+describe(__filename, () => {
+  it("lets club admins configure single sign-on", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    await expect(adminClient.getOidcProvider()).rejects.toMatchObject({
+      response: { status: 404 },
+    });
+    // the provider is checked before saving
+    await expect(
+      adminClient.setOidcProvider({
+        issuer_url: "http://localhost:1/unreachable",
+        client_id: "client",
+        client_secret: "secret",
+      }),
+    ).rejects.toMatchObject({ response: { status: 502 } });
+    await expect(
+      adminClient.setOidcProvider({
+        issuer_url: "http://localhost:1/unreachable",
+        client_id: "client",
+        client_secret: "secret",
+        default_role: "club_admin",
+      }),
+    ).rejects.toMatchObject({ response: { status: 422 } });
+    // the server's own network is off limits
+    for (const issuer_url of [
+      "http://169.254.169.254/latest",
+      "https://10.0.0.1/",
+      "https://[fd00::1]/",
+    ]) {
+      await expect(
+        adminClient.setOidcProvider({
+          issuer_url,
+          client_id: "client",
+          client_secret: "secret",
+        }),
+      ).rejects.toMatchObject({ response: { status: 422 } });
+    }
+
+    const playerId = await adminClient.createUser({
+      username: `player-${testId}`,
+      password: `player-pass-${testId}`,
+    });
+    await adminClient.assignRole({ user_id: playerId, role: "player" });
+    const playerClient = new TestClient({
+      ...(await testAuthUtils.logIn({
+        username: `player-${testId}`,
+        password: `player-pass-${testId}`,
+      })),
+      testId,
+    });
+    await expect(playerClient.getOidcProvider()).rejects.toMatchObject({
+      response: { status: 403 },
+    });
+  });
+
+  it("rejects log-ins without a started flow", async () => {
+    const start = await axios.get(API_URL + "/auth/oidc/no-such-club/start", {
+      maxRedirects: 0,
+      validateStatus: () => true,
+    });
+    expect(start.status).toBe(404);
+
+    const callback = await axios.get(
+      API_URL + "/auth/oidc/callback?code=forged&state=forged",
+      { maxRedirects: 0, validateStatus: () => true },
+    );
+    expect(callback.status).toBe(400);
+  });
+
+  (OIDC_ISSUER_URL ? it : it.skip)(
+    "redirects to the configured provider",
+    async () => {
+      const adminDetails = await testAuthUtils.signUpWithNewClub({
+        username: `sso-admin-${testId}`,
+        password: `sso-admin-pass-${testId}`,
+        clubTitle: `sso-club-${testId}`,
+      });
+      const adminClient = new TestClient({ ...adminDetails, testId });
+
+      await adminClient.setOidcProvider({
+        issuer_url: OIDC_ISSUER_URL!,
+        client_id: `client-${testId}`,
+        client_secret: "secret",
+        jit_provisioning: true,
+      });
+      expect(await adminClient.getOidcProvider()).toEqual({
+        issuer_url: OIDC_ISSUER_URL!.replace(/\/$/, ""),
+        client_id: `client-${testId}`,
+        jit_provisioning: true,
+        default_role: "player",
+      });
+
+      const authorizationUrl = new URL(await adminClient.startOidcLink());
+      expect(authorizationUrl.searchParams.get("client_id")).toBe(
+        `client-${testId}`,
+      );
+      expect(authorizationUrl.searchParams.get("code_challenge_method")).toBe(
+        "S256",
+      );
+
+      await adminClient.removeOidcProvider();
+      await expect(adminClient.getOidcProvider()).rejects.toMatchObject({
+        response: { status: 404 },
+      });
+    },
+  );
+});
@@ -0,0 +1,125 @@
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+
+const { testId } = makeTestId();
+
+const inDays = (days: number) => new Date(Date.now() + days * 24 * 3600 * 1000);
+
+describe(__filename, () => {
+  it("pages through lists with a cursor", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    for (const name of ["b", "c", "a"]) {
+      await adminClient.createTeam({
+        name: `${name}-${testId}`,
+        slug: `${name}-${testId}`,
+      });
+    }
+
+    const first = await adminClient.listTeams({ limit: 2 });
+    expect(first.total).toBe(3);
+    expect(first.items.map((team) => team.name)).toEqual([
+      `a-${testId}`,
+      `b-${testId}`,
+    ]);
+    expect(first.next_cursor).not.toBeNull();
+
+    const second = await adminClient.listTeams({
+      limit: 2,
+      cursor: first.next_cursor!,
+    });
+    expect(second.items.map((team) => team.name)).toEqual([`c-${testId}`]);
+    expect(second.next_cursor).toBeNull();
+
+    const descending = await adminClient.listTeams({ sort: "-name", q: "-" });
+    expect(descending.items.map((team) => team.name)).toEqual([
+      `c-${testId}`,
+      `b-${testId}`,
+      `a-${testId}`,
+    ]);
+
+    // a cursor only fits the order it came from
+    await expect(
+      adminClient.listTeams({ sort: "-name", cursor: first.next_cursor! }),
+    ).rejects.toMatchObject({
+      response: { status: 422, data: { field_errors: [{ field: "cursor" }] } },
+    });
+    await expect(
+      adminClient.listTeams({ limit: 0, sort: "password" }),
+    ).rejects.toMatchObject({
+      response: {
+        status: 422,
+        data: { field_errors: [{ field: "limit" }, { field: "sort" }] },
+      },
+    });
+  });
+
+  it("filters invites by response and time", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin2-${testId}`,
+      password: `admin2-pass-${testId}`,
+      clubTitle: `test-club2-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+    await adminClient.assignRole({
+      user_id: adminClient.ownId,
+      role: "player",
+    });
+
+    const teamId = await adminClient.createTeam({
+      name: `team-${testId}`,
+      slug: `team-${testId}`,
+    });
+    for (const days of [3, 1, 2]) {
+      await adminClient.createGame({
+        team_id: teamId,
+        opponent: `opp-${days}`,
+        start_time: inDays(days),
+        location: "pitch",
+        location_kind: "home",
+        invited_roles: ["player"],
+      });
+    }
+
+    const invites = await adminClient.listOwnInvites();
+    expect(invites.items.map((invite) => invite.opponent)).toEqual([
+      "opp-1",
+      "opp-2",
+      "opp-3",
+    ]);
+
+    await adminClient.respondToInvite({
+      invite_id: invites.items[0].invite_id,
+      response: "accepted",
+    });
+    const accepted = await adminClient.listOwnInvites({
+      response: "accepted",
+    });
+    expect(accepted.total).toBe(1);
+    expect(accepted.items).toMatchObject([{ opponent: "opp-1" }]);
+
+    const later = await adminClient.listOwnInvites({
+      from: inDays(1.5),
+      team_id: teamId,
+    });
+    expect(later.items.map((invite) => invite.opponent)).toEqual([
+      "opp-2",
+      "opp-3",
+    ]);
+
+    const games = await adminClient.listGamesForTeam(teamId, {
+      until: inDays(2.5),
+    });
+    // latest first by default
+    expect(games.items.map((game) => game.opponent)).toEqual([
+      "opp-2",
+      "opp-1",
+    ]);
+  });
+});
@@ -0,0 +1,90 @@
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+
+const { testId } = makeTestId();
+
+describe(__filename, () => {
+  it("changes password, resets via admin token and forces reset on next login", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const username = `member-${testId}`;
+    const firstPassword = `first-pass-${testId}`;
+    const memberId = await adminClient.createUser({
+      username,
+      password: firstPassword,
+    });
+
+    // ---- change password, other sessions are revoked -------------------
+    const otherDevice = new TestClient({
+      ...(await testAuthUtils.logIn({ username, password: firstPassword })),
+      testId,
+    });
+    const thisDevice = new TestClient({
+      ...(await testAuthUtils.logIn({ username, password: firstPassword })),
+      testId,
+    });
+
+    await expect(
+      thisDevice.changePassword({
+        currentPassword: "wrong-password",
+        newPassword: `second-pass-${testId}`,
+      }),
+    ).rejects.toMatchObject({ response: { status: 403 } });
+
+    const secondPassword = `second-pass-${testId}`;
+    await thisDevice.changePassword({
+      currentPassword: firstPassword,
+      newPassword: secondPassword,
+    });
+
+    await thisDevice.listTeams();
+    await expect(otherDevice.listTeams()).rejects.toMatchObject({
+      response: { status: 401 },
+    });
+
+    // ---- admin-issued reset token is single-use ---------------------------
+    const { token } = await adminClient.createPasswordResetForUser(memberId);
+    const thirdPassword = `third-pass-${testId}`;
+    await testAuthUtils.confirmPasswordReset({
+      token,
+      newPassword: thirdPassword,
+    });
+    await expect(
+      testAuthUtils.confirmPasswordReset({ token, newPassword: thirdPassword }),
+    ).rejects.toMatchObject({ response: { status: 400 } });
+
+    // resetting logs out everywhere
+    await expect(thisDevice.listTeams()).rejects.toMatchObject({
+      response: { status: 401 },
+    });
+
+    // ---- forced reset only allows changing the password --------------------
+    // a fresh client, so the change has to fetch its CSRF token while restricted
+    await adminClient.forcePasswordReset(memberId);
+    const forcedClient = new TestClient({
+      ...(await testAuthUtils.logIn({ username, password: thirdPassword })),
+      testId,
+    });
+    await expect(forcedClient.listTeams()).rejects.toMatchObject({
+      response: {
+        status: 403,
+        data: { code: "forbidden", message: "Password change required" },
+      },
+    });
+    await forcedClient.changePassword({
+      currentPassword: thirdPassword,
+      newPassword: `fourth-pass-${testId}`,
+    });
+    await forcedClient.listTeams();
+
+    // ---- Cleanup -----------------------------------------------------------
+    await forcedClient.deleteOwnUser();
+    await adminClient.deleteOwnclub();
+  });
+});
@@ -0,0 +1,88 @@
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+
+const { testId } = makeTestId();
+
+describe(__filename, () => {
+  it("lists the effective permissions of each role", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const adminPermissions = await adminClient.listOwnPermissions();
+    expect(adminPermissions).toEqual(
+      expect.arrayContaining([
+        "team.create",
+        "role.assign:club_admin",
+        "role.assign:coach",
+        "game.create",
+        "game.list",
+      ]),
+    );
+
+    const coachId = await adminClient.createUser({
+      username: `coach-${testId}`,
+      password: `coach-pass-${testId}`,
+    });
+    await adminClient.assignRole({ user_id: coachId, role: "coach" });
+    const coachClient = new TestClient({
+      ...(await testAuthUtils.logIn({
+        username: `coach-${testId}`,
+        password: `coach-pass-${testId}`,
+      })),
+      testId,
+    });
+
+    const coachPermissions = await coachClient.listOwnPermissions();
+    expect(coachPermissions).toEqual(
+      expect.arrayContaining([
+        "game.create",
+        "invite.list",
+        "role.assign:coach",
+        "role.assign:player",
+        "game.list",
+      ]),
+    );
+    expect(coachPermissions).not.toContain("team.create");
+    expect(coachPermissions).not.toContain("role.assign:club_admin");
+  });
+
+  it("rejects actions outside the role's permissions", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin2-${testId}`,
+      password: `admin2-pass-${testId}`,
+      clubTitle: `test-club2-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const coachId = await adminClient.createUser({
+      username: `coach2-${testId}`,
+      password: `coach2-pass-${testId}`,
+    });
+    await adminClient.assignRole({ user_id: coachId, role: "coach" });
+    const coachClient = new TestClient({
+      ...(await testAuthUtils.logIn({
+        username: `coach2-${testId}`,
+        password: `coach2-pass-${testId}`,
+      })),
+      testId,
+    });
+
+    await expect(
+      coachClient.createTeam({ name: `team-${testId}`, slug: `t-${testId}` }),
+    ).rejects.toMatchObject({ response: { status: 403 } });
+    await expect(
+      coachClient.assignRole({ user_id: coachId, role: "club_admin" }),
+    ).rejects.toMatchObject({ response: { status: 403 } });
+
+    const playerId = await adminClient.createUser({
+      username: `player2-${testId}`,
+      password: `player2-pass-${testId}`,
+    });
+    await coachClient.assignRole({ user_id: playerId, role: "player" });
+  });
+});
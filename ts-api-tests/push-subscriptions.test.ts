@@ -0,0 +1,122 @@
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+
+const { testId } = makeTestId();
+
+// the user agent keys of the RFC 8291 example
+const keys = {
+  p256dh:
+    "BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4",
+  auth: "BTBZMqHH6r4Tts7J_aSIgg",
+};
+
+describe(__filename, () => {
+  it("registers and unregisters devices", async () => {
+    const userDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const userClient = new TestClient({ ...userDetails, testId });
+    const endpoint = `https://fcm.googleapis.com/fcm/send/${testId}`;
+
+    await userClient.registerPushSubscription({ endpoint, keys });
+    // registering again keeps a single row
+    await userClient.registerPushSubscription({ endpoint, keys });
+    expect(await userClient.listOwnPushSubscriptions()).toMatchObject({
+      items: [{}],
+      total: 1,
+    });
+
+    await userClient.unregisterPushSubscription(endpoint);
+    expect((await userClient.listOwnPushSubscriptions()).items).toEqual([]);
+  });
+
+  it("pages through devices", async () => {
+    const userDetails = await testAuthUtils.signUpWithNewClub({
+      username: `pager-${testId}`,
+      password: `pager-pass-${testId}`,
+      clubTitle: `pager-club-${testId}`,
+    });
+    const userClient = new TestClient({ ...userDetails, testId });
+    for (const device of ["a", "b", "c"]) {
+      await userClient.registerPushSubscription({
+        endpoint: `https://fcm.googleapis.com/fcm/send/${testId}-${device}`,
+        keys,
+      });
+    }
+
+    const first = await userClient.listOwnPushSubscriptions({ limit: 2 });
+    expect(first.items).toHaveLength(2);
+    expect(first.total).toBe(3);
+    const second = await userClient.listOwnPushSubscriptions({
+      limit: 2,
+      cursor: first.next_cursor!,
+    });
+    expect(second.items).toHaveLength(1);
+    expect(second.next_cursor).toBeNull();
+    const ids = [...first.items, ...second.items].map(({ id }) => id);
+    expect(new Set(ids).size).toBe(3);
+  });
+
+  it("stops notifying devices that logged out", async () => {
+    const credentials = {
+      username: `leaver-${testId}`,
+      password: `leaver-pass-${testId}`,
+    };
+    const phoneClient = new TestClient({
+      ...(await testAuthUtils.signUpWithNewClub({
+        ...credentials,
+        clubTitle: `leaver-club-${testId}`,
+      })),
+      testId,
+    });
+    const laptopClient = new TestClient({
+      ...(await testAuthUtils.logIn(credentials)),
+      testId,
+    });
+    const endpoint = `https://fcm.googleapis.com/fcm/send/${testId}-phone`;
+
+    await phoneClient.registerPushSubscription({ endpoint, keys });
+    await phoneClient.logOut();
+
+    // the user's other sessions stay, but nothing is sent to the phone anymore
+    expect((await laptopClient.listOwnPushSubscriptions()).items).toEqual([]);
+  });
+
+  it("refuses endpoints outside the known push services", async () => {
+    const userDetails = await testAuthUtils.signUpWithNewClub({
+      username: `other-${testId}`,
+      password: `other-pass-${testId}`,
+      clubTitle: `other-club-${testId}`,
+    });
+    const userClient = new TestClient({ ...userDetails, testId });
+
+    for (const endpoint of [
+      "https://127.0.0.1/push",
+      "https://169.254.169.254/latest/meta-data",
+      "http://fcm.googleapis.com/fcm/send/abc",
+      "https://push.example.com/abc",
+    ]) {
+      await expect(
+        userClient.registerPushSubscription({ endpoint, keys }),
+      ).rejects.toMatchObject({
+        response: {
+          status: 422,
+          data: { field_errors: [{ field: "endpoint" }] },
+        },
+      });
+    }
+
+    await expect(
+      userClient.registerPushSubscription({
+        endpoint: `https://fcm.googleapis.com/fcm/send/${testId}-bad`,
+        keys: { ...keys, auth: "too-short" },
+      }),
+    ).rejects.toMatchObject({
+      response: { status: 422, data: { field_errors: [{ field: "keys" }] } },
+    });
+    expect((await userClient.listOwnPushSubscriptions()).items).toEqual([]);
+  });
+});
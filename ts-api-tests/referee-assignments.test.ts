@@ -0,0 +1,140 @@
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+
+const { testId } = makeTestId();
+
+const inDays = (days: number, hours = 0) =>
+  new Date(Date.now() + days * 24 * 3600 * 1000 + hours * 3600 * 1000);
+
+describe(__filename, () => {
+  it("fills referee slots via requests", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const refManagerId = await adminClient.createUser({
+      username: `ref-manager-${testId}`,
+      password: `ref-manager-pass-${testId}`,
+    });
+    await adminClient.assignRole({
+      user_id: refManagerId,
+      role: "referee_manager",
+    });
+    const refManagerClient = new TestClient({
+      ...(await testAuthUtils.logIn({
+        username: `ref-manager-${testId}`,
+        password: `ref-manager-pass-${testId}`,
+      })),
+      testId,
+    });
+
+    const refereeId = await adminClient.createUser({
+      username: `referee-${testId}`,
+      password: `referee-pass-${testId}`,
+    });
+    await refManagerClient.assignRole({ user_id: refereeId, role: "referee" });
+    const refereeClient = new TestClient({
+      ...(await testAuthUtils.logIn({
+        username: `referee-${testId}`,
+        password: `referee-pass-${testId}`,
+      })),
+      testId,
+    });
+
+    const teamId = await adminClient.createTeam({
+      name: `team-${testId}`,
+      slug: `t-${testId}`,
+    });
+    await refManagerClient.setRefereeDefaults(teamId, [
+      { position: "main", count: 1 },
+      { position: "assistant", count: 2 },
+    ]);
+
+    const firstGameId = await adminClient.createGame({
+      team_id: teamId,
+      opponent: "first",
+      start_time: inDays(7),
+      location: "pitch",
+      location_kind: "home",
+      invited_roles: ["player"],
+    });
+    const overlappingGameId = await adminClient.createGame({
+      team_id: teamId,
+      opponent: "overlapping",
+      start_time: inDays(7, 1),
+      location: "pitch",
+      location_kind: "home",
+      invited_roles: ["player"],
+    });
+
+    const slots = await refManagerClient.listRefereeSlotsForGame(firstGameId);
+    expect(slots.map((slot) => slot.position).sort()).toEqual([
+      "assistant",
+      "assistant",
+      "main",
+    ]);
+    const mainSlot = slots.find((slot) => slot.position === "main")!;
+
+    const requestId = await refManagerClient.requestReferee({
+      slot_id: mainSlot.id,
+      referee_user_id: refereeId,
+    });
+
+    // the referee is busy with the first game already
+    const [overlappingSlot] =
+      await refManagerClient.listRefereeSlotsForGame(overlappingGameId);
+    await expect(
+      refManagerClient.requestReferee({
+        slot_id: overlappingSlot.id,
+        referee_user_id: refereeId,
+      }),
+    ).rejects.toMatchObject({ response: { status: 409 } });
+
+    await refereeClient.respondToRefereeRequest({
+      request_id: requestId,
+      response: "accepted",
+    });
+    expect(
+      (await refereeClient.listOwnRefereeAssignments()).items,
+    ).toMatchObject([
+      { request_id: requestId, game_id: firstGameId, status: "accepted" },
+    ]);
+
+    const unfilled = await refManagerClient.listUnfilledRefereeSlots();
+    expect(unfilled.items.map((slot) => slot.id)).not.toContain(mainSlot.id);
+    expect(unfilled.total).toBe(5);
+
+    await refManagerClient.cancelRefereeRequest(requestId);
+    expect((await refereeClient.listOwnRefereeAssignments()).items).toEqual([]);
+  });
+
+  it("is managed by referee managers only", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin2-${testId}`,
+      password: `admin2-pass-${testId}`,
+      clubTitle: `test-club2-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const coachId = await adminClient.createUser({
+      username: `coach-${testId}`,
+      password: `coach-pass-${testId}`,
+    });
+    await adminClient.assignRole({ user_id: coachId, role: "coach" });
+    const coachClient = new TestClient({
+      ...(await testAuthUtils.logIn({
+        username: `coach-${testId}`,
+        password: `coach-pass-${testId}`,
+      })),
+      testId,
+    });
+
+    await expect(
+      coachClient.listUnfilledRefereeSlots(),
+    ).rejects.toMatchObject({ response: { status: 403 } });
+  });
+});
@@ -0,0 +1,137 @@
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+
+const { testId } = makeTestId();
+
+// a fixed day well ahead, at UTC hours
+const day = new Date(Date.now() + 30 * 24 * 3600 * 1000);
+day.setUTCHours(0, 0, 0, 0);
+const at = (hours: number) => new Date(day.getTime() + hours * 3600 * 1000);
+
+describe(__filename, () => {
+  it("proposes qualified, balanced assignments and commits them", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const juniorId = await adminClient.createUser({
+      username: `junior-${testId}`,
+      password: `junior-pass-${testId}`,
+    });
+    const seniorId = await adminClient.createUser({
+      username: `senior-${testId}`,
+      password: `senior-pass-${testId}`,
+    });
+    await adminClient.assignRole({ user_id: juniorId, role: "referee" });
+    await adminClient.assignRole({ user_id: seniorId, role: "referee" });
+    await adminClient.setRefereeLevel(seniorId, 2);
+
+    const teamId = await adminClient.createTeam({
+      name: `team-${testId}`,
+      slug: `t-${testId}`,
+    });
+    await adminClient.setRefereeDefaults(teamId, [
+      { position: "main", count: 1, min_level: 2 },
+      { position: "assistant", count: 1 },
+    ]);
+
+    for (const [opponent, start] of [
+      ["first", 10],
+      ["second", 14],
+    ] as const) {
+      await adminClient.createGame({
+        team_id: teamId,
+        opponent,
+        start_time: at(start),
+        stop_time: at(start + 2),
+        location: "pitch",
+        location_kind: "home",
+        invited_roles: ["player"],
+      });
+    }
+
+    const proposal = await adminClient.proposeRefereeAssignments({
+      from: at(0),
+      until: at(24),
+    });
+    expect(proposal.unfilled).toEqual([]);
+    expect(proposal.assignments).toHaveLength(4);
+    for (const assignment of proposal.assignments) {
+      expect(assignment.referee_user_id).toBe(
+        assignment.position === "main" ? seniorId : juniorId,
+      );
+    }
+    expect(
+      proposal.referee_load.map((load) => load.assignments).sort(),
+    ).toEqual([2, 2]);
+
+    // one game a day at most leaves the second game open
+    const strict = await adminClient.proposeRefereeAssignments({
+      from: at(0),
+      until: at(24),
+      max_games_per_day: 1,
+    });
+    expect(strict.unfilled).toHaveLength(2);
+
+    const requestIds = await adminClient.commitRefereeAssignments(
+      proposal.assignments.map((assignment) => ({
+        slot_id: assignment.slot_id,
+        referee_user_id: assignment.referee_user_id!,
+      })),
+    );
+    expect(requestIds).toHaveLength(4);
+    expect((await adminClient.listUnfilledRefereeSlots()).items).toMatchObject(
+      proposal.assignments.map(() => ({ status: "pending" })),
+    );
+
+    // committed slots are taken, nothing is left to propose
+    const again = await adminClient.proposeRefereeAssignments({
+      from: at(0),
+      until: at(24),
+    });
+    expect(again.assignments).toEqual([]);
+  });
+
+  it("refuses referees below the slot's level", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin2-${testId}`,
+      password: `admin2-pass-${testId}`,
+      clubTitle: `test-club2-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const refereeId = await adminClient.createUser({
+      username: `referee-${testId}`,
+      password: `referee-pass-${testId}`,
+    });
+    await adminClient.assignRole({ user_id: refereeId, role: "referee" });
+
+    const teamId = await adminClient.createTeam({
+      name: `team2-${testId}`,
+      slug: `t2-${testId}`,
+    });
+    const gameId = await adminClient.createGame({
+      team_id: teamId,
+      opponent: "opp",
+      start_time: at(10),
+      location: "pitch",
+      location_kind: "home",
+      invited_roles: ["player"],
+    });
+    const slotId = await adminClient.addRefereeSlot({
+      game_id: gameId,
+      position: "main",
+      min_level: 1,
+    });
+
+    await expect(
+      adminClient.commitRefereeAssignments([
+        { slot_id: slotId, referee_user_id: refereeId },
+      ]),
+    ).rejects.toMatchObject({ response: { status: 400 } });
+  });
+});
@@ -0,0 +1,174 @@
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+
+const { testId } = makeTestId();
+
+const logInAs = async (username: string, password: string) =>
+  new TestClient({
+    ...(await testAuthUtils.logIn({ username, password })),
+    testId,
+  });
+
+describe(__filename, () => {
+  it("lets team managers manage games but not roles", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const managerId = await adminClient.createUser({
+      username: `manager-${testId}`,
+      password: `manager-pass-${testId}`,
+    });
+    await adminClient.assignRole({ user_id: managerId, role: "team_manager" });
+    const managerClient = await logInAs(
+      `manager-${testId}`,
+      `manager-pass-${testId}`,
+    );
+
+    const teamId = await adminClient.createTeam({
+      name: `team-${testId}`,
+      slug: `t-${testId}`,
+    });
+    const gameId = await managerClient.createGame({
+      team_id: teamId,
+      opponent: "opp",
+      start_time: new Date(),
+      location: "pitch",
+      location_kind: "home",
+      invited_roles: ["player", "referee"],
+    });
+    await managerClient.listInvitesToGame(gameId);
+
+    await expect(
+      managerClient.assignRole({ user_id: managerId, role: "player" }),
+    ).rejects.toMatchObject({ response: { status: 403 } });
+
+    await expect(
+      managerClient.createGame({
+        team_id: teamId,
+        opponent: "opp",
+        start_time: new Date(),
+        location: "pitch",
+        location_kind: "home",
+        invited_roles: ["parent"],
+      }),
+    ).rejects.toMatchObject({ response: { status: 422 } });
+  });
+
+  it("lets referee managers assign the referee role only", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin2-${testId}`,
+      password: `admin2-pass-${testId}`,
+      clubTitle: `test-club2-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const refManagerId = await adminClient.createUser({
+      username: `ref-manager-${testId}`,
+      password: `ref-manager-pass-${testId}`,
+    });
+    await adminClient.assignRole({
+      user_id: refManagerId,
+      role: "referee_manager",
+    });
+    const refManagerClient = await logInAs(
+      `ref-manager-${testId}`,
+      `ref-manager-pass-${testId}`,
+    );
+
+    const refereeId = await adminClient.createUser({
+      username: `referee-${testId}`,
+      password: `referee-pass-${testId}`,
+    });
+    await refManagerClient.assignRole({ user_id: refereeId, role: "referee" });
+    await expect(
+      refManagerClient.assignRole({ user_id: refereeId, role: "coach" }),
+    ).rejects.toMatchObject({ response: { status: 403 } });
+
+    expect(await refManagerClient.listOwnPermissions()).toEqual(
+      expect.arrayContaining(["referee_assignment.manage", "game.list"]),
+    );
+  });
+
+  it("lets parents answer invites for their minors", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin3-${testId}`,
+      password: `admin3-pass-${testId}`,
+      clubTitle: `test-club3-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    const parentId = await adminClient.createUser({
+      username: `parent-${testId}`,
+      password: `parent-pass-${testId}`,
+    });
+    const minorId = await adminClient.createUser({
+      username: `minor-${testId}`,
+      password: `minor-pass-${testId}`,
+    });
+    await adminClient.assignRole({ user_id: minorId, role: "player" });
+
+    // the parent role comes first
+    await expect(
+      adminClient.linkGuardianship({
+        parent_user_id: parentId,
+        minor_user_id: minorId,
+      }),
+    ).rejects.toMatchObject({ response: { status: 400 } });
+
+    await adminClient.assignRole({ user_id: parentId, role: "parent" });
+    await adminClient.linkGuardianship({
+      parent_user_id: parentId,
+      minor_user_id: minorId,
+    });
+    expect((await adminClient.listGuardianships()).items).toMatchObject([
+      { parent_user_id: parentId, minor_user_id: minorId },
+    ]);
+
+    const teamId = await adminClient.createTeam({
+      name: `team3-${testId}`,
+      slug: `t3-${testId}`,
+    });
+    const gameId = await adminClient.createGame({
+      team_id: teamId,
+      opponent: "opp",
+      start_time: new Date(),
+      location: "pitch",
+      location_kind: "away",
+      invited_roles: ["player"],
+    });
+
+    const parentClient = await logInAs(
+      `parent-${testId}`,
+      `parent-pass-${testId}`,
+    );
+    expect((await parentClient.listOwnMinors()).items).toMatchObject([
+      { id: minorId, username: `minor-${testId}` },
+    ]);
+    const [invite] = (await parentClient.listMinorsInvites()).items;
+    expect(invite).toMatchObject({
+      user_id: minorId,
+      game_id: gameId,
+      response: "pending",
+    });
+
+    await parentClient.respondToInvite({
+      invite_id: invite.invite_id,
+      response: "accepted",
+    });
+    const minorClient = await logInAs(`minor-${testId}`, `minor-pass-${testId}`);
+    expect((await minorClient.listOwnInvites()).items).toMatchObject([
+      { game_id: gameId, response: "accepted" },
+    ]);
+
+    await adminClient.unlinkGuardianship({
+      parent_user_id: parentId,
+      minor_user_id: minorId,
+    });
+    expect((await parentClient.listMinorsInvites()).items).toEqual([]);
+  });
+});
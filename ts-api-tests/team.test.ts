@@ -25,7 +25,7 @@ describe(__filename, () => {
     });
 
     // ---- List teams ----------------------------------------------------
-    const teamsAfterCreate = await clubAdminClient.listTeams();
+    const teamsAfterCreate = (await clubAdminClient.listTeams()).items;
     expect(teamsAfterCreate).toEqual(
       expect.arrayContaining([
         expect.objectContaining({
@@ -67,7 +67,7 @@ describe(__filename, () => {
     await clubAdminClient.deleteTeamById(teamId);
 
     // Team should no longer appear in the list
-    const teamsAfterDelete = await clubAdminClient.listTeams();
+    const teamsAfterDelete = (await clubAdminClient.listTeams()).items;
     expect(teamsAfterDelete).not.toEqual(
       expect.arrayContaining([expect.objectContaining({ id: teamId })]),
     );
@@ -94,7 +94,10 @@ describe(__filename, () => {
     ).rejects.toMatchObject({
       response: {
         status: 403,
-        data: "Access denied. Needs one of roles: [super_admin, club_admin]",
+        data: {
+          code: "forbidden",
+          message: "Access denied. Missing permission: team.create",
+        },
       },
     });
 
@@ -0,0 +1,102 @@
+import { TwoFactorRequiredError } from "ts-shared";
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+import { totpCode } from "./utils/totp";
+
+const { testId } = makeTestId();
+
+const logInExpectingChallenge = async (username: string, password: string) => {
+  try {
+    await testAuthUtils.logIn({ username, password });
+  } catch (err) {
+    if (err instanceof TwoFactorRequiredError) {
+      return err.challenge;
+    }
+    throw err;
+  }
+  throw new Error("expected a two-factor challenge");
+};
+
+describe(__filename, () => {
+  it("requires admins to enrol and log in with a second factor", async () => {
+    const username = `admin-${testId}`;
+    const password = `admin-pass-${testId}`;
+    const adminClient = new TestClient({
+      ...(await testAuthUtils.signUpWithNewClub({
+        username,
+        password,
+        clubTitle: `test-club-${testId}`,
+      })),
+      testId,
+    });
+
+    // ---- club setting blocks admins until they enrol ---------------------------
+    await adminClient.setRequireAdminTwoFactor(true);
+    await expect(adminClient.listTeams()).rejects.toMatchObject({
+      response: { status: 403 },
+    });
+
+    // a fresh log-in has to fetch its CSRF token while restricted
+    const enrolClient = new TestClient({
+      ...(await testAuthUtils.logIn({ username, password })),
+      testId,
+    });
+    const { secret, otpauth_uri } = await enrolClient.enrolTwoFactor();
+    expect(otpauth_uri).toContain(`secret=${secret}`);
+
+    await expect(enrolClient.activateTwoFactor("000000")).rejects.toMatchObject(
+      { response: { status: 400 } },
+    );
+    const recoveryCodes = await enrolClient.activateTwoFactor(totpCode(secret));
+    expect(recoveryCodes).toHaveLength(10);
+    await adminClient.listTeams();
+
+    // required 2FA can't be switched off
+    await expect(
+      adminClient.disableTwoFactor({
+        currentPassword: password,
+        code: totpCode(secret, 1),
+      }),
+    ).rejects.toMatchObject({ response: { status: 403 } });
+
+    // ---- second log-in step ----------------------------------------------------
+    const challenge = await logInExpectingChallenge(username, password);
+    await expect(
+      testAuthUtils.completeTwoFactorLogIn({ challenge, code: "000000" }),
+    ).rejects.toMatchObject({ response: { status: 401 } });
+
+    // recovery codes work once
+    const recoveryClient = new TestClient({
+      ...(await testAuthUtils.completeTwoFactorLogIn({
+        challenge,
+        code: recoveryCodes[0],
+      })),
+      testId,
+    });
+    expect(await recoveryClient.getTwoFactorStatus()).toMatchObject({
+      enabled: true,
+      required: true,
+      recovery_codes_left: 9,
+    });
+    await expect(
+      testAuthUtils.completeTwoFactorLogIn({
+        challenge: await logInExpectingChallenge(username, password),
+        code: recoveryCodes[0],
+      }),
+    ).rejects.toMatchObject({ response: { status: 401 } });
+
+    // ---- admin reset -----------------------------------------------------------
+    const memberId = await adminClient.createUser({
+      username: `member-${testId}`,
+      password: `member-pass-${testId}`,
+    });
+    await adminClient.resetTwoFactorForUser(memberId);
+
+    // ---- Cleanup -----------------------------------------------------------------
+    await adminClient.setRequireAdminTwoFactor(false);
+    await adminClient.deleteUserById(memberId);
+    await recoveryClient.logOut();
+    await adminClient.deleteOwnclub();
+  });
+});
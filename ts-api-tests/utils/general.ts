@@ -9,7 +9,10 @@ export const makeTestId = () => {
   const callSites = util.getCallSites();
   const filePath = callSites[0].scriptName;
   const fileName = path.parse(filePath).base;
-  const testId = timestamp + randomUUID().slice(0, 4) + fileName;
+  // usable in slugs as well
+  const testId = (timestamp + randomUUID().slice(0, 4) + fileName)
+    .toLowerCase()
+    .replace(/[^a-z0-9-]+/g, "-");
   return { testId };
 };
 
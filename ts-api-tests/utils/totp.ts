@@ -0,0 +1,30 @@
+import { createHmac } from "crypto";
+
+const BASE32_ALPHABET = "ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+const decodeBase32 = (input: string) => {
+  let bits = "";
+  for (const char of input.replace(/=+$/, "")) {
+    bits += BASE32_ALPHABET.indexOf(char).toString(2).padStart(5, "0");
+  }
+  const bytes: number[] = [];
+  for (let i = 0; i + 8 <= bits.length; i += 8) {
+    bytes.push(parseInt(bits.slice(i, i + 8), 2));
+  }
+  return Buffer.from(bytes);
+};
+
+/** RFC 6238 code, `stepOffset` picks a neighbouring 30s window */
+export const totpCode = (secret: string, stepOffset = 0) => {
+  const step = Math.floor(Date.now() / 1000 / 30) + stepOffset;
+  const counter = Buffer.alloc(8);
+  counter.writeBigUInt64BE(BigInt(step));
+
+  const digest = createHmac("sha1", decodeBase32(secret))
+    .update(counter)
+    .digest();
+  const offset = digest[digest.length - 1] & 0x0f;
+  const value = (digest.readUInt32BE(offset) & 0x7fffffff) % 1_000_000;
+
+  return value.toString().padStart(6, "0");
+};
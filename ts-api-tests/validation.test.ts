@@ -0,0 +1,87 @@
+import axios from "axios";
+import { testAuthUtils } from "./utils/auth";
+import { TestClient } from "./utils/test-client";
+import { makeTestId } from "./utils/general";
+import { API_URL } from "./utils/env";
+
+const { testId } = makeTestId();
+
+describe(__filename, () => {
+  it("reports every invalid field at once", async () => {
+    const res = await axios.post(
+      API_URL + "/auth/log-in",
+      { username: "", password: "" },
+      { validateStatus: () => true },
+    );
+    expect(res.status).toBe(422);
+    expect(res.data).toMatchObject({
+      code: "validation_failed",
+      field_errors: [
+        { field: "username", message: "must not be empty" },
+        { field: "password", message: "must not be empty" },
+      ],
+    });
+  });
+
+  it("checks payloads before touching anything", async () => {
+    const adminDetails = await testAuthUtils.signUpWithNewClub({
+      username: `admin-${testId}`,
+      password: `admin-pass-${testId}`,
+      clubTitle: `test-club-${testId}`,
+    });
+    const adminClient = new TestClient({ ...adminDetails, testId });
+
+    await expect(
+      adminClient.createUser({ username: `short-${testId}`, password: "x" }),
+    ).rejects.toMatchObject({
+      response: { status: 422, data: { field_errors: [{ field: "password" }] } },
+    });
+    // `@` is left to email addresses, which log in as well
+    await expect(
+      adminClient.createUser({
+        username: `member-${testId}@example.com`,
+        password: `member-pass-${testId}`,
+      }),
+    ).rejects.toMatchObject({
+      response: {
+        status: 422,
+        data: {
+          field_errors: [{ field: "username", message: "must not contain @" }],
+        },
+      },
+    });
+
+    await expect(
+      adminClient.createTeam({ name: " ", slug: "Not A Slug" }),
+    ).rejects.toMatchObject({
+      response: {
+        status: 422,
+        data: { field_errors: [{ field: "name" }, { field: "slug" }] },
+      },
+    });
+
+    const teamId = await adminClient.createTeam({
+      name: `team-${testId}`,
+      slug: `t-${testId}`,
+    });
+    await expect(
+      adminClient.createGame({
+        team_id: teamId,
+        opponent: "opp",
+        start_time: new Date("2030-01-02T10:00:00Z"),
+        stop_time: new Date("2030-01-02T09:00:00Z"),
+        location: "pitch",
+        location_kind: "home",
+        invited_roles: [],
+      }),
+    ).rejects.toMatchObject({
+      response: {
+        status: 422,
+        data: {
+          field_errors: [{ field: "stop_time" }, { field: "invited_roles" }],
+        },
+      },
+    });
+    expect((await adminClient.listGamesForTeam(teamId)).items).toEqual([]);
+  });
+});
@@ -0,0 +1,34 @@
+// service worker - only used to show Web Push notifications sent by the backend
+
+self.addEventListener("push", (event) => {
+  if (!event.data) {
+    return;
+  }
+  const { title, body, url, tag } = event.data.json();
+
+  event.waitUntil(
+    self.registration.showNotification(title, {
+      body,
+      tag: tag ?? undefined,
+      data: { url: url ?? "/" },
+    }),
+  );
+});
+
+self.addEventListener("notificationclick", (event) => {
+  event.notification.close();
+  const url = event.notification.data?.url ?? "/";
+
+  event.waitUntil(
+    self.clients
+      .matchAll({ type: "window", includeUncontrolled: true })
+      .then((windows) => {
+        const open = windows.find((w) => "focus" in w);
+        if (open) {
+          open.navigate(url);
+          return open.focus();
+        }
+        return self.clients.openWindow(url);
+      }),
+  );
+});
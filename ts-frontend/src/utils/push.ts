@@ -0,0 +1,47 @@
+import { frontendClient } from "../client/fe-client";
+
+// applicationServerKey must be passed as raw bytes
+const base64UrlToBytes = (base64Url: string) => {
+  const base64 = (base64Url + "=".repeat((4 - (base64Url.length % 4)) % 4))
+    .replace(/-/g, "+")
+    .replace(/_/g, "/");
+  return Uint8Array.from(atob(base64), (c) => c.charCodeAt(0));
+};
+
+export const isPushSupported = () =>
+  "serviceWorker" in navigator && "PushManager" in window;
+
+/**
+ * Asks for permission, subscribes this device and registers it with the backend.
+ * Calling it again on an already subscribed device just refreshes the registration.
+ */
+export const enablePushNotifications = async () => {
+  if (!isPushSupported()) {
+    throw new Error("push notifications are not supported by this browser");
+  }
+
+  const permission = await Notification.requestPermission();
+  if (permission !== "granted") {
+    throw new Error("notification permission was not granted");
+  }
+
+  const registration = await navigator.serviceWorker.register("/sw.js");
+  const vapidPublicKey = await frontendClient.getVapidPublicKey();
+
+  const subscription =
+    (await registration.pushManager.getSubscription()) ??
+    (await registration.pushManager.subscribe({
+      userVisibleOnly: true,
+      applicationServerKey: base64UrlToBytes(vapidPublicKey),
+    }));
+
+  const { endpoint, keys } = subscription.toJSON();
+  if (!endpoint || !keys?.p256dh || !keys?.auth) {
+    throw new Error("browser returned an incomplete push subscription");
+  }
+
+  await frontendClient.registerPushSubscription({
+    endpoint,
+    keys: { p256dh: keys.p256dh, auth: keys.auth },
+  });
+};
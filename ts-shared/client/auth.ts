@@ -6,8 +6,21 @@ const loginResSchema = z.string();
 
 const AUTH_PREFIX = "/auth";
 
+const twoFactorChallengeSchema = z.object({
+  two_factor_challenge: z.string(),
+});
+
 export type LoginResult = { ownId: string; cookie: string };
 
+/** thrown by `logIn` when the password was right, but a second factor is needed */
+export class TwoFactorRequiredError extends Error {
+  challenge: string;
+  constructor(challenge: string) {
+    super("Two-factor authentication required");
+    this.challenge = challenge;
+  }
+}
+
 export class AuthUtils {
   axios: (x: AxiosRequestConfig) => AxiosPromise;
   API_URL: string;
@@ -25,7 +38,7 @@ export class AuthUtils {
     username: string;
     password: string;
   }): Promise<LoginResult> => {
-    const { data, headers } = await this.axios({
+    const { status, data, headers } = await this.axios({
       method: "POST",
       url: "/log-in",
       data: {
@@ -35,6 +48,39 @@ export class AuthUtils {
       validateStatus: () => true,
     });
 
+    if (status === 202) {
+      const { two_factor_challenge } = twoFactorChallengeSchema.parse(data);
+      throw new TwoFactorRequiredError(two_factor_challenge);
+    }
+
+    return this.toLoginResult(data, headers["set-cookie"], "login");
+  };
+
+  /**
+   * second log-in step, `code` is a TOTP code or a recovery code.
+   * After single sign-on `challenge` is omitted, the browser sends it as a cookie.
+   */
+  completeTwoFactorLogIn = async ({
+    challenge,
+    code,
+  }: {
+    challenge?: string;
+    code: string;
+  }): Promise<LoginResult> => {
+    const { data, headers } = await this.axios({
+      method: "POST",
+      url: "/log-in/2fa",
+      data: { challenge, code },
+    });
+
+    return this.toLoginResult(data, headers["set-cookie"], "2FA login");
+  };
+
+  private toLoginResult = (
+    data: unknown,
+    cookies: string[] | undefined,
+    action: string,
+  ): LoginResult => {
     const ownId = loginResSchema.parse(data);
 
     if (this.kind === "browser") {
@@ -42,24 +88,25 @@ export class AuthUtils {
       return { ownId, cookie: "fake-cookie" };
     }
 
-    const cookies = headers["set-cookie"];
     if (Array.isArray(cookies)) {
       const cookie = cookies.find((c) => c.startsWith("session_id="));
       if (cookie) {
         return { ownId, cookie };
       }
     }
-    throw new Error("Failed to retieve cookie from login");
+    throw new Error("Failed to retieve cookie from " + action);
   };
 
   signUpWithNewClub = async ({
     username,
     password,
     clubTitle,
+    email,
   }: {
     username: string;
     password: string;
     clubTitle: string;
+    email?: string;
   }): Promise<LoginResult> => {
     const { status, data, headers } = await this.axios({
       method: "POST",
@@ -68,6 +115,7 @@ export class AuthUtils {
         username,
         password,
         club_title: clubTitle,
+        email,
       },
       validateStatus: () => true,
     });
@@ -94,10 +142,12 @@ export class AuthUtils {
     username,
     password,
     inviteId,
+    email,
   }: {
     username: string;
     password: string;
     inviteId: string;
+    email?: string;
   }): Promise<LoginResult> => {
     const { status, data, headers } = await this.axios({
       method: "POST",
@@ -105,6 +155,7 @@ export class AuthUtils {
       data: {
         username,
         password,
+        email,
       },
       validateStatus: () => true,
     });
@@ -126,4 +177,39 @@ export class AuthUtils {
     }
     throw new Error("Failed to retieve cookie from signup with new club");
   };
+
+  /** always resolves the same way, whether or not the user exists */
+  requestPasswordReset = async ({ username }: { username: string }) => {
+    await this.axios({
+      method: "POST",
+      url: "/password-reset/request",
+      data: { username },
+    });
+  };
+
+  confirmPasswordReset = async ({
+    token,
+    newPassword,
+  }: {
+    token: string;
+    newPassword: string;
+  }) => {
+    await this.axios({
+      method: "POST",
+      url: "/password-reset/confirm",
+      data: { token, new_password: newPassword },
+    });
+  };
+
+  verifyEmail = async ({ token }: { token: string }) => {
+    await this.axios({
+      method: "POST",
+      url: "/verify-email",
+      data: { token },
+    });
+  };
+
+  /** where the browser goes to log in via the club's single sign-on provider */
+  oidcLogInUrl = (clubId: string) =>
+    `${this.API_URL}${AUTH_PREFIX}/oidc/${encodeURIComponent(clubId)}/start`;
 }
@@ -10,6 +10,21 @@ z.config({
   },
 });
 
+/** query parameters every list route takes, `sort` is descending with a leading `-` */
+export type ListParams = {
+  limit?: number;
+  cursor?: string;
+  sort?: string;
+};
+
+/** one page of a list route, pass `next_cursor` as `cursor` for the next one */
+const pageSchema = <T extends z.ZodType>(item: T) =>
+  z.object({
+    items: z.array(item),
+    next_cursor: z.string().nullable(),
+    total: z.number(),
+  });
+
 const listUsersResponseSchema = z.array(
   z.object({
     id: z.string(),
@@ -17,17 +32,125 @@ const listUsersResponseSchema = z.array(
   }),
 );
 
-export type Role = "super_admin" | "club_admin" | "coach" | "player";
+export type Role =
+  | "super_admin"
+  | "club_admin"
+  | "coach"
+  | "team_manager"
+  | "referee_manager"
+  | "referee"
+  | "player"
+  | "parent";
 export const roleSchema = z.enum([
   "super_admin",
   "club_admin",
   "coach",
+  "team_manager",
+  "referee_manager",
+  "referee",
   "player",
+  "parent",
 ]);
 
+/** body of every error response */
+export const apiErrorSchema = z.object({
+  code: z.string(),
+  message: z.string(),
+  field_errors: z.array(z.object({ field: z.string(), message: z.string() })),
+  request_id: z.string().nullable(),
+});
+export type ApiError = z.infer<typeof apiErrorSchema>;
+
 export type LocationKind = "home" | "away" | "other";
 
-const listRolesResSchema = z.record(z.string(), z.array(roleSchema));
+const issuedPasswordResetSchema = z.object({
+  token: z.string(),
+  reset_url: z.string(),
+  expires_at: z.coerce.date(),
+});
+
+const ownEmailSchema = z.object({
+  email: z.string().nullable(),
+  verified_at: z.coerce.date().nullable(),
+  pending_email: z.string().nullable(),
+});
+
+const twoFactorStatusSchema = z.object({
+  enabled: z.boolean(),
+  required: z.boolean(),
+  recovery_codes_left: z.number(),
+});
+
+const twoFactorEnrolmentSchema = z.object({
+  secret: z.string(),
+  otpauth_uri: z.string(),
+});
+
+const recoveryCodesSchema = z.object({
+  recovery_codes: z.array(z.string()),
+});
+
+const createdApiTokenSchema = z.object({
+  id: z.string(),
+  name: z.string(),
+  token: z.string(),
+  scopes: z.array(z.string()),
+  expires_at: z.coerce.date(),
+});
+
+const listOwnApiTokensResSchema = pageSchema(
+  z.object({
+    id: z.string(),
+    name: z.string(),
+    token_prefix: z.string(),
+    scopes: z.array(z.string()),
+    expires_at: z.coerce.date(),
+    last_used_at: z.coerce.date().nullable(),
+    created_at: z.coerce.date(),
+  }),
+);
+
+const auditLogPageSchema = pageSchema(
+  z.object({
+    id: z.string(),
+    actor_user_id: z.string().nullable(),
+    actor_username: z.string().nullable(),
+    action: z.string(),
+    target_type: z.string().nullable(),
+    target_id: z.string().nullable(),
+    details: z.record(z.string(), z.unknown()),
+    before: z.unknown().nullable(),
+    after: z.unknown().nullable(),
+    request_id: z.string().nullable(),
+    ip: z.string().nullable(),
+    created_at: z.coerce.date(),
+  }),
+);
+
+const impersonationStatusSchema = z.object({
+  impersonated_user_id: z.string(),
+  impersonated_username: z.string(),
+  expires_at: z.coerce.date(),
+});
+
+const oidcProviderSchema = z.object({
+  issuer_url: z.string(),
+  client_id: z.string(),
+  jit_provisioning: z.boolean(),
+  default_role: roleSchema,
+});
+
+const oidcLinkStartSchema = z.object({
+  authorization_url: z.string(),
+});
+
+const listRolesResSchema = pageSchema(
+  z.object({
+    user_id: z.string(),
+    username: z.string(),
+    roles: z.array(roleSchema),
+  }),
+);
 
 export type Team = {
   id: string;
@@ -38,8 +161,20 @@ export type Team = {
 
 export type ClientKind = "node" | "browser";
 
+const SAFE_METHODS = ["GET", "HEAD", "OPTIONS"];
+const CSRF_HEADER = "x-csrf-token";
+
+const csrfTokenSchema = z.object({ csrf_token: z.string() });
+
+declare module "axios" {
+  interface AxiosRequestConfig {
+    _csrfRetried?: boolean;
+  }
+}
+
 export class Client {
   cookie: string;
+  csrfToken: string | undefined;
   axios: AxiosInstance;
   kind: ClientKind;
   allCookies: string[];
@@ -87,16 +222,47 @@ export class Client {
       // browser
       this.axios = axios.create({ baseURL, withCredentials: true });
     }
+
+    this.axios.interceptors.request.use(async (config) => {
+      const method = (config.method ?? "GET").toUpperCase();
+      if (!SAFE_METHODS.includes(method)) {
+        this.csrfToken ??= await this.fetchCsrfToken();
+        config.headers.set(CSRF_HEADER, this.csrfToken);
+      }
+      return config;
+    });
+
+    this.axios.interceptors.response.use(undefined, async (err) => {
+      // the token belongs to the session - after logging in again it has to be fetched anew
+      const config = err?.config;
+      if (
+        err?.response?.status === 403 &&
+        err.response.data?.message === "Invalid CSRF token" &&
+        config &&
+        !config._csrfRetried
+      ) {
+        this.csrfToken = undefined;
+        return this.axios({ ...config, _csrfRetried: true });
+      }
+      throw err;
+    });
   }
 
+  private fetchCsrfToken = async () => {
+    const { data } = await this.axios({ method: "GET", url: "/csrf-token" });
+    return csrfTokenSchema.parse(data).csrf_token;
+  };
+
   // USER
 
   async createUser({
     username,
     password,
+    email,
   }: {
     username: string;
     password: string;
+    email?: string;
   }) {
     const { data } = await this.axios({
       method: "POST",
@@ -104,16 +270,18 @@ export class Client {
       data: {
         username,
         password,
+        email,
       },
     });
     return z.string().parse(data);
   }
 
-  async listUsers() {
-    const { status, data } = await this.axios({
+  async listUsers(params: ListParams & { q?: string; role?: Role } = {}) {
+    const { data } = await this.axios({
       url: "/users/list",
+      params,
     });
-    return listUsersResponseSchema.parse(data);
+    return pageSchema(listUsersResponseSchema.element).parse(data);
   }
 
   async deleteUserById(id: string) {
@@ -130,12 +298,277 @@ export class Client {
     });
   }
 
+  // PASSWORDS
+
+  async changePassword({
+    currentPassword,
+    newPassword,
+  }: {
+    currentPassword: string;
+    newPassword: string;
+  }) {
+    await this.axios({
+      method: "POST",
+      url: "/password/change",
+      data: {
+        current_password: currentPassword,
+        new_password: newPassword,
+      },
+    });
+  }
+
+  /**
+   * For users without email - the admin passes the token/link on.
+   */
+  async createPasswordResetForUser(userId: string) {
+    const { data } = await this.axios({
+      method: "POST",
+      url: "/users/create-password-reset/" + userId,
+    });
+    return issuedPasswordResetSchema.parse(data);
+  }
+
+  async forcePasswordReset(userId: string) {
+    await this.axios({
+      method: "POST",
+      url: "/users/force-password-reset/" + userId,
+    });
+  }
+
+  // EMAIL
+
+  async getOwnEmail() {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/email/get-own",
+    });
+    return ownEmailSchema.parse(data);
+  }
+
+  /** the new address is only used once verified */
+  async changeEmail(email: string) {
+    await this.axios({
+      method: "POST",
+      url: "/email/change",
+      data: { email },
+    });
+  }
+
+  async resendEmailVerification() {
+    await this.axios({
+      method: "POST",
+      url: "/email/resend-verification",
+    });
+  }
+
+  async removeEmail() {
+    await this.axios({
+      method: "DELETE",
+      url: "/email/remove",
+    });
+  }
+
+  // TWO-FACTOR AUTH
+
+  async getTwoFactorStatus() {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/2fa/status",
+    });
+    return twoFactorStatusSchema.parse(data);
+  }
+
+  /** 2FA is only switched on after `activateTwoFactor` with a first code */
+  async enrolTwoFactor() {
+    const { data } = await this.axios({
+      method: "POST",
+      url: "/2fa/enrol",
+    });
+    return twoFactorEnrolmentSchema.parse(data);
+  }
+
+  async activateTwoFactor(code: string) {
+    const { data } = await this.axios({
+      method: "POST",
+      url: "/2fa/activate",
+      data: { code },
+    });
+    return recoveryCodesSchema.parse(data).recovery_codes;
+  }
+
+  async disableTwoFactor({
+    currentPassword,
+    code,
+  }: {
+    currentPassword: string;
+    code: string;
+  }) {
+    await this.axios({
+      method: "POST",
+      url: "/2fa/disable",
+      data: { current_password: currentPassword, code },
+    });
+  }
+
+  async regenerateRecoveryCodes(code: string) {
+    const { data } = await this.axios({
+      method: "POST",
+      url: "/2fa/recovery-codes/regenerate",
+      data: { code },
+    });
+    return recoveryCodesSchema.parse(data).recovery_codes;
+  }
+
+  async resetTwoFactorForUser(userId: string) {
+    await this.axios({
+      method: "POST",
+      url: "/users/reset-2fa/" + userId,
+    });
+  }
+
+  async setRequireAdminTwoFactor(required: boolean) {
+    await this.axios({
+      method: "POST",
+      url: "/clubs/set-require-admin-2fa",
+      data: { required },
+    });
+  }
+
+  /** lifts log-in delays and lockouts after failed attempts */
+  async unlockUserLogin(userId: string) {
+    await this.axios({
+      method: "POST",
+      url: "/users/unlock-login/" + userId,
+    });
+  }
+
+  // API TOKENS
+
+  /** the token is only returned here, store it right away */
+  async createApiToken(payload: {
+    name: string;
+    scopes: string[];
+    expires_in_days?: number;
+  }) {
+    const { data } = await this.axios({
+      method: "POST",
+      url: "/api-tokens/create",
+      data: payload,
+    });
+    return createdApiTokenSchema.parse(data);
+  }
+
+  async listOwnApiTokens(params: ListParams = {}) {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/api-tokens/list-own",
+      params,
+    });
+    return listOwnApiTokensResSchema.parse(data);
+  }
+
+  async revokeApiToken(id: string) {
+    await this.axios({
+      method: "DELETE",
+      url: "/api-tokens/revoke/" + id,
+    });
+  }
+
+  // AUDIT LOG
+
+  async listAuditLog(
+    params: ListParams & {
+      action?: string;
+      actor_user_id?: string;
+      target_type?: string;
+      target_id?: string;
+      since?: Date;
+      until?: Date;
+    } = {},
+  ) {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/audit",
+      params,
+    });
+    return auditLogPageSchema.parse(data);
+  }
+
+  // IMPERSONATION
+
+  async startImpersonation(userId: string) {
+    const { data } = await this.axios({
+      method: "POST",
+      url: "/impersonation/start/" + userId,
+    });
+    return impersonationStatusSchema.parse(data);
+  }
+
+  async stopImpersonation() {
+    await this.axios({
+      method: "POST",
+      url: "/impersonation/stop",
+    });
+  }
+
+  /** `null` when not impersonating anyone */
+  async getImpersonationStatus() {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/impersonation/status",
+    });
+    return impersonationStatusSchema.nullable().parse(data);
+  }
+
+  // SINGLE SIGN-ON
+
+  async getOidcProvider() {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/clubs/oidc",
+    });
+    return oidcProviderSchema.parse(data);
+  }
+
+  async setOidcProvider(payload: {
+    issuer_url: string;
+    client_id: string;
+    client_secret: string;
+    jit_provisioning?: boolean;
+    default_role?: Role;
+  }) {
+    await this.axios({
+      method: "POST",
+      url: "/clubs/oidc/set",
+      data: payload,
+    });
+  }
+
+  async removeOidcProvider() {
+    await this.axios({
+      method: "DELETE",
+      url: "/clubs/oidc/remove",
+    });
+  }
+
+  /** the browser has to navigate to the returned URL to finish linking */
+  async startOidcLink() {
+    const { data } = await this.axios({
+      method: "POST",
+      url: "/oidc/link",
+    });
+    return oidcLinkStartSchema.parse(data).authorization_url;
+  }
+
   // ROLES
 
-  async listRoles() {
+  async listRoles(
+    params: ListParams & { user_id?: string; role?: Role } = {},
+  ) {
     const { data } = await this.axios({
       method: "GET",
       url: "/roles/list",
+      params,
     });
     return listRolesResSchema.parse(data);
   }
@@ -148,6 +581,15 @@ export class Client {
     return z.array(roleSchema).parse(data);
   }
 
+  /** permission names like `game.create` or `role.assign:coach` */
+  async listOwnPermissions() {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/permissions/list-own",
+    });
+    return z.array(z.string()).parse(data);
+  }
+
   async assignRole({ user_id, role }: { user_id: string; role: Role }) {
     await this.axios({
       method: "POST",
@@ -194,12 +636,13 @@ export class Client {
     slug: z.string(),
   });
 
-  private listTeamsResponseSchema = z.array(this.teamSchema);
+  private listTeamsResponseSchema = pageSchema(this.teamSchema);
 
-  async listTeams(): Promise<Team[]> {
+  async listTeams(params: ListParams & { q?: string } = {}) {
     const { data } = await this.axios({
       method: "get",
       url: `/teams/list`,
+      params,
     });
     return this.listTeamsResponseSchema.parse(data);
   }
@@ -285,7 +728,7 @@ export class Client {
     });
   }
 
-  private listGamesResponse = z.array(
+  private listGamesResponse = pageSchema(
     z.object({
       id: z.string(),
       team_id: z.string(),
@@ -297,11 +740,14 @@ export class Client {
     }),
   );
 
-  // Add the listGamesForTeam method
-  async listGamesForTeam(teamId: string) {
+  async listGamesForTeam(
+    teamId: string,
+    params: ListParams & { from?: Date; until?: Date } = {},
+  ) {
     const { data } = await this.axios({
       method: "GET",
       url: "/games/list-for-team/" + teamId,
+      params,
     });
 
     return this.listGamesResponse.parse(data);
@@ -309,22 +755,43 @@ export class Client {
 
   // EVENT INVITE
 
-  async listOwnInvites() {
+  async listOwnInvites(
+    params: ListParams & {
+      response?: InviteResponse;
+      team_id?: string;
+      from?: Date;
+      until?: Date;
+    } = {},
+  ) {
     const { data } = await this.axios({
       method: "GET",
       url: "/game-invites/list-own",
+      params,
     });
     return listOwnInvitesResSchema.parse(data);
   }
 
-  async listInvitesToGame(game_id: string) {
+  async listInvitesToGame(
+    game_id: string,
+    params: ListParams & { response?: InviteResponse } = {},
+  ) {
     const { data } = await this.axios({
       method: "GET",
       url: "/game-invites/list-to-game/" + game_id,
+      params,
     });
     return listInvitesToGameResSchema.parse(data);
   }
 
+  async listMinorsInvites(params: ListParams = {}) {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/game-invites/list-for-minors",
+      params,
+    });
+    return listMinorsInvitesResSchema.parse(data);
+  }
+
   async respondToInvite(payload: {
     invite_id: string;
     response: InviteResponseFromUser;
@@ -337,6 +804,294 @@ export class Client {
     return;
   }
 
+  // GUARDIANSHIPS
+
+  async linkGuardianship(payload: {
+    parent_user_id: string;
+    minor_user_id: string;
+  }) {
+    await this.axios({
+      method: "POST",
+      url: "/guardianships/link",
+      data: payload,
+    });
+  }
+
+  async unlinkGuardianship(payload: {
+    parent_user_id: string;
+    minor_user_id: string;
+  }) {
+    await this.axios({
+      method: "DELETE",
+      url: "/guardianships/unlink",
+      data: payload,
+      validateStatus: (s) => s === 204,
+    });
+  }
+
+  async listGuardianships(params: ListParams = {}) {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/guardianships/list",
+      params,
+    });
+    return listGuardianshipsResSchema.parse(data);
+  }
+
+  async listOwnMinors(params: ListParams = {}) {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/guardianships/list-own-minors",
+      params,
+    });
+    return pageSchema(listUsersResponseSchema.element).parse(data);
+  }
+
+  // REFEREE ASSIGNMENTS
+
+  async getRefereeDefaults(teamId: string) {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/referee-assignments/defaults/" + teamId,
+    });
+    return z.array(refereeSlotCountSchema).parse(data);
+  }
+
+  async setRefereeDefaults(
+    teamId: string,
+    defaults: { position: RefereePosition; count: number; min_level?: number }[],
+  ) {
+    await this.axios({
+      method: "PUT",
+      url: "/referee-assignments/defaults/" + teamId,
+      data: defaults,
+      validateStatus: (s) => s === 204,
+    });
+  }
+
+  async listRefereeSlotsForGame(gameId: string) {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/referee-assignments/slots/for-game/" + gameId,
+    });
+    return z.array(refereeSlotSchema).parse(data);
+  }
+
+  async listUnfilledRefereeSlots(params: ListParams = {}) {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/referee-assignments/slots/unfilled",
+      params,
+    });
+    return pageSchema(refereeSlotSchema).parse(data);
+  }
+
+  /**
+   *
+   * @returns {string} ID of the slot
+   */
+  async addRefereeSlot(payload: {
+    game_id: string;
+    position: RefereePosition;
+    min_level?: number;
+  }) {
+    const { data } = await this.axios({
+      method: "POST",
+      url: "/referee-assignments/slots/add",
+      data: payload,
+    });
+    return z.string().parse(data);
+  }
+
+  async setRefereeSlotMinLevel(slotId: string, level: number) {
+    await this.axios({
+      method: "PUT",
+      url: "/referee-assignments/slots/set-min-level/" + slotId,
+      data: { level },
+      validateStatus: (s) => s === 204,
+    });
+  }
+
+  async listReferees(params: ListParams = {}) {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/referee-assignments/referees",
+      params,
+    });
+    return listRefereesResSchema.parse(data);
+  }
+
+  async setRefereeLevel(userId: string, level: number) {
+    await this.axios({
+      method: "PUT",
+      url: "/referee-assignments/referees/set-level/" + userId,
+      data: { level },
+      validateStatus: (s) => s === 204,
+    });
+  }
+
+  async proposeRefereeAssignments(payload: {
+    from: Date;
+    until: Date;
+    max_games_per_day?: number;
+    travel_gap_minutes?: number;
+    referee_user_ids?: string[];
+  }) {
+    const { data } = await this.axios({
+      method: "POST",
+      url: "/referee-assignments/auto-assign/propose",
+      data: payload,
+    });
+    return assignmentProposalSchema.parse(data);
+  }
+
+  /**
+   *
+   * @returns {string[]} IDs of the created requests
+   */
+  async commitRefereeAssignments(
+    assignments: { slot_id: string; referee_user_id: string }[],
+  ) {
+    const { data } = await this.axios({
+      method: "POST",
+      url: "/referee-assignments/auto-assign/commit",
+      data: { assignments },
+    });
+    return z.array(z.string()).parse(data);
+  }
+
+  async removeRefereeSlot(slotId: string) {
+    await this.axios({
+      method: "DELETE",
+      url: "/referee-assignments/slots/remove/" + slotId,
+      validateStatus: (s) => s === 204,
+    });
+  }
+
+  /**
+   *
+   * @returns {string} ID of the request
+   */
+  async requestReferee(payload: { slot_id: string; referee_user_id: string }) {
+    const { data } = await this.axios({
+      method: "POST",
+      url: "/referee-assignments/requests/create",
+      data: payload,
+    });
+    return z.string().parse(data);
+  }
+
+  async respondToRefereeRequest(payload: {
+    request_id: string;
+    response: "accepted" | "declined";
+  }) {
+    await this.axios({
+      method: "POST",
+      url: "/referee-assignments/requests/respond",
+      data: payload,
+    });
+  }
+
+  async cancelRefereeRequest(requestId: string) {
+    await this.axios({
+      method: "DELETE",
+      url: "/referee-assignments/requests/cancel/" + requestId,
+      validateStatus: (s) => s === 204,
+    });
+  }
+
+  async listOwnRefereeAssignments(params: ListParams = {}) {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/referee-assignments/list-own",
+      params,
+    });
+    return listOwnRefereeAssignmentsResSchema.parse(data);
+  }
+
+  // ABSENCES
+
+  async createAbsence(payload: {
+    starts_on: string;
+    ends_on: string;
+    reason: AbsenceReason;
+    note?: string;
+  }) {
+    const { data } = await this.axios({
+      method: "POST",
+      url: "/absences/create",
+      data: payload,
+    });
+    return createdAbsenceResSchema.parse(data);
+  }
+
+  async listOwnAbsences(params: ListParams = {}) {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/absences/list-own",
+      params,
+    });
+    return listOwnAbsencesResSchema.parse(data);
+  }
+
+  async deleteAbsence(id: string) {
+    await this.axios({
+      method: "DELETE",
+      url: "/absences/delete/" + id,
+      validateStatus: (s) => s === 204,
+    });
+  }
+
+  async getTeamAvailabilityGrid(teamId: string, limit?: number) {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/absences/team-grid/" + teamId,
+      params: { limit },
+    });
+    return availabilityGridResSchema.parse(data);
+  }
+
+  // PUSH SUBSCRIPTIONS
+
+  async getVapidPublicKey(): Promise<string> {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/push-subscriptions/vapid-public-key",
+    });
+    return z.string().parse(data);
+  }
+
+  /**
+   * @param subscription result of `PushSubscription.toJSON()`
+   */
+  async registerPushSubscription(subscription: {
+    endpoint: string;
+    keys: { p256dh: string; auth: string };
+  }) {
+    await this.axios({
+      method: "POST",
+      url: "/push-subscriptions/register",
+      data: { endpoint: subscription.endpoint, keys: subscription.keys },
+    });
+  }
+
+  async unregisterPushSubscription(endpoint: string) {
+    await this.axios({
+      method: "DELETE",
+      url: "/push-subscriptions/unregister",
+      data: { endpoint },
+    });
+  }
+
+  async listOwnPushSubscriptions(params: ListParams = {}) {
+    const { data } = await this.axios({
+      method: "GET",
+      url: "/push-subscriptions/list-own",
+      params,
+    });
+    return listOwnPushSubscriptionsResSchema.parse(data);
+  }
+
   // LOG-OUT
 
   async logOut() {
@@ -344,6 +1099,7 @@ export class Client {
       method: "POST",
       url: "/log-out",
     });
+    this.csrfToken = undefined;
   }
 
   // SElF-DELETE
@@ -360,21 +1116,24 @@ export class Client {
 export type InviteResponse = "pending" | "accepted" | "declined" | "unsure";
 export type InviteResponseFromUser = "accepted" | "declined" | "unsure";
 
-const listOwnInvitesResSchema = z.array(
+const listOwnInvitesResSchema = pageSchema(
   z.object({
     invite_id: z.string(),
     game_id: z.string(),
+    team_id: z.string(),
     opponent: z.string(),
+    start_time: z.coerce.date(),
     response: z.union([
       z.literal("pending"),
       z.literal("accepted"),
       z.literal("declined"),
       z.literal("unsure"),
     ]),
+    decline_reason: z.string().nullable(),
   }),
 );
 
-const listInvitesToGameResSchema = z.array(
+const listInvitesToGameResSchema = pageSchema(
   z.object({
     invite_id: z.string(),
     user_id: z.string(),
@@ -385,5 +1144,160 @@ const listInvitesToGameResSchema = z.array(
       z.literal("declined"),
       z.literal("unsure"),
     ]),
+    decline_reason: z.string().nullable(),
+  }),
+);
+
+const listMinorsInvitesResSchema = pageSchema(
+  z.object({
+    user_id: z.string(),
+    username: z.string(),
+    invite_id: z.string(),
+    game_id: z.string(),
+    opponent: z.string(),
+    start_time: z.coerce.date(),
+    response: z.union([
+      z.literal("pending"),
+      z.literal("accepted"),
+      z.literal("declined"),
+      z.literal("unsure"),
+    ]),
+    decline_reason: z.string().nullable(),
+  }),
+);
+
+const listGuardianshipsResSchema = pageSchema(
+  z.object({
+    parent_user_id: z.string(),
+    parent_username: z.string(),
+    minor_user_id: z.string(),
+    minor_username: z.string(),
+  }),
+);
+
+export type RefereePosition = "main" | "assistant" | "table_official";
+const refereePositionSchema = z.enum(["main", "assistant", "table_official"]);
+const refereeRequestStatusSchema = z.enum(["pending", "accepted", "declined"]);
+
+const refereeSlotCountSchema = z.object({
+  position: refereePositionSchema,
+  count: z.number(),
+  min_level: z.number(),
+});
+
+const refereeSlotSchema = z.object({
+  id: z.string(),
+  position: refereePositionSchema,
+  min_level: z.number(),
+  game_id: z.string(),
+  opponent: z.string(),
+  team_name: z.string(),
+  start_time: z.coerce.date(),
+  request_id: z.string().nullable(),
+  referee_user_id: z.string().nullable(),
+  referee_username: z.string().nullable(),
+  status: refereeRequestStatusSchema.nullable(),
+});
+
+const listRefereesResSchema = pageSchema(
+  z.object({
+    id: z.string(),
+    username: z.string(),
+    level: z.number(),
+  }),
+);
+
+const proposedAssignmentSchema = z.object({
+  slot_id: z.string(),
+  game_id: z.string(),
+  position: refereePositionSchema,
+  min_level: z.number(),
+  opponent: z.string(),
+  location: z.string(),
+  start_time: z.coerce.date(),
+  referee_user_id: z.string().nullable(),
+  referee_username: z.string().nullable(),
+});
+
+const assignmentProposalSchema = z.object({
+  assignments: z.array(proposedAssignmentSchema),
+  unfilled: z.array(proposedAssignmentSchema),
+  referee_load: z.array(
+    z.object({
+      referee_user_id: z.string(),
+      username: z.string(),
+      level: z.number(),
+      assignments: z.number(),
+    }),
+  ),
+});
+
+const listOwnRefereeAssignmentsResSchema = pageSchema(
+  z.object({
+    request_id: z.string(),
+    slot_id: z.string(),
+    position: refereePositionSchema,
+    status: refereeRequestStatusSchema,
+    game_id: z.string(),
+    opponent: z.string(),
+    team_name: z.string(),
+    location: z.string(),
+    start_time: z.coerce.date(),
+    stop_time: z.coerce.date().nullable(),
+  }),
+);
+
+export type AbsenceReason = "holiday" | "illness" | "injury" | "work" | "other";
+const absenceReasonSchema = z.enum([
+  "holiday",
+  "illness",
+  "injury",
+  "work",
+  "other",
+]);
+
+const createdAbsenceResSchema = z.object({
+  id: z.string(),
+  declined_invites: z.number(),
+  declined_referee_requests: z.number(),
+});
+
+const listOwnAbsencesResSchema = pageSchema(
+  z.object({
+    id: z.string(),
+    starts_on: z.string(),
+    ends_on: z.string(),
+    reason: absenceReasonSchema,
+    note: z.string().nullable(),
+  }),
+);
+
+const availabilityGridCellSchema = z.object({
+  response: z.enum(["pending", "accepted", "declined", "unsure"]),
+  absence_reason: absenceReasonSchema.nullable(),
+});
+
+const availabilityGridResSchema = z.object({
+  games: z.array(
+    z.object({
+      id: z.string(),
+      opponent: z.string(),
+      start_time: z.coerce.date(),
+    }),
+  ),
+  members: z.array(
+    z.object({
+      user_id: z.string(),
+      username: z.string(),
+      games: z.array(availabilityGridCellSchema.nullable()),
+    }),
+  ),
+});
+
+const listOwnPushSubscriptionsResSchema = pageSchema(
+  z.object({
+    id: z.string(),
+    user_agent: z.string().nullable(),
+    created_at: z.string(),
   }),
 );